@@ -135,6 +135,7 @@ TOC = [
                     Page("Fonts", "config/fonts.md"),
                     Page("Font Shaping", "config/font-shaping.md"),
                     Page("Key Binding", "config/keys.md"),
+                    Page("Key Tables", "config/key-tables.md"),
                     Page("Mouse Binding", "config/mouse.md"),
                     Page("Colors & Appearance", "config/appearance.md"),
                 ],
@@ -147,6 +148,7 @@ TOC = [
             Page("iTerm Image Protocol", "imgcat.md"),
             Page("SSH", "ssh.md"),
             Page("Serial Ports & Arduino", "serial.md"),
+            Page("Recording Sessions", "recording.md"),
             Page("Multiplexing", "multiplexing.md"),
             Page("Escape Sequences", "escape-sequences.md"),
             Page("F.A.Q.", "faq.md"),
@@ -239,6 +241,20 @@ introspect the state of the terminal emulation for that pane.
 
 ## Available methods
 
+""",
+                    ),
+                    Gen(
+                        "object: Tab",
+                        "config/lua/tab",
+                        index="""
+# `Tab` object
+
+A Tab object cannot be created in lua code; it is typically passed to your
+code via an event callback.  A Tab object is a handle to a live instance of a
+Tab that is known to the wezterm process.
+
+## Available methods
+
 """,
                     ),
                     Gen(
@@ -262,6 +278,18 @@ TermWindow running in the wezterm process.
 # Events emitted by the `Window` object
 
 The following events can be handled using [wezterm.on](../wezterm/on.md):
+""",
+                    ),
+                    Gen(
+                        "events: Pane",
+                        "config/lua/pane-events",
+                        index="""
+# Events emitted for panes
+
+The following events can be handled using [wezterm.on](../wezterm/on.md).
+Each of them is passed the [pane](../pane/index.md) that the event is about,
+followed by the [tab](../tab/index.md) that contains it (which may be `nil`),
+followed by any parameters that are specific to the event:
 """,
                     ),
                 ],
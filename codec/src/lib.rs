@@ -401,7 +401,7 @@ macro_rules! pdu {
 /// The overall version of the codec.
 /// This must be bumped when backwards incompatible changes
 /// are made to the types and protocol.
-pub const CODEC_VERSION: usize = 8;
+pub const CODEC_VERSION: usize = 14;
 
 // Defines the Pdu enum.
 // Each struct has an explicit identifying number.
@@ -440,6 +440,13 @@ pdu! {
     PaneRemoved: 37,
     SetPalette: 38,
     NotifyAlert: 39,
+    SwapPanes: 40,
+    RotatePanes: 41,
+    MovePaneToNewTab: 42,
+    MovePaneToNewTabResponse: 43,
+    MovePaneToTab: 44,
+    ExportPane: 45,
+    ExportPaneResponse: 46,
 }
 
 impl Pdu {
@@ -573,6 +580,9 @@ pub struct Spawn {
     pub command: Option<CommandBuilder>,
     pub command_dir: Option<String>,
     pub size: PtySize,
+    /// The workspace for a new window.
+    /// If None, the active workspace of the server is used.
+    pub workspace: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -592,6 +602,43 @@ pub struct SpawnV2 {
     pub command: Option<CommandBuilder>,
     pub command_dir: Option<String>,
     pub size: PtySize,
+    /// The workspace for a new window.
+    /// If None, the active workspace of the server is used.
+    pub workspace: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SwapPanes {
+    pub pane_id: PaneId,
+    pub other_pane_id: PaneId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct RotatePanes {
+    /// Identifies the tab whose panes are to be rotated
+    pub pane_id: PaneId,
+    pub direction: config::keyassignment::RotationDirection,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MovePaneToNewTab {
+    pub pane_id: PaneId,
+    /// If None, create a new window for the new tab
+    pub window_id: Option<WindowId>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MovePaneToNewTabResponse {
+    pub tab_id: TabId,
+    pub window_id: WindowId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MovePaneToTab {
+    pub pane_id: PaneId,
+    /// The pane in the destination tab that will be split
+    pub target_pane_id: PaneId,
+    pub direction: config::keyassignment::PaneDirection,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -886,6 +933,20 @@ pub struct SearchScrollbackResponse {
     pub results: Vec<mux::pane::SearchResult>,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ExportPane {
+    pub pane_id: PaneId,
+    pub format: config::keyassignment::ExportFormat,
+    /// The selection is not known to the server, so this must be
+    /// either `Scrollback` or `LastSemanticZone`
+    pub scope: config::keyassignment::ExportScope,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ExportPaneResponse {
+    pub data: String,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -6,6 +6,7 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use wezterm_input_types::{KeyCode, Modifiers};
 use wezterm_term::input::MouseButton;
+use wezterm_term::SemanticType;
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum SelectionMode {
@@ -111,6 +112,27 @@ pub enum PaneDirection {
     Right,
 }
 
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum RotationDirection {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// What happens to the pane that is picked in the pane select overlay
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum PaneSelectMode {
+    /// Activate the selected pane
+    Activate,
+    /// Swap the position of the active pane with the selected pane
+    SwapWithActive,
+}
+
+impl Default for PaneSelectMode {
+    fn default() -> Self {
+        Self::Activate
+    }
+}
+
 #[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub enum ScrollbackEraseMode {
     ScrollbackOnly,
@@ -148,6 +170,70 @@ impl Default for ClipboardPasteSource {
     }
 }
 
+/// The format used when exporting the contents of a pane
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Plain text, without any attributes
+    Text,
+    /// Text with escape sequences that reproduce the colors and
+    /// other attributes of the cells
+    Ansi,
+    /// A styled HTML document, using the colors of the pane
+    Html,
+}
+
+impl Default for ExportFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+impl ExportFormat {
+    /// Returns the file extension that is conventionally used for
+    /// this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Text => "txt",
+            Self::Ansi => "ans",
+            Self::Html => "html",
+        }
+    }
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "ansi" => Ok(Self::Ansi),
+            "html" => Ok(Self::Html),
+            _ => Err(format!(
+                "invalid export format {}; expected one of text, ansi or html",
+                s
+            )),
+        }
+    }
+}
+
+/// Which part of a pane is exported
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ExportScope {
+    /// The whole of the scrollback, along with the viewport
+    Scrollback,
+    /// The current selection
+    Selection,
+    /// The most recent semantic zone of the specified type.
+    /// `LastSemanticZone("Output")` is the output of the most
+    /// recently run command.
+    LastSemanticZone(SemanticType),
+}
+
+impl Default for ExportScope {
+    fn default() -> Self {
+        Self::Scrollback
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub enum KeyAssignment {
     SpawnTab(SpawnTabDomain),
@@ -203,11 +289,66 @@ pub enum KeyAssignment {
     CloseCurrentPane { confirm: bool },
     EmitEvent(String),
     QuickSelect,
+
+    ActivateKeyTable {
+        name: String,
+        #[serde(default)]
+        timeout_milliseconds: Option<u64>,
+        #[serde(default = "crate::default_true")]
+        one_shot: bool,
+        #[serde(default)]
+        replace_current: bool,
+    },
+    PopKeyTable,
+    ClearKeyTableStack,
+    ActivateCommandPalette,
+
+    SwapPaneDirection(PaneDirection),
+    RotatePanes(RotationDirection),
+    MovePaneToNewTab,
+    MovePaneToNewWindow,
+    MovePaneToTab {
+        tab_index: usize,
+        direction: PaneDirection,
+    },
+    PaneSelect {
+        #[serde(default)]
+        mode: PaneSelectMode,
+        /// Overrides quick_select_alphabet for the pane labels
+        #[serde(default)]
+        alphabet: Option<String>,
+    },
+
+    SwitchToWorkspace {
+        /// If None, a new workspace with a generated name is created
+        #[serde(default)]
+        name: Option<String>,
+        /// What to spawn if the workspace has no windows
+        #[serde(default)]
+        spawn: Option<SpawnCommand>,
+    },
+    SwitchWorkspaceRelative(isize),
+    ShowWorkspacePicker,
+    ShowColorSchemePicker,
+
+    ExportScrollback {
+        #[serde(default)]
+        format: ExportFormat,
+        #[serde(default)]
+        scope: ExportScope,
+        /// Where to write the export.  If omitted, a file named
+        /// after the pane is created in the home directory.
+        #[serde(default)]
+        path: Option<PathBuf>,
+    },
 }
 impl_lua_conversion!(KeyAssignment);
 
+pub type KeyTable = HashMap<(KeyCode, Modifiers), KeyAssignment>;
+
 pub struct InputMap {
-    keys: HashMap<(KeyCode, Modifiers), KeyAssignment>,
+    keys: KeyTable,
+    key_tables: HashMap<String, KeyTable>,
     mouse: HashMap<(MouseEventTrigger, Modifiers), KeyAssignment>,
     leader: Option<LeaderKey>,
 }
@@ -217,6 +358,7 @@ impl InputMap {
         let mut mouse = config.mouse_bindings();
 
         let mut keys = config.key_bindings();
+        let mut key_tables = config.key_table_bindings();
 
         let leader = config.leader.clone();
 
@@ -302,6 +444,7 @@ impl InputMap {
                     Search(Pattern::CaseSensitiveString("".into()))
                 ],
                 [Modifiers::CTRL, KeyCode::Char('L'), ShowDebugOverlay],
+                [Modifiers::CTRL, KeyCode::Char('P'), ActivateCommandPalette],
                 [ctrl_shift, KeyCode::Char(' '), QuickSelect],
                 // Font size manipulation
                 [Modifiers::CTRL, KeyCode::Char('-'), DecreaseFontSize],
@@ -556,10 +699,14 @@ impl InputMap {
         }
 
         keys.retain(|_, v| *v != KeyAssignment::DisableDefaultAssignment);
+        for table in key_tables.values_mut() {
+            table.retain(|_, v| *v != KeyAssignment::DisableDefaultAssignment);
+        }
         mouse.retain(|_, v| *v != KeyAssignment::DisableDefaultAssignment);
 
         Self {
             keys,
+            key_tables,
             leader,
             mouse,
         }
@@ -586,6 +733,49 @@ impl InputMap {
             .cloned()
     }
 
+    /// Returns the keys in the main key map that are bound
+    /// to the specified action
+    pub fn keys_for_action(&self, action: &KeyAssignment) -> Vec<(KeyCode, Modifiers)> {
+        let mut keys: Vec<(KeyCode, Modifiers)> = self
+            .keys
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(k, _)| k.clone())
+            .collect();
+        // Make the order stable so that displaying these is consistent
+        keys.sort_by_key(|(key, mods)| (mods.bits(), format!("{:?}", key)));
+        keys
+    }
+
+    /// Returns all of the actions bound in the main key map
+    pub fn bound_actions(&self) -> Vec<KeyAssignment> {
+        let mut actions: Vec<KeyAssignment> = vec![];
+        for action in self.keys.values() {
+            if !actions.contains(action) {
+                actions.push(action.clone());
+            }
+        }
+        actions
+    }
+
+    pub fn has_table(&self, name: &str) -> bool {
+        self.key_tables.contains_key(name)
+    }
+
+    /// Look up a key in the named key table, rather than in the
+    /// main set of key assignments
+    pub fn lookup_key_in_table(
+        &self,
+        name: &str,
+        key: &KeyCode,
+        mods: Modifiers,
+    ) -> Option<KeyAssignment> {
+        self.key_tables
+            .get(name)?
+            .get(&key.normalize_shift(Self::remove_positional_alt(mods)))
+            .cloned()
+    }
+
     pub fn lookup_mouse(&self, event: MouseEventTrigger, mods: Modifiers) -> Option<KeyAssignment> {
         self.mouse
             .get(&(event, Self::remove_positional_alt(mods)))
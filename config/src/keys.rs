@@ -10,10 +10,46 @@ pub struct Key {
     pub key: KeyCode,
     #[serde(deserialize_with = "de_modifiers", default)]
     pub mods: Modifiers,
+    /// If non-empty, the action is only triggered after the key
+    /// above is followed by this sequence of additional key presses
+    #[serde(default)]
+    pub followed_by: Vec<KeyPress>,
     pub action: KeyAssignment,
 }
 impl_lua_conversion!(Key);
 
+impl Key {
+    /// Returns the normalized sequence of key presses that
+    /// triggers this assignment
+    pub fn sequence(&self) -> Vec<(KeyCode, Modifiers)> {
+        std::iter::once(self.key.normalize_shift(self.mods))
+            .chain(
+                self.followed_by
+                    .iter()
+                    .map(|k| k.key.normalize_shift(k.mods)),
+            )
+            .collect()
+    }
+}
+
+/// An additional entry to show in the command palette
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CommandPaletteEntry {
+    pub label: String,
+    pub action: KeyAssignment,
+}
+impl_lua_conversion!(CommandPaletteEntry);
+
+/// A single key press within a multi-key sequence
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct KeyPress {
+    #[serde(deserialize_with = "de_keycode")]
+    pub key: KeyCode,
+    #[serde(deserialize_with = "de_modifiers", default)]
+    pub mods: Modifiers,
+}
+impl_lua_conversion!(KeyPress);
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LeaderKey {
     #[serde(deserialize_with = "de_keycode")]
@@ -134,6 +170,43 @@ lazy_static::lazy_static! {
     static ref KEYCODE_MAP: HashMap<String, KeyCode> = make_map();
 }
 
+/// Returns a human readable description of a key press, using the
+/// same key and modifier names that are accepted by the configuration;
+/// for example `CTRL+SHIFT+c` or `LEADER+PageUp`.
+pub fn describe_key(key: &KeyCode, mods: Modifiers) -> String {
+    let mut result = String::new();
+    if mods.contains(Modifiers::LEADER) {
+        result.push_str("LEADER+");
+    }
+    if mods.contains(Modifiers::SUPER) {
+        result.push_str("SUPER+");
+    }
+    if mods.contains(Modifiers::CTRL) {
+        result.push_str("CTRL+");
+    }
+    if mods.intersects(Modifiers::ALT | Modifiers::LEFT_ALT | Modifiers::RIGHT_ALT) {
+        result.push_str("ALT+");
+    }
+    if mods.contains(Modifiers::SHIFT) {
+        result.push_str("SHIFT+");
+    }
+
+    match key {
+        KeyCode::Char(' ') => result.push_str("Space"),
+        KeyCode::RawCode(code) => result.push_str(&format!("raw:{}", code)),
+        KeyCode::Composed(s) => result.push_str(s),
+        _ => match KEYCODE_MAP.iter().find(|(_, code)| *code == key) {
+            Some((name, _)) => result.push_str(name),
+            None => match key {
+                KeyCode::Char(c) => result.push(*c),
+                _ => result.push_str(&format!("{:?}", key)),
+            },
+        },
+    }
+
+    result
+}
+
 fn de_keycode<'de, D>(deserializer: D) -> Result<KeyCode, D::Error>
 where
     D: Deserializer<'de>,
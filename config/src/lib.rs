@@ -22,6 +22,8 @@ use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use termwiz::bidi::{BidiMode, ParagraphDirectionHint};
+use termwiz::cell::UnicodeWidthPolicy;
 use termwiz::hyperlink;
 use termwiz::surface::CursorShape;
 use toml;
@@ -34,6 +36,7 @@ mod frontend;
 pub mod keyassignment;
 mod keys;
 pub mod lua;
+mod scheme_import;
 mod ssh;
 mod terminal;
 mod tls;
@@ -45,6 +48,7 @@ pub use daemon::*;
 pub use font::*;
 pub use frontend::*;
 pub use keys::*;
+pub use scheme_import::*;
 pub use ssh::*;
 pub use terminal::*;
 pub use tls::*;
@@ -759,6 +763,13 @@ pub struct Config {
     #[serde(default = "default_scrollback_lines")]
     pub scrollback_lines: usize,
 
+    /// When enabled, lines that are older than `scrollback_lines`
+    /// are moved to a temporary file on disk instead of being
+    /// discarded, so that the scrollback is limited only by the
+    /// available disk space.
+    #[serde(default)]
+    pub enable_disk_scrollback: bool,
+
     /// If no `prog` is specified on the command line, use this
     /// instead of running the user's shell.
     /// For example, to have `wezterm` always run `top` by default,
@@ -900,6 +911,26 @@ pub struct Config {
     pub disable_default_key_bindings: bool,
     pub leader: Option<LeaderKey>,
 
+    /// Named sets of key assignments that can be activated
+    /// using the ActivateKeyTable key assignment
+    #[serde(default)]
+    pub key_tables: HashMap<String, Vec<Key>>,
+
+    /// How long to wait for the next key press in a multi-key
+    /// sequence before giving up and sending the keys that were
+    /// pressed so far to the terminal
+    #[serde(default = "default_key_sequence_timeout_milliseconds")]
+    pub key_sequence_timeout_milliseconds: u64,
+
+    /// Additional entries to show in the command palette
+    #[serde(default)]
+    pub command_palette_entries: Vec<CommandPaletteEntry>,
+
+    /// The name of the workspace that is active when wezterm starts.
+    /// If omitted, "default" is used.
+    #[serde(default)]
+    pub default_workspace: Option<String>,
+
     #[serde(default)]
     pub disable_default_quick_select_patterns: bool,
     #[serde(default)]
@@ -1163,6 +1194,36 @@ pub struct Config {
 
     #[serde(default)]
     pub pane_focus_follows_mouse: bool,
+
+    /// When true, emoji are treated as single width, as they were
+    /// prior to Unicode 9.
+    #[serde(default)]
+    pub narrow_emoji: bool,
+
+    /// When true, characters with an East Asian Width of Ambiguous
+    /// are treated as double width, as they are in CJK locales.
+    #[serde(default)]
+    pub treat_east_asian_ambiguous_width_as_wide: bool,
+
+    /// When true, an emoji followed by VARIATION SELECTOR-16 is
+    /// treated as double width.
+    #[serde(default)]
+    pub treat_emoji_vs16_as_wide: bool,
+
+    /// When true, lines are reordered for display using the Unicode
+    /// bidirectional algorithm, until the application changes this
+    /// using the BDSM escape sequence.
+    #[serde(default)]
+    pub bidi_enabled: bool,
+
+    /// The initial paragraph direction used by the bidi algorithm
+    #[serde(default)]
+    pub bidi_direction: ParagraphDirectionHint,
+
+    /// When true, box drawing characters are mirrored when they
+    /// appear in right-to-left text.
+    #[serde(default)]
+    pub bidi_mirror_box_drawing: bool,
 }
 impl_lua_conversion!(Config);
 
@@ -1187,6 +1248,10 @@ fn default_status_update_interval() -> u64 {
     1_000
 }
 
+fn default_key_sequence_timeout_milliseconds() -> u64 {
+    1_000
+}
+
 fn default_alternate_buffer_wheel_scroll_speed() -> u8 {
     3
 }
@@ -1460,6 +1525,9 @@ impl Config {
         let mut map = HashMap::new();
 
         for k in &self.keys {
+            if !k.followed_by.is_empty() {
+                continue;
+            }
             let (key, mods) = k.key.normalize_shift(k.mods);
             map.insert((key, mods), k.action.clone());
         }
@@ -1467,6 +1535,40 @@ impl Config {
         map
     }
 
+    /// Returns the multi-key sequences defined in `keys`
+    pub fn key_sequence_bindings(&self) -> Vec<(Vec<(KeyCode, Modifiers)>, KeyAssignment)> {
+        self.keys
+            .iter()
+            .filter(|k| !k.followed_by.is_empty())
+            .map(|k| (k.sequence(), k.action.clone()))
+            .collect()
+    }
+
+    pub fn key_table_bindings(
+        &self,
+    ) -> HashMap<String, HashMap<(KeyCode, Modifiers), KeyAssignment>> {
+        let mut tables = HashMap::new();
+
+        for (name, keys) in &self.key_tables {
+            let mut map = HashMap::new();
+            for k in keys {
+                if !k.followed_by.is_empty() {
+                    log::warn!(
+                        "key_tables.{}: followed_by is only supported in keys; ignoring {:?}",
+                        name,
+                        k
+                    );
+                    continue;
+                }
+                let (key, mods) = k.key.normalize_shift(k.mods);
+                map.insert((key, mods), k.action.clone());
+            }
+            tables.insert(name.clone(), map);
+        }
+
+        tables
+    }
+
     pub fn mouse_bindings(&self) -> HashMap<(MouseEventTrigger, Modifiers), KeyAssignment> {
         let mut map = HashMap::new();
 
@@ -1583,53 +1685,50 @@ impl Config {
     }
 
     fn load_color_schemes(&mut self, paths: &[PathBuf]) -> Result<(), Error> {
-        fn extract_scheme_name(name: &str) -> Option<&str> {
-            if name.ends_with(".toml") {
-                let len = name.len();
-                Some(&name[..len - 5])
-            } else {
-                None
-            }
-        }
+        for colors_dir in paths {
+            let dir = match std::fs::read_dir(colors_dir) {
+                Ok(dir) => dir,
+                Err(_) => continue,
+            };
 
-        fn load_scheme(path: &Path) -> Result<ColorSchemeFile, Error> {
-            let s = std::fs::read_to_string(path)?;
-            let scheme: ColorSchemeFile = toml::from_str(&s).context("parsing TOML")?;
-            Ok(scheme)
-        }
+            // If the same scheme is present in more than one format
+            // then prefer our own format over the imported ones
+            let mut files: Vec<(bool, String, ColorSchemeFormat, PathBuf)> = dir
+                .filter_map(|entry| {
+                    let entry = entry.ok()?;
+                    let name = entry.file_name();
+                    let (format, scheme_name) = ColorSchemeFormat::from_file_name(name.to_str()?)?;
+                    Some((
+                        format != ColorSchemeFormat::Wezterm,
+                        scheme_name.to_string(),
+                        format,
+                        entry.path(),
+                    ))
+                })
+                .collect();
+            files.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+
+            for (_, scheme_name, format, path) in files {
+                if self.color_schemes.contains_key(&scheme_name) {
+                    // This scheme has already been defined
+                    continue;
+                }
 
-        for colors_dir in paths {
-            if let Ok(dir) = std::fs::read_dir(colors_dir) {
-                for entry in dir {
-                    if let Ok(entry) = entry {
-                        if let Some(name) = entry.file_name().to_str() {
-                            if let Some(scheme_name) = extract_scheme_name(name) {
-                                if self.color_schemes.contains_key(scheme_name) {
-                                    // This scheme has already been defined
-                                    continue;
-                                }
-
-                                let path = entry.path();
-                                match load_scheme(&path) {
-                                    Ok(scheme) => {
-                                        log::trace!(
-                                            "Loaded color scheme `{}` from {}",
-                                            scheme_name,
-                                            path.display()
-                                        );
-                                        self.color_schemes
-                                            .insert(scheme_name.to_string(), scheme.colors);
-                                    }
-                                    Err(err) => {
-                                        log::error!(
-                                            "Color scheme in `{}` failed to load: {:#}",
-                                            path.display(),
-                                            err
-                                        );
-                                    }
-                                }
-                            }
-                        }
+                match ColorSchemeFile::load_file(&path, format) {
+                    Ok(scheme) => {
+                        log::trace!(
+                            "Loaded color scheme `{}` from {}",
+                            scheme_name,
+                            path.display()
+                        );
+                        self.color_schemes.insert(scheme_name, scheme.colors);
+                    }
+                    Err(err) => {
+                        log::error!(
+                            "Color scheme in `{}` failed to load: {:#}",
+                            path.display(),
+                            err
+                        );
                     }
                 }
             }
@@ -1648,6 +1747,45 @@ impl Config {
         }
     }
 
+    /// Returns all of the color schemes that can be named by
+    /// `color_scheme`, sorted case insensitively by name.
+    /// Schemes loaded from `color_scheme_dirs` replace built-in
+    /// schemes of the same name, just as they do when resolving.
+    pub fn all_color_schemes(&self) -> Vec<(String, Palette)> {
+        let mut schemes: HashMap<&str, &Palette> = COLOR_SCHEMES
+            .iter()
+            .map(|(name, palette)| (name.as_str(), palette))
+            .collect();
+        for (name, palette) in &self.color_schemes {
+            schemes.insert(name.as_str(), palette);
+        }
+        let mut schemes: Vec<(String, Palette)> = schemes
+            .into_iter()
+            .map(|(name, palette)| (name.to_string(), palette.clone()))
+            .collect();
+        schemes.sort_by_cached_key(|(name, _)| name.to_lowercase());
+        schemes
+    }
+
+    /// Returns the policy used to compute the number of cells
+    /// occupied by a grapheme
+    pub fn unicode_width_policy(&self) -> UnicodeWidthPolicy {
+        UnicodeWidthPolicy {
+            narrow_emoji: self.narrow_emoji,
+            ambiguous_are_wide: self.treat_east_asian_ambiguous_width_as_wide,
+            vs16_is_wide: self.treat_emoji_vs16_as_wide,
+        }
+    }
+
+    /// Returns the initial bidi mode of the terminal
+    pub fn bidi_mode(&self) -> BidiMode {
+        BidiMode {
+            enabled: self.bidi_enabled,
+            hint: self.bidi_direction,
+            mirror_box_drawing: self.bidi_mirror_box_drawing,
+        }
+    }
+
     pub fn initial_size(&self) -> PtySize {
         PtySize {
             rows: self.initial_rows,
@@ -1792,3 +1930,49 @@ fn default_write_timeout() -> Duration {
 fn default_bypass_mouse_reporting_modifiers() -> Modifiers {
     Modifiers::SHIFT
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn all_color_schemes() {
+        let mut config = Config::default_config();
+        let schemes = config.all_color_schemes();
+        assert_eq!(schemes.len(), COLOR_SCHEMES.len());
+        assert!(schemes
+            .windows(2)
+            .all(|pair| pair[0].0.to_lowercase() <= pair[1].0.to_lowercase()));
+
+        // User schemes are added to the list, replacing any
+        // built-in scheme with the same name
+        let custom = Palette {
+            foreground: Some(RgbColor::new_8bpc(1, 2, 3)),
+            ..Palette::default()
+        };
+        config
+            .color_schemes
+            .insert("Dracula".to_string(), custom.clone());
+        config.color_schemes.insert("my scheme".to_string(), custom);
+        let schemes = config.all_color_schemes();
+        assert_eq!(schemes.len(), COLOR_SCHEMES.len() + 1);
+        let foreground = |name: &str| {
+            schemes
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, palette)| palette.foreground)
+        };
+        assert_eq!(
+            foreground("Dracula"),
+            Some(Some(RgbColor::new_8bpc(1, 2, 3)))
+        );
+        assert_eq!(
+            foreground("my scheme"),
+            Some(Some(RgbColor::new_8bpc(1, 2, 3)))
+        );
+        assert_eq!(
+            foreground("Builtin Dark"),
+            Some(COLOR_SCHEMES["Builtin Dark"].foreground)
+        );
+    }
+}
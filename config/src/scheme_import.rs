@@ -0,0 +1,655 @@
+//! Loaders for color schemes written for other terminal emulators.
+//! Files in these formats can be placed in `color_scheme_dirs` alongside
+//! wezterm's own TOML schemes, or converted to TOML using
+//! `wezterm convert-color-scheme`.
+use crate::color::{ColorSchemeFile, Palette, RgbColor};
+use anyhow::{anyhow, bail, Context};
+use std::collections::HashMap;
+use std::path::Path;
+use wezterm_term::color::ColorPalette;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSchemeFormat {
+    /// wezterm's own TOML format
+    Wezterm,
+    /// An iTerm2 `.itermcolors` property list
+    ITerm2,
+    /// The `colors` section of an alacritty YAML configuration file
+    Alacritty,
+    /// A kitty theme `.conf` file
+    Kitty,
+    /// An X resources file, as would be loaded by `xrdb`
+    Xresources,
+}
+
+impl ColorSchemeFormat {
+    /// Determines the format of a scheme file from its extension.
+    /// Returns the format and the name of the scheme, which is
+    /// the file name without its extension.
+    pub fn from_file_name(name: &str) -> Option<(Self, &str)> {
+        let idx = name.rfind('.')?;
+        if idx == 0 {
+            return None;
+        }
+        let format = match &name[idx + 1..] {
+            "toml" => Self::Wezterm,
+            "itermcolors" => Self::ITerm2,
+            "yml" | "yaml" => Self::Alacritty,
+            "conf" => Self::Kitty,
+            "Xresources" | "xresources" | "xrdb" => Self::Xresources,
+            _ => return None,
+        };
+        Some((format, &name[..idx]))
+    }
+}
+
+impl std::str::FromStr for ColorSchemeFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "wezterm" | "toml" => Ok(Self::Wezterm),
+            "iterm2" | "itermcolors" => Ok(Self::ITerm2),
+            "alacritty" | "yaml" | "yml" => Ok(Self::Alacritty),
+            "kitty" | "conf" => Ok(Self::Kitty),
+            "xresources" | "xrdb" => Ok(Self::Xresources),
+            _ => bail!(
+                "unknown color scheme format `{}`; expected one of \
+                 wezterm, iterm2, alacritty, kitty or xresources",
+                s
+            ),
+        }
+    }
+}
+
+impl ColorSchemeFile {
+    /// Parses a color scheme from `data`, which is in the specified format
+    pub fn parse(format: ColorSchemeFormat, data: &str) -> anyhow::Result<Self> {
+        let colors = match format {
+            ColorSchemeFormat::Wezterm => return toml::from_str(data).context("parsing TOML"),
+            ColorSchemeFormat::ITerm2 => parse_iterm2(data).context("parsing itermcolors")?,
+            ColorSchemeFormat::Alacritty => {
+                parse_alacritty(data).context("parsing alacritty YAML")?
+            }
+            ColorSchemeFormat::Kitty => parse_kitty(data).context("parsing kitty theme")?,
+            ColorSchemeFormat::Xresources => {
+                parse_xresources(data).context("parsing X resources")?
+            }
+        };
+        Ok(Self { colors })
+    }
+
+    /// Loads a color scheme in the specified format from `path`
+    pub fn load_file(path: &Path, format: ColorSchemeFormat) -> anyhow::Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        Self::parse(format, &data)
+    }
+
+    /// Returns the scheme in wezterm's TOML format
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        toml::to_string(self).context("serializing color scheme as TOML")
+    }
+}
+
+/// Accumulates the colors found in a scheme file, which may specify
+/// them in any order, and may omit some of them.
+#[derive(Default)]
+struct PaletteBuilder {
+    foreground: Option<RgbColor>,
+    background: Option<RgbColor>,
+    cursor_fg: Option<RgbColor>,
+    cursor_bg: Option<RgbColor>,
+    selection_fg: Option<RgbColor>,
+    selection_bg: Option<RgbColor>,
+    indexed: [Option<RgbColor>; 16],
+}
+
+impl PaletteBuilder {
+    fn set_indexed(&mut self, idx: usize, color: RgbColor) {
+        if let Some(slot) = self.indexed.get_mut(idx) {
+            slot.replace(color);
+        }
+    }
+
+    fn build(self) -> anyhow::Result<Palette> {
+        if self.foreground.is_none()
+            && self.background.is_none()
+            && self.indexed.iter().all(Option::is_none)
+        {
+            bail!("no colors were found");
+        }
+
+        // Any of the 16 colors that were not specified are taken
+        // from the default palette
+        let defaults = ColorPalette::default();
+        let mut ansi = [RgbColor::default(); 8];
+        let mut brights = [RgbColor::default(); 8];
+        for (idx, color) in self.indexed.iter().enumerate() {
+            let color = color.unwrap_or(defaults.colors.0[idx]);
+            if idx < 8 {
+                ansi[idx] = color;
+            } else {
+                brights[idx - 8] = color;
+            }
+        }
+
+        Ok(Palette {
+            foreground: self.foreground,
+            background: self.background,
+            cursor_fg: self.cursor_fg,
+            cursor_bg: self.cursor_bg,
+            cursor_border: self.cursor_bg,
+            selection_fg: self.selection_fg,
+            selection_bg: self.selection_bg,
+            ansi: Some(ansi),
+            brights: Some(brights),
+            ..Palette::default()
+        })
+    }
+}
+
+/// Parses a color value, accepting the `0xRRGGBB` form used by
+/// alacritty in addition to the forms that wezterm accepts
+fn parse_color(value: &str) -> Option<RgbColor> {
+    let value = value.trim();
+    if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        return RgbColor::from_rgb_str(&format!("#{}", hex));
+    }
+    RgbColor::from_named_or_rgb_string(value)
+}
+
+/// The names used for the ansi colors by alacritty
+const ALACRITTY_COLOR_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// A value parsed from an XML property list
+#[derive(Debug)]
+enum PlistValue {
+    Dict(Vec<(String, PlistValue)>),
+    Number(f32),
+    /// Strings, arrays, booleans, dates and data, none of which
+    /// are needed to read a color scheme
+    Other,
+}
+
+impl PlistValue {
+    fn get(&self, key: &str) -> Option<&PlistValue> {
+        match self {
+            Self::Dict(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn number(&self) -> Option<f32> {
+        match self {
+            Self::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum PlistToken<'a> {
+    Open(&'a str),
+    Close(&'a str),
+    Empty(&'a str),
+    Text(&'a str),
+}
+
+/// A minimal reader for XML property lists.  It understands just
+/// enough of XML to read the files produced by iTerm2: elements,
+/// text, comments and the prolog.
+struct PlistReader<'a> {
+    data: &'a str,
+    pos: usize,
+}
+
+impl<'a> PlistReader<'a> {
+    fn next_token(&mut self) -> anyhow::Result<Option<PlistToken<'a>>> {
+        loop {
+            let rest = &self.data[self.pos..];
+            if rest.is_empty() {
+                return Ok(None);
+            }
+            if !rest.starts_with('<') {
+                let len = rest.find('<').unwrap_or(rest.len());
+                self.pos += len;
+                let text = rest[..len].trim();
+                if text.is_empty() {
+                    continue;
+                }
+                return Ok(Some(PlistToken::Text(text)));
+            }
+
+            if rest.starts_with("<!--") {
+                let end = rest
+                    .find("-->")
+                    .ok_or_else(|| anyhow!("unterminated comment"))?;
+                self.pos += end + 3;
+                continue;
+            }
+
+            let end = rest
+                .find('>')
+                .ok_or_else(|| anyhow!("unterminated element"))?;
+            self.pos += end + 1;
+            let tag = &rest[1..end];
+            if tag.starts_with('?') || tag.starts_with('!') {
+                // The XML declaration or the DOCTYPE
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                return Ok(Some(PlistToken::Close(name.trim())));
+            }
+            if let Some(tag) = tag.strip_suffix('/') {
+                let name = tag.split_whitespace().next().unwrap_or("");
+                return Ok(Some(PlistToken::Empty(name)));
+            }
+            let name = tag.split_whitespace().next().unwrap_or("");
+            return Ok(Some(PlistToken::Open(name)));
+        }
+    }
+
+    fn expect_token(&mut self) -> anyhow::Result<PlistToken<'a>> {
+        self.next_token()?
+            .ok_or_else(|| anyhow!("unexpected end of property list"))
+    }
+
+    /// Reads the text content of the element named `name`, whose
+    /// opening tag has already been read
+    fn read_text(&mut self, name: &str) -> anyhow::Result<String> {
+        match self.expect_token()? {
+            PlistToken::Close(n) if n == name => Ok(String::new()),
+            PlistToken::Text(text) => {
+                match self.expect_token()? {
+                    PlistToken::Close(n) if n == name => {}
+                    token => bail!("expected </{}> but found {:?}", name, token),
+                }
+                Ok(text
+                    .replace("&lt;", "<")
+                    .replace("&gt;", ">")
+                    .replace("&quot;", "\"")
+                    .replace("&apos;", "'")
+                    .replace("&amp;", "&"))
+            }
+            token => bail!("expected the text of <{}> but found {:?}", name, token),
+        }
+    }
+
+    /// Skips over the content of the element named `name`, whose
+    /// opening tag has already been read
+    fn skip_element(&mut self, name: &str) -> anyhow::Result<()> {
+        let mut depth = 0;
+        loop {
+            match self.expect_token()? {
+                PlistToken::Open(n) if n == name => depth += 1,
+                PlistToken::Close(n) if n == name => {
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Reads the value that starts with `token`
+    fn read_value(&mut self, token: PlistToken<'a>) -> anyhow::Result<PlistValue> {
+        match token {
+            PlistToken::Open("dict") => {
+                let mut entries = vec![];
+                loop {
+                    match self.expect_token()? {
+                        PlistToken::Close("dict") => return Ok(PlistValue::Dict(entries)),
+                        PlistToken::Open("key") => {
+                            let key = self.read_text("key")?;
+                            let token = self.expect_token()?;
+                            entries.push((key, self.read_value(token)?));
+                        }
+                        token => bail!("expected <key> in <dict> but found {:?}", token),
+                    }
+                }
+            }
+            PlistToken::Open(name @ "real") | PlistToken::Open(name @ "integer") => {
+                let text = self.read_text(name)?;
+                let value = text
+                    .parse::<f32>()
+                    .with_context(|| format!("parsing <{}>{}</{}>", name, text, name))?;
+                Ok(PlistValue::Number(value))
+            }
+            PlistToken::Open(name) => {
+                self.skip_element(name)?;
+                Ok(PlistValue::Other)
+            }
+            PlistToken::Empty(_) => Ok(PlistValue::Other),
+            token => bail!("expected a value but found {:?}", token),
+        }
+    }
+
+    fn read_plist(data: &'a str) -> anyhow::Result<PlistValue> {
+        let mut reader = Self { data, pos: 0 };
+        match reader.expect_token()? {
+            PlistToken::Open("plist") => {}
+            token => bail!("expected <plist> but found {:?}", token),
+        }
+        let token = reader.expect_token()?;
+        reader.read_value(token)
+    }
+}
+
+fn parse_iterm2(data: &str) -> anyhow::Result<Palette> {
+    let plist = PlistReader::read_plist(data)?;
+
+    let color = |key: &str| -> anyhow::Result<Option<RgbColor>> {
+        let dict = match plist.get(key) {
+            Some(dict) => dict,
+            None => return Ok(None),
+        };
+        let component = |name: &str| {
+            dict.get(name)
+                .and_then(PlistValue::number)
+                .ok_or_else(|| anyhow!("`{}` has no `{}`", key, name))
+        };
+        let to_8bpc = |c: f32| (c.max(0.).min(1.) * 255.).round() as u8;
+        Ok(Some(RgbColor::new_8bpc(
+            to_8bpc(component("Red Component")?),
+            to_8bpc(component("Green Component")?),
+            to_8bpc(component("Blue Component")?),
+        )))
+    };
+
+    let mut builder = PaletteBuilder {
+        foreground: color("Foreground Color")?,
+        background: color("Background Color")?,
+        cursor_fg: color("Cursor Text Color")?,
+        cursor_bg: color("Cursor Color")?,
+        selection_fg: color("Selected Text Color")?,
+        selection_bg: color("Selection Color")?,
+        ..PaletteBuilder::default()
+    };
+    for idx in 0..16 {
+        if let Some(c) = color(&format!("Ansi {} Color", idx))? {
+            builder.set_indexed(idx, c);
+        }
+    }
+    builder.build()
+}
+
+/// Removes a trailing comment from a line of YAML, taking care
+/// not to treat a `#` inside a quoted string as a comment
+fn strip_yaml_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut prior_is_space = true;
+    for (idx, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == '#' && prior_is_space => return &line[..idx],
+            None => {}
+        }
+        prior_is_space = c.is_whitespace();
+    }
+    line
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    for q in &['\'', '"'] {
+        if value.len() >= 2 && value.starts_with(*q) && value.ends_with(*q) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
+
+/// Reads the nested mappings of a YAML document into a map from the
+/// dotted path of each scalar value to that value.
+/// This handles the block-style mappings that alacritty themes use;
+/// sequences and flow-style collections are ignored.
+fn parse_yaml_mappings(data: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    // The indentation and key of each of the enclosing mappings
+    let mut stack: Vec<(usize, String)> = vec![];
+
+    for line in data.lines() {
+        let line = strip_yaml_comment(line).trim_end();
+        let content = line.trim_start();
+        if content.is_empty() || content == "---" || content.starts_with('-') {
+            continue;
+        }
+        let indent = line.len() - content.len();
+        while stack.last().map(|(i, _)| *i >= indent).unwrap_or(false) {
+            stack.pop();
+        }
+
+        let (key, value) = match content.find(':') {
+            Some(idx) => (unquote(&content[..idx]), content[idx + 1..].trim()),
+            None => continue,
+        };
+        if value.is_empty() || value.starts_with('&') {
+            stack.push((indent, key.to_string()));
+        } else {
+            let mut path: Vec<&str> = stack.iter().map(|(_, k)| k.as_str()).collect();
+            path.push(key);
+            values.insert(path.join("."), unquote(value).to_string());
+        }
+    }
+
+    values
+}
+
+fn parse_alacritty(data: &str) -> anyhow::Result<Palette> {
+    let values = parse_yaml_mappings(data);
+    let color = |path: &str| {
+        values
+            .get(&format!("colors.{}", path))
+            .and_then(|value| parse_color(value))
+    };
+
+    let mut builder = PaletteBuilder {
+        foreground: color("primary.foreground"),
+        background: color("primary.background"),
+        cursor_fg: color("cursor.text"),
+        cursor_bg: color("cursor.cursor"),
+        selection_fg: color("selection.text"),
+        selection_bg: color("selection.background"),
+        ..PaletteBuilder::default()
+    };
+    for (idx, name) in ALACRITTY_COLOR_NAMES.iter().enumerate() {
+        if let Some(c) = color(&format!("normal.{}", name)) {
+            builder.set_indexed(idx, c);
+        }
+        if let Some(c) = color(&format!("bright.{}", name)) {
+            builder.set_indexed(idx + 8, c);
+        }
+    }
+    builder.build()
+}
+
+fn parse_kitty(data: &str) -> anyhow::Result<Palette> {
+    let mut builder = PaletteBuilder::default();
+
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let (key, value) = match (fields.next(), fields.next()) {
+            (Some(key), Some(value)) => (key, value),
+            _ => continue,
+        };
+        // Values such as `none` or `background` are not colors
+        // and leave the default in place
+        let color = match parse_color(value) {
+            Some(color) => color,
+            None => continue,
+        };
+
+        match key {
+            "foreground" => builder.foreground = Some(color),
+            "background" => builder.background = Some(color),
+            "cursor" => builder.cursor_bg = Some(color),
+            "cursor_text_color" => builder.cursor_fg = Some(color),
+            "selection_foreground" => builder.selection_fg = Some(color),
+            "selection_background" => builder.selection_bg = Some(color),
+            _ => {
+                if let Some(idx) = key
+                    .strip_prefix("color")
+                    .and_then(|idx| idx.parse::<usize>().ok())
+                {
+                    builder.set_indexed(idx, color);
+                }
+            }
+        }
+    }
+
+    builder.build()
+}
+
+fn parse_xresources(data: &str) -> anyhow::Result<Palette> {
+    let mut defines: HashMap<&str, &str> = HashMap::new();
+    let mut builder = PaletteBuilder::default();
+
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('!') {
+            continue;
+        }
+        if let Some(define) = line.strip_prefix("#define") {
+            let mut fields = define.split_whitespace();
+            if let (Some(name), Some(value)) = (fields.next(), fields.next()) {
+                defines.insert(name, value);
+            }
+            continue;
+        }
+        if line.starts_with('#') {
+            // Other preprocessor directives
+            continue;
+        }
+
+        let (resource, value) = match line.find(':') {
+            Some(idx) => (line[..idx].trim(), line[idx + 1..].trim()),
+            None => continue,
+        };
+        // The resource name is the final component of the specification,
+        // so that `*.foreground`, `*foreground` and `URxvt.foreground`
+        // are all treated the same way
+        let name = match resource.rfind(|c| c == '.' || c == '*') {
+            Some(idx) => &resource[idx + 1..],
+            None => resource,
+        };
+        let value = defines.get(value).copied().unwrap_or(value);
+        let color = match parse_color(value) {
+            Some(color) => color,
+            None => continue,
+        };
+
+        match name {
+            "foreground" => builder.foreground = Some(color),
+            "background" => builder.background = Some(color),
+            "cursorColor" => builder.cursor_bg = Some(color),
+            "cursorColor2" => builder.cursor_fg = Some(color),
+            "highlightTextColor" => builder.selection_fg = Some(color),
+            "highlightColor" => builder.selection_bg = Some(color),
+            _ => {
+                if let Some(idx) = name
+                    .strip_prefix("color")
+                    .and_then(|idx| idx.parse::<usize>().ok())
+                {
+                    builder.set_indexed(idx, color);
+                }
+            }
+        }
+    }
+
+    builder.build()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Each of the sample files describes the same scheme
+    const ITERM2: &str = include_str!("../test-data/colors/Sample.itermcolors");
+    const ALACRITTY: &str = include_str!("../test-data/colors/Sample.yml");
+    const KITTY: &str = include_str!("../test-data/colors/Sample.conf");
+    const XRESOURCES: &str = include_str!("../test-data/colors/Sample.Xresources");
+    const WEZTERM: &str = include_str!("../test-data/colors/Sample.toml");
+
+    fn as_json(scheme: &ColorSchemeFile) -> serde_json::Value {
+        serde_json::to_value(scheme).unwrap()
+    }
+
+    fn round_trip(format: ColorSchemeFormat, data: &str) {
+        let expected = ColorSchemeFile::parse(ColorSchemeFormat::Wezterm, WEZTERM).unwrap();
+        let scheme = ColorSchemeFile::parse(format, data).unwrap();
+        assert_eq!(as_json(&scheme), as_json(&expected), "{:?}", format);
+
+        let toml = scheme.to_toml().unwrap();
+        let reloaded = ColorSchemeFile::parse(ColorSchemeFormat::Wezterm, &toml).unwrap();
+        assert_eq!(as_json(&reloaded), as_json(&scheme), "{:?}", format);
+    }
+
+    #[test]
+    fn iterm2() {
+        round_trip(ColorSchemeFormat::ITerm2, ITERM2);
+    }
+
+    #[test]
+    fn alacritty() {
+        round_trip(ColorSchemeFormat::Alacritty, ALACRITTY);
+    }
+
+    #[test]
+    fn kitty() {
+        round_trip(ColorSchemeFormat::Kitty, KITTY);
+    }
+
+    #[test]
+    fn xresources() {
+        round_trip(ColorSchemeFormat::Xresources, XRESOURCES);
+    }
+
+    #[test]
+    fn format_from_file_name() {
+        assert_eq!(
+            ColorSchemeFormat::from_file_name("Sample.itermcolors"),
+            Some((ColorSchemeFormat::ITerm2, "Sample"))
+        );
+        assert_eq!(
+            ColorSchemeFormat::from_file_name("Some.Theme.yaml"),
+            Some((ColorSchemeFormat::Alacritty, "Some.Theme"))
+        );
+        assert_eq!(ColorSchemeFormat::from_file_name(".Xresources"), None);
+        assert_eq!(ColorSchemeFormat::from_file_name("README.md"), None);
+    }
+
+    #[test]
+    fn missing_colors_use_defaults() {
+        let scheme = ColorSchemeFile::parse(
+            ColorSchemeFormat::Kitty,
+            "foreground #ffffff\ncolor1 #ff0000\n",
+        )
+        .unwrap();
+        let defaults = ColorPalette::default();
+        let ansi = scheme.colors.ansi.unwrap();
+        assert_eq!(ansi[0], defaults.colors.0[0]);
+        assert_eq!(ansi[1], RgbColor::new_8bpc(0xff, 0, 0));
+        assert!(scheme.colors.background.is_none());
+    }
+
+    #[test]
+    fn no_colors_is_an_error() {
+        assert!(ColorSchemeFile::parse(ColorSchemeFormat::Kitty, "font_size 12\n").is_err());
+        assert!(
+            ColorSchemeFile::parse(ColorSchemeFormat::Alacritty, "font:\n  size: 12\n").is_err()
+        );
+    }
+}
@@ -1,6 +1,8 @@
 //! Bridge our gui config into the terminal crate configuration
 
 use crate::configuration;
+use termwiz::bidi::BidiMode;
+use termwiz::cell::UnicodeWidthPolicy;
 use termwiz::hyperlink::Rule as HyperlinkRule;
 use wezterm_term::color::ColorPalette;
 
@@ -16,6 +18,10 @@ impl wezterm_term::TerminalConfiguration for TermConfig {
         configuration().scrollback_lines
     }
 
+    fn enable_disk_scrollback(&self) -> bool {
+        configuration().enable_disk_scrollback
+    }
+
     fn hyperlink_rules(&self) -> (usize, Vec<HyperlinkRule>) {
         let config = configuration();
         (config.generation(), config.hyperlink_rules.clone())
@@ -34,4 +40,12 @@ impl wezterm_term::TerminalConfiguration for TermConfig {
     fn alternate_buffer_wheel_scroll_speed(&self) -> u8 {
         configuration().alternate_buffer_wheel_scroll_speed
     }
+
+    fn unicode_width_policy(&self) -> UnicodeWidthPolicy {
+        configuration().unicode_width_policy()
+    }
+
+    fn bidi_mode(&self) -> BidiMode {
+        configuration().bidi_mode()
+    }
 }
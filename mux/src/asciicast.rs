@@ -0,0 +1,548 @@
+//! Reading and writing of terminal session recordings in the
+//! asciicast v2 format.
+//! A recording is a JSON header line followed by one JSON array per
+//! line for each event: `[time, code, data]`, where time is the number
+//! of seconds since the start of the recording.
+//! See <https://github.com/asciinema/asciinema/blob/develop/doc/asciicast-v2.md>
+use crate::termwiztermtab::TermWizTerminal;
+use anyhow::{anyhow, bail, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant, SystemTime};
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::terminal::Terminal;
+
+/// The header line that starts every recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    pub version: u32,
+    pub width: usize,
+    pub height: usize,
+    /// Unix timestamp of the start of the recording
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Environment variables that influence the recorded session,
+    /// typically `SHELL` and `TERM`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+}
+
+impl Header {
+    pub fn new(width: usize, height: usize) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs());
+        let env = ["SHELL", "TERM"]
+            .iter()
+            .filter_map(|name| {
+                std::env::var(name)
+                    .ok()
+                    .map(|value| (name.to_string(), value))
+            })
+            .collect();
+        Self {
+            version: 2,
+            width,
+            height,
+            timestamp,
+            title: None,
+            env,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventData {
+    /// Data that was output by the pty
+    Output(String),
+    /// Data that was sent to the pty
+    Input(String),
+    /// The terminal was resized
+    Resize { cols: usize, rows: usize },
+    /// A named point of interest in the recording
+    Marker(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// Seconds since the start of the recording
+    pub time: f64,
+    pub data: EventData,
+}
+
+impl Event {
+    fn to_json(&self) -> anyhow::Result<String> {
+        let (code, data) = match &self.data {
+            EventData::Output(s) => ("o", s.clone()),
+            EventData::Input(s) => ("i", s.clone()),
+            EventData::Resize { cols, rows } => ("r", format!("{}x{}", cols, rows)),
+            EventData::Marker(s) => ("m", s.clone()),
+        };
+        Ok(serde_json::to_string(&(self.time, code, data))?)
+    }
+
+    fn from_json(line: &str) -> anyhow::Result<Self> {
+        let (time, code, data): (f64, String, String) = serde_json::from_str(line)?;
+        let data = match code.as_str() {
+            "o" => EventData::Output(data),
+            "i" => EventData::Input(data),
+            "m" => EventData::Marker(data),
+            "r" => {
+                let mut fields = data.splitn(2, 'x');
+                let cols = fields.next().and_then(|s| s.parse().ok());
+                let rows = fields.next().and_then(|s| s.parse().ok());
+                match (cols, rows) {
+                    (Some(cols), Some(rows)) => EventData::Resize { cols, rows },
+                    _ => bail!("invalid resize event data {:?}", data),
+                }
+            }
+            _ => bail!("unknown event code {:?}", code),
+        };
+        Ok(Self { time, data })
+    }
+}
+
+/// Holds back a trailing incomplete UTF-8 sequence so that a multi-byte
+/// character split across two reads is recorded intact.
+/// Invalid sequences are recorded as U+FFFD.
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let mut result = String::new();
+    let mut bytes = &pending[..];
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => {
+                result.push_str(s);
+                bytes = &[];
+                break;
+            }
+            Err(err) => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                result.push_str(std::str::from_utf8(valid).expect("validated above"));
+                match err.error_len() {
+                    Some(len) => {
+                        result.push(std::char::REPLACEMENT_CHARACTER);
+                        bytes = &rest[len..];
+                    }
+                    None => {
+                        bytes = rest;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    let remainder = bytes.to_vec();
+    *pending = remainder;
+    result
+}
+
+/// How often buffered events are flushed to the output stream while
+/// recording.  The remainder is flushed when the recorder is dropped.
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Writes events to an asciicast v2 stream, timestamping them relative
+/// to the point at which the recorder was created
+pub struct Recorder {
+    out: Box<dyn Write + Send>,
+    start: Instant,
+    last_flush: Instant,
+    unflushed: bool,
+    record_input: bool,
+    pending_output: Vec<u8>,
+    pending_input: Vec<u8>,
+}
+
+impl Recorder {
+    /// Write the header to `out` and start the clock.
+    /// Input events are only recorded if `record_input` is true.
+    pub fn new(
+        mut out: Box<dyn Write + Send>,
+        header: &Header,
+        record_input: bool,
+    ) -> anyhow::Result<Self> {
+        writeln!(out, "{}", serde_json::to_string(header)?)?;
+        out.flush()?;
+        let start = Instant::now();
+        Ok(Self {
+            out,
+            start,
+            last_flush: start,
+            unflushed: false,
+            record_input,
+            pending_output: vec![],
+            pending_input: vec![],
+        })
+    }
+
+    fn write_event(&mut self, data: EventData) -> anyhow::Result<()> {
+        let event = Event {
+            time: self.start.elapsed().as_secs_f64(),
+            data,
+        };
+        writeln!(self.out, "{}", event.to_json()?)?;
+        self.unflushed = true;
+        self.flush_if_due()
+    }
+
+    /// Flush the events that were written since the last flush, if
+    /// FLUSH_INTERVAL has passed since then.  This is called
+    /// periodically so that events are flushed even if the session
+    /// becomes idle.
+    pub fn flush_if_due(&mut self) -> anyhow::Result<()> {
+        if self.unflushed && self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.out.flush()?;
+            self.last_flush = Instant::now();
+            self.unflushed = false;
+        }
+        Ok(())
+    }
+
+    /// Record data that was output by the pty
+    pub fn output(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        self.pending_output.extend_from_slice(data);
+        let text = take_utf8(&mut self.pending_output);
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.write_event(EventData::Output(text))
+    }
+
+    /// Record data that was sent to the pty
+    pub fn input(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        if !self.record_input {
+            return Ok(());
+        }
+        self.pending_input.extend_from_slice(data);
+        let text = take_utf8(&mut self.pending_input);
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.write_event(EventData::Input(text))
+    }
+
+    /// Record a change in the terminal dimensions
+    pub fn resize(&mut self, cols: usize, rows: usize) -> anyhow::Result<()> {
+        self.write_event(EventData::Resize { cols, rows })
+    }
+
+    /// Record a named point of interest
+    pub fn marker(&mut self, label: &str) -> anyhow::Result<()> {
+        self.write_event(EventData::Marker(label.to_string()))
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(err) = self.out.flush() {
+            log::error!("error while flushing recording: {:#}", err);
+        }
+    }
+}
+
+/// Parse a complete recording
+pub fn read_recording<R: BufRead>(reader: R) -> anyhow::Result<(Header, Vec<Event>)> {
+    let mut lines = reader.lines();
+    let header = lines
+        .next()
+        .ok_or_else(|| anyhow!("recording is empty"))??;
+    let header: Header = serde_json::from_str(&header).context("parsing asciicast header")?;
+    if header.version != 2 {
+        bail!(
+            "unsupported asciicast version {}; only version 2 is supported",
+            header.version
+        );
+    }
+
+    let mut events = vec![];
+    for (idx, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // The header is line 1
+        let event = Event::from_json(&line).with_context(|| format!("parsing line {}", idx + 2))?;
+        events.push(event);
+    }
+    Ok((header, events))
+}
+
+/// Controls the pacing of a replayed recording
+#[derive(Debug, Clone, Copy)]
+pub struct ReplayTiming {
+    /// Playback speed multiplier; 2.0 plays twice as fast as recorded
+    pub speed: f64,
+    /// If set, pauses between events are limited to this duration
+    /// (measured before applying the speed multiplier)
+    pub max_idle: Option<Duration>,
+}
+
+impl Default for ReplayTiming {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            max_idle: None,
+        }
+    }
+}
+
+impl ReplayTiming {
+    /// Returns the delay to observe before an event at `time` that
+    /// follows an event at `prev_time`
+    pub fn delay(&self, prev_time: f64, time: f64) -> Duration {
+        let mut delay = duration_from_secs(time - prev_time);
+        if let Some(max_idle) = self.max_idle {
+            delay = delay.min(max_idle);
+        }
+        if self.speed > 0. {
+            duration_from_secs(delay.as_secs_f64() / self.speed)
+        } else {
+            delay
+        }
+    }
+}
+
+/// Converts seconds to a Duration, clamping negative and NaN values
+/// to zero and values that are too large to represent to the maximum,
+/// rather than panicking as Duration::from_secs_f64 would
+pub fn duration_from_secs(secs: f64) -> Duration {
+    if secs.is_nan() || secs <= 0. {
+        Duration::from_secs(0)
+    } else if secs >= u64::MAX as f64 {
+        Duration::from_secs(u64::MAX)
+    } else {
+        Duration::from_secs_f64(secs)
+    }
+}
+
+enum ReplayControl {
+    Continue,
+    TogglePause,
+    Quit,
+}
+
+fn replay_control(input: Option<InputEvent>) -> ReplayControl {
+    match input {
+        Some(InputEvent::Key(KeyEvent {
+            key: KeyCode::Char('q'),
+            ..
+        }))
+        | Some(InputEvent::Key(KeyEvent {
+            key: KeyCode::Escape,
+            ..
+        }))
+        | Some(InputEvent::Key(KeyEvent {
+            key: KeyCode::Char('C'),
+            modifiers: Modifiers::CTRL,
+        })) => ReplayControl::Quit,
+        Some(InputEvent::Key(KeyEvent {
+            key: KeyCode::Char(' '),
+            ..
+        })) => ReplayControl::TogglePause,
+        _ => ReplayControl::Continue,
+    }
+}
+
+/// Feed the output events of a recording to the pane associated with
+/// `term`, pacing them according to `timing`.
+/// While replaying, space pauses and resumes, and `q`, Escape or
+/// CTRL-C stop the replay.
+/// Returns false if the replay was stopped before it completed.
+pub fn replay(
+    term: &mut TermWizTerminal,
+    events: &[Event],
+    timing: ReplayTiming,
+) -> anyhow::Result<bool> {
+    let mut prev_time = 0.;
+    for event in events {
+        let mut deadline = Instant::now() + timing.delay(prev_time, event.time);
+        prev_time = event.time;
+
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            match replay_control(term.poll_input(Some(deadline - now))?) {
+                ReplayControl::Continue => {}
+                ReplayControl::Quit => return Ok(false),
+                ReplayControl::TogglePause => {
+                    let paused_at = Instant::now();
+                    loop {
+                        match replay_control(term.poll_input(None)?) {
+                            ReplayControl::Continue => {}
+                            ReplayControl::Quit => return Ok(false),
+                            ReplayControl::TogglePause => break,
+                        }
+                    }
+                    deadline += paused_at.elapsed();
+                }
+            }
+        }
+
+        match &event.data {
+            EventData::Output(data) => {
+                term.write_raw(data.as_bytes())?;
+                term.flush()?;
+            }
+            EventData::Resize { cols, rows } => {
+                // The size of the pane is controlled by its window
+                log::debug!("ignoring recorded resize to {}x{}", cols, rows);
+            }
+            EventData::Input(_) | EventData::Marker(_) => {}
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>, Arc<Mutex<usize>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            *self.1.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn flush_on_drop() {
+        let buf = SharedBuf::default();
+        let mut recorder =
+            Recorder::new(Box::new(buf.clone()), &Header::new(80, 24), false).unwrap();
+        let flushes = *buf.1.lock().unwrap();
+        for _ in 0..10 {
+            recorder.output(b"hello").unwrap();
+        }
+        assert_eq!(*buf.1.lock().unwrap(), flushes);
+        drop(recorder);
+        assert_eq!(*buf.1.lock().unwrap(), flushes + 1);
+    }
+
+    #[test]
+    fn flush_when_idle() {
+        let buf = SharedBuf::default();
+        let mut recorder =
+            Recorder::new(Box::new(buf.clone()), &Header::new(80, 24), false).unwrap();
+        let flushes = *buf.1.lock().unwrap();
+        recorder.output(b"hello").unwrap();
+        recorder.flush_if_due().unwrap();
+        assert_eq!(*buf.1.lock().unwrap(), flushes);
+
+        recorder.last_flush -= FLUSH_INTERVAL;
+        recorder.flush_if_due().unwrap();
+        assert_eq!(*buf.1.lock().unwrap(), flushes + 1);
+
+        // There is nothing new to flush
+        recorder.last_flush -= FLUSH_INTERVAL;
+        recorder.flush_if_due().unwrap();
+        assert_eq!(*buf.1.lock().unwrap(), flushes + 1);
+    }
+
+    #[test]
+    fn round_trip() {
+        let buf = SharedBuf::default();
+        let mut header = Header::new(80, 24);
+        header.env.clear();
+        let mut recorder = Recorder::new(Box::new(buf.clone()), &header, false).unwrap();
+        recorder.output(b"hello\r\n").unwrap();
+        recorder.input(b"ignored").unwrap();
+        recorder.resize(100, 30).unwrap();
+        recorder.marker("done").unwrap();
+        drop(recorder);
+
+        let data = buf.0.lock().unwrap().clone();
+        let (parsed_header, events) = read_recording(&data[..]).unwrap();
+        assert_eq!(parsed_header, header);
+        let events: Vec<EventData> = events.into_iter().map(|e| e.data).collect();
+        assert_eq!(
+            events,
+            vec![
+                EventData::Output("hello\r\n".to_string()),
+                EventData::Resize {
+                    cols: 100,
+                    rows: 30
+                },
+                EventData::Marker("done".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_utf8() {
+        let buf = SharedBuf::default();
+        let mut recorder =
+            Recorder::new(Box::new(buf.clone()), &Header::new(80, 24), true).unwrap();
+        let bytes = "a❤".as_bytes();
+        recorder.input(&bytes[0..2]).unwrap();
+        recorder.input(&bytes[2..]).unwrap();
+        recorder.output(b"\xffb").unwrap();
+        drop(recorder);
+
+        let data = buf.0.lock().unwrap().clone();
+        let (_, events) = read_recording(&data[..]).unwrap();
+        let events: Vec<EventData> = events.into_iter().map(|e| e.data).collect();
+        assert_eq!(
+            events,
+            vec![
+                EventData::Input("a".to_string()),
+                EventData::Input("❤".to_string()),
+                EventData::Output("\u{fffd}b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_asciinema() {
+        let data = "{\"version\": 2, \"width\": 10, \"height\": 2, \"timestamp\": 1504467315}\n\
+                    [0.248848, \"o\", \"\\u001b[1;31mhi\"]\n\
+                    [1.001376, \"r\", \"20x4\"]\n";
+        let (header, events) = read_recording(data.as_bytes()).unwrap();
+        assert_eq!(header.width, 10);
+        assert_eq!(header.timestamp, Some(1504467315));
+        assert_eq!(
+            events[0].data,
+            EventData::Output("\x1b[1;31mhi".to_string())
+        );
+        assert_eq!(events[1].data, EventData::Resize { cols: 20, rows: 4 });
+
+        assert!(
+            read_recording("{\"version\": 1, \"width\": 1, \"height\": 1}".as_bytes()).is_err()
+        );
+    }
+
+    #[test]
+    fn timing() {
+        let timing = ReplayTiming {
+            speed: 2.0,
+            max_idle: Some(Duration::from_secs(1)),
+        };
+        assert_eq!(timing.delay(1.0, 2.0), Duration::from_millis(500));
+        assert_eq!(timing.delay(0.0, 10.0), Duration::from_millis(500));
+        assert_eq!(timing.delay(2.0, 1.0), Duration::from_secs(0));
+
+        // Timestamps that are out of range don't panic
+        let timing = ReplayTiming {
+            speed: 1e-300,
+            max_idle: None,
+        };
+        assert_eq!(timing.delay(0.0, 1e300), Duration::from_secs(u64::MAX));
+        assert_eq!(timing.delay(0.0, 1.0), Duration::from_secs(u64::MAX));
+        assert_eq!(timing.delay(f64::NAN, 1.0), Duration::from_secs(0));
+        assert_eq!(
+            ReplayTiming::default().delay(0.0, f64::INFINITY),
+            Duration::from_secs(u64::MAX)
+        );
+    }
+}
@@ -5,7 +5,7 @@
 //! container or actually remote, running on the other end
 //! of an ssh session somewhere.
 
-use crate::localpane::LocalPane;
+use crate::localpane::{LocalPane, PaneRecorder};
 use crate::pane::{alloc_pane_id, Pane, PaneId};
 use crate::tab::{SplitDirection, Tab, TabId};
 use crate::window::WindowId;
@@ -13,6 +13,7 @@ use crate::Mux;
 use anyhow::{bail, Error};
 use async_trait::async_trait;
 use config::configuration;
+use config::keyassignment::{PaneDirection, RotationDirection};
 use downcast_rs::{impl_downcast, Downcast};
 use portable_pty::{native_pty_system, CommandBuilder, PtySize, PtySystem};
 use std::rc::Rc;
@@ -50,6 +51,48 @@ pub trait Domain: Downcast {
         split_direction: SplitDirection,
     ) -> anyhow::Result<Rc<dyn Pane>>;
 
+    /// Swaps the positions of two panes that are in the same tab.
+    /// The default implementation rearranges the local tab; domains
+    /// that mirror a remote mux override this to ask the remote
+    /// mux to perform the operation.
+    async fn swap_panes(&self, pane_id: PaneId, other_pane_id: PaneId) -> anyhow::Result<()> {
+        Mux::get().unwrap().swap_panes(pane_id, other_pane_id)
+    }
+
+    /// Rotates the panes in the tab that contains the specified pane
+    async fn rotate_panes(
+        &self,
+        pane_id: PaneId,
+        direction: RotationDirection,
+    ) -> anyhow::Result<()> {
+        Mux::get().unwrap().rotate_panes(pane_id, direction)
+    }
+
+    /// Moves a pane out of its tab and into a new tab in the specified
+    /// window, or in a new window if `window_id` is None.
+    /// Returns the new tab and the id of the window that contains it.
+    async fn move_pane_to_new_tab(
+        &self,
+        pane_id: PaneId,
+        window_id: Option<WindowId>,
+    ) -> anyhow::Result<(Rc<Tab>, WindowId)> {
+        Mux::get().unwrap().move_pane_to_new_tab(pane_id, window_id)
+    }
+
+    /// Moves a pane out of its tab and into the tab that contains
+    /// `target_pane_id`, placing it on the specified side of the
+    /// target pane.
+    async fn move_pane_to_tab(
+        &self,
+        pane_id: PaneId,
+        target_pane_id: PaneId,
+        direction: PaneDirection,
+    ) -> anyhow::Result<()> {
+        Mux::get()
+            .unwrap()
+            .move_pane_to_tab(pane_id, target_pane_id, direction)
+    }
+
     /// Returns false if the `spawn` method will never succeed.
     /// There are some internal placeholder domains that are
     /// pre-created with local UI that we do not want to allow
@@ -137,13 +180,14 @@ impl Domain for LocalDomain {
         log::trace!("spawned: {:?}", child);
 
         let writer = pair.master.try_clone_writer()?;
+        let recorder = PaneRecorder::new();
 
         let terminal = wezterm_term::Terminal::new(
             crate::pty_size_to_terminal_size(size),
             std::sync::Arc::new(config::TermConfig {}),
             "WezTerm",
             config::wezterm_version(),
-            Box::new(writer),
+            recorder.wrap_writer(Box::new(writer)),
         );
 
         let mux = Mux::get().unwrap();
@@ -153,6 +197,7 @@ impl Domain for LocalDomain {
             child,
             pair.master,
             self.id,
+            recorder,
         ));
 
         let tab = Rc::new(Tab::new(&size));
@@ -216,13 +261,14 @@ impl Domain for LocalDomain {
         log::trace!("spawned: {:?}", child);
 
         let writer = pair.master.try_clone_writer()?;
+        let recorder = PaneRecorder::new();
 
         let terminal = wezterm_term::Terminal::new(
             crate::pty_size_to_terminal_size(split_size.second),
             std::sync::Arc::new(config::TermConfig {}),
             "WezTerm",
             config::wezterm_version(),
-            Box::new(writer),
+            recorder.wrap_writer(Box::new(writer)),
         );
 
         let pane: Rc<dyn Pane> = Rc::new(LocalPane::new(
@@ -231,6 +277,7 @@ impl Domain for LocalDomain {
             child,
             pair.master,
             self.id,
+            recorder,
         ));
 
         tab.split_and_insert(pane_index, direction, Rc::clone(&pane))?;
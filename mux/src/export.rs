@@ -0,0 +1,482 @@
+//! Renders the contents of a pane as plain text, as text with ANSI
+//! escape sequences or as a styled HTML document, so that it can be
+//! saved to a file and attached to a bug report.
+//! The attributes of the cells are regenerated from `Line::changes`,
+//! so the export reflects what is on the screen rather than the
+//! exact sequence of escapes that produced it.
+use crate::pane::Pane;
+use anyhow::{anyhow, bail};
+use config::keyassignment::{ExportFormat, ExportScope};
+use std::ops::Range;
+use termwiz::cell::{Blink, CellAttributes, Intensity, SemanticType, Underline};
+use termwiz::color::{ColorAttribute, ColorSpec};
+use termwiz::escape::csi::{Sgr, CSI};
+use termwiz::escape::osc::OperatingSystemCommand;
+use termwiz::surface::Change;
+use wezterm_term::color::ColorPalette;
+use wezterm_term::{Line, StableRowIndex};
+
+/// The number of rows that are fetched from the pane at a time
+const ROWS_PER_FETCH: StableRowIndex = 1000;
+
+/// Exports `scope` from `pane` in the requested format.
+/// The selection is held by the gui, so `ExportScope::Selection`
+/// must be resolved by the caller and exported via `export_region`.
+pub fn export_pane(
+    pane: &dyn Pane,
+    format: ExportFormat,
+    scope: ExportScope,
+) -> anyhow::Result<String> {
+    match scope {
+        ExportScope::Scrollback => {
+            let dims = pane.get_dimensions();
+            let rows =
+                dims.scrollback_top..dims.physical_top + dims.viewport_rows as StableRowIndex;
+            Ok(export_region(pane, rows, |_| 0..usize::max_value(), format))
+        }
+        ExportScope::LastSemanticZone(semantic_type) => {
+            let zone = pane
+                .get_semantic_zones()?
+                .into_iter()
+                .rev()
+                .find(|zone| zone.semantic_type == semantic_type)
+                .ok_or_else(|| anyhow!("pane has no {:?} zone", semantic_type))?;
+            let rows = zone.start_y..zone.end_y + 1;
+            Ok(export_region(
+                pane,
+                rows,
+                |row| {
+                    let start = if row == zone.start_y { zone.start_x } else { 0 };
+                    let end = if row == zone.end_y {
+                        zone.end_x.saturating_add(1)
+                    } else {
+                        usize::max_value()
+                    };
+                    start..end
+                },
+                format,
+            ))
+        }
+        ExportScope::Selection => bail!("the selection can only be exported by the gui"),
+    }
+}
+
+/// Exports the cells in `cols_for_row(row)` for each of the `rows`
+/// of `pane` in the requested format
+pub fn export_region<F: Fn(StableRowIndex) -> Range<usize>>(
+    pane: &dyn Pane,
+    rows: Range<StableRowIndex>,
+    cols_for_row: F,
+    format: ExportFormat,
+) -> String {
+    let mut exporter = Exporter::new(format, pane.palette());
+    if format == ExportFormat::Html {
+        exporter.html_header(&pane.get_title());
+    }
+
+    let mut start = rows.start;
+    while start < rows.end {
+        let end = (start + ROWS_PER_FETCH).min(rows.end);
+        let (first_row, lines) = pane.get_lines(start..end);
+        for (idx, line) in lines.iter().enumerate() {
+            let row = first_row + idx as StableRowIndex;
+            exporter.add_line(line, cols_for_row(row));
+        }
+        start = end;
+    }
+
+    if format == ExportFormat::Html {
+        exporter.html_footer();
+    }
+    exporter.output
+}
+
+struct Exporter {
+    format: ExportFormat,
+    palette: ColorPalette,
+    output: String,
+    /// Whether a line has been added yet
+    started: bool,
+    /// Whether the most recently added line wrapped onto the next
+    last_was_wrapped: bool,
+}
+
+impl Exporter {
+    fn new(format: ExportFormat, palette: ColorPalette) -> Self {
+        Self {
+            format,
+            palette,
+            output: String::new(),
+            started: false,
+            last_was_wrapped: false,
+        }
+    }
+
+    fn add_line(&mut self, line: &Line, cols: Range<usize>) {
+        // Lines that were wrapped are joined back together, so that
+        // the export can be re-flowed to a different width
+        if self.started && !self.last_was_wrapped {
+            self.output.push('\n');
+        }
+        self.started = true;
+
+        let last_col_idx = cols.end.min(line.cells().len()).saturating_sub(1);
+        self.last_was_wrapped = line
+            .cells()
+            .get(last_col_idx)
+            .map(|cell| cell.attrs().wrapped())
+            .unwrap_or(false);
+        let line = sub_line(line, cols);
+
+        match self.format {
+            ExportFormat::Text => {
+                let text = line.as_str();
+                // Trailing spaces of a wrapped line separate it from
+                // the text that continues on the next
+                if self.last_was_wrapped {
+                    self.output.push_str(&text);
+                } else {
+                    self.output.push_str(text.trim_end());
+                }
+            }
+            ExportFormat::Ansi => self.add_ansi_line(&line),
+            ExportFormat::Html => self.add_html_line(&line),
+        }
+    }
+
+    fn add_ansi_line(&mut self, line: &Line) {
+        let default_attrs = CellAttributes::default();
+        let mut current = default_attrs.clone();
+        for change in line.changes(&default_attrs) {
+            match change {
+                Change::AllAttributes(attrs) => {
+                    self.output.push_str(&sgr_for_attributes(&current, &attrs));
+                    current = attrs;
+                }
+                Change::Text(text) => self.output.push_str(&text),
+                _ => {}
+            }
+        }
+
+        // Each line is self-contained, so that the export can be
+        // viewed in part with tools such as `less -R`
+        if current != default_attrs {
+            self.output
+                .push_str(&sgr_for_attributes(&current, &default_attrs));
+        }
+    }
+
+    fn add_html_line(&mut self, line: &Line) {
+        let default_attrs = CellAttributes::default();
+        let mut close = "";
+        for change in line.changes(&default_attrs) {
+            match change {
+                Change::AllAttributes(attrs) => {
+                    self.output.push_str(close);
+                    close = "";
+                    let css = css_for_attributes(&attrs, &self.palette);
+                    if !css.is_empty() {
+                        self.output.push_str("<span style=\"");
+                        self.output.push_str(&css);
+                        self.output.push_str("\">");
+                        close = "</span>";
+                    }
+                    if let Some(link) = attrs.hyperlink().filter(|link| is_safe_href(link.uri())) {
+                        self.output.push_str("<a href=\"");
+                        self.output.push_str(&html_escape(link.uri()));
+                        self.output.push_str("\">");
+                        close = if css.is_empty() {
+                            "</a>"
+                        } else {
+                            "</a></span>"
+                        };
+                    }
+                }
+                Change::Text(text) => self.output.push_str(&html_escape(&text)),
+                _ => {}
+            }
+        }
+        self.output.push_str(close);
+    }
+
+    fn html_header(&mut self, title: &str) {
+        let fg = self.palette.foreground.to_rgb_string();
+        let bg = self.palette.background.to_rgb_string();
+        self.output.push_str(&format!(
+            "<!DOCTYPE html>\n\
+             <html>\n\
+             <head>\n\
+             <meta charset=\"utf-8\">\n\
+             <title>{}</title>\n\
+             </head>\n\
+             <body style=\"background-color: {}\">\n\
+             <pre style=\"color: {}; background-color: {}; font-family: monospace\">",
+            html_escape(title),
+            bg,
+            fg,
+            bg
+        ));
+    }
+
+    fn html_footer(&mut self) {
+        self.output.push_str("</pre>\n</body>\n</html>\n");
+    }
+}
+
+/// Returns a line holding just the cells of `line` that are in `cols`.
+/// The wrapped and semantic type attributes are reset, as they don't
+/// affect the appearance of the cells and would otherwise break up
+/// the runs produced by `Line::changes`.
+fn sub_line(line: &Line, cols: Range<usize>) -> Line {
+    let mut result = Line::with_width(0);
+    for (idx, cell) in line.visible_cells() {
+        if idx < cols.start {
+            continue;
+        }
+        if idx >= cols.end {
+            break;
+        }
+        let mut cell = cell.clone();
+        cell.attrs_mut()
+            .set_wrapped(false)
+            .set_semantic_type(SemanticType::default());
+        result.set_cell(idx - cols.start, cell);
+    }
+    result
+}
+
+fn color_spec(color: ColorAttribute) -> ColorSpec {
+    match color {
+        ColorAttribute::Default => ColorSpec::Default,
+        ColorAttribute::PaletteIndex(idx) => ColorSpec::PaletteIndex(idx),
+        ColorAttribute::TrueColorWithPaletteFallback(color, _)
+        | ColorAttribute::TrueColorWithDefaultFallback(color) => ColorSpec::TrueColor(color),
+    }
+}
+
+/// Returns the escape sequences that switch from the attributes
+/// `prior` to `attrs`
+fn sgr_for_attributes(prior: &CellAttributes, attrs: &CellAttributes) -> String {
+    let mut sgr = vec![Sgr::Reset];
+    if attrs.intensity() != Intensity::Normal {
+        sgr.push(Sgr::Intensity(attrs.intensity()));
+    }
+    if attrs.underline() != Underline::None {
+        sgr.push(Sgr::Underline(attrs.underline()));
+    }
+    if attrs.underline_color() != ColorAttribute::Default {
+        sgr.push(Sgr::UnderlineColor(color_spec(attrs.underline_color())));
+    }
+    if attrs.blink() != Blink::None {
+        sgr.push(Sgr::Blink(attrs.blink()));
+    }
+    if attrs.italic() {
+        sgr.push(Sgr::Italic(true));
+    }
+    if attrs.reverse() {
+        sgr.push(Sgr::Inverse(true));
+    }
+    if attrs.invisible() {
+        sgr.push(Sgr::Invisible(true));
+    }
+    if attrs.strikethrough() {
+        sgr.push(Sgr::StrikeThrough(true));
+    }
+    if attrs.overline() {
+        sgr.push(Sgr::Overline(true));
+    }
+    if attrs.foreground() != ColorAttribute::Default {
+        sgr.push(Sgr::Foreground(color_spec(attrs.foreground())));
+    }
+    if attrs.background() != ColorAttribute::Default {
+        sgr.push(Sgr::Background(color_spec(attrs.background())));
+    }
+
+    let mut result: String = sgr.into_iter().map(|s| CSI::Sgr(s).to_string()).collect();
+    if prior.hyperlink() != attrs.hyperlink() {
+        let link = attrs.hyperlink().map(|link| (**link).clone());
+        result.push_str(&OperatingSystemCommand::SetHyperlink(link).to_string());
+    }
+    result
+}
+
+/// Returns the inline CSS that renders `attrs` using `palette`
+fn css_for_attributes(attrs: &CellAttributes, palette: &ColorPalette) -> String {
+    let mut fg = palette.resolve_fg(attrs.foreground());
+    let mut bg = palette.resolve_bg(attrs.background());
+    let mut fg_changed = attrs.foreground() != ColorAttribute::Default;
+    let mut bg_changed = attrs.background() != ColorAttribute::Default;
+    if attrs.reverse() {
+        std::mem::swap(&mut fg, &mut bg);
+        fg_changed = true;
+        bg_changed = true;
+    }
+    if attrs.invisible() {
+        fg = bg;
+        fg_changed = true;
+    }
+
+    let mut css = vec![];
+    if fg_changed {
+        css.push(format!("color: {}", fg.to_rgb_string()));
+    }
+    if bg_changed {
+        css.push(format!("background-color: {}", bg.to_rgb_string()));
+    }
+    match attrs.intensity() {
+        Intensity::Normal => {}
+        Intensity::Bold => css.push("font-weight: bold".to_string()),
+        Intensity::Half => css.push("opacity: 0.6".to_string()),
+    }
+    if attrs.italic() {
+        css.push("font-style: italic".to_string());
+    }
+
+    let mut decoration = vec![];
+    if attrs.underline() != Underline::None {
+        decoration.push("underline");
+        let style = match attrs.underline() {
+            Underline::Double => Some("double"),
+            Underline::Curly => Some("wavy"),
+            Underline::Dotted => Some("dotted"),
+            Underline::Dashed => Some("dashed"),
+            Underline::None | Underline::Single => None,
+        };
+        if let Some(style) = style {
+            css.push(format!("text-decoration-style: {}", style));
+        }
+        if attrs.underline_color() != ColorAttribute::Default {
+            css.push(format!(
+                "text-decoration-color: {}",
+                palette.resolve_fg(attrs.underline_color()).to_rgb_string()
+            ));
+        }
+    }
+    if attrs.strikethrough() {
+        decoration.push("line-through");
+    }
+    if attrs.overline() {
+        decoration.push("overline");
+    }
+    if !decoration.is_empty() {
+        css.push(format!("text-decoration-line: {}", decoration.join(" ")));
+    }
+
+    css.join("; ")
+}
+
+/// Returns true if `uri` can be used as the target of a link in the
+/// exported html.  Other schemes, such as `javascript:`, could run
+/// code when the link is followed.
+fn is_safe_href(uri: &str) -> bool {
+    match url::Url::parse(uri) {
+        Ok(url) => matches!(url.scheme(), "http" | "https" | "file"),
+        Err(_) => false,
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use termwiz::color::AnsiColor;
+    use termwiz::hyperlink::Hyperlink;
+
+    fn export_lines(format: ExportFormat, lines: &[Line]) -> String {
+        let mut exporter = Exporter::new(format, ColorPalette::default());
+        for line in lines {
+            exporter.add_line(line, 0..usize::max_value());
+        }
+        exporter.output
+    }
+
+    fn sample_lines() -> Vec<Line> {
+        let mut red = CellAttributes::default();
+        red.set_foreground(AnsiColor::Maroon)
+            .set_intensity(Intensity::Bold);
+        let mut first = Line::from_text("ls <&>   ", &CellAttributes::default());
+        for (idx, cell) in Line::from_text("red", &red).cells().iter().enumerate() {
+            first.set_cell(idx + 9, cell.clone());
+        }
+        vec![first, Line::from_text("done", &CellAttributes::default())]
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(
+            export_lines(ExportFormat::Text, &sample_lines()),
+            "ls <&>   red\ndone"
+        );
+    }
+
+    #[test]
+    fn ansi() {
+        assert_eq!(
+            export_lines(ExportFormat::Ansi, &sample_lines()),
+            "ls <&>   \u{1b}[0m\u{1b}[1m\u{1b}[31mred\u{1b}[0m\ndone"
+        );
+    }
+
+    #[test]
+    fn html() {
+        assert_eq!(
+            export_lines(ExportFormat::Html, &sample_lines()),
+            "ls &lt;&amp;&gt;   \
+             <span style=\"color: #cc5555; font-weight: bold\">red</span>\ndone"
+        );
+    }
+
+    #[test]
+    fn wrapped_lines_are_joined() {
+        let mut first = Line::from_text("abc", &CellAttributes::default());
+        first.set_last_cell_was_wrapped(true);
+        let second = Line::from_text("def", &CellAttributes::default());
+        assert_eq!(export_lines(ExportFormat::Text, &[first, second]), "abcdef");
+    }
+
+    #[test]
+    fn lines_wrapped_at_a_space_are_joined() {
+        let mut first = Line::from_text("abc ", &CellAttributes::default());
+        first.set_last_cell_was_wrapped(true);
+        let second = Line::from_text("def", &CellAttributes::default());
+        assert_eq!(
+            export_lines(ExportFormat::Text, &[first, second]),
+            "abc def"
+        );
+    }
+
+    #[test]
+    fn only_safe_links_are_exported() {
+        let link_line = |uri: &str| {
+            let mut attrs = CellAttributes::default();
+            attrs.set_hyperlink(Some(Arc::new(Hyperlink::new(uri))));
+            Line::from_text("x", &attrs)
+        };
+        assert_eq!(
+            export_lines(ExportFormat::Html, &[link_line("https://wezfurlong.org/")]),
+            "<a href=\"https://wezfurlong.org/\">x</a>"
+        );
+        assert_eq!(
+            export_lines(ExportFormat::Html, &[link_line("file:///tmp/a%20b")]),
+            "<a href=\"file:///tmp/a%20b\">x</a>"
+        );
+        assert_eq!(
+            export_lines(ExportFormat::Html, &[link_line("javascript:alert(1)")]),
+            "x"
+        );
+    }
+}
@@ -24,8 +24,10 @@ use thiserror::*;
 use winapi::um::winsock2::{SOL_SOCKET, SO_RCVBUF, SO_SNDBUF};
 
 pub mod activity;
+pub mod asciicast;
 pub mod connui;
 pub mod domain;
+pub mod export;
 pub mod localpane;
 pub mod pane;
 pub mod renderable;
@@ -42,9 +44,16 @@ pub enum MuxNotification {
     PaneOutput(PaneId),
     PaneAdded(PaneId),
     PaneRemoved(PaneId),
+    /// The process running in a local pane has terminated
+    PaneExited {
+        pane_id: PaneId,
+        status: portable_pty::ExitStatus,
+    },
     WindowCreated(WindowId),
     WindowRemoved(WindowId),
     WindowInvalidated(WindowId),
+    WindowWorkspaceChanged(WindowId),
+    ActiveWorkspaceChanged(String),
     Alert {
         pane_id: PaneId,
         alert: wezterm_term::Alert,
@@ -63,8 +72,12 @@ pub struct Mux {
     domains_by_name: RefCell<HashMap<String, Arc<dyn Domain>>>,
     subscribers: RefCell<HashMap<usize, Box<dyn Fn(MuxNotification) -> bool>>>,
     banner: RefCell<Option<String>>,
+    active_workspace: RefCell<String>,
 }
 
+/// The name of the workspace that is used when none is configured
+pub const DEFAULT_WORKSPACE: &str = "default";
+
 const BUFSIZE: usize = 1024 * 1024;
 
 /// This function bounces parsed actions over to the main thread to feed to
@@ -283,6 +296,12 @@ impl Mux {
             domains: RefCell::new(domains),
             subscribers: RefCell::new(HashMap::new()),
             banner: RefCell::new(None),
+            active_workspace: RefCell::new(
+                configuration()
+                    .default_workspace
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_WORKSPACE.to_string()),
+            ),
         }
     }
 
@@ -432,6 +451,16 @@ impl Mux {
         self.prune_dead_windows();
     }
 
+    /// Kills the pane, removing it from the split tree of the tab
+    /// that contains it, if any, so that its space is given to
+    /// its neighbor
+    pub fn kill_pane(&self, pane_id: PaneId) {
+        if let Ok(tab) = self.tab_containing_pane(pane_id) {
+            tab.remove_pane(pane_id);
+        }
+        self.remove_pane(pane_id);
+    }
+
     pub fn remove_tab(&self, tab_id: TabId) -> Option<Rc<Tab>> {
         let tab = self.remove_tab_internal(tab_id);
         self.prune_dead_windows();
@@ -512,8 +541,10 @@ impl Mux {
         window.get_active().map(Rc::clone)
     }
 
-    pub fn new_empty_window(&self) -> MuxWindowBuilder {
-        let window = Window::new();
+    /// Creates a new window in the specified workspace, or in the
+    /// active workspace if `workspace` is None
+    pub fn new_empty_window(&self, workspace: Option<String>) -> MuxWindowBuilder {
+        let window = Window::new(workspace.unwrap_or_else(|| self.active_workspace()));
         let window_id = window.window_id();
         self.windows.borrow_mut().insert(window_id, window);
         MuxWindowBuilder {
@@ -558,6 +589,78 @@ impl Mux {
         self.windows.borrow().keys().cloned().collect()
     }
 
+    /// Returns the name of the workspace whose windows are shown
+    pub fn active_workspace(&self) -> String {
+        self.active_workspace.borrow().clone()
+    }
+
+    /// Changes the active workspace.  The windows in the prior workspace
+    /// are kept in the mux, but are hidden by the gui.
+    pub fn set_active_workspace(&self, workspace: &str) {
+        if *self.active_workspace.borrow() == workspace {
+            return;
+        }
+        *self.active_workspace.borrow_mut() = workspace.to_string();
+        self.notify(MuxNotification::ActiveWorkspaceChanged(
+            workspace.to_string(),
+        ));
+    }
+
+    /// Moves a window to the named workspace
+    pub fn set_window_workspace(&self, window_id: WindowId, workspace: &str) {
+        {
+            let mut window = match self.get_window_mut(window_id) {
+                Some(window) => window,
+                None => return,
+            };
+            if window.get_workspace() == workspace {
+                return;
+            }
+            window.set_workspace(workspace);
+        }
+        self.notify(MuxNotification::WindowWorkspaceChanged(window_id));
+    }
+
+    /// Returns the sorted names of the workspaces that contain windows
+    pub fn iter_workspaces(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .windows
+            .borrow()
+            .values()
+            .map(|w| w.get_workspace().to_string())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn iter_windows_in_workspace(&self, workspace: &str) -> Vec<WindowId> {
+        let mut windows: Vec<WindowId> = self
+            .windows
+            .borrow()
+            .iter()
+            .filter_map(|(window_id, w)| {
+                if w.get_workspace() == workspace {
+                    Some(*window_id)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        windows.sort();
+        windows
+    }
+
+    /// Returns a workspace name that is not yet in use
+    pub fn generate_workspace_name(&self) -> String {
+        let used = self.iter_workspaces();
+        let active = self.active_workspace();
+        (1..)
+            .map(|n| format!("workspace-{}", n))
+            .find(|name| *name != active && !used.contains(name))
+            .unwrap()
+    }
+
     pub fn iter_domains(&self) -> Vec<Arc<dyn Domain>> {
         self.domains.borrow().values().cloned().collect()
     }
@@ -577,6 +680,155 @@ impl Mux {
         Some((domain_id, window_id, tab_id))
     }
 
+    /// Returns the tab that contains the specified pane
+    pub fn tab_containing_pane(&self, pane_id: PaneId) -> anyhow::Result<Rc<Tab>> {
+        self.tabs
+            .borrow()
+            .values()
+            .find(|tab| tab.contains_pane(pane_id))
+            .map(Rc::clone)
+            .ok_or_else(|| anyhow!("pane {} is not in any tab", pane_id))
+    }
+
+    fn invalidate_window_containing_tab(&self, tab_id: TabId) {
+        if let Some(window_id) = self.window_containing_tab(tab_id) {
+            self.notify(MuxNotification::WindowInvalidated(window_id));
+        }
+    }
+
+    /// Swaps the positions of two panes that are in the same tab
+    pub fn swap_panes(&self, pane_id: PaneId, other_pane_id: PaneId) -> anyhow::Result<()> {
+        let tab = self.tab_containing_pane(pane_id)?;
+        tab.set_zoomed(false);
+        let panes = tab.iter_panes();
+        let index_of = |pane_id: PaneId| {
+            panes
+                .iter()
+                .find(|p| p.pane.pane_id() == pane_id)
+                .map(|p| p.index)
+                .ok_or_else(|| anyhow!("pane {} is not in tab {}", pane_id, tab.tab_id()))
+        };
+        tab.swap_panes(index_of(pane_id)?, index_of(other_pane_id)?)?;
+        self.invalidate_window_containing_tab(tab.tab_id());
+        Ok(())
+    }
+
+    /// Rotates the panes in the tab that contains the specified pane
+    pub fn rotate_panes(
+        &self,
+        pane_id: PaneId,
+        direction: config::keyassignment::RotationDirection,
+    ) -> anyhow::Result<()> {
+        let tab = self.tab_containing_pane(pane_id)?;
+        tab.rotate_panes(direction);
+        self.invalidate_window_containing_tab(tab.tab_id());
+        Ok(())
+    }
+
+    /// Removes a pane from its tab and places it in a new tab of its own.
+    /// The new tab is added to the specified window, or to a new window
+    /// if `window_id` is None.
+    /// Returns the new tab and the id of the window that contains it.
+    pub fn move_pane_to_new_tab(
+        &self,
+        pane_id: PaneId,
+        window_id: Option<WindowId>,
+    ) -> anyhow::Result<(Rc<Tab>, WindowId)> {
+        let src_tab = self.tab_containing_pane(pane_id)?;
+        anyhow::ensure!(
+            src_tab.count_panes() > 1,
+            "pane {} is the only pane in tab {}",
+            pane_id,
+            src_tab.tab_id()
+        );
+
+        let size = src_tab.get_size();
+        // A new window is placed in the same workspace as the source
+        let workspace = self
+            .window_containing_tab(src_tab.tab_id())
+            .and_then(|w| self.get_window(w).map(|w| w.get_workspace().to_string()));
+        let pane = src_tab
+            .remove_pane(pane_id)
+            .ok_or_else(|| anyhow!("failed to remove pane {} from its tab", pane_id))?;
+        self.invalidate_window_containing_tab(src_tab.tab_id());
+
+        let tab = Rc::new(Tab::new(&size));
+        tab.assign_pane(&pane);
+        pane.resize(size).ok();
+        self.add_tab_no_panes(&tab);
+
+        // Keep the builder alive until the tab has been added, so that
+        // the window isn't announced while it is still empty
+        let window_builder;
+        let window_id = match window_id {
+            Some(window_id) => window_id,
+            None => {
+                window_builder = self.new_empty_window(workspace);
+                *window_builder
+            }
+        };
+        self.add_tab_to_window(&tab, window_id)?;
+
+        Ok((tab, window_id))
+    }
+
+    /// Removes a pane from its tab and inserts it into the tab that
+    /// contains `target_pane_id`, by splitting the target pane.
+    /// `direction` specifies which side of the target pane the
+    /// moved pane is placed on.
+    /// If the pane was the only pane in its tab, that tab is removed.
+    pub fn move_pane_to_tab(
+        &self,
+        pane_id: PaneId,
+        target_pane_id: PaneId,
+        direction: config::keyassignment::PaneDirection,
+    ) -> anyhow::Result<()> {
+        use config::keyassignment::PaneDirection;
+
+        let src_tab = self.tab_containing_pane(pane_id)?;
+        let dest_tab = self.tab_containing_pane(target_pane_id)?;
+        anyhow::ensure!(
+            src_tab.tab_id() != dest_tab.tab_id(),
+            "pane {} is already in tab {}",
+            pane_id,
+            dest_tab.tab_id()
+        );
+        let pane = self
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+
+        dest_tab.set_zoomed(false);
+        let target_index = dest_tab
+            .iter_panes()
+            .iter()
+            .find(|p| p.pane.pane_id() == target_pane_id)
+            .map(|p| p.index)
+            .ok_or_else(|| anyhow!("pane {} is not in any tab", target_pane_id))?;
+        let split_direction = match direction {
+            PaneDirection::Left | PaneDirection::Right => tab::SplitDirection::Horizontal,
+            PaneDirection::Up | PaneDirection::Down => tab::SplitDirection::Vertical,
+        };
+
+        // The pane is inserted into its new tab before it is removed
+        // from the old one, so that nothing is lost if the split fails
+        let index = dest_tab.split_and_insert(target_index, split_direction, pane)?;
+        if let PaneDirection::Left | PaneDirection::Up = direction {
+            // split_and_insert always places the new pane to the
+            // right or below, so swap it into the other side
+            dest_tab.swap_panes(target_index, index)?;
+        }
+        self.invalidate_window_containing_tab(dest_tab.tab_id());
+
+        src_tab.remove_pane(pane_id);
+        if src_tab.count_panes() == 0 {
+            self.remove_tab(src_tab.tab_id());
+        } else {
+            self.invalidate_window_containing_tab(src_tab.tab_id());
+        }
+
+        Ok(())
+    }
+
     pub fn domain_was_detached(&self, domain: DomainId) {
         let mut dead_panes = vec![];
         for pane in self.panes.borrow().values() {
@@ -626,3 +878,151 @@ pub(crate) fn pty_size_to_terminal_size(size: portable_pty::PtySize) -> wezterm_
         pixel_height: size.pixel_height as usize,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tab::test::FakePane;
+    use crate::tab::SplitDirection;
+    use portable_pty::PtySize;
+
+    fn size() -> PtySize {
+        PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        }
+    }
+
+    /// Adds a window without going through new_empty_window, whose
+    /// notifications need the gui scheduler
+    fn add_window(mux: &Mux, workspace: &str) -> WindowId {
+        let window = Window::new(workspace.to_string());
+        let window_id = window.window_id();
+        mux.windows.borrow_mut().insert(window_id, window);
+        window_id
+    }
+
+    /// Adds the pane to the mux without starting its reader thread
+    fn add_pane(mux: &Mux, pane: &Rc<dyn Pane>) {
+        mux.panes
+            .borrow_mut()
+            .insert(pane.pane_id(), Rc::clone(pane));
+    }
+
+    /// Adds a tab holding a pair of panes split side by side
+    fn add_split_tab(mux: &Mux, window_id: WindowId, first: PaneId, second: PaneId) -> Rc<Tab> {
+        let tab = Rc::new(Tab::new(&size()));
+        let pane = FakePane::new(first, size());
+        tab.assign_pane(&pane);
+        add_pane(mux, &pane);
+        let split = tab
+            .compute_split_size(0, SplitDirection::Horizontal)
+            .unwrap();
+        let pane = FakePane::new(second, split.second);
+        tab.split_and_insert(0, SplitDirection::Horizontal, Rc::clone(&pane))
+            .unwrap();
+        add_pane(mux, &pane);
+        mux.add_tab_no_panes(&tab);
+        mux.add_tab_to_window(&tab, window_id).unwrap();
+        tab
+    }
+
+    #[test]
+    fn workspaces() {
+        let mux = Mux::new(None);
+        assert_eq!(mux.active_workspace(), DEFAULT_WORKSPACE);
+        assert!(mux.iter_workspaces().is_empty());
+
+        let first = add_window(&mux, "work");
+        let second = add_window(&mux, DEFAULT_WORKSPACE);
+        let third = add_window(&mux, "work");
+        assert_eq!(mux.iter_workspaces(), vec![DEFAULT_WORKSPACE, "work"]);
+        assert_eq!(mux.iter_windows_in_workspace("work"), vec![first, third]);
+        assert_eq!(
+            mux.iter_windows_in_workspace(DEFAULT_WORKSPACE),
+            vec![second]
+        );
+        assert!(mux.iter_windows_in_workspace("play").is_empty());
+
+        let changed = Rc::new(RefCell::new(vec![]));
+        {
+            let changed = Rc::clone(&changed);
+            mux.subscribe(move |n| {
+                if let MuxNotification::WindowWorkspaceChanged(window_id) = n {
+                    changed.borrow_mut().push(window_id);
+                }
+                true
+            });
+        }
+
+        mux.set_window_workspace(second, "work");
+        // Moving a window to the workspace that it is already in,
+        // or moving a window that doesn't exist, is a no-op
+        mux.set_window_workspace(second, "work");
+        mux.set_window_workspace(second + 100, "work");
+        assert_eq!(*changed.borrow(), vec![second]);
+        assert_eq!(mux.iter_workspaces(), vec!["work"]);
+        assert_eq!(
+            mux.iter_windows_in_workspace("work"),
+            vec![first, second, third]
+        );
+        assert!(mux.iter_windows_in_workspace(DEFAULT_WORKSPACE).is_empty());
+    }
+
+    #[test]
+    fn generated_workspace_names_are_unused() {
+        let mux = Mux::new(None);
+        assert_eq!(mux.generate_workspace_name(), "workspace-1");
+
+        add_window(&mux, "workspace-1");
+        assert_eq!(mux.generate_workspace_name(), "workspace-2");
+
+        // The active workspace is in use even if it has no windows
+        mux.set_active_workspace("workspace-2");
+        assert_eq!(mux.generate_workspace_name(), "workspace-3");
+
+        add_window(&mux, "workspace-4");
+        assert_eq!(mux.generate_workspace_name(), "workspace-3");
+    }
+
+    #[test]
+    fn kill_pane_removes_it_from_its_tab() {
+        let mux = Mux::new(None);
+        let window_id = add_window(&mux, DEFAULT_WORKSPACE);
+        let tab = add_split_tab(&mux, window_id, 100, 101);
+
+        mux.kill_pane(101);
+        assert!(mux.get_pane(101).is_none());
+        assert!(!tab.contains_pane(101));
+        let panes = tab.iter_panes();
+        assert_eq!(panes.len(), 1);
+        assert_eq!(panes[0].pane.pane_id(), 100);
+        assert_eq!(panes[0].width, 80);
+
+        // Killing the last pane takes the tab with it
+        mux.kill_pane(100);
+        assert!(mux.get_pane(100).is_none());
+        assert!(mux.get_tab(tab.tab_id()).is_none());
+    }
+
+    #[test]
+    fn standalone_panes_are_not_in_a_tab() {
+        let mux = Mux::new(None);
+        let window_id = add_window(&mux, DEFAULT_WORKSPACE);
+        add_split_tab(&mux, window_id, 200, 201);
+
+        // Overlays are added to the mux without being placed in a tab
+        let overlay = FakePane::new(202, size());
+        add_pane(&mux, &overlay);
+
+        assert!(mux.tab_containing_pane(200).is_ok());
+        assert!(mux.tab_containing_pane(202).is_err());
+
+        mux.kill_pane(202);
+        assert!(mux.get_pane(202).is_none());
+        assert!(mux.get_pane(200).is_some());
+        assert!(mux.get_pane(201).is_some());
+    }
+}
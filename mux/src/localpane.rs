@@ -1,9 +1,10 @@
+use crate::asciicast::{Header, Recorder, FLUSH_INTERVAL};
 use crate::domain::DomainId;
 use crate::pane::{Pane, PaneId, Pattern, SearchResult};
 use crate::renderable::*;
 use crate::tmux::{TmuxDomain, TmuxDomainState};
 use crate::{Domain, Mux, MuxNotification};
-use anyhow::Error;
+use anyhow::{Context, Error};
 use async_trait::async_trait;
 use config::keyassignment::ScrollbackEraseMode;
 use config::{configuration, ExitBehavior};
@@ -14,17 +15,19 @@ use rangeset::RangeSet;
 use smol::channel::{bounded, Receiver, TryRecvError};
 use std::cell::{RefCell, RefMut};
 use std::collections::{HashMap, HashSet};
-use std::io::Result as IoResult;
+use std::io::{BufWriter, Read, Result as IoResult, Write};
 use std::ops::Range;
 #[cfg(windows)]
 use std::os::windows::io::{AsRawHandle, RawHandle};
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use termwiz::escape::DeviceControlMode;
 use termwiz::surface::Line;
 use url::Url;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
-    Alert, AlertHandler, CellAttributes, Clipboard, KeyCode, KeyModifiers, MouseEvent,
+    Alert, AlertHandler, CellAttributes, Clipboard, KeyCode, KeyModifiers, MemoryUsage, MouseEvent,
     SemanticZone, StableRowIndex, Terminal,
 };
 
@@ -46,9 +49,178 @@ pub struct LocalPane {
     pane_id: PaneId,
     terminal: RefCell<Terminal>,
     process: RefCell<ProcessState>,
-    pty: RefCell<Box<dyn MasterPty>>,
+    pty: RefCell<RecordedPty>,
     domain_id: DomainId,
     tmux_domain: RefCell<Option<Arc<TmuxDomainState>>>,
+    recorder: PaneRecorder,
+}
+
+/// Captures the pty traffic of a `LocalPane` in asciicast format.
+/// The recorder is shared between the pane, the reader that feeds its
+/// output to the mux and the writer that the terminal uses to send
+/// input, so recording can be started and stopped at any time.
+#[derive(Clone, Default)]
+pub struct PaneRecorder {
+    inner: Arc<Mutex<Option<Recorder>>>,
+    /// Set while the terminal is handling input from the user.
+    /// The terminal also uses its writer to respond to queries from
+    /// the application, and those responses are not recorded.
+    user_input: Arc<AtomicBool>,
+    /// Set while a thread is periodically flushing the recording.
+    /// Only changed while `inner` is locked.
+    flushing: Arc<AtomicBool>,
+}
+
+impl PaneRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wrap the writer that is used to send input to the pty so that
+    /// the input can be recorded
+    pub fn wrap_writer(&self, writer: Box<dyn Write + Send>) -> Box<dyn Write + Send> {
+        Box::new(RecordingWriter {
+            inner: writer,
+            recorder: self.clone(),
+        })
+    }
+
+    fn wrap_reader(&self, reader: Box<dyn Read + Send>) -> Box<dyn Read + Send> {
+        Box::new(RecordingReader {
+            inner: reader,
+            recorder: self.clone(),
+        })
+    }
+
+    fn start(&self, recorder: Recorder) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.replace(recorder);
+        if !self.flushing.swap(true, Ordering::SeqCst) {
+            self.spawn_flusher();
+        }
+    }
+
+    /// Events are flushed as they are recorded, but only once
+    /// FLUSH_INTERVAL has passed since the previous flush.  This thread
+    /// takes care of flushing the remainder when the pane is idle, and
+    /// exits once the recording is stopped.
+    fn spawn_flusher(&self) {
+        let weak_inner = Arc::downgrade(&self.inner);
+        let flushing = Arc::clone(&self.flushing);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(FLUSH_INTERVAL);
+            let inner = match weak_inner.upgrade() {
+                Some(inner) => inner,
+                None => return,
+            };
+            let mut inner = inner.lock().unwrap();
+            let result = match inner.as_mut() {
+                Some(recorder) => recorder.flush_if_due(),
+                None => {
+                    flushing.store(false, Ordering::SeqCst);
+                    return;
+                }
+            };
+            if let Err(err) = result {
+                log::error!("error while recording pane; stopping recording: {:#}", err);
+                inner.take();
+                flushing.store(false, Ordering::SeqCst);
+                return;
+            }
+        });
+    }
+
+    fn stop(&self) -> bool {
+        self.inner.lock().unwrap().take().is_some()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.inner.lock().unwrap().is_some()
+    }
+
+    /// Call `func`, recording anything that it sends to the pty
+    /// as input from the user
+    fn user_input<T, F: FnOnce() -> T>(&self, func: F) -> T {
+        self.user_input.store(true, Ordering::SeqCst);
+        let result = func();
+        self.user_input.store(false, Ordering::SeqCst);
+        result
+    }
+
+    /// Apply `func` to the active recorder, if any.
+    /// If recording fails, eg: because the disk is full, recording
+    /// is stopped rather than interfering with the pane.
+    fn with_recorder<F: FnOnce(&mut Recorder) -> anyhow::Result<()>>(&self, func: F) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(recorder) = inner.as_mut() {
+            if let Err(err) = func(recorder) {
+                log::error!("error while recording pane; stopping recording: {:#}", err);
+                inner.take();
+            }
+        }
+    }
+}
+
+struct RecordingReader {
+    inner: Box<dyn Read + Send>,
+    recorder: PaneRecorder,
+}
+
+impl Read for RecordingReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let size = self.inner.read(buf)?;
+        if size > 0 {
+            self.recorder.with_recorder(|r| r.output(&buf[0..size]));
+        }
+        Ok(size)
+    }
+}
+
+/// Anything written to the pty through `Pane::writer` comes from the
+/// user, for example via the SendString key assignment, so it is
+/// recorded as input
+struct RecordedPty {
+    pty: Box<dyn MasterPty>,
+    recorder: PaneRecorder,
+}
+
+impl std::ops::Deref for RecordedPty {
+    type Target = dyn MasterPty;
+
+    fn deref(&self) -> &dyn MasterPty {
+        self.pty.as_ref()
+    }
+}
+
+impl Write for RecordedPty {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let size = self.pty.write(buf)?;
+        self.recorder.with_recorder(|r| r.input(&buf[0..size]));
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.pty.flush()
+    }
+}
+
+struct RecordingWriter {
+    inner: Box<dyn Write + Send>,
+    recorder: PaneRecorder,
+}
+
+impl Write for RecordingWriter {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let size = self.inner.write(buf)?;
+        if self.recorder.user_input.load(Ordering::SeqCst) {
+            self.recorder.with_recorder(|r| r.input(&buf[0..size]));
+        }
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
 }
 
 #[async_trait(?Send)]
@@ -121,6 +293,7 @@ impl Pane for LocalPane {
     fn is_dead(&self) -> bool {
         let mut proc = self.process.borrow_mut();
         let mut notify = None;
+        let mut exited = None;
 
         const EXIT_BEHAVIOR: &str = "\x1b]8;;https://wezfurlong.org/wezterm/\
                                      config/lua/config/exit_behavior.html\
@@ -138,6 +311,7 @@ impl Pane for LocalPane {
                     _ => Some(ExitStatus::with_exit_code(1)),
                 };
                 if let Some(status) = status {
+                    exited.replace(status.clone());
                     match (configuration().exit_behavior, status.success(), killed) {
                         (ExitBehavior::Close, _, _) => *proc = ProcessState::Dead,
                         (ExitBehavior::CloseOnCleanExit, false, false) => {
@@ -176,6 +350,17 @@ impl Pane for LocalPane {
             ProcessState::Dead => {}
         }
 
+        if let Some(status) = exited {
+            // Notify asynchronously, as subscribers may want to
+            // inspect this pane while the process state is borrowed
+            let pane_id = self.pane_id;
+            promise::spawn::spawn_into_main_thread(async move {
+                let mux = Mux::get().unwrap();
+                mux.notify(MuxNotification::PaneExited { pane_id, status });
+            })
+            .detach();
+        }
+
         if let Some(notify) = notify {
             let pane_id = self.pane_id;
             promise::spawn::spawn_into_main_thread(async move {
@@ -207,7 +392,8 @@ impl Pane for LocalPane {
     }
 
     fn mouse_event(&self, event: MouseEvent) -> Result<(), Error> {
-        self.terminal.borrow_mut().mouse_event(event)
+        self.recorder
+            .user_input(|| self.terminal.borrow_mut().mouse_event(event))
     }
 
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> Result<(), Error> {
@@ -218,7 +404,8 @@ impl Pane for LocalPane {
             }
             return Ok(());
         } else {
-            self.terminal.borrow_mut().key_down(key, mods)
+            self.recorder
+                .user_input(|| self.terminal.borrow_mut().key_down(key, mods))
         }
     }
 
@@ -230,6 +417,8 @@ impl Pane for LocalPane {
             size.pixel_width as usize,
             size.pixel_height as usize,
         );
+        self.recorder
+            .with_recorder(|r| r.resize(size.cols as usize, size.rows as usize));
         Ok(())
     }
 
@@ -238,14 +427,16 @@ impl Pane for LocalPane {
     }
 
     fn reader(&self) -> Result<Box<dyn std::io::Read + Send>, Error> {
-        self.pty.borrow_mut().try_clone_reader()
+        let reader = self.pty.borrow_mut().try_clone_reader()?;
+        Ok(self.recorder.wrap_reader(reader))
     }
 
     fn send_paste(&self, text: &str) -> Result<(), Error> {
         if self.tmux_domain.borrow().is_some() {
             Ok(())
         } else {
-            self.terminal.borrow_mut().send_paste(text)
+            self.recorder
+                .user_input(|| self.terminal.borrow_mut().send_paste(text))
         }
     }
 
@@ -338,6 +529,10 @@ impl Pane for LocalPane {
         term.get_semantic_zones()
     }
 
+    fn get_memory_usage(&self) -> Option<MemoryUsage> {
+        Some(self.terminal.borrow().memory_usage())
+    }
+
     async fn search(&self, mut pattern: Pattern) -> anyhow::Result<Vec<SearchResult>> {
         let term = self.terminal.borrow();
         let screen = term.screen();
@@ -419,9 +614,8 @@ impl Pane for LocalPane {
             }
         }
 
-        for (idx, line) in screen.lines.iter().enumerate() {
-            let stable_row = screen.phys_to_stable_row_index(idx);
-
+        // This includes any lines that have been moved to the disk scrollback
+        for (stable_row, line) in screen.iter_stable_lines() {
             let mut wrapped = false;
             for (grapheme_idx, cell) in line.visible_cells() {
                 coords.push(Coord {
@@ -640,6 +834,7 @@ impl LocalPane {
         process: Box<dyn Child + Send>,
         pty: Box<dyn MasterPty>,
         domain_id: DomainId,
+        recorder: PaneRecorder,
     ) -> Self {
         let (process, signaller) = split_child(process);
 
@@ -656,12 +851,41 @@ impl LocalPane {
                 signaller,
                 killed: false,
             }),
-            pty: RefCell::new(pty),
+            pty: RefCell::new(RecordedPty {
+                pty,
+                recorder: recorder.clone(),
+            }),
             domain_id,
             tmux_domain: RefCell::new(None),
+            recorder,
         }
     }
 
+    /// Start recording the pane to `path` in asciicast v2 format,
+    /// replacing any recording that is already in progress.
+    /// Input is only captured if `record_input` is true and the
+    /// terminal was created with a writer from `PaneRecorder::wrap_writer`.
+    pub fn start_recording(&self, path: &Path, record_input: bool) -> anyhow::Result<()> {
+        let dims = self.get_dimensions();
+        let mut header = Header::new(dims.cols, dims.viewport_rows);
+        header.title.replace(self.get_title());
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("creating recording file {}", path.display()))?;
+        let recorder = Recorder::new(Box::new(BufWriter::new(file)), &header, record_input)?;
+        self.recorder.start(recorder);
+        log::info!("recording pane {} to {}", self.pane_id, path.display());
+        Ok(())
+    }
+
+    /// Stop recording.  Returns false if the pane wasn't being recorded.
+    pub fn stop_recording(&self) -> bool {
+        self.recorder.stop()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_recording()
+    }
+
     #[cfg(target_os = "macos")]
     fn divine_current_working_dir_macos(&self) -> Option<Url> {
         if let Some(pid) = self.pty.borrow().process_group_leader() {
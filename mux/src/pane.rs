@@ -15,7 +15,9 @@ use termwiz::hyperlink::Rule;
 use termwiz::surface::Line;
 use url::Url;
 use wezterm_term::color::ColorPalette;
-use wezterm_term::{Clipboard, KeyCode, KeyModifiers, MouseEvent, SemanticZone, StableRowIndex};
+use wezterm_term::{
+    Clipboard, KeyCode, KeyModifiers, MemoryUsage, MouseEvent, SemanticZone, StableRowIndex,
+};
 
 static PANE_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
 pub type PaneId = usize;
@@ -332,6 +334,12 @@ pub trait Pane: Downcast {
         Ok(vec![])
     }
 
+    /// Returns the memory used to hold the lines of the pane, if it
+    /// is known.  Remote panes don't know this.
+    fn get_memory_usage(&self) -> Option<MemoryUsage> {
+        None
+    }
+
     /// Returns true if the terminal has grabbed the mouse and wants to
     /// give the embedded application a chance to process events.
     /// In practice this controls whether the gui will perform local
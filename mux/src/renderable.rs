@@ -55,14 +55,8 @@ pub fn terminal_get_dirty_lines(
     let screen = term.screen();
     let phys = screen.stable_range(&lines);
     let mut set = RangeSet::new();
-    for (idx, line) in screen
-        .lines
-        .iter()
-        .enumerate()
-        .skip(phys.start)
-        .take(phys.end - phys.start)
-    {
-        if line.is_dirty() {
+    for idx in phys {
+        if screen.lines.is_dirty(idx) {
             set.add(screen.phys_to_stable_row_index(idx))
         }
     }
@@ -75,21 +69,34 @@ pub fn terminal_get_lines(
     lines: Range<StableRowIndex>,
 ) -> (StableRowIndex, Vec<Line>) {
     let screen = term.screen_mut();
-    let phys_range = screen.stable_range(&lines);
-    (
-        screen.phys_to_stable_row_index(phys_range.start),
-        screen
-            .lines
-            .iter_mut()
-            .skip(phys_range.start)
-            .take(phys_range.end - phys_range.start)
-            .map(|line| {
-                let cloned = line.clone();
-                line.clear_dirty();
-                cloned
-            })
-            .collect(),
-    )
+
+    // Lines that precede those held in memory may be in the disk scrollback
+    let memory_top = screen.phys_to_stable_row_index(0);
+    let (first_row, mut result, phys_range) =
+        if lines.start < memory_top && screen.disk_scrollback_rows() > 0 {
+            let first_row = lines.start.max(screen.scrollback_top());
+            let disk_lines = screen.get_disk_lines(first_row..lines.end.min(memory_top));
+            let phys_range = if lines.end > memory_top {
+                screen.stable_range(&(memory_top..lines.end))
+            } else {
+                0..0
+            };
+            (first_row, disk_lines, phys_range)
+        } else {
+            let phys_range = screen.stable_range(&lines);
+            (
+                screen.phys_to_stable_row_index(phys_range.start),
+                vec![],
+                phys_range,
+            )
+        };
+
+    result.extend(phys_range.map(|idx| {
+        let line = screen.lines.get(idx).expect("in range").into_owned();
+        screen.lines.clear_dirty(idx);
+        line
+    }));
+    (first_row, result)
 }
 
 /// Implements Pane::get_dimensions for Terminal
@@ -98,8 +105,8 @@ pub fn terminal_get_dimensions(term: &mut Terminal) -> RenderableDimensions {
     RenderableDimensions {
         cols: screen.physical_cols,
         viewport_rows: screen.physical_rows,
-        scrollback_rows: screen.lines.len(),
+        scrollback_rows: screen.lines.len() + screen.disk_scrollback_rows(),
         physical_top: screen.visible_row_to_stable_row(0),
-        scrollback_top: screen.phys_to_stable_row_index(0),
+        scrollback_top: screen.scrollback_top(),
     }
 }
@@ -1,6 +1,6 @@
 use crate::connui::ConnectionUI;
 use crate::domain::{alloc_domain_id, Domain, DomainId, DomainState};
-use crate::localpane::LocalPane;
+use crate::localpane::{LocalPane, PaneRecorder};
 use crate::pane::{alloc_pane_id, Pane, PaneId};
 use crate::tab::{SplitDirection, Tab, TabId};
 use crate::window::WindowId;
@@ -544,16 +544,19 @@ impl Domain for RemoteSshDomain {
         // eg: tmux integration to be tunnelled via the remote
         // session without duplicating a lot of logic over here.
 
+        let recorder = PaneRecorder::new();
         let terminal = wezterm_term::Terminal::new(
             crate::pty_size_to_terminal_size(size),
             std::sync::Arc::new(config::TermConfig {}),
             "WezTerm",
             config::wezterm_version(),
-            writer,
+            recorder.wrap_writer(writer),
         );
 
         let mux = Mux::get().unwrap();
-        let pane: Rc<dyn Pane> = Rc::new(LocalPane::new(pane_id, terminal, child, pty, self.id));
+        let pane: Rc<dyn Pane> = Rc::new(LocalPane::new(
+            pane_id, terminal, child, pty, self.id, recorder,
+        ));
         let tab = Rc::new(Tab::new(&size));
         tab.assign_pane(&pane);
 
@@ -623,13 +626,14 @@ impl Domain for RemoteSshDomain {
             .await?;
 
         let writer = pty.try_clone_writer()?;
+        let recorder = PaneRecorder::new();
 
         let terminal = wezterm_term::Terminal::new(
             crate::pty_size_to_terminal_size(split_size.second),
             std::sync::Arc::new(config::TermConfig {}),
             "WezTerm",
             config::wezterm_version(),
-            Box::new(writer),
+            recorder.wrap_writer(Box::new(writer)),
         );
 
         let pane: Rc<dyn Pane> = Rc::new(LocalPane::new(
@@ -638,6 +642,7 @@ impl Domain for RemoteSshDomain {
             Box::new(child),
             Box::new(pty),
             self.id,
+            recorder,
         ));
 
         tab.split_and_insert(pane_index, direction, Rc::clone(&pane))?;
@@ -2,7 +2,7 @@ use crate::domain::DomainId;
 use crate::pane::*;
 use crate::{Mux, WindowId};
 use bintree::PathBranch;
-use config::keyassignment::PaneDirection;
+use config::keyassignment::{PaneDirection, RotationDirection};
 use portable_pty::PtySize;
 use rangeset::range_intersection;
 use serde::{Deserialize, Serialize};
@@ -24,6 +24,7 @@ pub struct Tab {
     size: RefCell<PtySize>,
     active: RefCell<usize>,
     zoomed: RefCell<Option<Rc<dyn Pane>>>,
+    title: RefCell<String>,
 }
 
 #[derive(Clone)]
@@ -153,14 +154,19 @@ fn pane_tree(
     tree: &Tree,
     tab_id: TabId,
     window_id: WindowId,
+    workspace: &str,
     active: Option<&Rc<dyn Pane>>,
     zoomed: Option<&Rc<dyn Pane>>,
 ) -> PaneNode {
     match tree {
         Tree::Empty => PaneNode::Empty,
         Tree::Node { left, right, data } => PaneNode::Split {
-            left: Box::new(pane_tree(&*left, tab_id, window_id, active, zoomed)),
-            right: Box::new(pane_tree(&*right, tab_id, window_id, active, zoomed)),
+            left: Box::new(pane_tree(
+                &*left, tab_id, window_id, workspace, active, zoomed,
+            )),
+            right: Box::new(pane_tree(
+                &*right, tab_id, window_id, workspace, active, zoomed,
+            )),
             node: data.unwrap(),
         },
         Tree::Leaf(pane) => {
@@ -170,6 +176,7 @@ fn pane_tree(
             PaneNode::Leaf(PaneEntry {
                 window_id,
                 tab_id,
+                workspace: workspace.to_string(),
                 pane_id: pane.pane_id(),
                 title: pane.get_title(),
                 is_active_pane: is_pane(pane, &active),
@@ -408,6 +415,7 @@ impl Tab {
             size: RefCell::new(*size),
             active: RefCell::new(0),
             zoomed: RefCell::new(None),
+            title: RefCell::new(String::new()),
         }
     }
 
@@ -483,10 +491,22 @@ impl Tab {
             }
         };
 
+        let workspace = match mux.get_window(window_id) {
+            Some(w) => w.get_workspace().to_string(),
+            None => return PaneNode::Empty,
+        };
+
         let zoomed = self.zoomed.borrow();
         let active = self.get_active_pane();
         if let Some(root) = self.pane.borrow().as_ref() {
-            pane_tree(root, tab_id, window_id, active.as_ref(), zoomed.as_ref())
+            pane_tree(
+                root,
+                tab_id,
+                window_id,
+                &workspace,
+                active.as_ref(),
+                zoomed.as_ref(),
+            )
         } else {
             PaneNode::Empty
         }
@@ -693,6 +713,17 @@ impl Tab {
         self.id
     }
 
+    /// Returns the title that was explicitly assigned to this tab,
+    /// which is empty if none was assigned.  When it is empty, the
+    /// title of the active pane is used in its place.
+    pub fn get_title(&self) -> String {
+        self.title.borrow().clone()
+    }
+
+    pub fn set_title(&self, title: &str) {
+        *self.title.borrow_mut() = title.to_string();
+    }
+
     pub fn get_size(&self) -> PtySize {
         *self.size.borrow()
     }
@@ -1028,16 +1059,28 @@ impl Tab {
         if self.zoomed.borrow().is_some() {
             return;
         }
-        let panes = self.iter_panes();
+        if !self.iter_panes().iter().any(|pane| pane.is_active) {
+            // No active pane somehow...
+            self.set_active_idx(0);
+            return;
+        }
 
-        let active = match panes.iter().find(|pane| pane.is_active) {
-            Some(p) => p,
-            None => {
-                // No active pane somehow...
-                self.set_active_idx(0);
-                return;
-            }
-        };
+        if let Some(target) = self.get_pane_direction(direction) {
+            self.set_active_idx(target);
+        }
+    }
+
+    /// Returns the index of the pane that is adjacent to the active
+    /// pane in the specified direction, if any.
+    /// In cases where there are multiple adjacent panes in the
+    /// intended direction, we take the pane that has the largest
+    /// edge intersection.
+    pub fn get_pane_direction(&self, direction: PaneDirection) -> Option<usize> {
+        if self.zoomed.borrow().is_some() {
+            return None;
+        }
+        let panes = self.iter_panes();
+        let active = panes.iter().find(|pane| pane.is_active)?;
 
         let mut best = None;
 
@@ -1097,32 +1140,115 @@ impl Tab {
             }
         }
 
-        if let Some((_, target)) = best.take() {
-            self.set_active_idx(target.index);
+        best.map(|(_, target)| target.index)
+    }
+
+    /// Swaps the positions of the panes with the specified indices.
+    /// Each pane is resized to fit its new position.  The active pane
+    /// remains active and so focus follows it to its new position.
+    pub fn swap_panes(&self, a: usize, b: usize) -> anyhow::Result<()> {
+        self.set_zoomed(false);
+        let mut panes: Vec<Rc<dyn Pane>> = self.iter_panes().into_iter().map(|p| p.pane).collect();
+        if a >= panes.len() || b >= panes.len() {
+            anyhow::bail!("invalid pane index; cannot swap {} and {}", a, b);
+        }
+        panes.swap(a, b);
+        self.reorder_panes(panes);
+        Ok(())
+    }
+
+    /// Moves each pane to the position of the next pane in topological
+    /// order (or the prior pane, when rotating counter-clockwise),
+    /// wrapping around at the end.  The layout of the splits is preserved
+    /// and the active pane remains active.
+    pub fn rotate_panes(&self, direction: RotationDirection) {
+        self.set_zoomed(false);
+        let mut panes: Vec<Rc<dyn Pane>> = self.iter_panes().into_iter().map(|p| p.pane).collect();
+        if panes.len() < 2 {
+            return;
+        }
+        match direction {
+            RotationDirection::Clockwise => panes.rotate_right(1),
+            RotationDirection::CounterClockwise => panes.rotate_left(1),
+        }
+        self.reorder_panes(panes);
+    }
+
+    /// Assigns `panes` to the leaves of the tree, in topological order,
+    /// and resizes them to fit.  `panes` must be a permutation of the
+    /// panes that are already in the tab.
+    fn reorder_panes(&self, panes: Vec<Rc<dyn Pane>>) {
+        let active_id = self.get_active_pane().map(|p| p.pane_id());
+        let size = *self.size.borrow();
+        {
+            let mut root = self.pane.borrow_mut();
+            let mut cursor = root.take().unwrap().cursor();
+            let mut panes = panes.into_iter();
+
+            loop {
+                if let Some(leaf) = cursor.leaf_mut() {
+                    if let Some(pane) = panes.next() {
+                        *leaf = pane;
+                    }
+                }
+                match cursor.preorder_next() {
+                    Ok(c) => cursor = c,
+                    Err(c) => {
+                        root.replace(c.tree());
+                        break;
+                    }
+                }
+            }
+
+            apply_sizes_from_splits(root.as_ref().unwrap(), &size);
+        }
+
+        if let Some(pos) = self
+            .iter_panes()
+            .iter()
+            .find(|p| Some(p.pane.pane_id()) == active_id)
+        {
+            *self.active.borrow_mut() = pos.index;
         }
     }
 
     pub fn prune_dead_panes(&self) -> bool {
-        self.remove_pane_if(|_, pane| pane.is_dead())
+        !self
+            .remove_pane_if(|_, pane| pane.is_dead(), true)
+            .is_empty()
     }
 
     pub fn kill_pane(&self, pane_id: PaneId) -> bool {
-        self.remove_pane_if(|_, pane| pane.pane_id() == pane_id)
+        !self
+            .remove_pane_if(|_, pane| pane.pane_id() == pane_id, true)
+            .is_empty()
     }
 
     pub fn kill_panes_in_domain(&self, domain: DomainId) -> bool {
-        self.remove_pane_if(|_, pane| pane.domain_id() == domain)
+        !self
+            .remove_pane_if(|_, pane| pane.domain_id() == domain, true)
+            .is_empty()
     }
 
-    fn remove_pane_if<F>(&self, f: F) -> bool
+    /// Removes the pane from the tab without killing it, so that it
+    /// can be placed elsewhere.  The space that it occupied is given
+    /// to its neighbor.
+    pub fn remove_pane(&self, pane_id: PaneId) -> Option<Rc<dyn Pane>> {
+        self.set_zoomed(false);
+        self.remove_pane_if(|_, pane| pane.pane_id() == pane_id, false)
+            .pop()
+    }
+
+    fn remove_pane_if<F>(&self, f: F, kill: bool) -> Vec<Rc<dyn Pane>>
     where
         F: Fn(usize, &Rc<dyn Pane>) -> bool,
     {
-        let mut dead_panes = vec![];
+        let mut dead_panes: Vec<Rc<dyn Pane>> = vec![];
 
         {
             let root_size = *self.size.borrow();
-            let mut active_idx = *self.active.borrow();
+            let prior_active_idx = *self.active.borrow();
+            let mut active_idx = prior_active_idx;
             let mut root = self.pane.borrow_mut();
             let mut cursor = root.take().unwrap().cursor();
             let mut pane_index = 0;
@@ -1144,13 +1270,16 @@ impl Tab {
                 if cursor.is_leaf() {
                     let pane = Rc::clone(cursor.leaf_mut().unwrap());
                     if f(pane_index, &pane) {
-                        if pane_index == active_idx {
-                            active_idx = pane_index.saturating_sub(1);
+                        // Removing the active pane activates the pane before
+                        // it, and removing a pane before the active pane
+                        // shifts the active index down
+                        if pane_index <= prior_active_idx {
+                            active_idx = active_idx.saturating_sub(1);
                         }
                         let parent;
                         match cursor.unsplit_leaf() {
                             Ok((c, dead, p)) => {
-                                dead_panes.push(dead.pane_id());
+                                dead_panes.push(dead);
                                 parent = p.unwrap();
                                 cursor = c;
                             }
@@ -1158,7 +1287,7 @@ impl Tab {
                                 // We might be the root, for example
                                 if c.is_top() && c.is_leaf() {
                                     root.replace(Tree::Empty);
-                                    dead_panes.push(pane.pane_id());
+                                    dead_panes.push(pane);
                                 } else {
                                     root.replace(c.tree());
                                 }
@@ -1200,18 +1329,17 @@ impl Tab {
             *self.active.borrow_mut() = active_idx;
         }
 
-        if !dead_panes.is_empty() {
+        if kill && !dead_panes.is_empty() {
+            let dead_pane_ids: Vec<PaneId> = dead_panes.iter().map(|p| p.pane_id()).collect();
             promise::spawn::spawn_into_main_thread(async move {
                 let mux = Mux::get().unwrap();
-                for pane_id in dead_panes.into_iter() {
+                for pane_id in dead_pane_ids.into_iter() {
                     mux.remove_pane(pane_id);
                 }
             })
             .detach();
-            true
-        } else {
-            false
         }
+        dead_panes
     }
 
     pub fn can_close_without_prompting(&self) -> bool {
@@ -1480,6 +1608,18 @@ impl PaneNode {
             PaneNode::Leaf(entry) => Some((entry.window_id, entry.tab_id)),
         }
     }
+
+    /// Returns the workspace of the window that contains this tab
+    pub fn workspace(&self) -> Option<&str> {
+        match self {
+            PaneNode::Empty => None,
+            PaneNode::Split { left, right, .. } => match left.workspace() {
+                Some(res) => Some(res),
+                None => right.workspace(),
+            },
+            PaneNode::Leaf(entry) => Some(&entry.workspace),
+        }
+    }
 }
 
 /// This type is used directly by the codec, take care to bump
@@ -1488,6 +1628,7 @@ impl PaneNode {
 pub struct PaneEntry {
     pub window_id: WindowId,
     pub tab_id: TabId,
+    pub workspace: String,
     pub pane_id: PaneId,
     pub title: String,
     pub size: PtySize,
@@ -1529,7 +1670,7 @@ impl Into<String> for SerdeUrl {
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use super::*;
     use crate::renderable::*;
     use rangeset::RangeSet;
@@ -1538,13 +1679,13 @@ mod test {
     use wezterm_term::color::ColorPalette;
     use wezterm_term::{KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex};
 
-    struct FakePane {
+    pub(crate) struct FakePane {
         id: PaneId,
         size: RefCell<PtySize>,
     }
 
     impl FakePane {
-        fn new(id: PaneId, size: PtySize) -> Rc<dyn Pane> {
+        pub(crate) fn new(id: PaneId, size: PtySize) -> Rc<dyn Pane> {
             Rc::new(Self {
                 id,
                 size: RefCell::new(size),
@@ -1775,4 +1916,141 @@ mod test {
         assert_eq!(390, panes[2].pixel_width);
         assert_eq!(600, panes[2].pixel_height);
     }
+
+    fn pane_ids(tab: &Tab) -> Vec<PaneId> {
+        tab.iter_panes().iter().map(|p| p.pane.pane_id()).collect()
+    }
+
+    #[test]
+    fn tab_rearranging() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        // Produces a layout with pane 1 at the top left, 3 below it
+        // and 2 to the right of both, with pane 3 active
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+        let horz_size = tab
+            .compute_split_size(0, SplitDirection::Horizontal)
+            .unwrap();
+        tab.split_and_insert(
+            0,
+            SplitDirection::Horizontal,
+            FakePane::new(2, horz_size.second),
+        )
+        .unwrap();
+        let vert_size = tab.compute_split_size(0, SplitDirection::Vertical).unwrap();
+        tab.split_and_insert(
+            0,
+            SplitDirection::Vertical,
+            FakePane::new(3, vert_size.second),
+        )
+        .unwrap();
+        assert_eq!(pane_ids(&tab), vec![1, 3, 2]);
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 3);
+
+        assert_eq!(tab.get_pane_direction(PaneDirection::Up), Some(0));
+        assert_eq!(tab.get_pane_direction(PaneDirection::Right), Some(2));
+        assert_eq!(tab.get_pane_direction(PaneDirection::Left), None);
+
+        tab.rotate_panes(RotationDirection::Clockwise);
+        assert_eq!(pane_ids(&tab), vec![2, 1, 3]);
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 3);
+        let panes = tab.iter_panes();
+        assert_eq!(40, panes[0].width);
+        assert_eq!(12, panes[0].height);
+        assert_eq!(39, panes[2].width);
+        assert_eq!(24, panes[2].height);
+
+        tab.rotate_panes(RotationDirection::CounterClockwise);
+        assert_eq!(pane_ids(&tab), vec![1, 3, 2]);
+
+        tab.swap_panes(1, 2).unwrap();
+        assert_eq!(pane_ids(&tab), vec![1, 2, 3]);
+        assert_eq!(tab.get_active_idx(), 2);
+        assert!(tab.swap_panes(0, 3).is_err());
+
+        let removed = tab.remove_pane(2).unwrap();
+        assert_eq!(removed.pane_id(), 2);
+        assert_eq!(pane_ids(&tab), vec![1, 3]);
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 3);
+        let panes = tab.iter_panes();
+        assert_eq!(40, panes[0].width);
+        assert_eq!(24, panes[0].height);
+        assert!(tab.remove_pane(2).is_none());
+    }
+
+    /// Produces a tab with panes 1, 2 and 3 side by side, in that
+    /// order, with pane 2 active
+    fn three_pane_tab() -> Tab {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+        for (pane_index, pane_id) in [(0, 2), (1, 3)].iter() {
+            let split = tab
+                .compute_split_size(*pane_index, SplitDirection::Horizontal)
+                .unwrap();
+            tab.split_and_insert(
+                *pane_index,
+                SplitDirection::Horizontal,
+                FakePane::new(*pane_id, split.second),
+            )
+            .unwrap();
+        }
+        tab.set_active_idx(1);
+        assert_eq!(pane_ids(&tab), vec![1, 2, 3]);
+        tab
+    }
+
+    #[test]
+    fn remove_pane_keeps_active_pane() {
+        // Removing a pane before the active pane keeps the same
+        // pane active, even though its index changes
+        let tab = three_pane_tab();
+        tab.remove_pane(1).unwrap();
+        assert_eq!(pane_ids(&tab), vec![2, 3]);
+        assert_eq!(tab.get_active_idx(), 0);
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 2);
+
+        // Removing the active pane activates the pane before it
+        let tab = three_pane_tab();
+        tab.remove_pane(2).unwrap();
+        assert_eq!(pane_ids(&tab), vec![1, 3]);
+        assert_eq!(tab.get_active_idx(), 0);
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 1);
+
+        // Removing a pane after the active pane changes nothing
+        let tab = three_pane_tab();
+        tab.remove_pane(3).unwrap();
+        assert_eq!(pane_ids(&tab), vec![1, 2]);
+        assert_eq!(tab.get_active_idx(), 1);
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 2);
+
+        // Removing the first pane while it is active activates
+        // the pane that follows it
+        let tab = three_pane_tab();
+        tab.set_active_idx(0);
+        tab.remove_pane(1).unwrap();
+        assert_eq!(pane_ids(&tab), vec![2, 3]);
+        assert_eq!(tab.get_active_idx(), 0);
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 2);
+
+        // Removing panes either side of the active pane in one pass
+        let tab = three_pane_tab();
+        let removed = tab.remove_pane_if(|_, pane| pane.pane_id() != 2, false);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(pane_ids(&tab), vec![2]);
+        assert_eq!(tab.get_active_idx(), 0);
+        assert_eq!(tab.get_active_pane().unwrap().pane_id(), 2);
+    }
 }
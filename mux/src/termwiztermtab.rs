@@ -275,6 +275,14 @@ impl TermWizTerminal {
     pub fn no_grab_mouse_in_raw_mode(&mut self) {
         self.grab_mouse = false;
     }
+
+    /// Send raw data, such as previously captured pty output, to
+    /// the pane, bypassing the renderer.  The data is interpreted
+    /// by the terminal emulator in the pane.
+    pub fn write_raw(&mut self, data: &[u8]) -> termwiz::Result<()> {
+        self.render_tx.write_all(data)?;
+        Ok(())
+    }
 }
 
 struct TermWizTerminalRenderTty {
@@ -481,7 +489,7 @@ pub async fn run<
         let domain: Arc<dyn Domain> = Arc::new(TermWizTerminalDomain::new());
         mux.add_domain(&domain);
 
-        let window_id = mux.new_empty_window();
+        let window_id = mux.new_empty_window(None);
 
         let pane = TermWizTerminalPane::new(domain.domain_id(), size, input_tx, render_rx);
         let pane: Rc<dyn Pane> = Rc::new(pane);
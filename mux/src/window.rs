@@ -12,19 +12,32 @@ pub struct Window {
     active: usize,
     last_active: Option<TabId>,
     clipboard: Option<Arc<dyn Clipboard>>,
+    workspace: String,
 }
 
 impl Window {
-    pub fn new() -> Self {
+    pub fn new(workspace: String) -> Self {
         Self {
             id: WIN_ID.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed),
             tabs: vec![],
             active: 0,
             last_active: None,
             clipboard: None,
+            workspace,
         }
     }
 
+    /// Returns the name of the workspace to which this window belongs
+    pub fn get_workspace(&self) -> &str {
+        &self.workspace
+    }
+
+    /// Use Mux::set_window_workspace to change the workspace, so that
+    /// subscribers are notified once the window is no longer borrowed
+    pub(crate) fn set_workspace(&mut self, workspace: &str) {
+        self.workspace = workspace.to_string();
+    }
+
     pub fn set_clipboard(&mut self, clipboard: &Arc<dyn Clipboard>) {
         self.clipboard.replace(Arc::clone(clipboard));
     }
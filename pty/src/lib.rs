@@ -143,10 +143,11 @@ pub trait SlavePty {
 
 /// Represents the exit status of a child process.
 /// This is rather anemic in the current version of this crate,
-/// holding only an indicator of success or failure.
+/// holding only an indicator of success or failure and the exit code.
 #[derive(Debug, Clone)]
 pub struct ExitStatus {
     successful: bool,
+    code: u32,
 }
 
 impl ExitStatus {
@@ -154,18 +155,27 @@ impl ExitStatus {
     pub fn with_exit_code(code: u32) -> Self {
         Self {
             successful: code == 0,
+            code,
         }
     }
 
     pub fn success(&self) -> bool {
         self.successful
     }
+
+    /// Returns the exit code of the process.
+    /// A process that was terminated by a signal has no exit code of
+    /// its own; 1 is reported for it.
+    pub fn exit_code(&self) -> u32 {
+        self.code
+    }
 }
 
 impl From<std::process::ExitStatus> for ExitStatus {
     fn from(status: std::process::ExitStatus) -> ExitStatus {
         ExitStatus {
             successful: status.success(),
+            code: status.code().map(|code| code as u32).unwrap_or(1),
         }
     }
 }
@@ -0,0 +1,365 @@
+//! Support for the ISO 2022 style G0-G3 character set designations
+//! that DEC terminals use to select between ASCII, the DEC graphic
+//! sets and the National Replacement Character Sets (NRCS).
+//! wezterm only supports UTF-8, so these are implemented as a mapping
+//! from the 7-bit graphic characters to their unicode equivalents.
+use termwiz::escape::EscCode;
+
+/// The character sets that can be designated into G0-G3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharSet {
+    Ascii,
+    Uk,
+    DecLineDrawing,
+    DecSupplemental,
+    DecTechnical,
+    IsoLatin1Supplemental,
+    Dutch,
+    Finnish,
+    French,
+    FrenchCanadian,
+    German,
+    Italian,
+    NorwegianDanish,
+    Spanish,
+    Swedish,
+    Swiss,
+}
+
+impl CharSet {
+    /// Map a 7-bit graphic character through this character set.
+    /// Returns None if the character is unchanged by the mapping.
+    pub fn map(self, c: u8) -> Option<char> {
+        if !(0x21..=0x7e).contains(&c) {
+            return None;
+        }
+        let mapped = match self {
+            Self::Ascii => None,
+            Self::Uk => match c {
+                b'#' => Some('£'),
+                _ => None,
+            },
+            Self::DecLineDrawing => dec_line_drawing(c),
+            Self::DecSupplemental => Some(dec_supplemental(c)),
+            Self::DecTechnical => dec_technical(c),
+            Self::IsoLatin1Supplemental => std::char::from_u32(c as u32 + 0x80),
+            Self::Dutch => nrcs(c, "£¾ĳ½|   ¨ƒ¼´"),
+            Self::Finnish => nrcs(c, "  ÄÖÅÜ éäöåü"),
+            Self::French => nrcs(c, "£à°ç§   éùè¨"),
+            Self::FrenchCanadian => nrcs(c, " àâçêî ôéùèû"),
+            Self::German => nrcs(c, " §ÄÖÜ   äöüß"),
+            Self::Italian => nrcs(c, "£§°çé  ùàòèì"),
+            Self::NorwegianDanish => nrcs(c, " ÄÆØÅÜ äæøåü"),
+            Self::Spanish => nrcs(c, "£§¡Ñ¿   °ñç "),
+            Self::Swedish => nrcs(c, " ÉÄÖÅÜ éäöåü"),
+            Self::Swiss => nrcs(c, "ùàéçêîèôäöüû"),
+        };
+        mapped.filter(|&m| m != c as char)
+    }
+
+    /// Returns the G0-G3 index and character set designated by
+    /// an escape sequence, or None if it is not a designation
+    pub fn designation(code: EscCode) -> Option<(usize, Self)> {
+        use EscCode::*;
+        let charset = match code {
+            DecLineDrawingG0 | DecLineDrawingG1 | DecLineDrawingG2 | DecLineDrawingG3 => {
+                Self::DecLineDrawing
+            }
+            UkCharacterSetG0 | UkCharacterSetG1 | UkCharacterSetG2 | UkCharacterSetG3 => Self::Uk,
+            AsciiCharacterSetG0 | AsciiCharacterSetG1 | AsciiCharacterSetG2
+            | AsciiCharacterSetG3 => Self::Ascii,
+            DecSupplementalG0 | DecSupplementalG1 | DecSupplementalG2 | DecSupplementalG3 => {
+                Self::DecSupplemental
+            }
+            DecTechnicalG0 | DecTechnicalG1 | DecTechnicalG2 | DecTechnicalG3 => Self::DecTechnical,
+            DutchCharacterSetG0 | DutchCharacterSetG1 | DutchCharacterSetG2
+            | DutchCharacterSetG3 => Self::Dutch,
+            FinnishCharacterSetG0
+            | FinnishCharacterSetG1
+            | FinnishCharacterSetG2
+            | FinnishCharacterSetG3 => Self::Finnish,
+            FrenchCharacterSetG0 | FrenchCharacterSetG1 | FrenchCharacterSetG2
+            | FrenchCharacterSetG3 => Self::French,
+            FrenchCanadianCharacterSetG0
+            | FrenchCanadianCharacterSetG1
+            | FrenchCanadianCharacterSetG2
+            | FrenchCanadianCharacterSetG3 => Self::FrenchCanadian,
+            GermanCharacterSetG0 | GermanCharacterSetG1 | GermanCharacterSetG2
+            | GermanCharacterSetG3 => Self::German,
+            ItalianCharacterSetG0
+            | ItalianCharacterSetG1
+            | ItalianCharacterSetG2
+            | ItalianCharacterSetG3 => Self::Italian,
+            NorwegianDanishCharacterSetG0
+            | NorwegianDanishCharacterSetG1
+            | NorwegianDanishCharacterSetG2
+            | NorwegianDanishCharacterSetG3 => Self::NorwegianDanish,
+            SpanishCharacterSetG0
+            | SpanishCharacterSetG1
+            | SpanishCharacterSetG2
+            | SpanishCharacterSetG3 => Self::Spanish,
+            SwedishCharacterSetG0
+            | SwedishCharacterSetG1
+            | SwedishCharacterSetG2
+            | SwedishCharacterSetG3 => Self::Swedish,
+            SwissCharacterSetG0 | SwissCharacterSetG1 | SwissCharacterSetG2
+            | SwissCharacterSetG3 => Self::Swiss,
+            IsoLatin1SupplementalG1 | IsoLatin1SupplementalG2 | IsoLatin1SupplementalG3 => {
+                Self::IsoLatin1Supplemental
+            }
+            _ => return None,
+        };
+        let idx = match code {
+            DecLineDrawingG0
+            | UkCharacterSetG0
+            | AsciiCharacterSetG0
+            | DecSupplementalG0
+            | DecTechnicalG0
+            | DutchCharacterSetG0
+            | FinnishCharacterSetG0
+            | FrenchCharacterSetG0
+            | FrenchCanadianCharacterSetG0
+            | GermanCharacterSetG0
+            | ItalianCharacterSetG0
+            | NorwegianDanishCharacterSetG0
+            | SpanishCharacterSetG0
+            | SwedishCharacterSetG0
+            | SwissCharacterSetG0 => 0,
+            DecLineDrawingG1
+            | UkCharacterSetG1
+            | AsciiCharacterSetG1
+            | DecSupplementalG1
+            | DecTechnicalG1
+            | DutchCharacterSetG1
+            | FinnishCharacterSetG1
+            | FrenchCharacterSetG1
+            | FrenchCanadianCharacterSetG1
+            | GermanCharacterSetG1
+            | ItalianCharacterSetG1
+            | NorwegianDanishCharacterSetG1
+            | SpanishCharacterSetG1
+            | SwedishCharacterSetG1
+            | SwissCharacterSetG1
+            | IsoLatin1SupplementalG1 => 1,
+            DecLineDrawingG2
+            | UkCharacterSetG2
+            | AsciiCharacterSetG2
+            | DecSupplementalG2
+            | DecTechnicalG2
+            | DutchCharacterSetG2
+            | FinnishCharacterSetG2
+            | FrenchCharacterSetG2
+            | FrenchCanadianCharacterSetG2
+            | GermanCharacterSetG2
+            | ItalianCharacterSetG2
+            | NorwegianDanishCharacterSetG2
+            | SpanishCharacterSetG2
+            | SwedishCharacterSetG2
+            | SwissCharacterSetG2
+            | IsoLatin1SupplementalG2 => 2,
+            DecLineDrawingG3
+            | UkCharacterSetG3
+            | AsciiCharacterSetG3
+            | DecSupplementalG3
+            | DecTechnicalG3
+            | DutchCharacterSetG3
+            | FinnishCharacterSetG3
+            | FrenchCharacterSetG3
+            | FrenchCanadianCharacterSetG3
+            | GermanCharacterSetG3
+            | ItalianCharacterSetG3
+            | NorwegianDanishCharacterSetG3
+            | SpanishCharacterSetG3
+            | SwedishCharacterSetG3
+            | SwissCharacterSetG3
+            | IsoLatin1SupplementalG3 => 3,
+            _ => return None,
+        };
+        Some((idx, charset))
+    }
+}
+
+/// The positions that the National Replacement Character Sets
+/// may replace, in the order used by the tables passed to `nrcs`
+const NRCS_POSITIONS: &[u8] = b"#@[\\]^_`{|}~";
+
+/// Look up `c` in a national replacement table; the table holds
+/// one character for each of `NRCS_POSITIONS`, with a space
+/// meaning that the ASCII character is retained.
+fn nrcs(c: u8, table: &str) -> Option<char> {
+    let idx = NRCS_POSITIONS.iter().position(|&p| p == c)?;
+    table.chars().nth(idx).filter(|&m| m != ' ')
+}
+
+/// https://vt100.net/docs/vt220-rm/table2-4.html
+fn dec_line_drawing(c: u8) -> Option<char> {
+    Some(match c {
+        b'`' => '◆',
+        b'a' => '▒',
+        b'b' => '␉',
+        b'c' => '␌',
+        b'd' => '␍',
+        b'e' => '␊',
+        b'f' => '°',
+        b'g' => '±',
+        b'h' => '␤',
+        b'i' => '␋',
+        b'j' => '┘',
+        b'k' => '┐',
+        b'l' => '┌',
+        b'm' => '└',
+        b'n' => '┼',
+        b'o' => '⎺',
+        b'p' => '⎻',
+        b'q' => '─',
+        b'r' => '⎼',
+        b's' => '⎽',
+        b't' => '├',
+        b'u' => '┤',
+        b'v' => '┴',
+        b'w' => '┬',
+        b'x' => '│',
+        b'y' => '≤',
+        b'z' => '≥',
+        b'{' => 'π',
+        b'|' => '≠',
+        b'}' => '£',
+        b'~' => '·',
+        _ => return None,
+    })
+}
+
+/// https://vt100.net/docs/vt220-rm/table2-3b.html
+/// The DEC Supplemental Graphic set is mostly the same as the
+/// upper half of ISO Latin-1; the differences are listed here.
+fn dec_supplemental(c: u8) -> char {
+    match c {
+        0x28 => '¤',
+        0x57 => 'Œ',
+        0x5d => 'Ÿ',
+        0x77 => 'œ',
+        0x7d => 'ÿ',
+        _ => std::char::from_u32(c as u32 + 0x80).unwrap_or(c as char),
+    }
+}
+
+/// https://vt100.net/docs/vt220-rm/table2-5.html (VT320 and later)
+fn dec_technical(c: u8) -> Option<char> {
+    const TABLE: &str = concat!(
+        "⎷┌─⌠⌡│⎡⎣⎤⎦⎧⎩⎫⎭⎨",
+        "⎬⎲⎳╲╱⌝⌟⟩    ≤≠≥∫",
+        "∴∝∞÷Δ∇ΦΓ∼≃Θ×Λ⇔⇒≡",
+        "ΠΨ Σ  √ΩΞΥ⊂⊃∩∪∧∨",
+        "¬αβχδεφγηιθκλ ν∂",
+        "πψρστ ƒωξυζ←↑→↓",
+    );
+    TABLE.chars().nth((c - 0x21) as usize).filter(|&m| m != ' ')
+}
+
+/// Tracks the G0-G3 designations and which of them is invoked
+/// into GL, the left half of the code table that is used to map
+/// the 7-bit graphic characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CharSetState {
+    designations: [CharSet; 4],
+    gl: usize,
+    single_shift: Option<usize>,
+}
+
+impl Default for CharSetState {
+    fn default() -> Self {
+        Self {
+            // G1 is pre-designated as DEC Special Graphics so that
+            // applications can switch to it with just Shift Out
+            designations: [
+                CharSet::Ascii,
+                CharSet::DecLineDrawing,
+                CharSet::Ascii,
+                CharSet::Ascii,
+            ],
+            gl: 0,
+            single_shift: None,
+        }
+    }
+}
+
+impl CharSetState {
+    /// Designate `charset` as G`idx`
+    pub fn designate(&mut self, idx: usize, charset: CharSet) {
+        self.designations[idx] = charset;
+    }
+
+    /// Invoke G`idx` into GL until further notice (SI, SO, LS2, LS3)
+    pub fn locking_shift(&mut self, idx: usize) {
+        self.gl = idx;
+    }
+
+    /// Invoke G`idx` into GL for the next graphic character only
+    /// (SS2, SS3)
+    pub fn single_shift(&mut self, idx: usize) {
+        self.single_shift = Some(idx);
+    }
+
+    /// Returns a copy of the state suitable for DECSC; a pending
+    /// single shift is not saved.
+    pub fn saved(&self) -> Self {
+        Self {
+            single_shift: None,
+            ..*self
+        }
+    }
+
+    /// Map a grapheme through the currently invoked character set,
+    /// consuming any pending single shift.  `buf` provides the
+    /// storage for a mapped character.
+    pub fn map<'a>(&mut self, g: &'a str, buf: &'a mut [u8; 4]) -> &'a str {
+        let idx = self.single_shift.take().unwrap_or(self.gl);
+        match g.as_bytes() {
+            [c] => match self.designations[idx].map(*c) {
+                Some(mapped) => mapped.encode_utf8(buf),
+                None => g,
+            },
+            _ => g,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tables() {
+        assert_eq!(CharSet::Ascii.map(b'#'), None);
+        assert_eq!(CharSet::Uk.map(b'#'), Some('£'));
+        assert_eq!(CharSet::DecLineDrawing.map(b'q'), Some('─'));
+        assert_eq!(CharSet::DecSupplemental.map(b'!'), Some('¡'));
+        assert_eq!(CharSet::DecSupplemental.map(b'W'), Some('Œ'));
+        assert_eq!(CharSet::DecTechnical.map(b'!'), Some('⎷'));
+        assert_eq!(CharSet::DecTechnical.map(b'~'), Some('↓'));
+        assert_eq!(CharSet::DecTechnical.map(b'8'), None);
+        assert_eq!(CharSet::IsoLatin1Supplemental.map(b'~'), Some('þ'));
+        assert_eq!(CharSet::German.map(b'~'), Some('ß'));
+        assert_eq!(CharSet::German.map(b'#'), None);
+        assert_eq!(CharSet::French.map(b'{'), Some('é'));
+        assert_eq!(CharSet::Spanish.map(b'~'), None);
+        assert_eq!(CharSet::Swiss.map(b'#'), Some('ù'));
+        assert_eq!(CharSet::Swedish.map(b'a'), None);
+    }
+
+    #[test]
+    fn shifts() {
+        let mut state = CharSetState::default();
+        state.designate(2, CharSet::German);
+        let mut buf = [0u8; 4];
+        assert_eq!(state.map("{", &mut buf), "{");
+        state.single_shift(2);
+        assert_eq!(state.map("{", &mut buf), "ä");
+        assert_eq!(state.map("{", &mut buf), "{");
+        state.locking_shift(1);
+        assert_eq!(state.map("q", &mut buf), "─");
+        state.locking_shift(2);
+        assert_eq!(state.map("}", &mut buf), "ü");
+        assert_eq!(state.map("ü", &mut buf), "ü");
+    }
+}
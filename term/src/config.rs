@@ -1,4 +1,6 @@
 use crate::color::ColorPalette;
+use termwiz::bidi::BidiMode;
+use termwiz::cell::UnicodeWidthPolicy;
 use termwiz::hyperlink::Rule as HyperlinkRule;
 
 /// TerminalConfiguration allows for the embedding application to pass configuration
@@ -21,6 +23,13 @@ pub trait TerminalConfiguration: std::fmt::Debug {
         3500
     }
 
+    /// Return true if lines that are older than `scrollback_size`
+    /// should be moved to a temporary file on disk rather than
+    /// being discarded.
+    fn enable_disk_scrollback(&self) -> bool {
+        false
+    }
+
     /// Return true if the embedding application wants to use CSI-u encoding
     /// for keys that would otherwise be ambiguous.
     /// <http://www.leonerd.org.uk/hacks/fixterms/>
@@ -72,4 +81,17 @@ pub trait TerminalConfiguration: std::fmt::Debug {
     fn alternate_buffer_wheel_scroll_speed(&self) -> u8 {
         3
     }
+
+    /// Returns the policy used to compute the number of cells occupied
+    /// by printed graphemes.  This should match the `wcwidth` of the
+    /// system on which the applications are running.
+    fn unicode_width_policy(&self) -> UnicodeWidthPolicy {
+        UnicodeWidthPolicy::default()
+    }
+
+    /// Returns the initial bidi mode of the terminal, which can be
+    /// changed by the application using BDSM, SCP and DECSET 2500/2501.
+    fn bidi_mode(&self) -> BidiMode {
+        BidiMode::default()
+    }
 }
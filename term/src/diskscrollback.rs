@@ -0,0 +1,247 @@
+//! DiskScrollback holds the lines that are too old to be kept in
+//! the in-memory scrollback of the primary screen.
+//! Lines are appended to an anonymous temporary file that is private
+//! to the screen; the file has no name on disk and its space is
+//! reclaimed by the OS when the screen drops it.
+//! The lines are grouped into blocks; each block is serialized and
+//! deflated before it is written, and an index of the blocks is kept
+//! in memory so that lines can be located by their `StableRowIndex`.
+use super::*;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
+
+/// The number of lines that are accumulated before they are
+/// compressed and written to disk as a block
+const LINES_PER_BLOCK: usize = 256;
+
+#[derive(Debug)]
+struct Block {
+    first_row: StableRowIndex,
+    num_rows: usize,
+    offset: u64,
+    len: usize,
+}
+
+#[derive(Debug)]
+pub struct DiskScrollback {
+    file: File,
+    /// The stable row index of the first line held here
+    first_row: StableRowIndex,
+    /// The number of lines held here, including the pending lines
+    len: usize,
+    /// The blocks that have been written to the file, in order
+    blocks: Vec<Block>,
+    /// The size of the file
+    file_len: u64,
+    /// Lines that have not yet been written as a block
+    pending: Vec<CompressedLine>,
+    /// The most recently read block, so that reading consecutive
+    /// ranges of lines doesn't repeatedly decode the same block
+    cache: RefCell<Option<(usize, Rc<Vec<CompressedLine>>)>>,
+}
+
+#[cfg(feature = "use_serde")]
+fn encode_block(lines: &[CompressedLine]) -> anyhow::Result<Vec<u8>> {
+    let data = serde_json::to_vec(lines)?;
+    Ok(miniz_oxide::deflate::compress_to_vec(&data, 6))
+}
+
+#[cfg(feature = "use_serde")]
+fn decode_block(data: &[u8]) -> anyhow::Result<Vec<CompressedLine>> {
+    let data = miniz_oxide::inflate::decompress_to_vec(data)
+        .map_err(|err| anyhow::anyhow!("failed to inflate scrollback block: {:?}", err))?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+#[cfg(not(feature = "use_serde"))]
+fn encode_block(_lines: &[CompressedLine]) -> anyhow::Result<Vec<u8>> {
+    anyhow::bail!("disk scrollback requires the use_serde feature");
+}
+
+#[cfg(not(feature = "use_serde"))]
+fn decode_block(_data: &[u8]) -> anyhow::Result<Vec<CompressedLine>> {
+    anyhow::bail!("disk scrollback requires the use_serde feature");
+}
+
+impl DiskScrollback {
+    /// Create an empty store whose first line will have the
+    /// stable row index `first_row`
+    pub fn new(first_row: StableRowIndex) -> anyhow::Result<Self> {
+        if cfg!(not(feature = "use_serde")) {
+            anyhow::bail!("disk scrollback requires the use_serde feature");
+        }
+
+        // The scrollback may hold sensitive output, so use a file
+        // that is only reachable through this handle
+        let file = tempfile::tempfile()
+            .map_err(|err| anyhow::anyhow!("creating scrollback file: {:#}", err))?;
+
+        Ok(Self {
+            file,
+            first_row,
+            len: 0,
+            blocks: vec![],
+            file_len: 0,
+            pending: vec![],
+            cache: RefCell::new(None),
+        })
+    }
+
+    /// Returns the stable row index of the first line
+    pub fn first_row(&self) -> StableRowIndex {
+        self.first_row
+    }
+
+    /// Returns the number of lines
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the stable row index that follows the last line
+    pub fn end_row(&self) -> StableRowIndex {
+        self.first_row + self.len() as StableRowIndex
+    }
+
+    /// Returns the number of bytes that have been written to disk
+    pub fn disk_usage(&self) -> u64 {
+        self.file_len
+    }
+
+    /// Appends a line; its stable row index is `end_row()`
+    pub fn push(&mut self, line: CompressedLine) -> anyhow::Result<()> {
+        self.pending.push(line);
+        self.len += 1;
+        if self.pending.len() >= LINES_PER_BLOCK {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let data = encode_block(&self.pending)?;
+        self.file.seek(SeekFrom::Start(self.file_len))?;
+        self.file.write_all(&data)?;
+
+        let first_row = self.end_row() - self.pending.len() as StableRowIndex;
+        self.blocks.push(Block {
+            first_row,
+            num_rows: self.pending.len(),
+            offset: self.file_len,
+            len: data.len(),
+        });
+        self.file_len += data.len() as u64;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn read_block(&self, idx: usize) -> anyhow::Result<Rc<Vec<CompressedLine>>> {
+        if let Some((cached_idx, lines)) = &*self.cache.borrow() {
+            if *cached_idx == idx {
+                return Ok(Rc::clone(lines));
+            }
+        }
+
+        let block = &self.blocks[idx];
+        let mut data = vec![0u8; block.len];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(block.offset))?;
+        file.read_exact(&mut data)?;
+        let lines = Rc::new(decode_block(&data)?);
+
+        self.cache.borrow_mut().replace((idx, Rc::clone(&lines)));
+        Ok(lines)
+    }
+
+    /// Returns the lines whose stable row indices fall within `range`.
+    /// Rows outside of the rows held here are ignored.
+    pub fn get_lines(&self, range: Range<StableRowIndex>) -> anyhow::Result<Vec<Line>> {
+        let start = range.start.max(self.first_row);
+        let end = range.end.min(self.end_row());
+        let mut lines = vec![];
+        if start >= end {
+            return Ok(lines);
+        }
+
+        let first_block = match self.blocks.binary_search_by(|b| {
+            if b.first_row + b.num_rows as StableRowIndex <= start {
+                Ordering::Less
+            } else if b.first_row > start {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        }) {
+            Ok(idx) | Err(idx) => idx,
+        };
+        for idx in first_block..self.blocks.len() {
+            let block = &self.blocks[idx];
+            if block.first_row >= end {
+                break;
+            }
+            let block_lines = self.read_block(idx)?;
+            let skip = (start - block.first_row).max(0) as usize;
+            let take = (end - block.first_row) as usize;
+            lines.extend(
+                block_lines
+                    .iter()
+                    .take(take)
+                    .skip(skip)
+                    .map(CompressedLine::to_line),
+            );
+        }
+
+        let pending_first_row = self.end_row() - self.pending.len() as StableRowIndex;
+        if end > pending_first_row {
+            let skip = (start - pending_first_row).max(0) as usize;
+            let take = (end - pending_first_row) as usize;
+            lines.extend(
+                self.pending
+                    .iter()
+                    .take(take)
+                    .skip(skip)
+                    .map(CompressedLine::to_line),
+            );
+        }
+
+        Ok(lines)
+    }
+
+    /// Iterates over all of the lines, decoding one block at a time.
+    /// A block that cannot be read is logged and skipped.
+    pub fn iter(&self) -> impl Iterator<Item = Line> + '_ {
+        let blocks = (0..self.blocks.len()).flat_map(move |idx| {
+            let lines = match self.read_block(idx) {
+                Ok(lines) => lines,
+                Err(err) => {
+                    log::error!("reading scrollback from disk: {:#}", err);
+                    Rc::new(vec![])
+                }
+            };
+            (0..lines.len()).map(move |i| lines[i].to_line())
+        });
+        blocks.chain(self.pending.iter().map(CompressedLine::to_line))
+    }
+
+    /// Discards all of the lines.  The next line that is pushed will
+    /// have the stable row index `first_row`.
+    pub fn clear(&mut self, first_row: StableRowIndex) -> anyhow::Result<()> {
+        self.file.set_len(0)?;
+        self.file_len = 0;
+        self.blocks.clear();
+        self.pending.clear();
+        self.len = 0;
+        self.cache.borrow_mut().take();
+        self.first_row = first_row;
+        Ok(())
+    }
+}
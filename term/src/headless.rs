@@ -0,0 +1,562 @@
+//! Runs a program in a pty attached to a `Terminal` without any GUI.
+//! This is intended for writing integration tests of command line and
+//! TUI applications: spawn the program, wait for the screen to reach
+//! some state, send it some keys and then compare a snapshot of the
+//! screen against a known good snapshot.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use wezterm_term::headless::{CommandBuilder, HeadlessTerminal};
+//!
+//! let mut cmd = CommandBuilder::new("bash");
+//! cmd.args(&["--norc", "--noprofile"]);
+//! let mut term = HeadlessTerminal::spawn(cmd, 24, 80)?;
+//! term.wait_for_text("$", Duration::from_secs(5))?;
+//! term.send_text("echo hello\n")?;
+//! term.wait_for_text("hello", Duration::from_secs(5))?;
+//! println!("{}", term.snapshot().to_text());
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+use crate::color::ColorPalette;
+use crate::{KeyCode, KeyModifiers, Terminal, TerminalConfiguration, TerminalSize};
+use anyhow::{bail, Context};
+use portable_pty::{Child, ExitStatus, MasterPty, PtySize};
+use serde::Serialize;
+use std::io::Read;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use termwiz::cell::{Blink, CellAttributes, Intensity, Underline};
+use termwiz::color::ColorAttribute;
+use termwiz::surface::CursorVisibility;
+
+pub use portable_pty::CommandBuilder;
+
+/// The nominal size of a cell; programs that query the pixel
+/// dimensions of the terminal will see sizes based on this
+const CELL_PIXEL_WIDTH: usize = 8;
+const CELL_PIXEL_HEIGHT: usize = 16;
+
+#[derive(Debug)]
+struct HeadlessConfig {
+    scrollback: usize,
+}
+
+impl TerminalConfiguration for HeadlessConfig {
+    fn scrollback_size(&self) -> usize {
+        self.scrollback
+    }
+
+    fn color_palette(&self) -> ColorPalette {
+        ColorPalette::default()
+    }
+}
+
+/// A program running in a pty, whose output is fed to a `Terminal`
+pub struct HeadlessTerminal {
+    terminal: Terminal,
+    master: Box<dyn MasterPty + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    output: Receiver<Vec<u8>>,
+    eof: bool,
+}
+
+impl HeadlessTerminal {
+    /// Spawn `cmd` in a pty of the specified size.
+    /// `TERM` defaults to `xterm-256color` unless `cmd` specifies it.
+    pub fn spawn(mut cmd: CommandBuilder, rows: usize, cols: usize) -> anyhow::Result<Self> {
+        if !cmd.iter_env_as_str().any(|(key, _)| key == "TERM") {
+            cmd.env("TERM", "xterm-256color");
+        }
+
+        let pty_system = portable_pty::native_pty_system();
+        let pair = pty_system.openpty(pty_size(rows, cols))?;
+        let child = pair
+            .slave
+            .spawn_command(cmd)
+            .context("spawning command in pty")?;
+        // Close our copy of the slave so that we see EOF
+        // when the child exits
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.try_clone_writer()?;
+
+        let (tx, output) = channel();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 8192];
+            loop {
+                match reader.read(&mut buf) {
+                    // Linux reports EIO rather than EOF once the
+                    // child has exited
+                    Ok(0) | Err(_) => break,
+                    Ok(size) => {
+                        if tx.send(buf[0..size].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        let terminal = Terminal::new(
+            terminal_size(rows, cols),
+            Arc::new(HeadlessConfig { scrollback: 0 }),
+            "WezTerm",
+            "headless",
+            writer,
+        );
+
+        Ok(Self {
+            terminal,
+            master: pair.master,
+            child,
+            output,
+            eof: false,
+        })
+    }
+
+    pub fn terminal(&self) -> &Terminal {
+        &self.terminal
+    }
+
+    pub fn terminal_mut(&mut self) -> &mut Terminal {
+        &mut self.terminal
+    }
+
+    /// Returns true if the program has closed the pty, which
+    /// typically means that it has exited
+    pub fn is_eof(&self) -> bool {
+        self.eof
+    }
+
+    /// Feed any output from the program to the terminal, waiting up to
+    /// `timeout` for some to arrive.  Returns true if any output was
+    /// processed.
+    pub fn process_output(&mut self, timeout: Duration) -> bool {
+        if self.eof {
+            return false;
+        }
+        match self.output.recv_timeout(timeout) {
+            Ok(data) => {
+                self.terminal.advance_bytes(data);
+                while let Ok(data) = self.output.try_recv() {
+                    self.terminal.advance_bytes(data);
+                }
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => {
+                self.eof = true;
+                false
+            }
+        }
+    }
+
+    /// Process output until `condition` returns true for the terminal.
+    /// Fails if the condition isn't met within `timeout`, or if the
+    /// program closes the pty first; the error includes the screen text
+    /// to help diagnose the failure.
+    pub fn wait_for<F: FnMut(&Terminal) -> bool>(
+        &mut self,
+        timeout: Duration,
+        mut condition: F,
+    ) -> anyhow::Result<()> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if condition(&self.terminal) {
+                return Ok(());
+            }
+            if self.eof {
+                bail!(
+                    "the program exited before the condition was met.  Screen:\n{}",
+                    self.screen_text()
+                );
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                bail!(
+                    "timed out after {:?} waiting for the condition.  Screen:\n{}",
+                    timeout,
+                    self.screen_text()
+                );
+            }
+            self.process_output(deadline - now);
+        }
+    }
+
+    /// Wait for `text` to appear anywhere in the visible screen
+    pub fn wait_for_text(&mut self, text: &str, timeout: Duration) -> anyhow::Result<()> {
+        self.wait_for(timeout, |term| visible_text(term).contains(text))
+            .with_context(|| format!("waiting for {:?}", text))
+    }
+
+    /// Wait for the cursor to move to the specified zero-based position
+    pub fn wait_for_cursor(&mut self, x: usize, y: usize, timeout: Duration) -> anyhow::Result<()> {
+        self.wait_for(timeout, |term| {
+            let cursor = term.cursor_pos();
+            cursor.x == x && cursor.y == y as i64
+        })
+        .with_context(|| format!("waiting for the cursor to move to {},{}", x, y))
+    }
+
+    /// Wait up to `timeout` for the program to exit, processing any
+    /// output that it produces in the meantime
+    pub fn wait_for_exit(&mut self, timeout: Duration) -> anyhow::Result<ExitStatus> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = self.child.try_wait()? {
+                // Pick up anything that was output just before exit
+                while self.process_output(Duration::from_millis(10)) {}
+                return Ok(status);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                bail!(
+                    "timed out after {:?} waiting for the program to exit",
+                    timeout
+                );
+            }
+            if !self.process_output((deadline - now).min(Duration::from_millis(50))) {
+                std::thread::sleep(Duration::from_millis(10).min(deadline - now));
+            }
+        }
+    }
+
+    /// Send a key press to the program, encoded according to the
+    /// modes that the program has enabled
+    pub fn send_key(&mut self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
+        self.terminal.key_down(key, mods)
+    }
+
+    /// Type `text` as a series of key presses.
+    /// Newlines are sent as the Enter key.
+    pub fn send_text(&mut self, text: &str) -> anyhow::Result<()> {
+        for c in text.chars() {
+            let key = match c {
+                '\n' | '\r' => KeyCode::Enter,
+                '\t' => KeyCode::Tab,
+                c => KeyCode::Char(c),
+            };
+            self.send_key(key, KeyModifiers::NONE)?;
+        }
+        Ok(())
+    }
+
+    /// Send `text` as a paste; it will be bracketed if the program
+    /// has enabled bracketed paste mode
+    pub fn send_paste(&mut self, text: &str) -> anyhow::Result<()> {
+        self.terminal.send_paste(text)
+    }
+
+    /// Resize both the pty and the terminal
+    pub fn resize(&mut self, rows: usize, cols: usize) -> anyhow::Result<()> {
+        self.master.resize(pty_size(rows, cols))?;
+        self.terminal.resize(
+            rows,
+            cols,
+            cols * CELL_PIXEL_WIDTH,
+            rows * CELL_PIXEL_HEIGHT,
+        );
+        Ok(())
+    }
+
+    /// Returns the text of the visible screen, with trailing
+    /// whitespace removed from each line
+    pub fn screen_text(&self) -> String {
+        visible_text(&self.terminal)
+    }
+
+    /// Capture the visible screen, including attributes
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::capture(&self.terminal)
+    }
+}
+
+impl Drop for HeadlessTerminal {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            self.child.kill().ok();
+        }
+    }
+}
+
+fn pty_size(rows: usize, cols: usize) -> PtySize {
+    PtySize {
+        rows: rows as u16,
+        cols: cols as u16,
+        pixel_width: (cols * CELL_PIXEL_WIDTH) as u16,
+        pixel_height: (rows * CELL_PIXEL_HEIGHT) as u16,
+    }
+}
+
+fn terminal_size(rows: usize, cols: usize) -> TerminalSize {
+    TerminalSize {
+        physical_rows: rows,
+        physical_cols: cols,
+        pixel_width: cols * CELL_PIXEL_WIDTH,
+        pixel_height: rows * CELL_PIXEL_HEIGHT,
+    }
+}
+
+fn visible_text(term: &Terminal) -> String {
+    let lines: Vec<String> = term
+        .screen()
+        .visible_lines()
+        .iter()
+        .map(|line| line.as_str().trim_end().to_string())
+        .collect();
+    lines.join("\n")
+}
+
+/// A run of cells on a line that share the same non-default attributes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SnapshotSpan {
+    /// The zero-based column of the first cell in the span
+    pub start: usize,
+    /// The number of columns spanned
+    pub width: usize,
+    /// A description of each non-default attribute, such as `bold`
+    /// or `fg=1`
+    pub attributes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SnapshotLine {
+    /// The text of the line with trailing whitespace removed
+    pub text: String,
+    pub spans: Vec<SnapshotSpan>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SnapshotCursor {
+    pub x: usize,
+    pub y: usize,
+    pub visible: bool,
+}
+
+/// A stable representation of the visible screen and its attributes,
+/// suitable for comparing against a stored snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Snapshot {
+    pub rows: usize,
+    pub cols: usize,
+    pub cursor: SnapshotCursor,
+    pub lines: Vec<SnapshotLine>,
+}
+
+impl Snapshot {
+    pub fn capture(term: &Terminal) -> Self {
+        let screen = term.screen();
+        let cursor = term.cursor_pos();
+        let lines = screen
+            .visible_lines()
+            .iter()
+            .map(|line| {
+                let mut spans: Vec<SnapshotSpan> = vec![];
+                for (idx, cell) in line.visible_cells() {
+                    let attributes = describe_attributes(cell.attrs());
+                    if attributes.is_empty() {
+                        continue;
+                    }
+                    match spans.last_mut() {
+                        Some(span)
+                            if span.start + span.width == idx && span.attributes == attributes =>
+                        {
+                            span.width += cell.width();
+                        }
+                        _ => spans.push(SnapshotSpan {
+                            start: idx,
+                            width: cell.width(),
+                            attributes,
+                        }),
+                    }
+                }
+                SnapshotLine {
+                    text: line.as_str().trim_end().to_string(),
+                    spans,
+                }
+            })
+            .collect();
+
+        Self {
+            rows: screen.physical_rows,
+            cols: screen.physical_cols,
+            cursor: SnapshotCursor {
+                x: cursor.x,
+                y: cursor.y.max(0) as usize,
+                visible: cursor.visibility == CursorVisibility::Visible,
+            },
+            lines,
+        }
+    }
+
+    /// Render the snapshot as text.  Each line of the screen is
+    /// prefixed with its one-based line number, and is followed by
+    /// a line marking each attributed span with `^` characters
+    /// and listing its attributes:
+    ///
+    /// ```text
+    /// size: 10x2 cursor: 5,0
+    /// 1|hello
+    ///  |^^^^^ bold fg=1
+    /// 2|
+    /// ```
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "size: {}x{} cursor: {},{}{}\n",
+            self.cols,
+            self.rows,
+            self.cursor.x,
+            self.cursor.y,
+            if self.cursor.visible { "" } else { " hidden" }
+        );
+        let gutter = self.lines.len().to_string().len();
+        for (idx, line) in self.lines.iter().enumerate() {
+            text.push_str(&format!(
+                "{:>width$}|{}\n",
+                idx + 1,
+                line.text,
+                width = gutter
+            ));
+            for span in &line.spans {
+                text.push_str(&format!(
+                    "{:width$}|{}{} {}\n",
+                    "",
+                    " ".repeat(span.start),
+                    "^".repeat(span.width),
+                    span.attributes.join(" "),
+                    width = gutter
+                ));
+            }
+        }
+        text
+    }
+
+    /// Render the snapshot as pretty printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("snapshot is always serializable")
+    }
+}
+
+fn describe_color(color: ColorAttribute) -> Option<String> {
+    match color {
+        ColorAttribute::Default => None,
+        ColorAttribute::PaletteIndex(idx) => Some(idx.to_string()),
+        ColorAttribute::TrueColorWithPaletteFallback(rgb, _)
+        | ColorAttribute::TrueColorWithDefaultFallback(rgb) => Some(rgb.to_rgb_string()),
+    }
+}
+
+fn describe_attributes(attrs: &CellAttributes) -> Vec<String> {
+    let mut result = vec![];
+    match attrs.intensity() {
+        Intensity::Normal => {}
+        Intensity::Bold => result.push("bold".to_string()),
+        Intensity::Half => result.push("dim".to_string()),
+    }
+    match attrs.underline() {
+        Underline::None => {}
+        Underline::Single => result.push("underline".to_string()),
+        underline => result.push(format!("underline={:?}", underline).to_lowercase()),
+    }
+    match attrs.blink() {
+        Blink::None => {}
+        Blink::Slow => result.push("blink".to_string()),
+        Blink::Rapid => result.push("rapid-blink".to_string()),
+    }
+    for (enabled, name) in &[
+        (attrs.italic(), "italic"),
+        (attrs.reverse(), "reverse"),
+        (attrs.strikethrough(), "strikethrough"),
+        (attrs.invisible(), "invisible"),
+        (attrs.overline(), "overline"),
+    ] {
+        if *enabled {
+            result.push(name.to_string());
+        }
+    }
+    if let Some(fg) = describe_color(attrs.foreground()) {
+        result.push(format!("fg={}", fg));
+    }
+    if let Some(bg) = describe_color(attrs.background()) {
+        result.push(format!("bg={}", bg));
+    }
+    if let Some(link) = attrs.hyperlink() {
+        result.push(format!("link={}", link.uri()));
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn new_terminal(rows: usize, cols: usize) -> Terminal {
+        Terminal::new(
+            terminal_size(rows, cols),
+            Arc::new(HeadlessConfig { scrollback: 0 }),
+            "WezTerm",
+            "headless",
+            Box::new(Vec::new()),
+        )
+    }
+
+    #[test]
+    fn snapshot_text() {
+        let mut term = new_terminal(3, 10);
+        term.advance_bytes("\x1b[1;31mhello\x1b[0m ok\r\n\x1b[4mx\x1b[0m");
+        let snapshot = Snapshot::capture(&term);
+        assert_eq!(
+            snapshot.to_text(),
+            "size: 10x3 cursor: 1,1\n\
+             1|hello ok\n\
+             \x20|^^^^^ bold fg=1\n\
+             2|x\n\
+             \x20|^ underline\n\
+             3|\n"
+        );
+    }
+
+    #[test]
+    fn snapshot_json() {
+        let mut term = new_terminal(1, 4);
+        term.advance_bytes("\x1b[?25l\x1b[38;2;255;0;0mab");
+        let snapshot = Snapshot::capture(&term);
+        assert!(!snapshot.cursor.visible);
+        assert_eq!(
+            snapshot.lines[0].spans,
+            vec![SnapshotSpan {
+                start: 0,
+                width: 2,
+                attributes: vec!["fg=#ff0000".to_string()],
+            }]
+        );
+        let json: serde_json::Value = serde_json::from_str(&snapshot.to_json()).unwrap();
+        assert_eq!(json["lines"][0]["text"], "ab");
+        assert_eq!(json["cursor"]["x"], 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn_and_wait() {
+        let mut cmd = CommandBuilder::new("sh");
+        cmd.args(&[
+            "-c",
+            "printf 'ready\\n'; read line; printf 'got %s\\n' \"$line\"",
+        ]);
+        let mut term = HeadlessTerminal::spawn(cmd, 5, 20).unwrap();
+        term.wait_for_text("ready", Duration::from_secs(10))
+            .unwrap();
+        term.send_text("hi\n").unwrap();
+        term.wait_for_text("got hi", Duration::from_secs(10))
+            .unwrap();
+        assert!(term
+            .wait_for_exit(Duration::from_secs(10))
+            .unwrap()
+            .success());
+        assert!(term
+            .wait_for_text("never", Duration::from_millis(100))
+            .is_err());
+    }
+}
@@ -12,6 +12,9 @@
 //! manage a PTY; you provide a `std::io::Write` implementation that
 //! could connect to a PTY, and supply bytes to the model via the
 //! `advance_bytes` method.
+//! The exception is the optional `headless` feature, whose
+//! [headless](headless/index.html) module runs a program in a PTY
+//! so that its output can be tested without a gui.
 //!
 //! The entrypoint to the crate is the [Terminal](terminal/struct.Terminal.html)
 //! struct.
@@ -21,9 +24,14 @@ use serde::{Deserialize, Serialize};
 use std::ops::{Deref, DerefMut, Range};
 use std::str;
 
+pub mod charset;
+
 pub mod config;
 pub use config::TerminalConfiguration;
 
+#[cfg(feature = "headless")]
+pub mod headless;
+
 pub mod input;
 pub use crate::input::*;
 
@@ -31,6 +39,12 @@ pub use termwiz::cell::{self, *};
 
 pub use termwiz::surface::line::*;
 
+pub mod diskscrollback;
+pub use crate::diskscrollback::*;
+
+pub mod linestore;
+pub use crate::linestore::*;
+
 pub mod screen;
 pub use crate::screen::*;
 
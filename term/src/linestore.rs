@@ -0,0 +1,251 @@
+//! LineStore holds the lines of a Screen, including its scrollback.
+//! Lines that have scrolled off the top of the screen are rarely
+//! modified, so they can be held as a `CompressedLine` which uses
+//! a fraction of the memory of a full `Line`.  Compressed lines are
+//! materialized when they are read, and turned back into full lines
+//! if they need to be modified.
+use super::*;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone)]
+enum StoredLine {
+    Line(Line),
+    Compressed(CompressedLine),
+}
+
+impl StoredLine {
+    fn to_line(&self) -> Cow<Line> {
+        match self {
+            Self::Line(line) => Cow::Borrowed(line),
+            Self::Compressed(line) => Cow::Owned(line.to_line()),
+        }
+    }
+
+    fn into_line(self) -> Line {
+        match self {
+            Self::Line(line) => line,
+            Self::Compressed(line) => line.to_line(),
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        match self {
+            Self::Line(line) => line.is_dirty(),
+            Self::Compressed(line) => line.is_dirty(),
+        }
+    }
+
+    fn set_dirty(&mut self) {
+        match self {
+            Self::Line(line) => line.set_dirty(),
+            Self::Compressed(line) => line.set_dirty(),
+        }
+    }
+
+    fn clear_dirty(&mut self) {
+        match self {
+            Self::Line(line) => line.clear_dirty(),
+            Self::Compressed(line) => line.clear_dirty(),
+        }
+    }
+
+    fn is_whitespace(&self) -> bool {
+        match self {
+            Self::Line(line) => line.is_whitespace(),
+            Self::Compressed(line) => line.is_whitespace(),
+        }
+    }
+
+    /// Returns the number of bytes used by the line, not counting
+    /// the size of the StoredLine itself
+    fn heap_size(&self) -> usize {
+        match self {
+            Self::Line(line) => line.memory_usage() - std::mem::size_of::<Line>(),
+            Self::Compressed(line) => line.memory_usage() - std::mem::size_of::<CompressedLine>(),
+        }
+    }
+}
+
+/// Describes the memory used to hold the lines of a screen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// The total number of lines
+    pub lines: usize,
+    /// The number of lines that are held in compressed form
+    pub compressed_lines: usize,
+    /// An estimate of the number of bytes used to hold the lines
+    pub bytes: usize,
+    /// The number of lines that have been moved to disk
+    pub disk_lines: usize,
+    /// The number of bytes used on disk to hold those lines
+    pub disk_bytes: usize,
+}
+
+impl std::ops::Add for MemoryUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            lines: self.lines + other.lines,
+            compressed_lines: self.compressed_lines + other.compressed_lines,
+            bytes: self.bytes + other.bytes,
+            disk_lines: self.disk_lines + other.disk_lines,
+            disk_bytes: self.disk_bytes + other.disk_bytes,
+        }
+    }
+}
+
+/// A double-ended queue of lines, indexed by `PhysRowIndex`
+#[derive(Debug, Clone, Default)]
+pub struct LineStore {
+    lines: VecDeque<StoredLine>,
+}
+
+impl LineStore {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.lines.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.lines.reserve(additional)
+    }
+
+    /// Returns the line at `idx`.  The line is borrowed if it is not
+    /// compressed, otherwise it is materialized.
+    pub fn get(&self, idx: PhysRowIndex) -> Option<Cow<Line>> {
+        self.lines.get(idx).map(StoredLine::to_line)
+    }
+
+    /// Returns the line at `idx` in compressed form
+    pub fn get_compressed(&self, idx: PhysRowIndex) -> Option<Cow<CompressedLine>> {
+        self.lines.get(idx).map(|stored| match stored {
+            StoredLine::Line(line) => Cow::Owned(line.compress()),
+            StoredLine::Compressed(line) => Cow::Borrowed(line),
+        })
+    }
+
+    /// Returns a mutable reference to the line at `idx`, decompressing
+    /// it first if required
+    pub fn get_mut(&mut self, idx: PhysRowIndex) -> Option<&mut Line> {
+        let stored = self.lines.get_mut(idx)?;
+        if let StoredLine::Compressed(line) = stored {
+            *stored = StoredLine::Line(line.to_line());
+        }
+        match stored {
+            StoredLine::Line(line) => Some(line),
+            StoredLine::Compressed(_) => unreachable!(),
+        }
+    }
+
+    /// Iterates over the lines, materializing compressed lines
+    pub fn iter(&self) -> impl Iterator<Item = Cow<Line>> + '_ {
+        self.lines.iter().map(StoredLine::to_line)
+    }
+
+    /// Removes all of the lines, returning them in order
+    pub fn drain(&mut self) -> impl Iterator<Item = Line> + '_ {
+        self.lines.drain(..).map(StoredLine::into_line)
+    }
+
+    pub fn push_back(&mut self, line: Line) {
+        self.lines.push_back(StoredLine::Line(line));
+    }
+
+    pub fn pop_back(&mut self) -> Option<Line> {
+        self.lines.pop_back().map(StoredLine::into_line)
+    }
+
+    pub fn insert(&mut self, idx: PhysRowIndex, line: Line) {
+        self.lines.insert(idx, StoredLine::Line(line));
+    }
+
+    pub fn remove(&mut self, idx: PhysRowIndex) -> Option<Line> {
+        self.lines.remove(idx).map(StoredLine::into_line)
+    }
+
+    /// Discards the first `n` lines
+    pub fn truncate_front(&mut self, n: usize) {
+        self.lines.drain(..n.min(self.lines.len()));
+    }
+
+    pub fn is_dirty(&self, idx: PhysRowIndex) -> bool {
+        self.lines
+            .get(idx)
+            .map(StoredLine::is_dirty)
+            .unwrap_or(false)
+    }
+
+    pub fn set_dirty(&mut self, idx: PhysRowIndex) {
+        if let Some(line) = self.lines.get_mut(idx) {
+            line.set_dirty();
+        }
+    }
+
+    pub fn clear_dirty(&mut self, idx: PhysRowIndex) {
+        if let Some(line) = self.lines.get_mut(idx) {
+            line.clear_dirty();
+        }
+    }
+
+    pub fn set_all_dirty(&mut self) {
+        for line in &mut self.lines {
+            line.set_dirty();
+        }
+    }
+
+    pub fn clear_all_dirty(&mut self) {
+        for line in &mut self.lines {
+            line.clear_dirty();
+        }
+    }
+
+    /// Returns true if the line at `idx` consists solely of whitespace
+    pub fn is_whitespace(&self, idx: PhysRowIndex) -> bool {
+        self.lines
+            .get(idx)
+            .map(StoredLine::is_whitespace)
+            .unwrap_or(false)
+    }
+
+    /// Compresses the lines in `range`.  Lines that are already
+    /// compressed are left alone.
+    pub fn compress(&mut self, range: Range<PhysRowIndex>) {
+        for stored in self.lines.range_mut(range) {
+            if let StoredLine::Line(line) = stored {
+                *stored = StoredLine::Compressed(line.compress());
+            }
+        }
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            lines: self.lines.len(),
+            compressed_lines: self
+                .lines
+                .iter()
+                .filter(|line| matches!(line, StoredLine::Compressed(_)))
+                .count(),
+            bytes: self.lines.capacity() * std::mem::size_of::<StoredLine>()
+                + self.lines.iter().map(StoredLine::heap_size).sum::<usize>(),
+            disk_lines: 0,
+            disk_bytes: 0,
+        }
+    }
+}
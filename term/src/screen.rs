@@ -1,14 +1,16 @@
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::range_plus_one))]
 use super::*;
 use log::debug;
-use std::collections::VecDeque;
+use std::borrow::Cow;
 use std::sync::Arc;
+use termwiz::bidi::BidiMode;
+use termwiz::surface::line::LineRendition;
 
 /// Holds the model of a screen.  This can either be the primary screen
 /// which includes lines of scrollback text, or the alternate screen
 /// which holds no scrollback.  The intent is to have one instance of
 /// Screen for each of these things.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Screen {
     /// Holds the line data that comprises the screen contents.
     /// This is allocated with capacity for the entire scrollback.
@@ -17,14 +19,20 @@ pub struct Screen {
     /// Index 0 is the topmost line of the screen/scrollback (depending
     /// on the current window size) and will be the first line to be
     /// popped off the front of the screen when a new line is added that
-    /// would otherwise have exceeded the line capacity
-    pub lines: VecDeque<Line>,
+    /// would otherwise have exceeded the line capacity.
+    /// Lines in the scrollback are held in compressed form.
+    pub lines: LineStore,
 
     /// Whenever we scroll a line off the top of the scrollback, we
     /// increment this.  We use this offset to translate between
     /// PhysRowIndex and StableRowIndex.
     stable_row_index_offset: usize,
 
+    /// When disk scrollback is enabled, holds the lines that were
+    /// removed from the front of `lines`.  The last line held here
+    /// immediately precedes the line at PhysRowIndex 0.
+    disk: Option<DiskScrollback>,
+
     /// config so we can access Maximum number of lines of scrollback
     config: Arc<dyn TerminalConfiguration>,
 
@@ -32,6 +40,11 @@ pub struct Screen {
     /// that we're the primary rather than the alternate screen.
     allow_scrollback: bool,
 
+    /// The row at which the most recent shell prompt started, as
+    /// reported via OSC 133.  It is cleared once the shell reports
+    /// that the command has started to produce output.
+    prompt_start: Option<StableRowIndex>,
+
     /// Physical, visible height of the screen (not including scrollback)
     pub physical_rows: usize,
     /// Physical, visible width of the screen
@@ -60,7 +73,7 @@ impl Screen {
         let physical_cols = physical_cols.max(1);
 
         let mut lines =
-            VecDeque::with_capacity(physical_rows + scrollback_size(config, allow_scrollback));
+            LineStore::with_capacity(physical_rows + scrollback_size(config, allow_scrollback));
         for _ in 0..physical_rows {
             lines.push_back(Line::with_width(0));
         }
@@ -72,6 +85,8 @@ impl Screen {
             physical_rows,
             physical_cols,
             stable_row_index_offset: 0,
+            disk: None,
+            prompt_start: None,
         }
     }
 
@@ -86,15 +101,18 @@ impl Screen {
         cursor_x: usize,
         cursor_y: PhysRowIndex,
     ) -> (usize, PhysRowIndex) {
-        let mut rewrapped = VecDeque::new();
+        let mut rewrapped = LineStore::with_capacity(self.lines.capacity());
         let mut logical_line: Option<Line> = None;
         let mut logical_cursor_x: Option<usize> = None;
         let mut adjusted_cursor = (cursor_y, cursor_y);
+        let last_idx = self.lines.len().saturating_sub(1);
 
-        for (phys_idx, mut line) in self.lines.drain(..).enumerate() {
+        for (phys_idx, mut line) in self.lines.drain().enumerate() {
             line.invalidate_implicit_hyperlinks();
             line.set_dirty();
-            let was_wrapped = line.last_cell_was_wrapped();
+            // The final line has no successor to join with, even if
+            // its last cell is marked as wrapped
+            let was_wrapped = line.last_cell_was_wrapped() && phys_idx != last_idx;
 
             if was_wrapped {
                 line.set_last_cell_was_wrapped(false);
@@ -134,6 +152,11 @@ impl Screen {
                     rewrapped.push_back(line);
                 }
             }
+
+            // Compress the lines that are too far back to be visible as
+            // we go, so that the whole scrollback isn't held uncompressed
+            let top = rewrapped.len().saturating_sub(physical_rows);
+            rewrapped.compress(top.saturating_sub(physical_rows)..top);
         }
         self.lines = rewrapped;
 
@@ -143,15 +166,52 @@ impl Screen {
         // out first in the rewrap case so that we don't lose any
         // real information off the top of the scrollback
         let capacity = physical_rows + self.scrollback_size();
-        while self.lines.len() > capacity
-            && self.lines.back().map(Line::is_whitespace).unwrap_or(false)
-        {
+        while self.lines.len() > capacity && self.lines.is_whitespace(self.lines.len() - 1) {
             self.lines.pop_back();
         }
 
         adjusted_cursor
     }
 
+    /// Record the row at which the shell prompt started, or clear it
+    /// when the command starts to produce output
+    pub fn set_prompt_start(&mut self, row: Option<StableRowIndex>) {
+        self.prompt_start = row;
+    }
+
+    /// If the shell has reported the start of its prompt and the cursor
+    /// is within the prompt and input that follows it, returns the
+    /// physical row at which the prompt starts.  If any of those lines
+    /// hold command output then the shell didn't report the start of
+    /// the output, and we cannot safely treat them as the prompt.
+    fn prompt_zone_start(&self, cursor_phys: PhysRowIndex) -> Option<PhysRowIndex> {
+        let start = self.stable_row_to_phys(self.prompt_start?)?;
+        if start > cursor_phys {
+            return None;
+        }
+        for idx in start..self.lines.len() {
+            let line = self.lines.get(idx)?;
+            let has_output = line.cells().iter().any(|cell| {
+                cell.attrs().semantic_type() == SemanticType::Output && cell.str() != " "
+            });
+            if has_output {
+                return None;
+            }
+        }
+        Some(start)
+    }
+
+    /// Erases the prompt zone that begins at `start`, retaining blank
+    /// lines from there through to the cursor row
+    fn erase_prompt_zone(&mut self, start: PhysRowIndex, cursor_phys: PhysRowIndex) {
+        while self.lines.len() > start {
+            self.lines.pop_back();
+        }
+        for _ in start..=cursor_phys {
+            self.lines.push_back(Line::with_width(0));
+        }
+    }
+
     /// Resize the physical, viewable portion of the screen
     pub fn resize(
         &mut self,
@@ -171,7 +231,7 @@ impl Screen {
         // maximized states.
         let cursor_phys = self.phys_row(cursor.y);
         for _ in cursor_phys + 1..self.lines.len() {
-            if self.lines.back().map(Line::is_whitespace).unwrap_or(false) {
+            if self.lines.is_whitespace(self.lines.len() - 1) {
                 self.lines.pop_back();
             }
         }
@@ -181,22 +241,37 @@ impl Screen {
             // wrapped due to reaching the right hand side of the terminal.
             // For each one that we find, we need to join it with its
             // successor and then re-split it.
-            // We only do this for the primary, and not for the alternate
-            // screen (hence the check for allow_scrollback), to avoid
-            // conflicting screen updates with full screen apps.
             if self.allow_scrollback {
-                self.rewrap_lines(physical_cols, physical_rows, cursor.x, cursor_phys)
-            } else {
-                for line in &mut self.lines {
-                    if physical_cols < self.physical_cols {
-                        // Do a simple prune of the lines instead
-                        line.resize(physical_cols);
-                    } else {
-                        // otherwise: invalidate them
-                        line.set_dirty();
+                // The shell redraws its prompt when it is notified of the
+                // resize, so rather than reflowing the prompt, erase it
+                // and leave the cursor in the same row relative to the
+                // start of the prompt, which is where the shell will
+                // expect to find it.  This avoids leaving stale copies
+                // of the prompt in the scrollback.
+                let cursor_x = match self.prompt_zone_start(cursor_phys) {
+                    Some(start) => {
+                        self.erase_prompt_zone(start, cursor_phys);
+                        cursor.x.min(physical_cols - 1)
                     }
+                    None => cursor.x,
+                };
+                self.rewrap_lines(physical_cols, physical_rows, cursor_x, cursor_phys)
+            } else {
+                // Full screen applications will repaint the alternate
+                // screen after the resize, but we reflow it so that it
+                // remains legible until they do.  There is no scrollback,
+                // so we discard the lines that no longer fit, preferring
+                // to keep those around the cursor.
+                let (cursor_x, cursor_y) =
+                    self.rewrap_lines(physical_cols, physical_rows, cursor.x, cursor_phys);
+                let excess = self.lines.len().saturating_sub(physical_rows).min(cursor_y);
+                self.lines.truncate_front(excess);
+                self.stable_row_index_offset += excess;
+                let cursor_y = cursor_y - excess;
+                while self.lines.len() > physical_rows.max(cursor_y + 1) {
+                    self.lines.pop_back();
                 }
-                (cursor.x, cursor_phys)
+                (cursor_x, cursor_y)
             }
         } else {
             (cursor.x, cursor_phys)
@@ -254,23 +329,33 @@ impl Screen {
         }
     }
 
+    /// Get a line, relative to start of scrollback.
+    /// A compressed line is materialized without replacing the
+    /// stored copy, so prefer this to `line_mut` when only reading.
+    #[inline]
+    pub fn line(&self, idx: PhysRowIndex) -> Cow<Line> {
+        self.lines
+            .get(idx)
+            .unwrap_or_else(|| panic!("line index {} is out of range", idx))
+    }
+
     /// Get mutable reference to a line, relative to start of scrollback.
     #[inline]
     pub fn line_mut(&mut self, idx: PhysRowIndex) -> &mut Line {
-        &mut self.lines[idx]
+        self.lines
+            .get_mut(idx)
+            .unwrap_or_else(|| panic!("line index {} is out of range", idx))
     }
 
     /// Sets a line dirty.  The line is relative to the visible origin.
     #[inline]
     pub fn dirty_line(&mut self, idx: VisibleRowIndex) {
         let line_idx = self.phys_row(idx);
-        if line_idx < self.lines.len() {
-            self.lines[line_idx].set_dirty();
-        }
+        self.lines.set_dirty(line_idx);
     }
 
     /// Returns a copy of the visible lines in the screen (no scrollback)
-    #[cfg(test)]
+    #[cfg(any(test, feature = "headless"))]
     pub fn visible_lines(&self) -> Vec<Line> {
         let line_idx = self.lines.len() - self.physical_rows;
         let mut lines = Vec::new();
@@ -278,7 +363,7 @@ impl Screen {
             if lines.len() >= self.physical_rows {
                 break;
             }
-            lines.push(line.clone());
+            lines.push(line.into_owned());
         }
         lines
     }
@@ -286,7 +371,7 @@ impl Screen {
     /// Returns a copy of the lines in the screen (including scrollback)
     #[cfg(test)]
     pub fn all_lines(&self) -> Vec<Line> {
-        self.lines.iter().map(|l| l.clone()).collect()
+        self.lines.iter().map(|l| l.into_owned()).collect()
     }
 
     pub fn insert_cell(&mut self, x: usize, y: VisibleRowIndex, right_margin: usize) {
@@ -324,6 +409,43 @@ impl Screen {
         line.fill_range(cols, &Cell::new(' ', attr.clone()));
     }
 
+    /// Returns the size at which the line at the visible row y is displayed
+    pub fn line_rendition(&self, y: VisibleRowIndex) -> LineRendition {
+        self.lines
+            .get(self.phys_row(y))
+            .map(|line| line.rendition())
+            .unwrap_or(LineRendition::SingleWidth)
+    }
+
+    /// Returns the number of cells that fit on the line at the visible
+    /// row y, taking its rendition into account
+    pub fn line_width(&self, y: VisibleRowIndex) -> usize {
+        if self.line_rendition(y).is_double_width() {
+            (self.physical_cols / 2).max(1)
+        } else {
+            self.physical_cols
+        }
+    }
+
+    /// Change the rendition of the line at the visible row y.
+    /// Cells that no longer fit on the display are discarded.
+    pub fn set_line_rendition(&mut self, y: VisibleRowIndex, rendition: LineRendition) {
+        let line_idx = self.phys_row(y);
+        let line = self.line_mut(line_idx);
+        line.set_rendition(rendition);
+        let width = self.line_width(y);
+        let line = self.line_mut(line_idx);
+        if line.cells().len() > width {
+            line.resize(width);
+        }
+    }
+
+    /// Set the bidi mode used when displaying the line
+    pub fn set_line_bidi_mode(&mut self, y: VisibleRowIndex, mode: BidiMode) {
+        let line_idx = self.phys_row(y);
+        self.line_mut(line_idx).set_bidi_mode(mode);
+    }
+
     /// Translate a VisibleRowIndex into a PhysRowIndex.  The resultant index
     /// will be invalidated by inserting or removing rows!
     #[inline]
@@ -439,7 +561,7 @@ impl Screen {
 
                 // Copy the source cells first
                 let cells = {
-                    self.lines[src_row]
+                    self.line(src_row)
                         .cells()
                         .iter()
                         .skip(left_and_right_margins.start)
@@ -541,6 +663,10 @@ impl Screen {
             phys_scroll.start
         };
 
+        if remove_idx == 0 && lines_removed > 0 {
+            self.spill_to_disk(lines_removed);
+        }
+
         // To avoid thrashing the heap, prefer to move lines that were
         // scrolled off the top and re-use them at the bottom.
         let to_move = lines_removed.min(num_rows);
@@ -580,15 +706,116 @@ impl Screen {
                 self.lines.insert(phys_scroll.end, Line::with_width(0));
             }
         }
+
+        if scroll_region.start == 0 && self.allow_scrollback {
+            // The rows that were scrolled off the top of the display
+            // are now just above it, in the scrollback
+            let top = self.lines.len() - self.physical_rows;
+            self.lines.compress(top.saturating_sub(num_rows)..top);
+        }
     }
 
     pub fn erase_scrollback(&mut self) {
         let len = self.lines.len();
         let to_clear = len - self.physical_rows;
-        for _ in 0..to_clear {
-            self.lines.pop_front();
-            self.stable_row_index_offset += 1;
+        self.lines.truncate_front(to_clear);
+        self.stable_row_index_offset += to_clear;
+        self.disk = None;
+    }
+
+    /// Copies the first `num_rows` lines to the disk scrollback,
+    /// if it is enabled, prior to their removal from `lines`
+    fn spill_to_disk(&mut self, num_rows: usize) {
+        if !self.allow_scrollback || !self.config.enable_disk_scrollback() {
+            self.disk = None;
+            return;
+        }
+
+        let top = self.phys_to_stable_row_index(0);
+        if self.disk.is_none() {
+            match DiskScrollback::new(top) {
+                Ok(disk) => self.disk = Some(disk),
+                Err(err) => {
+                    log::error!("unable to create disk scrollback: {:#}", err);
+                    return;
+                }
+            }
         }
+
+        let disk = self.disk.as_mut().unwrap();
+        let mut result = Ok(());
+        if disk.end_row() != top {
+            // The lines on disk are no longer contiguous with those
+            // in memory, most likely because the screen was resized
+            result = disk.clear(top);
+        }
+        for idx in 0..num_rows.min(self.lines.len()) {
+            if result.is_err() {
+                break;
+            }
+            if let Some(line) = self.lines.get_compressed(idx) {
+                result = disk.push(line.into_owned());
+            }
+        }
+
+        if let Err(err) = result {
+            log::error!("writing scrollback to disk: {:#}", err);
+            self.disk = None;
+        }
+    }
+
+    /// Returns the number of lines held in the disk scrollback
+    pub fn disk_scrollback_rows(&self) -> usize {
+        self.disk.as_ref().map(DiskScrollback::len).unwrap_or(0)
+    }
+
+    /// Returns the memory used by the lines, along with the space
+    /// used by the disk scrollback
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = self.lines.memory_usage();
+        if let Some(disk) = &self.disk {
+            usage.disk_lines = disk.len();
+            usage.disk_bytes = disk.disk_usage() as usize;
+        }
+        usage
+    }
+
+    /// Returns the stable row index of the oldest line in the
+    /// scrollback, including the disk scrollback
+    pub fn scrollback_top(&self) -> StableRowIndex {
+        match &self.disk {
+            Some(disk) if !disk.is_empty() => disk.first_row(),
+            _ => self.phys_to_stable_row_index(0),
+        }
+    }
+
+    /// Returns the lines from the disk scrollback whose stable
+    /// row indices fall within `range`
+    pub fn get_disk_lines(&self, range: Range<StableRowIndex>) -> Vec<Line> {
+        match &self.disk {
+            Some(disk) => disk.get_lines(range).unwrap_or_else(|err| {
+                log::error!("reading scrollback from disk: {:#}", err);
+                vec![]
+            }),
+            None => vec![],
+        }
+    }
+
+    /// Iterates over every line, including those in the disk
+    /// scrollback, along with its stable row index
+    pub fn iter_stable_lines(&self) -> impl Iterator<Item = (StableRowIndex, Cow<Line>)> + '_ {
+        let disk = self.disk.iter().flat_map(|disk| {
+            let first_row = disk.first_row();
+            disk.iter()
+                .enumerate()
+                .map(move |(idx, line)| (first_row + idx as StableRowIndex, Cow::Owned(line)))
+        });
+        let memory = self
+            .lines
+            .iter()
+            .enumerate()
+            .map(move |(idx, line)| (self.phys_to_stable_row_index(idx), line));
+        disk.chain(memory)
     }
 
     /// ```text
@@ -648,7 +875,7 @@ impl Screen {
 
                 // Copy the source cells first
                 let cells = {
-                    self.lines[src_row]
+                    self.line(src_row)
                         .cells()
                         .iter()
                         .skip(left_and_right_margins.start)
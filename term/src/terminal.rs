@@ -54,6 +54,11 @@ pub enum Alert {
     TitleMaybeChanged,
     /// When the color palette has been updated
     PaletteChanged,
+    /// When the current working directory has been changed by OSC 7
+    CurrentWorkingDirectoryChanged,
+    /// When a user variable has been set by the iTerm2 `SetUserVar`
+    /// escape sequence
+    SetUserVar { name: String, value: String },
 }
 
 pub trait AlertHandler {
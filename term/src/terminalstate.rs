@@ -2,6 +2,7 @@
 // and inclusive range
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::range_plus_one))]
 use super::*;
+use crate::charset::{CharSet, CharSetState};
 use crate::color::{ColorPalette, RgbColor};
 use anyhow::bail;
 use image::imageops::FilterType;
@@ -13,10 +14,12 @@ use std::collections::HashMap;
 use std::fmt::Write;
 use std::sync::mpsc::{channel, Sender};
 use std::sync::Arc;
+use termwiz::bidi::{BidiMode, ParagraphDirectionHint};
 use termwiz::escape::csi::{
-    Cursor, CursorStyle, DecPrivateMode, DecPrivateModeCode, Device, Edit, EraseInDisplay,
-    EraseInLine, Mode, Sgr, TabulationClear, TerminalMode, TerminalModeCode, Window, XtSmGraphics,
-    XtSmGraphicsAction, XtSmGraphicsItem, XtSmGraphicsStatus,
+    AttributeChangeExtent, CharacterPath, Cursor, CursorStyle, DecPrivateMode, DecPrivateModeCode,
+    Device, Edit, EraseInDisplay, EraseInLine, Mode, Rectangle, Sgr, TabulationClear, TerminalMode,
+    TerminalModeCode, Window, XtSmGraphics, XtSmGraphicsAction, XtSmGraphicsItem,
+    XtSmGraphicsStatus,
 };
 use termwiz::escape::osc::{
     ChangeColorPair, ColorOrQuery, FinalTermSemanticPrompt, ITermFileData, ITermProprietary,
@@ -27,6 +30,7 @@ use termwiz::escape::{
     SixelData, CSI,
 };
 use termwiz::image::{ImageCell, ImageData, TextureCoordinate};
+use termwiz::surface::line::LineRendition;
 use termwiz::surface::{CursorShape, CursorVisibility};
 use url::Url;
 
@@ -35,13 +39,6 @@ struct TabStop {
     tab_width: usize,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum CharSet {
-    Ascii,
-    Uk,
-    DecLineDrawing,
-}
-
 impl TabStop {
     fn new(screen_width: usize, tab_width: usize) -> Self {
         let mut tabs = Vec::with_capacity(screen_width);
@@ -110,9 +107,7 @@ struct SavedCursor {
     wrap_next: bool,
     pen: CellAttributes,
     dec_origin_mode: bool,
-    g0_charset: CharSet,
-    g1_charset: CharSet,
-    // TODO: selective_erase when supported
+    charsets: CharSetState,
 }
 
 struct ScreenOrAlt {
@@ -240,6 +235,9 @@ pub struct TerminalState {
     /// Reverse Wraparound Mode
     reverse_wraparound_mode: bool,
 
+    /// https://vt100.net/docs/vt510-rm/DECSACE.html
+    attribute_change_extent: AttributeChangeExtent,
+
     /// https://vt100.net/docs/vt510-rm/DECOM.html
     /// When OriginMode is enabled, cursor is constrained to the
     /// scroll region and its position is relative to the scroll
@@ -286,10 +284,11 @@ pub struct TerminalState {
     last_mouse_move: Option<MouseEvent>,
     cursor_visible: bool,
 
-    /// Support for US, UK, and DEC Special Graphics
-    g0_charset: CharSet,
-    g1_charset: CharSet,
-    shift_out: bool,
+    /// The G0-G3 character set designations and shift state
+    charsets: CharSetState,
+
+    /// The bidi mode that is applied to lines as they are printed to
+    bidi_mode: BidiMode,
 
     tabs: TabStop,
 
@@ -461,6 +460,7 @@ impl TerminalState {
         let screen = ScreenOrAlt::new(size.physical_rows, size.physical_cols, &config);
 
         let color_map = default_color_map();
+        let bidi_mode = config.bidi_mode();
 
         TerminalState {
             config,
@@ -475,6 +475,7 @@ impl TerminalState {
             // a dec terminal is false, because it is more useful this way.
             dec_auto_wrap: true,
             reverse_wraparound_mode: false,
+            attribute_change_extent: AttributeChangeExtent::default(),
             dec_origin_mode: false,
             insert: false,
             application_cursor_keys: false,
@@ -492,9 +493,8 @@ impl TerminalState {
             mouse_tracking: false,
             last_mouse_move: None,
             cursor_visible: true,
-            g0_charset: CharSet::Ascii,
-            g1_charset: CharSet::DecLineDrawing,
-            shift_out: false,
+            charsets: CharSetState::default(),
+            bidi_mode,
             current_mouse_button: MouseButton::None,
             tabs: TabStop::new(size.physical_cols, 8),
             title: "wezterm".to_string(),
@@ -590,6 +590,12 @@ impl TerminalState {
         &mut self.screen
     }
 
+    /// Returns the memory used to hold the lines of both the primary
+    /// and the alternate screen
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.screen.screen.memory_usage() + self.screen.alt_screen.memory_usage()
+    }
+
     fn set_clipboard_contents(
         &self,
         selection: ClipboardSelection,
@@ -821,11 +827,11 @@ impl TerminalState {
         self.erase_in_display(EraseInDisplay::EraseScrollback);
 
         let row_index = self.screen.phys_row(self.cursor.y);
-        let row = self.screen.lines[row_index].clone();
+        let row = self.screen.line_mut(row_index).clone();
 
         self.erase_in_display(EraseInDisplay::EraseDisplay);
 
-        self.screen.lines[0] = row;
+        *self.screen.line_mut(0) = row;
 
         self.cursor.y = 0;
     }
@@ -1216,18 +1222,12 @@ impl TerminalState {
 
     /// Clear the dirty flag for all dirty lines
     pub fn clean_dirty_lines(&mut self) {
-        let screen = self.screen_mut();
-        for line in &mut screen.lines {
-            line.clear_dirty();
-        }
+        self.screen_mut().lines.clear_all_dirty();
     }
 
     /// When dealing with selection, mark a range of lines as dirty
     pub fn make_all_lines_dirty(&mut self) {
-        let screen = self.screen_mut();
-        for line in &mut screen.lines {
-            line.set_dirty();
-        }
+        self.screen_mut().lines.set_all_dirty();
     }
 
     /// Returns the 0-based cursor position relative to the top left of
@@ -1317,7 +1317,10 @@ impl TerminalState {
             .max(0),
         };
 
-        self.set_cursor_position_absolute(x as usize, y);
+        // Double width lines can hold only half as many cells
+        let x = (x as usize).min(self.screen().line_width(y) - 1);
+
+        self.set_cursor_position_absolute(x, y);
     }
 
     fn scroll_up(&mut self, num_rows: usize) {
@@ -1340,6 +1343,23 @@ impl TerminalState {
         )
     }
 
+    /// Records the cursor row as the start of the shell prompt, so that
+    /// the prompt can be erased rather than reflowed on resize
+    fn mark_prompt_start(&mut self) {
+        let row = self.screen().visible_row_to_stable_row(self.cursor.y);
+        self.screen_mut().set_prompt_start(Some(row));
+    }
+
+    /// Moving to the next line while the pen is tagged as input means
+    /// that the command was submitted.  A shell that doesn't report the
+    /// start of the output would otherwise leave it within the prompt
+    /// zone, and a resize would erase that output along with the prompt.
+    fn end_prompt_on_new_line(&mut self) {
+        if self.pen.semantic_type() == SemanticType::Input {
+            self.screen_mut().set_prompt_start(None);
+        }
+    }
+
     /// Defined by FinalTermSemanticPrompt; a fresh-line is a NOP if the
     /// cursor is already at the left margin, otherwise it is the same as
     /// a new line.
@@ -1369,6 +1389,7 @@ impl TerminalState {
     /// Moves the cursor down one line in the same column.
     /// If the cursor is at the bottom margin, the page scrolls up.
     fn c1_index(&mut self) {
+        self.end_prompt_on_new_line();
         if self.left_and_right_margins.contains(&self.cursor.x) {
             if self.cursor.y == self.top_and_bottom_margins.end - 1 {
                 self.scroll_up(1);
@@ -1381,6 +1402,7 @@ impl TerminalState {
     /// Moves the cursor to the first position on the next line.
     /// If the cursor is at the bottom margin, the page scrolls up.
     fn c1_nel(&mut self) {
+        self.end_prompt_on_new_line();
         let y_clamp = if self.top_and_bottom_margins.contains(&self.cursor.y) {
             self.top_and_bottom_margins.end - 1
         } else {
@@ -1415,15 +1437,37 @@ impl TerminalState {
         self.tabs.set_tab_stop(self.cursor.x);
     }
 
+    /// Returns the effective right margin for the line that holds the
+    /// cursor.  Double width lines can hold only half as many cells.
+    fn right_margin_for_cursor_line(&self) -> usize {
+        self.left_and_right_margins
+            .end
+            .min(self.screen().line_width(self.cursor.y))
+    }
+
+    /// Implements DECSWL, DECDWL and DECDHL, which change the size
+    /// of the line that holds the cursor.
+    /// Cells that no longer fit on the line are discarded and the
+    /// cursor is moved to the new right edge of the line if necessary.
+    fn set_cursor_line_rendition(&mut self, rendition: LineRendition) {
+        let y = self.cursor.y;
+        self.screen_mut().set_line_rendition(y, rendition);
+        let width = self.screen().line_width(y);
+        if self.cursor.x >= width {
+            self.cursor.x = width - 1;
+        }
+    }
+
     /// Moves the cursor to the next tab stop. If there are no more tab stops,
     /// the cursor moves to the right margin. HT does not cause text to auto
     /// wrap.
     fn c0_horizontal_tab(&mut self) {
+        let right_margin = self.right_margin_for_cursor_line();
         let x = match self.tabs.find_next_tab_stop(self.cursor.x) {
             Some(x) => x,
-            None => self.left_and_right_margins.end - 1,
+            None => right_margin - 1,
         };
-        self.cursor.x = x.min(self.left_and_right_margins.end - 1);
+        self.cursor.x = x.min(right_margin - 1);
         let y = self.cursor.y;
         self.screen_mut().dirty_line(y);
     }
@@ -1785,6 +1829,7 @@ impl TerminalState {
                 self.screen.saved_cursor().take();
 
                 self.reverse_wraparound_mode = false;
+                self.attribute_change_extent = AttributeChangeExtent::default();
             }
             Device::RequestPrimaryDeviceAttributes => {
                 let mut ident = "\x1b[?65".to_string(); // Vt500
@@ -1792,6 +1837,7 @@ impl TerminalState {
                 ident.push_str(";6"); // Selective erase
                 ident.push_str(";18"); // windowing extensions
                 ident.push_str(";22"); // ANSI color, vt525
+                ident.push_str(";28"); // Rectangular editing
                 ident.push('c');
 
                 self.writer.write(ident.as_bytes()).ok();
@@ -1967,6 +2013,47 @@ impl TerminalState {
                 self.insert = false;
             }
 
+            Mode::SetMode(TerminalMode::Code(TerminalModeCode::BiDirectionalSupportMode)) => {
+                self.set_bidi_mode(BidiMode {
+                    enabled: true,
+                    ..self.bidi_mode
+                });
+            }
+            Mode::ResetMode(TerminalMode::Code(TerminalModeCode::BiDirectionalSupportMode)) => {
+                self.set_bidi_mode(BidiMode {
+                    enabled: false,
+                    ..self.bidi_mode
+                });
+            }
+
+            Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::MirrorBoxDrawing)) => {
+                self.set_bidi_mode(BidiMode {
+                    mirror_box_drawing: true,
+                    ..self.bidi_mode
+                });
+            }
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(
+                DecPrivateModeCode::MirrorBoxDrawing,
+            )) => {
+                self.set_bidi_mode(BidiMode {
+                    mirror_box_drawing: false,
+                    ..self.bidi_mode
+                });
+            }
+
+            Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::BidiAutoDetect)) => {
+                self.set_bidi_mode(BidiMode {
+                    hint: self.bidi_mode.hint.with_auto(true),
+                    ..self.bidi_mode
+                });
+            }
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::BidiAutoDetect)) => {
+                self.set_bidi_mode(BidiMode {
+                    hint: self.bidi_mode.hint.with_auto(false),
+                    ..self.bidi_mode
+                });
+            }
+
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::BracketedPaste)) => {
                 self.bracketed_paste = true;
             }
@@ -2198,6 +2285,29 @@ impl TerminalState {
         checksum
     }
 
+    /// SCP selects the direction of the paragraph, retaining the
+    /// autodetection setting of DECSET 2501.
+    fn select_character_path(&mut self, path: CharacterPath) {
+        let auto = self.bidi_mode.hint.is_auto();
+        let hint = match path {
+            CharacterPath::ImplementationDefault => self.config.bidi_mode().hint,
+            CharacterPath::LeftToRightOrTopToBottom => ParagraphDirectionHint::LeftToRight,
+            CharacterPath::RightToLeftOrBottomToTop => ParagraphDirectionHint::RightToLeft,
+        };
+        self.set_bidi_mode(BidiMode {
+            hint: hint.with_auto(auto),
+            ..self.bidi_mode
+        });
+    }
+
+    /// Changes to the bidi mode take effect on the line holding the
+    /// cursor as well as on the lines that are subsequently printed to.
+    fn set_bidi_mode(&mut self, mode: BidiMode) {
+        self.bidi_mode = mode;
+        let y = self.cursor.y;
+        self.screen_mut().set_line_bidi_mode(y, mode);
+    }
+
     fn perform_csi_window(&mut self, window: Window) {
         match window {
             Window::ReportTextAreaSizeCells => {
@@ -2298,6 +2408,9 @@ impl TerminalState {
         {
             let screen = self.screen_mut();
             for y in row_range.clone() {
+                // Lines that are erased in their entirety revert
+                // to single width
+                screen.set_line_rendition(y, LineRendition::SingleWidth);
                 screen.clear_line(y, col_range.clone(), &pen);
             }
         }
@@ -2309,8 +2422,8 @@ impl TerminalState {
                 let y = self.cursor.y;
                 let x = self.cursor.x;
 
-                if x >= self.left_and_right_margins.start && x < self.left_and_right_margins.end {
-                    let right_margin = self.left_and_right_margins.end;
+                let right_margin = self.right_margin_for_cursor_line();
+                if x >= self.left_and_right_margins.start && x < right_margin {
                     let limit = (x + n as usize).min(right_margin);
 
                     let screen = self.screen_mut();
@@ -2335,7 +2448,7 @@ impl TerminalState {
             Edit::EraseCharacter(n) => {
                 let y = self.cursor.y;
                 let x = self.cursor.x;
-                let limit = (x + n as usize).min(self.screen().physical_cols);
+                let limit = (x + n as usize).min(self.screen().line_width(y));
                 {
                     let blank = Cell::new(' ', self.pen.clone_sgr_only());
                     let screen = self.screen_mut();
@@ -2367,10 +2480,11 @@ impl TerminalState {
 
                 let y = self.cursor.y;
                 let x = self.cursor.x;
+                let margin = self.right_margin_for_cursor_line();
                 if self.top_and_bottom_margins.contains(&y)
-                    && self.left_and_right_margins.contains(&x)
+                    && x >= self.left_and_right_margins.start
+                    && x < margin
                 {
-                    let margin = self.left_and_right_margins.end;
                     let screen = self.screen_mut();
                     for _ in 0..n as usize {
                         screen.insert_cell(x, y, margin);
@@ -2449,6 +2563,232 @@ impl TerminalState {
                 self.cursor.x = x;
                 self.cursor.y = y;
             }
+            Edit::CopyRectangularArea {
+                source,
+                dest_top,
+                dest_left,
+                ..
+            } => self.copy_rectangle(&source, dest_top, dest_left),
+            Edit::FillRectangularArea { ch, area } => {
+                let cell = Cell::new(ch, self.pen.clone_sgr_only());
+                self.fill_rectangle(&area, &cell);
+            }
+            Edit::EraseRectangularArea(area) => {
+                let cell = Cell::new(' ', self.pen.clone_sgr_only());
+                self.fill_rectangle(&area, &cell);
+            }
+            Edit::SelectiveEraseRectangularArea(area) => {
+                if let Some((rows, cols)) = self.rectangle_ranges(&area) {
+                    for y in rows {
+                        self.selective_erase(y, cols.clone());
+                    }
+                }
+            }
+            Edit::ChangeAttributesInRectangularArea { area, attributes } => {
+                self.change_rectangle_attributes(&area, |attrs| {
+                    for sgr in &attributes {
+                        change_attribute(attrs, sgr);
+                    }
+                });
+            }
+            Edit::ReverseAttributesInRectangularArea { area, attributes } => {
+                self.change_rectangle_attributes(&area, |attrs| {
+                    for sgr in &attributes {
+                        reverse_attribute(attrs, sgr);
+                    }
+                });
+            }
+            Edit::SelectAttributeChangeExtent(extent) => {
+                self.attribute_change_extent = extent;
+            }
+            Edit::SelectCharacterProtection(protect) => {
+                self.pen.set_protected(protect);
+            }
+            Edit::SelectiveEraseInDisplay(erase) => {
+                let cy = self.cursor.y;
+                let rows = self.screen().physical_rows as VisibleRowIndex;
+                let cols = self.screen().physical_cols;
+                let row_range = match erase {
+                    EraseInDisplay::EraseToEndOfDisplay => {
+                        self.perform_csi_edit(Edit::SelectiveEraseInLine(
+                            EraseInLine::EraseToEndOfLine,
+                        ));
+                        cy + 1..rows
+                    }
+                    EraseInDisplay::EraseToStartOfDisplay => {
+                        self.perform_csi_edit(Edit::SelectiveEraseInLine(
+                            EraseInLine::EraseToStartOfLine,
+                        ));
+                        0..cy
+                    }
+                    EraseInDisplay::EraseDisplay => 0..rows,
+                    // There is no selective form of erasing the scrollback
+                    EraseInDisplay::EraseScrollback => 0..0,
+                };
+                for y in row_range {
+                    self.selective_erase(y, 0..cols);
+                }
+            }
+            Edit::SelectiveEraseInLine(erase) => {
+                let cx = self.cursor.x;
+                let cy = self.cursor.y;
+                let cols = self.screen().physical_cols;
+                let range = match erase {
+                    EraseInLine::EraseToEndOfLine => cx..cols,
+                    EraseInLine::EraseToStartOfLine => 0..cx + 1,
+                    EraseInLine::EraseLine => 0..cols,
+                };
+                self.selective_erase(cy, range);
+            }
+        }
+    }
+
+    /// Implements the selective erase operations (DECSED, DECSEL and
+    /// DECSERA), which replace the characters in `cols` of the visible
+    /// row `y` with blanks, except for those that are protected by DECSCA.
+    /// The attributes of the erased characters are left alone.
+    fn selective_erase(&mut self, y: VisibleRowIndex, cols: Range<usize>) {
+        let screen = self.screen_mut();
+        let line_idx = screen.phys_row(y);
+        let line = screen.line_mut(line_idx);
+        for x in cols {
+            match line.cells().get(x) {
+                Some(cell) if !cell.attrs().protected() => {
+                    let attrs = cell.attrs().clone();
+                    line.set_cell(x, Cell::new(' ', attrs));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Translates the one-based rectangle of a DEC rectangular area
+    /// operation into zero-based, inclusive (top, left, bottom, right)
+    /// screen coordinates, taking origin mode into account and clipping
+    /// to the screen.
+    fn rectangle_bounds(&self, area: &Rectangle) -> (usize, usize, usize, usize) {
+        let (y_origin, x_origin) = if self.dec_origin_mode {
+            (
+                self.top_and_bottom_margins.start as usize,
+                self.left_and_right_margins.start,
+            )
+        } else {
+            (0, 0)
+        };
+        let screen = self.screen();
+
+        let top = y_origin.saturating_add(area.top.as_zero_based() as usize);
+        let bottom = y_origin
+            .saturating_add(area.bottom.as_zero_based() as usize)
+            .min(screen.physical_rows - 1);
+        let left = x_origin.saturating_add(area.left.as_zero_based() as usize);
+        let right = x_origin
+            .saturating_add(area.right.as_zero_based() as usize)
+            .min(screen.physical_cols - 1);
+
+        (top, left, bottom, right)
+    }
+
+    /// Returns the ranges of visible rows and columns that are covered
+    /// by the rectangle of a DEC rectangular area operation, or None if
+    /// the rectangle is empty.
+    fn rectangle_ranges(&self, area: &Rectangle) -> Option<(Range<VisibleRowIndex>, Range<usize>)> {
+        let (top, left, bottom, right) = self.rectangle_bounds(area);
+        if top > bottom || left > right {
+            None
+        } else {
+            Some((
+                top as VisibleRowIndex..bottom as VisibleRowIndex + 1,
+                left..right + 1,
+            ))
+        }
+    }
+
+    /// Implements DECFRA and DECERA
+    fn fill_rectangle(&mut self, area: &Rectangle, cell: &Cell) {
+        if let Some((rows, cols)) = self.rectangle_ranges(area) {
+            let screen = self.screen_mut();
+            for y in rows {
+                let line_idx = screen.phys_row(y);
+                screen.line_mut(line_idx).fill_range(cols.clone(), cell);
+            }
+        }
+    }
+
+    /// Implements DECCRA
+    fn copy_rectangle(&mut self, source: &Rectangle, dest_top: OneBased, dest_left: OneBased) {
+        let (rows, cols) = match self.rectangle_ranges(source) {
+            Some(ranges) => ranges,
+            None => return,
+        };
+        let dest = match self.rectangle_ranges(&Rectangle {
+            top: dest_top,
+            left: dest_left,
+            bottom: OneBased::new(u32::max_value()),
+            right: OneBased::new(u32::max_value()),
+        }) {
+            Some(ranges) => ranges,
+            None => return,
+        };
+
+        // Take a copy of the source first, as the source and
+        // destination may overlap
+        let screen = self.screen_mut();
+        let cells: Vec<Vec<Cell>> = rows
+            .map(|y| {
+                let line_idx = screen.phys_row(y);
+                let line = screen.line_mut(line_idx);
+                cols.clone()
+                    .map(|x| line.cells().get(x).cloned().unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+
+        for (y, row) in dest.0.zip(cells) {
+            let line_idx = screen.phys_row(y);
+            let line = screen.line_mut(line_idx);
+            for (x, cell) in dest.1.clone().zip(row) {
+                line.set_cell(x, cell);
+            }
+        }
+    }
+
+    /// Implements DECCARA and DECRARA, applying `func` to the attributes
+    /// of each of the cells selected by the rectangle and DECSACE.
+    fn change_rectangle_attributes<F: FnMut(&mut CellAttributes)>(
+        &mut self,
+        area: &Rectangle,
+        mut func: F,
+    ) {
+        let (top, left, bottom, right) = self.rectangle_bounds(area);
+        let extent = self.attribute_change_extent;
+        let screen = self.screen_mut();
+        let width = screen.physical_cols;
+
+        for y in top..=bottom {
+            let cols = match extent {
+                AttributeChangeExtent::Rectangle => left..right + 1,
+                // The stream runs from the top left position to the
+                // bottom right position in reading order
+                AttributeChangeExtent::Stream => {
+                    let start = if y == top { left } else { 0 };
+                    let end = if y == bottom { right + 1 } else { width };
+                    start..end
+                }
+            };
+            if cols.start >= cols.end {
+                continue;
+            }
+            let y = y as VisibleRowIndex;
+            let line_idx = screen.phys_row(y);
+            let line = screen.line_mut(line_idx);
+            if line.cells().len() < cols.end {
+                line.resize(cols.end);
+            }
+            for cell in &mut line.cells_mut_for_attr_changes_only()[cols] {
+                func(cell.attrs_mut());
+            }
+            line.set_dirty();
         }
     }
 
@@ -2772,8 +3112,7 @@ impl TerminalState {
             wrap_next: self.wrap_next,
             pen: self.pen.clone(),
             dec_origin_mode: self.dec_origin_mode,
-            g0_charset: self.g0_charset,
-            g1_charset: self.g1_charset,
+            charsets: self.charsets.saved(),
         };
         debug!(
             "saving cursor {:?} is_alt={}",
@@ -2794,8 +3133,7 @@ impl TerminalState {
                 wrap_next: false,
                 pen: Default::default(),
                 dec_origin_mode: false,
-                g0_charset: CharSet::Ascii,
-                g1_charset: CharSet::DecLineDrawing,
+                charsets: CharSetState::default(),
             });
         debug!(
             "restore cursor {:?} is_alt={}",
@@ -2811,9 +3149,7 @@ impl TerminalState {
         self.wrap_next = saved.wrap_next;
         self.pen = saved.pen;
         self.dec_origin_mode = saved.dec_origin_mode;
-        self.g0_charset = saved.g0_charset;
-        self.g1_charset = saved.g1_charset;
-        self.shift_out = false;
+        self.charsets = saved.charsets;
     }
 
     fn perform_csi_sgr(&mut self, sgr: Sgr) {
@@ -2822,9 +3158,12 @@ impl TerminalState {
             Sgr::Reset => {
                 let link = self.pen.hyperlink().map(Arc::clone);
                 let semantic_type = self.pen.semantic_type();
+                // Character protection is selected by DECSCA rather than SGR
+                let protected = self.pen.protected();
                 self.pen = CellAttributes::default();
                 self.pen.set_hyperlink(link);
                 self.pen.set_semantic_type(semantic_type);
+                self.pen.set_protected(protected);
             }
             Sgr::Intensity(intensity) => {
                 self.pen.set_intensity(intensity);
@@ -2875,7 +3214,7 @@ impl TerminalState {
     pub fn get_semantic_zones(&self) -> anyhow::Result<Vec<SemanticZone>> {
         let screen = self.screen();
 
-        let mut last_cell: Option<&Cell> = None;
+        let mut last_semantic_type = None;
         let mut current_zone = None;
         let mut zones = vec![];
         let blank_cell = Cell::new(' ', Default::default());
@@ -2898,10 +3237,7 @@ impl TerminalState {
                     break;
                 }
                 let semantic_type = cell.attrs().semantic_type();
-                let new_zone = match last_cell {
-                    None => true,
-                    Some(c) => c.attrs().semantic_type() != semantic_type,
-                };
+                let new_zone = last_semantic_type != Some(semantic_type);
 
                 if new_zone {
                     if let Some(zone) = current_zone.take() {
@@ -2922,7 +3258,7 @@ impl TerminalState {
                     zone.end_y = stable_row;
                 }
 
-                last_cell.replace(cell);
+                last_semantic_type.replace(semantic_type);
             }
         }
         if let Some(zone) = current_zone.take() {
@@ -2960,6 +3296,70 @@ impl<'a> Drop for Performer<'a> {
     }
 }
 
+/// Applies one of the attributes of DECCARA to `attrs`.
+/// Only the attributes defined for DECCARA can be changed;
+/// the others are ignored.
+fn change_attribute(attrs: &mut CellAttributes, sgr: &Sgr) {
+    match sgr {
+        Sgr::Reset => {
+            attrs.set_intensity(Intensity::Normal);
+            attrs.set_underline(Underline::None);
+            attrs.set_blink(Blink::None);
+            attrs.set_reverse(false);
+            attrs.set_invisible(false);
+        }
+        Sgr::Intensity(intensity) => {
+            attrs.set_intensity(*intensity);
+        }
+        Sgr::Underline(underline) => {
+            attrs.set_underline(*underline);
+        }
+        Sgr::Blink(blink) => {
+            attrs.set_blink(*blink);
+        }
+        Sgr::Inverse(inverse) => {
+            attrs.set_reverse(*inverse);
+        }
+        Sgr::Invisible(invisible) => {
+            attrs.set_invisible(*invisible);
+        }
+        _ => debug!("DECCARA: ignoring {:?}", sgr),
+    }
+}
+
+/// Toggles one of the attributes of DECRARA in `attrs`.
+/// `Sgr::Reset` toggles all of them.
+fn reverse_attribute(attrs: &mut CellAttributes, sgr: &Sgr) {
+    let all = *sgr == Sgr::Reset;
+    if all || matches!(sgr, Sgr::Intensity(Intensity::Bold)) {
+        attrs.set_intensity(if attrs.intensity() == Intensity::Bold {
+            Intensity::Normal
+        } else {
+            Intensity::Bold
+        });
+    }
+    if all || matches!(sgr, Sgr::Underline(Underline::Single)) {
+        attrs.set_underline(if attrs.underline() == Underline::None {
+            Underline::Single
+        } else {
+            Underline::None
+        });
+    }
+    if all || matches!(sgr, Sgr::Blink(Blink::Slow)) {
+        attrs.set_blink(if attrs.blink() == Blink::None {
+            Blink::Slow
+        } else {
+            Blink::None
+        });
+    }
+    if all || matches!(sgr, Sgr::Inverse(true)) {
+        attrs.set_reverse(!attrs.reverse());
+    }
+    if matches!(sgr, Sgr::Invisible(true)) {
+        attrs.set_invisible(!attrs.invisible());
+    }
+}
+
 fn selection_to_selection(sel: Selection) -> ClipboardSelection {
     match sel {
         Selection::CLIPBOARD => ClipboardSelection::Clipboard,
@@ -2984,62 +3384,26 @@ impl<'a> Performer<'a> {
             None => return,
         };
 
+        let width_policy = self.config.unicode_width_policy();
+        let bidi_mode = self.bidi_mode;
+        // The row whose bidi mode was most recently applied; the mode
+        // only needs to be set once for each row that we print into
+        let mut bidi_row = None;
+
         for g in unicode_segmentation::UnicodeSegmentation::graphemes(p.as_str(), true) {
-            let g = if (self.shift_out && self.g1_charset == CharSet::DecLineDrawing)
-                || (!self.shift_out && self.g0_charset == CharSet::DecLineDrawing)
-            {
-                match g {
-                    "`" => "◆",
-                    "a" => "▒",
-                    "b" => "␉",
-                    "c" => "␌",
-                    "d" => "␍",
-                    "e" => "␊",
-                    "f" => "°",
-                    "g" => "±",
-                    "h" => "␤",
-                    "i" => "␋",
-                    "j" => "┘",
-                    "k" => "┐",
-                    "l" => "┌",
-                    "m" => "└",
-                    "n" => "┼",
-                    "o" => "⎺",
-                    "p" => "⎻",
-                    "q" => "─",
-                    "r" => "⎼",
-                    "s" => "⎽",
-                    "t" => "├",
-                    "u" => "┤",
-                    "v" => "┴",
-                    "w" => "┬",
-                    "x" => "│",
-                    "y" => "≤",
-                    "z" => "≥",
-                    "{" => "π",
-                    "|" => "≠",
-                    "}" => "£",
-                    "~" => "·",
-                    _ => g,
-                }
-            } else if (self.shift_out && self.g1_charset == CharSet::Uk)
-                || (!self.shift_out && self.g0_charset == CharSet::Uk)
-            {
-                match g {
-                    "#" => "£",
-                    _ => g,
-                }
-            } else {
-                g
-            };
+            let mut buf = [0u8; 4];
+            let g = self.charsets.map(g, &mut buf);
 
             if self.wrap_next {
                 self.new_line(true);
+                // The cursor row may be unchanged if the screen scrolled,
+                // but it now refers to a different line
+                bidi_row = None;
             }
 
             let x = self.cursor.x;
             let y = self.cursor.y;
-            let width = self.left_and_right_margins.end;
+            let width = self.right_margin_for_cursor_line();
 
             let mut pen = self.pen.clone();
             // the max(1) here is to ensure that we advance to the next cell
@@ -3047,13 +3411,14 @@ impl<'a> Performer<'a> {
             // they occupy a cell so that we can re-emit them when we output them.
             // If we didn't do this, then we'd effectively filter them out from
             // the model, which seems like a lossy design choice.
-            let print_width = unicode_column_width(g).max(1);
+            let grapheme_width = width_policy.grapheme_width(g);
+            let print_width = grapheme_width.max(1);
 
             if x + print_width >= width {
                 pen.set_wrapped(true);
             }
 
-            let cell = Cell::new_grapheme(g, pen);
+            let cell = Cell::new_grapheme_with_width(g, grapheme_width, pen);
 
             if self.insert {
                 let margin = self.left_and_right_margins.end;
@@ -3066,6 +3431,10 @@ impl<'a> Performer<'a> {
             // Assign the cell
             log::trace!("print x={} y={} cell={:?}", x, y, cell);
             self.screen_mut().set_cell(x, y, &cell);
+            if bidi_row != Some(y) {
+                self.screen_mut().set_line_bidi_mode(y, bidi_mode);
+                bidi_row = Some(y);
+            }
 
             if x + print_width < width {
                 self.cursor.x += print_width;
@@ -3163,6 +3532,7 @@ impl<'a> Performer<'a> {
         self.flush_print();
         match control {
             ControlCode::LineFeed | ControlCode::VerticalTab | ControlCode::FormFeed => {
+                self.end_prompt_on_new_line();
                 if self.left_and_right_margins.contains(&self.cursor.x) {
                     self.new_line(false);
                 } else {
@@ -3236,18 +3606,15 @@ impl<'a> Performer<'a> {
             }
             ControlCode::RI => self.c1_reverse_index(),
 
-            // wezterm only supports UTF-8, so does not support the
-            // DEC National Replacement Character Sets.  However, it does
-            // support the DEC Special Graphics character set used by
-            // numerous ncurses applications.  DEC Special Graphics can be
-            // selected by ASCII Shift Out (0x0E, ^N) or by setting G0
-            // via ESC ( 0 .
-            ControlCode::ShiftIn => {
-                self.shift_out = false;
-            }
-            ControlCode::ShiftOut => {
-                self.shift_out = true;
-            }
+            // wezterm only supports UTF-8, so the designated character
+            // sets are applied by mapping the 7-bit graphic characters
+            // in flush_print.  G1 defaults to DEC Special Graphics, which
+            // numerous ncurses applications select via ASCII Shift Out
+            // (0x0E, ^N).
+            ControlCode::ShiftIn => self.charsets.locking_shift(0),
+            ControlCode::ShiftOut => self.charsets.locking_shift(1),
+            ControlCode::SS2 => self.charsets.single_shift(2),
+            ControlCode::SS3 => self.charsets.single_shift(3),
             _ => log::warn!("unhandled ControlCode {:?}", control),
         }
     }
@@ -3262,6 +3629,7 @@ impl<'a> Performer<'a> {
             CSI::Device(dev) => self.state.perform_device(*dev),
             CSI::Mouse(mouse) => error!("mouse report sent by app? {:?}", mouse),
             CSI::Window(window) => self.state.perform_csi_window(window),
+            CSI::SelectCharacterPath(path, _) => self.state.select_character_path(path),
             CSI::Unspecified(unspec) => {
                 log::warn!("unknown unspecified CSI: {:?}", format!("{}", unspec))
             }
@@ -3287,26 +3655,25 @@ impl<'a> Performer<'a> {
             Esc::Code(EscCode::Index) => self.c1_index(),
             Esc::Code(EscCode::NextLine) => self.c1_nel(),
             Esc::Code(EscCode::HorizontalTabSet) => self.c1_hts(),
-            Esc::Code(EscCode::DecLineDrawingG0) => {
-                self.g0_charset = CharSet::DecLineDrawing;
-            }
-            Esc::Code(EscCode::AsciiCharacterSetG0) => {
-                self.g0_charset = CharSet::Ascii;
-            }
-            Esc::Code(EscCode::UkCharacterSetG0) => {
-                self.g0_charset = CharSet::Uk;
+            Esc::Code(EscCode::LockingShiftG2) => self.charsets.locking_shift(2),
+            Esc::Code(EscCode::LockingShiftG3) => self.charsets.locking_shift(3),
+            Esc::Code(EscCode::SingleShiftG2) => self.charsets.single_shift(2),
+            Esc::Code(EscCode::SingleShiftG3) => self.charsets.single_shift(3),
+            Esc::Code(EscCode::DecSaveCursorPosition) => self.dec_save_cursor(),
+            Esc::Code(EscCode::DecRestoreCursorPosition) => self.dec_restore_cursor(),
+
+            Esc::Code(EscCode::DecDoubleHeightTopHalfLine) => {
+                self.set_cursor_line_rendition(LineRendition::DoubleHeightTop)
             }
-            Esc::Code(EscCode::DecLineDrawingG1) => {
-                self.g1_charset = CharSet::DecLineDrawing;
+            Esc::Code(EscCode::DecDoubleHeightBottomHalfLine) => {
+                self.set_cursor_line_rendition(LineRendition::DoubleHeightBottom)
             }
-            Esc::Code(EscCode::AsciiCharacterSetG1) => {
-                self.g1_charset = CharSet::Ascii;
+            Esc::Code(EscCode::DecSingleWidthLine) => {
+                self.set_cursor_line_rendition(LineRendition::SingleWidth)
             }
-            Esc::Code(EscCode::UkCharacterSetG1) => {
-                self.g1_charset = CharSet::Uk;
+            Esc::Code(EscCode::DecDoubleWidthLine) => {
+                self.set_cursor_line_rendition(LineRendition::DoubleWidth)
             }
-            Esc::Code(EscCode::DecSaveCursorPosition) => self.dec_save_cursor(),
-            Esc::Code(EscCode::DecRestoreCursorPosition) => self.dec_restore_cursor(),
 
             Esc::Code(EscCode::DecScreenAlignmentDisplay) => {
                 // This one is just to make vttest happy;
@@ -3318,6 +3685,7 @@ impl<'a> Performer<'a> {
                 for y in 0..screen.physical_rows as VisibleRowIndex {
                     let line_idx = screen.phys_row(y);
                     let line = screen.line_mut(line_idx);
+                    line.set_rendition(LineRendition::SingleWidth);
                     line.resize(col_range.end);
                     line.fill_range(
                         col_range.clone(),
@@ -3341,6 +3709,7 @@ impl<'a> Performer<'a> {
                 self.insert = false;
                 self.dec_auto_wrap = true;
                 self.reverse_wraparound_mode = false;
+                self.attribute_change_extent = AttributeChangeExtent::default();
                 self.dec_origin_mode = false;
                 self.use_private_color_registers_for_each_graphic = false;
                 self.color_map = default_color_map();
@@ -3356,9 +3725,8 @@ impl<'a> Performer<'a> {
                 self.button_event_mouse = false;
                 self.current_mouse_button = MouseButton::None;
                 self.cursor_visible = true;
-                self.g0_charset = CharSet::Ascii;
-                self.g1_charset = CharSet::DecLineDrawing;
-                self.shift_out = false;
+                self.charsets = CharSetState::default();
+                self.bidi_mode = self.config.bidi_mode();
                 self.tabs = TabStop::new(self.screen().physical_cols, 8);
                 self.palette.take();
                 self.top_and_bottom_margins = 0..self.screen().physical_rows as VisibleRowIndex;
@@ -3372,6 +3740,10 @@ impl<'a> Performer<'a> {
                 }
             }
 
+            Esc::Code(code) => match CharSet::designation(code) {
+                Some((idx, charset)) => self.charsets.designate(idx, charset),
+                None => log::warn!("ESC: unhandled {:?}", esc),
+            },
             _ => log::warn!("ESC: unhandled {:?}", esc),
         }
     }
@@ -3432,9 +3804,10 @@ impl<'a> Performer<'a> {
             OperatingSystemCommand::ITermProprietary(iterm) => match iterm {
                 ITermProprietary::File(image) => self.set_image(*image),
                 ITermProprietary::SetUserVar { name, value } => {
-                    self.user_vars.insert(name, value);
+                    self.user_vars.insert(name.clone(), value.clone());
                     if let Some(handler) = self.alert_handler.as_mut() {
                         handler.alert(Alert::TitleMaybeChanged);
+                        handler.alert(Alert::SetUserVar { name, value });
                     }
                 }
                 _ => log::warn!("unhandled iterm2: {:?}", iterm),
@@ -3447,6 +3820,7 @@ impl<'a> Performer<'a> {
                 FinalTermSemanticPrompt::FreshLineAndStartPrompt { .. },
             ) => {
                 self.fresh_line();
+                self.mark_prompt_start();
                 self.pen.set_semantic_type(SemanticType::Prompt);
             }
             OperatingSystemCommand::FinalTermSemanticPrompt(
@@ -3458,6 +3832,7 @@ impl<'a> Performer<'a> {
                 FinalTermSemanticPrompt::MarkEndOfCommandWithFreshLine { .. },
             ) => {
                 self.fresh_line();
+                self.mark_prompt_start();
                 self.pen.set_semantic_type(SemanticType::Prompt);
             }
             OperatingSystemCommand::FinalTermSemanticPrompt(
@@ -3468,6 +3843,7 @@ impl<'a> Performer<'a> {
             OperatingSystemCommand::FinalTermSemanticPrompt(
                 FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { .. },
             ) => {
+                self.screen_mut().set_prompt_start(None);
                 self.pen.set_semantic_type(SemanticType::Output);
             }
 
@@ -3515,6 +3891,7 @@ impl<'a> Performer<'a> {
                 self.current_dir = Url::parse(&url).ok();
                 if let Some(handler) = self.alert_handler.as_mut() {
                     handler.alert(Alert::TitleMaybeChanged);
+                    handler.alert(Alert::CurrentWorkingDirectoryChanged);
                 }
             }
             OperatingSystemCommand::ChangeColorNumber(specs) => {
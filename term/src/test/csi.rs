@@ -1,4 +1,5 @@
 use super::*;
+use pretty_assertions::assert_eq;
 
 #[test]
 fn test_vpa() {
@@ -187,3 +188,233 @@ fn test_ed_erase_scrollback() {
     term.print("b");
     assert_all_contents(&term, file!(), line!(), &["111", "222", "ab"]);
 }
+
+/// Returns the intensity of each of the cells of the visible line y
+fn line_intensities(term: &TestTerm, y: usize) -> Vec<Intensity> {
+    term.screen().visible_lines()[y]
+        .cells()
+        .iter()
+        .map(|cell| cell.attrs().intensity())
+        .collect()
+}
+
+#[test]
+fn test_decfra() {
+    let mut term = TestTerm::new(4, 5, 0);
+    term.print("abcde\r\nfghij\r\nklmno\r\npqrst");
+    term.print("\x1b[42;2;2;3;4$x");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["abcde", "f***j", "k***o", "pqrst"],
+    );
+
+    // The rectangle is clipped to the screen
+    term.print("\x1b[43;4;4;10;10$x");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["abcde", "f***j", "k***o", "pqr++"],
+    );
+}
+
+#[test]
+fn test_decera() {
+    let mut term = TestTerm::new(4, 5, 0);
+    term.print("abcde\r\nfghij\r\nklmno\r\npqrst");
+    term.print("\x1b[2;2;3;4$z");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["abcde", "f   j", "k   o", "pqrst"],
+    );
+
+    // The default rectangle is the whole screen
+    term.print("\x1b[$z");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["     ", "     ", "     ", "     "],
+    );
+}
+
+#[test]
+fn test_decsera() {
+    let mut term = TestTerm::new(2, 5, 0);
+    term.print("ab\x1b[1mcde\r\nfghij");
+    term.print("\x1b[1;2;1;4${");
+    assert_visible_contents(&term, file!(), line!(), &["a   e", "fghij"]);
+
+    // The attributes of the erased cells are retained
+    assert_eq!(
+        line_intensities(&term, 0),
+        vec![
+            Intensity::Normal,
+            Intensity::Normal,
+            Intensity::Bold,
+            Intensity::Bold,
+            Intensity::Bold
+        ]
+    );
+}
+
+#[test]
+fn test_deccra() {
+    let mut term = TestTerm::new(4, 5, 0);
+    term.print("abcde\r\nfghij\r\nklmno\r\npqrst");
+    term.print("\x1b[1;1;2;2;1;3;4;1$v");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["abcde", "fghij", "klmab", "pqrfg"],
+    );
+
+    // Overlapping source and destination
+    term.print("\x1b[1;1;2;4;1;1;2;1$v");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["aabcd", "ffghi", "klmab", "pqrfg"],
+    );
+}
+
+#[test]
+fn test_deccara() {
+    let mut term = TestTerm::new(3, 4, 0);
+    term.print("abcd\r\nefgh\r\nijkl");
+
+    // The default extent is a stream of characters
+    term.print("\x1b[1;3;2;2;1$r");
+    let normal = Intensity::Normal;
+    let bold = Intensity::Bold;
+    assert_eq!(line_intensities(&term, 0), vec![normal, normal, bold, bold]);
+    assert_eq!(line_intensities(&term, 1), vec![bold, bold, normal, normal]);
+
+    // Select the rectangle extent
+    term.print("\x1b[2*x");
+    term.print("\x1b[1;1;3;4;0$r");
+    term.print("\x1b[2;2;3;3;1$r");
+    assert_eq!(line_intensities(&term, 0), vec![normal; 4]);
+    assert_eq!(line_intensities(&term, 1), vec![normal, bold, bold, normal]);
+    assert_eq!(line_intensities(&term, 2), vec![normal, bold, bold, normal]);
+}
+
+#[test]
+fn test_decrara() {
+    let mut term = TestTerm::new(2, 4, 0);
+    term.print("ab\x1b[4mcd\x1b[0m\r\nefgh");
+    term.print("\x1b[2*x");
+    term.print("\x1b[1;2;2;3;4$t");
+
+    let underlines = |term: &TestTerm, y: usize| -> Vec<Underline> {
+        term.screen().visible_lines()[y]
+            .cells()
+            .iter()
+            .map(|cell| cell.attrs().underline())
+            .collect()
+    };
+    let none = Underline::None;
+    let single = Underline::Single;
+    assert_eq!(underlines(&term, 0), vec![none, single, none, single]);
+    assert_eq!(underlines(&term, 1), vec![none, single, single, none]);
+}
+
+#[test]
+fn test_decsca_decsel() {
+    let mut term = TestTerm::new(2, 6, 0);
+    // "cd" is protected; SGR 0 doesn't change the protection
+    term.print("ab\x1b[1\"q\x1b[0mcd\x1b[0\"qef");
+    term.cup(0, 0);
+    term.print("\x1b[?K");
+    assert_visible_contents(&term, file!(), line!(), &["  cd  ", ""]);
+
+    // A regular EL ignores the protection
+    term.print("\x1b[K");
+    assert_visible_contents(&term, file!(), line!(), &["      ", ""]);
+}
+
+#[test]
+fn test_decsed() {
+    let mut term = TestTerm::new(3, 4, 0);
+    term.print("abcd\r\n\x1b[1\"qef\x1b[0\"qgh\r\nijkl");
+    term.cup(2, 1);
+    term.print("\x1b[?1J");
+    assert_visible_contents(&term, file!(), line!(), &["    ", "ef h", "ijkl"]);
+
+    term.print("\x1b[?2J");
+    assert_visible_contents(&term, file!(), line!(), &["    ", "ef  ", "    "]);
+}
+
+#[test]
+fn test_decsera_protected() {
+    let mut term = TestTerm::new(2, 4, 0);
+    term.print("a\x1b[1\"qbc\x1b[0\"qd\r\nefgh");
+    term.print("\x1b[1;1;2;4${");
+    assert_visible_contents(&term, file!(), line!(), &[" bc ", "    "]);
+
+    // DECERA erases protected characters too
+    term.print("\x1b[1;1;2;4$z");
+    assert_visible_contents(&term, file!(), line!(), &["    ", "    "]);
+}
+
+#[test]
+fn test_bidi_modes() {
+    use termwiz::bidi::{BidiMode, ParagraphDirectionHint};
+    let mut term = TestTerm::new(3, 8, 0);
+    let modes = |term: &TestTerm| {
+        term.screen()
+            .visible_lines()
+            .iter()
+            .map(|line| line.bidi_mode())
+            .collect::<Vec<_>>()
+    };
+
+    // Explicit mode by default; nothing is reordered
+    term.print("ab אבג");
+    assert_eq!(modes(&term)[0], BidiMode::default());
+
+    // BDSM and SCP apply to the cursor line and subsequent output
+    term.print("\x1b[8h\x1b[2 k\r\nאבג\r\n\x1b[?2501h\x1b[?2500h\x1b[1 kxyz");
+    let rtl = BidiMode {
+        enabled: true,
+        hint: ParagraphDirectionHint::RightToLeft,
+        mirror_box_drawing: false,
+    };
+    assert_eq!(
+        modes(&term),
+        vec![
+            rtl,
+            rtl,
+            BidiMode {
+                enabled: true,
+                hint: ParagraphDirectionHint::AutoLeftToRight,
+                mirror_box_drawing: true,
+            }
+        ]
+    );
+
+    // The text remains in logical order; only the display is reordered
+    assert_visible_contents(&term, file!(), line!(), &["ab אבג", "אבג", "xyz"]);
+    let line = &term.screen().visible_lines()[1];
+    assert_eq!(
+        line.bidi_visual_line(8).unwrap().line().as_str(),
+        "     גבא"
+    );
+
+    // SCP 0 restores the configured direction, retaining autodetection
+    term.print("\x1b[2 k\x1b[ k");
+    assert_eq!(
+        modes(&term)[2].hint,
+        ParagraphDirectionHint::AutoLeftToRight
+    );
+
+    term.print("\x1b[8l");
+    assert!(!modes(&term)[2].enabled);
+    assert_eq!(term.screen().visible_lines()[2].bidi_visual_line(8), None);
+}
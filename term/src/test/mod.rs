@@ -54,19 +54,41 @@ struct TestTerm {
 #[derive(Debug)]
 struct TestTermConfig {
     scrollback: usize,
+    disk_scrollback: bool,
+    width_policy: UnicodeWidthPolicy,
 }
 impl TerminalConfiguration for TestTermConfig {
     fn scrollback_size(&self) -> usize {
         self.scrollback
     }
 
+    fn enable_disk_scrollback(&self) -> bool {
+        self.disk_scrollback
+    }
+
     fn color_palette(&self) -> ColorPalette {
         ColorPalette::default()
     }
+
+    fn unicode_width_policy(&self) -> UnicodeWidthPolicy {
+        self.width_policy
+    }
 }
 
 impl TestTerm {
     fn new(height: usize, width: usize, scrollback: usize) -> Self {
+        Self::with_config(
+            height,
+            width,
+            TestTermConfig {
+                scrollback,
+                disk_scrollback: false,
+                width_policy: UnicodeWidthPolicy::default(),
+            },
+        )
+    }
+
+    fn with_config(height: usize, width: usize, config: TestTermConfig) -> Self {
         let _ = pretty_env_logger::formatted_builder()
             .is_test(true)
             .filter_level(log::LevelFilter::Trace)
@@ -79,7 +101,7 @@ impl TestTerm {
                 pixel_width: width * 8,
                 pixel_height: height * 16,
             },
-            Arc::new(TestTermConfig { scrollback }),
+            Arc::new(config),
             "WezTerm",
             "O_o",
             Box::new(Vec::new()),
@@ -173,13 +195,8 @@ impl TestTerm {
     }
 
     fn assert_dirty_lines(&self, expected: &[usize], reason: Option<&str>) {
-        let dirty_indices: Vec<usize> = self
-            .screen()
-            .lines
-            .iter()
-            .enumerate()
-            .filter_map(|(i, line)| if line.is_dirty() { Some(i) } else { None })
-            .collect();
+        let lines = &self.screen().lines;
+        let dirty_indices: Vec<usize> = (0..lines.len()).filter(|&i| lines.is_dirty(i)).collect();
         assert_eq!(
             &dirty_indices, &expected,
             "actual dirty lines (left) didn't match expected dirty lines (right) reason={:?}",
@@ -703,6 +720,107 @@ fn test_dec_special_graphics() {
     );
 }
 
+/// Test that the configured width policy is used when printing
+#[test]
+fn test_unicode_width_policy() {
+    let mut term = TestTerm::with_config(
+        2,
+        10,
+        TestTermConfig {
+            scrollback: 0,
+            disk_scrollback: false,
+            width_policy: UnicodeWidthPolicy {
+                ambiguous_are_wide: true,
+                vs16_is_wide: true,
+                ..Default::default()
+            },
+        },
+    );
+
+    // WHITE CIRCLE has ambiguous width; HEAVY BLACK HEART is
+    // only wide when followed by VS16
+    term.print("\u{25cb}a\u{2764}\u{fe0f}b\u{2764}c");
+    term.assert_cursor_pos(8, 0, None);
+    let lines = term.screen().visible_lines();
+    let cells = lines[0].cells();
+    assert_eq!(cells[0].width(), 2);
+    assert_eq!(cells[3].width(), 2);
+    assert_eq!(cells[6].width(), 1);
+}
+
+/// Test G2/G3 designation, locking and single shifts
+#[test]
+fn test_g2_g3_charsets() {
+    let mut term = TestTerm::new(3, 20, 0);
+
+    // German NRCS in G2, DEC Technical in G3; SS2 and SS3 affect
+    // only the next character
+    term.print("\x1b*K\x1b+>\x1bN{{\x1bO!!\r\n");
+    // LS2 keeps G2 invoked until SI
+    term.print("\x1bn{|}~\x0f{|}~\r\n");
+    // DECRC restores the designations, so the character printed
+    // after it overwrites the ASCII one using French NRCS
+    term.print("\x1b(R\x1b7\x1b(B{\x1b8{");
+    assert_visible_contents(&term, file!(), line!(), &["ä{⎷!", "äöüß{|}~", "é"]);
+}
+
+/// Test DECDWL/DECDHL line renditions
+#[test]
+fn test_double_width_lines() {
+    use termwiz::surface::line::LineRendition;
+    let mut term = TestTerm::new(4, 10, 0);
+
+    // Switching to double width discards the cells that no longer fit
+    // and pulls the cursor back onto the line
+    term.print("abcdefgh\x1b#6");
+    assert_eq!(term.screen().line_rendition(0), LineRendition::DoubleWidth);
+    term.assert_cursor_pos(4, 0, None);
+    assert_visible_contents(&term, file!(), line!(), &["abcde", "", "", ""]);
+
+    // Text wraps at half of the width
+    term.print("\r\n\x1b#30123456789");
+    assert_eq!(
+        term.screen().line_rendition(1),
+        LineRendition::DoubleHeightTop
+    );
+    assert_eq!(term.screen().line_rendition(2), LineRendition::SingleWidth);
+    assert_visible_contents(&term, file!(), line!(), &["abcde", "01234", "56789", ""]);
+
+    // Cursor positioning is clamped to the double width line
+    term.cup(8, 0);
+    term.assert_cursor_pos(4, 0, None);
+    term.cup(8, 3);
+    term.assert_cursor_pos(8, 3, None);
+
+    term.cup(0, 1);
+    term.print("\x1b#5");
+    assert_eq!(term.screen().line_rendition(1), LineRendition::SingleWidth);
+
+    // Erasing the display reverts the lines to single width
+    term.erase_in_display(EraseInDisplay::EraseDisplay);
+    assert_eq!(term.screen().line_rendition(0), LineRendition::SingleWidth);
+}
+
+/// Test that character editing stops at the edge of a double width line
+#[test]
+fn test_double_width_line_edits() {
+    let mut term = TestTerm::new(2, 10, 0);
+
+    // Characters inserted by ICH push the others off the line
+    term.print("abcde#6");
+    term.cup(1, 0);
+    term.print("[2@");
+    assert_visible_contents(&term, file!(), line!(), &["a  bc", ""]);
+
+    // DCH moves the remaining characters left
+    term.print("[3P");
+    assert_visible_contents(&term, file!(), line!(), &["ac", ""]);
+
+    // ECH doesn't extend the line past its edge
+    term.print("[41m[10X");
+    assert_eq!(term.screen().visible_lines()[0].cells().len(), 5);
+}
+
 /// Test the behavior of wrapped lines when we resize the terminal
 /// wider and then narrower.
 #[test]
@@ -776,6 +894,76 @@ fn test_resize_wrap() {
     );
 }
 
+/// Test that the prompt reported via OSC 133 is erased, rather than
+/// reflowed, when the terminal is resized
+#[test]
+fn test_resize_erases_prompt() {
+    let mut term = TestTerm::new(5, 10, 0);
+    term.print("out\r\n\x1b]133;A\x1b\\$ \x1b]133;B\x1b\\abcdefghijkl");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["out", "$ abcdefgh", "ijkl", "", ""],
+    );
+    term.assert_cursor_pos(4, 2, None);
+
+    // The shell will redraw the prompt, so the prompt rows are blanked
+    // and the cursor remains in the same row relative to the prompt
+    term.resize(5, 6, 0, 0);
+    assert_visible_contents(&term, file!(), line!(), &["out", "", "", "", ""]);
+    term.assert_cursor_pos(4, 2, None);
+
+    // Once the command has produced output, the lines are reflowed
+    term.print("\r\x1b[1A$ cmd\r\n\x1b]133;C\x1b\\result");
+    term.resize(5, 3, 0, 0);
+    assert_visible_contents(&term, file!(), line!(), &["out", "$ c", "md", "res", "ult"]);
+}
+
+/// Test that output is reflowed rather than erased when the shell
+/// reports the prompt and the input (OSC 133 A and B) but not the
+/// start of the output (OSC 133 C)
+#[test]
+fn test_resize_keeps_output_without_output_marker() {
+    let mut term = TestTerm::new(5, 10, 0);
+    term.print("\x1b]133;A\x1b\\$ \x1b]133;B\x1b\\cmd\r\nabcdefghijkl");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["$ cmd", "abcdefghij", "kl", "", ""],
+    );
+
+    term.resize(5, 6, 0, 0);
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["$ cmd", "abcdef", "ghijkl", "", ""],
+    );
+}
+
+/// Test that the alternate screen is reflowed when resized
+#[test]
+fn test_resize_alt_screen() {
+    let mut term = TestTerm::new(3, 6, 0);
+    term.print("\x1b[?1049hone\r\ntwo\r\nabcdef");
+    term.assert_cursor_pos(5, 2, None);
+    assert_eq!(term.screen().visible_row_to_stable_row(1), 1);
+
+    // The reflowed lines no longer fit, so the top line is discarded.
+    // The remaining lines keep their stable row indices.
+    term.resize(3, 3, 0, 0);
+    assert_visible_contents(&term, file!(), line!(), &["two", "abc", "def"]);
+    term.assert_cursor_pos(2, 2, None);
+    assert_eq!(term.screen().visible_row_to_stable_row(0), 1);
+    assert_eq!(term.screen().stable_row_to_phys(0), None);
+
+    term.resize(3, 6, 0, 0);
+    assert_visible_contents(&term, file!(), line!(), &["two", "abcdef", ""]);
+    assert_eq!(term.screen().visible_row_to_stable_row(0), 1);
+}
+
 #[test]
 fn test_scrollup() {
     let mut term = TestTerm::new(2, 1, 4);
@@ -812,6 +1000,84 @@ fn test_scrollup() {
     assert_eq!(term.screen().visible_row_to_stable_row(0), 7);
 }
 
+#[test]
+fn test_compressed_scrollback() {
+    let mut term = TestTerm::new(2, 6, 4);
+    term.print("\x1b[1mone\x1b[0m\r\ntwo\r\nthree\r\nfour");
+    assert_all_contents(&term, file!(), line!(), &["one", "two", "three", "four"]);
+
+    // The lines that scrolled off the top are compressed, but
+    // still report their attributes
+    let usage = term.memory_usage();
+    assert_eq!(usage.lines, 6);
+    assert_eq!(usage.compressed_lines, 2);
+    let first = term.screen().lines.get(0).unwrap().into_owned();
+    assert_eq!(first.cells()[0].attrs().intensity(), Intensity::Bold);
+
+    // Modifying a compressed line decompresses it
+    term.screen_mut()
+        .line_mut(0)
+        .set_cell(0, Cell::new('O', CellAttributes::default()));
+    assert_all_contents(&term, file!(), line!(), &["One", "two", "three", "four"]);
+    assert_eq!(term.memory_usage().compressed_lines, 1);
+
+    // Rewrapping preserves the content of compressed lines
+    term.resize(2, 3, 0, 0);
+    assert_all_contents(
+        &term,
+        file!(),
+        line!(),
+        &["One", "two", "thr", "ee", "fou", "r"],
+    );
+    assert_eq!(term.memory_usage().compressed_lines, 4);
+}
+
+#[test]
+fn test_disk_scrollback() {
+    let mut term = TestTerm::with_config(
+        2,
+        4,
+        TestTermConfig {
+            scrollback: 2,
+            disk_scrollback: true,
+            width_policy: UnicodeWidthPolicy::default(),
+        },
+    );
+    for i in 0..600 {
+        term.print(format!("{}\r\n", i));
+    }
+    term.print("end");
+
+    // The lines that no longer fit in memory were moved to disk
+    let screen = term.screen();
+    assert_eq!(screen.lines.len(), 4);
+    assert_eq!(screen.disk_scrollback_rows(), 597);
+    assert_eq!(screen.scrollback_top(), 0);
+    assert_eq!(term.memory_usage().disk_lines, 597);
+
+    let lines = screen.get_disk_lines(254..258);
+    let text: Vec<String> = lines
+        .iter()
+        .map(|line| line.as_str().trim_end().to_string())
+        .collect();
+    assert_eq!(text, vec!["254", "255", "256", "257"]);
+
+    let all: Vec<(StableRowIndex, String)> = screen
+        .iter_stable_lines()
+        .map(|(row, line)| (row, line.as_str().trim_end().to_string()))
+        .collect();
+    assert_eq!(all.len(), 601);
+    assert_eq!(all[0], (0, "0".to_string()));
+    assert_eq!(all[596], (596, "596".to_string()));
+    assert_eq!(all[597], (597, "597".to_string()));
+    assert_eq!(all[600], (600, "end".to_string()));
+
+    // Clearing the scrollback also discards the lines on disk
+    term.erase_scrollback();
+    assert_eq!(term.screen().disk_scrollback_rows(), 0);
+    assert_eq!(term.screen().scrollback_top(), 599);
+}
+
 #[test]
 fn test_ri() {
     let mut term = TestTerm::new(3, 1, 10);
@@ -27,6 +27,22 @@ impl LineEditorHost for Host {
         &mut self.history
     }
 
+    /// Demo of multi-line input: a line ending with a backslash
+    /// continues onto the next line
+    fn is_line_complete(&self, line: &str) -> bool {
+        !line.ends_with('\\')
+    }
+
+    /// Demo of the hint API; suggests the remainder of "hello world"
+    fn hint(&self, line: &str, cursor_position: usize) -> Option<String> {
+        let suggestion = "hello world";
+        if !line.is_empty() && cursor_position == line.len() && suggestion.starts_with(line) {
+            Some(suggestion[line.len()..].to_string())
+        } else {
+            None
+        }
+    }
+
     /// Demo of the completion API for words starting with "h" or "he"
     fn complete(&self, line: &str, cursor_position: usize) -> Vec<CompletionCandidate> {
         let mut candidates = vec![];
@@ -0,0 +1,217 @@
+//! Support for the display of bidirectional text.
+//!
+//! The `Line` type stores a `BidiMode` alongside its cells which
+//! controls whether, and how, the Unicode Bidirectional Algorithm
+//! is applied when the line is reordered for display.
+//! The text held in the cells is always in logical order; only the
+//! display order is affected.
+//! See <https://terminal-wg.pages.freedesktop.org/bidi/> for the
+//! recommendation that this implementation follows.
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+use unicode_bidi::{bidi_class, BidiClass};
+
+/// Specifies the base direction of a paragraph of text
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParagraphDirectionHint {
+    /// The paragraph is left-to-right
+    LeftToRight,
+    /// The paragraph is right-to-left
+    RightToLeft,
+    /// The direction is taken from the first strong directional
+    /// character in the paragraph, falling back to left-to-right
+    AutoLeftToRight,
+    /// The direction is taken from the first strong directional
+    /// character in the paragraph, falling back to right-to-left
+    AutoRightToLeft,
+}
+
+impl Default for ParagraphDirectionHint {
+    fn default() -> Self {
+        Self::LeftToRight
+    }
+}
+
+impl ParagraphDirectionHint {
+    /// Returns true if the direction is taken from the contents
+    /// of the paragraph
+    pub fn is_auto(self) -> bool {
+        matches!(self, Self::AutoLeftToRight | Self::AutoRightToLeft)
+    }
+
+    /// Returns the equivalent hint with autodetection enabled
+    /// or disabled, preserving the fallback direction
+    pub fn with_auto(self, auto: bool) -> Self {
+        match (self.is_rtl(), auto) {
+            (false, false) => Self::LeftToRight,
+            (true, false) => Self::RightToLeft,
+            (false, true) => Self::AutoLeftToRight,
+            (true, true) => Self::AutoRightToLeft,
+        }
+    }
+
+    /// Returns true if the paragraph is right-to-left, or if
+    /// right-to-left is the fallback when autodetecting
+    pub fn is_rtl(self) -> bool {
+        matches!(self, Self::RightToLeft | Self::AutoRightToLeft)
+    }
+
+    /// Resolve the direction of the paragraph with the specified
+    /// text, returning true if it is right-to-left.
+    pub fn resolve_is_rtl(self, text: &str) -> bool {
+        if self.is_auto() {
+            for c in text.chars() {
+                match bidi_class(c) {
+                    BidiClass::L => return false,
+                    BidiClass::R | BidiClass::AL => return true,
+                    _ => {}
+                }
+            }
+        }
+        self.is_rtl()
+    }
+}
+
+/// The direction in which a run of text is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    LeftToRight,
+    RightToLeft,
+}
+
+impl Default for Direction {
+    fn default() -> Self {
+        Self::LeftToRight
+    }
+}
+
+/// Describes how the bidi algorithm is applied to a `Line`
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BidiMode {
+    /// When true, the line is reordered for display ("implicit" mode).
+    /// When false, the line is displayed in the order that it was
+    /// written ("explicit" mode) and any reordering is the
+    /// responsibility of the application.
+    pub enabled: bool,
+    /// The base direction of the line
+    pub hint: ParagraphDirectionHint,
+    /// When true, box drawing characters are mirrored when they
+    /// appear in a right-to-left run
+    pub mirror_box_drawing: bool,
+}
+
+/// Returns the mirrored counterpart of `c` for display in a
+/// right-to-left run (rule L4 of the bidi algorithm), or None
+/// if `c` is displayed as-is.
+/// Box drawing characters are only mirrored when `box_drawing`
+/// is true.
+pub fn mirror_char(c: char, box_drawing: bool) -> Option<char> {
+    const PAIRS: &[(char, char)] = &[
+        ('(', ')'),
+        ('<', '>'),
+        ('[', ']'),
+        ('{', '}'),
+        ('«', '»'),
+        ('‹', '›'),
+        ('⁅', '⁆'),
+        ('⁽', '⁾'),
+        ('₍', '₎'),
+        ('≤', '≥'),
+        ('⟨', '⟩'),
+        ('〈', '〉'),
+        ('《', '》'),
+        ('「', '」'),
+        ('『', '』'),
+        ('【', '】'),
+        ('〔', '〕'),
+        ('（', '）'),
+        ('［', '］'),
+        ('｛', '｝'),
+    ];
+    const BOX_PAIRS: &[(char, char)] = &[
+        ('┌', '┐'),
+        ('└', '┘'),
+        ('├', '┤'),
+        ('┍', '┑'),
+        ('┎', '┒'),
+        ('┏', '┓'),
+        ('┕', '┙'),
+        ('┖', '┚'),
+        ('┗', '┛'),
+        ('┝', '┥'),
+        ('┞', '┦'),
+        ('┟', '┧'),
+        ('┠', '┨'),
+        ('┡', '┩'),
+        ('┢', '┪'),
+        ('┣', '┫'),
+        ('┭', '┮'),
+        ('┵', '┶'),
+        ('┽', '┾'),
+        ('╒', '╕'),
+        ('╓', '╖'),
+        ('╔', '╗'),
+        ('╘', '╛'),
+        ('╙', '╜'),
+        ('╚', '╝'),
+        ('╞', '╡'),
+        ('╟', '╢'),
+        ('╠', '╣'),
+        ('╭', '╮'),
+        ('╰', '╯'),
+        ('╱', '╲'),
+        ('╴', '╶'),
+        ('╸', '╺'),
+        ('╼', '╾'),
+        ('▌', '▐'),
+        ('▖', '▗'),
+        ('▘', '▝'),
+        ('▙', '▟'),
+        ('▛', '▜'),
+        ('▚', '▞'),
+    ];
+
+    let lookup = |pairs: &[(char, char)]| {
+        pairs.iter().find_map(|&(a, b)| {
+            if a == c {
+                Some(b)
+            } else if b == c {
+                Some(a)
+            } else {
+                None
+            }
+        })
+    };
+
+    lookup(PAIRS).or_else(|| if box_drawing { lookup(BOX_PAIRS) } else { None })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resolve_direction() {
+        use ParagraphDirectionHint::*;
+        assert!(!LeftToRight.resolve_is_rtl("שלום"));
+        assert!(RightToLeft.resolve_is_rtl("hello"));
+        assert!(AutoLeftToRight.resolve_is_rtl("123 שלום hello"));
+        assert!(!AutoRightToLeft.resolve_is_rtl("123 hello שלום"));
+        assert!(!AutoLeftToRight.resolve_is_rtl("123"));
+        assert!(AutoRightToLeft.resolve_is_rtl("123"));
+        assert_eq!(RightToLeft.with_auto(true), AutoRightToLeft);
+        assert_eq!(AutoLeftToRight.with_auto(false), LeftToRight);
+    }
+
+    #[test]
+    fn mirroring() {
+        assert_eq!(mirror_char('(', false), Some(')'));
+        assert_eq!(mirror_char('»', false), Some('«'));
+        assert_eq!(mirror_char('a', true), None);
+        assert_eq!(mirror_char('┌', false), None);
+        assert_eq!(mirror_char('┌', true), Some('┐'));
+        assert_eq!(mirror_char('╝', true), Some('╚'));
+    }
+}
@@ -86,12 +86,18 @@ builder! {
         hyperlinks: Option<bool>,
 
         /// Configure whether sixel graphics are supported.
+        /// Images are only rendered as sixel graphics when termwiz
+        /// is built with the `use_image` feature.
         sixel: Option<bool>,
 
         /// Configure whether iTerm2 style graphics embedding is supported
         /// See <https://www.iterm2.com/documentation-images.html>
         iterm2_image: Option<bool>,
 
+        /// Configure whether the kitty graphics protocol is supported
+        /// See <https://sw.kovidgoyal.net/kitty/graphics-protocol/>
+        kitty_graphics: Option<bool>,
+
         /// Specify whether `bce`, background color erase, is supported.
         bce: Option<bool>,
 
@@ -150,6 +156,7 @@ pub struct Capabilities {
     hyperlinks: bool,
     sixel: bool,
     iterm2_image: bool,
+    kitty_graphics: bool,
     bce: bool,
     terminfo_db: Option<terminfo::Database>,
     bracketed_paste: bool,
@@ -279,6 +286,10 @@ impl Capabilities {
             }
         });
 
+        let kitty_graphics = hints
+            .kitty_graphics
+            .unwrap_or_else(|| hints.term.as_ref().map(String::as_ref) == Some("xterm-kitty"));
+
         let bracketed_paste = hints.bracketed_paste.unwrap_or(true);
         let mouse_reporting = hints.mouse_reporting.unwrap_or(true);
 
@@ -287,6 +298,7 @@ impl Capabilities {
             sixel,
             hyperlinks,
             iterm2_image,
+            kitty_graphics,
             bce,
             terminfo_db,
             bracketed_paste,
@@ -300,6 +312,8 @@ impl Capabilities {
     }
 
     /// Does the terminal support SIXEL graphics?
+    /// Note that the renderer only uses sixel graphics when termwiz
+    /// is built with the `use_image` feature.
     pub fn sixel(&self) -> bool {
         self.sixel
     }
@@ -316,6 +330,12 @@ impl Capabilities {
         self.iterm2_image
     }
 
+    /// Does the terminal support the kitty graphics protocol?
+    /// See <https://sw.kovidgoyal.net/kitty/graphics-protocol/>
+    pub fn kitty_graphics(&self) -> bool {
+        self.kitty_graphics
+    }
+
     /// Is `bce`, background color erase supported?
     /// <http://invisible-island.net/ncurses/ncurses-slang.html#env_COLORTERM_BCE>
     pub fn bce(&self) -> bool {
@@ -357,6 +377,7 @@ mod test {
         assert_eq!(caps.sixel(), false);
         assert_eq!(caps.hyperlinks(), true);
         assert_eq!(caps.iterm2_image(), false);
+        assert_eq!(caps.kitty_graphics(), false);
         assert_eq!(caps.bce(), false);
     }
 
@@ -470,4 +491,25 @@ mod test {
         .unwrap();
         assert_eq!(caps.iterm2_image(), true);
     }
+
+    #[test]
+    fn kitty_graphics() {
+        let caps =
+            Capabilities::new_with_hints(ProbeHints::default().term(Some("xterm-256color".into())))
+                .unwrap();
+        assert_eq!(caps.kitty_graphics(), false);
+
+        let caps =
+            Capabilities::new_with_hints(ProbeHints::default().term(Some("xterm-kitty".into())))
+                .unwrap();
+        assert_eq!(caps.kitty_graphics(), true);
+
+        let caps = Capabilities::new_with_hints(
+            ProbeHints::default()
+                .term(Some("xterm-256color".into()))
+                .kitty_graphics(Some(true)),
+        )
+        .unwrap();
+        assert_eq!(caps.kitty_graphics(), true);
+    }
 }
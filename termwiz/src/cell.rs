@@ -6,7 +6,7 @@ use crate::image::ImageCell;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::mem;
 use std::sync::Arc;
-use unicode_width::UnicodeWidthStr;
+use unicode_width::UnicodeWidthChar;
 
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -63,6 +63,7 @@ impl std::fmt::Debug for CellAttributes {
             .field("wrapped", &self.wrapped())
             .field("overline", &self.overline())
             .field("semantic_type", &self.semantic_type())
+            .field("protected", &self.protected())
             .field("foreground", &self.foreground)
             .field("background", &self.background)
             .field("fat", &self.fat)
@@ -241,6 +242,8 @@ impl CellAttributes {
     bitfield!(wrapped, set_wrapped, 11);
     bitfield!(overline, set_overline, 12);
     bitfield!(semantic_type, set_semantic_type, SemanticType, 0b11, 13);
+    // The character is protected from selective erasure (DECSCA)
+    bitfield!(protected, set_protected, 15);
 
     /// Returns true if the attribute bits in both objects are equal.
     /// This can be used to cheaply test whether the styles of the two
@@ -402,6 +405,8 @@ impl CellAttributes {
         // be deterministically tagged as Output so that we have an
         // easier time in get_semantic_zones.
         res.set_semantic_type(SemanticType::default());
+        // Erased cells are never protected
+        res.set_protected(false);
         res.set_underline_color(self.underline_color());
         res
     }
@@ -416,6 +421,23 @@ impl CellAttributes {
             .and_then(|fat| fat.image.as_ref().map(|im| im.as_ref()))
     }
 
+    /// Returns the number of bytes allocated on the heap to hold
+    /// the less common attributes.  Hyperlinks are shared between
+    /// cells and so are not counted here.
+    pub(crate) fn heap_size(&self) -> usize {
+        match &self.fat {
+            Some(fat) => {
+                std::mem::size_of::<FatAttributes>()
+                    + fat
+                        .image
+                        .as_ref()
+                        .map(|_| std::mem::size_of::<ImageCell>())
+                        .unwrap_or(0)
+            }
+            None => 0,
+        }
+    }
+
     pub fn underline_color(&self) -> ColorAttribute {
         self.fat
             .as_ref()
@@ -429,8 +451,8 @@ fn deserialize_teenystring<'de, D>(deserializer: D) -> Result<TeenyString, D::Er
 where
     D: Deserializer<'de>,
 {
-    let text = String::deserialize(deserializer)?;
-    Ok(TeenyString::from_slice(text.as_bytes()))
+    let (text, width) = <(String, usize)>::deserialize(deserializer)?;
+    Ok(TeenyString::from_slice(text.as_bytes(), width))
 }
 
 #[cfg(feature = "use_serde")]
@@ -441,101 +463,124 @@ where
     // unsafety: this is safe because the Cell constructor guarantees
     // that the storage is valid utf8
     let s = unsafe { std::str::from_utf8_unchecked(value.as_bytes()) };
-    s.serialize(serializer)
+    // The width is serialized alongside the text because it depends
+    // upon the width policy that was in effect when the cell was created
+    (s, value.width()).serialize(serializer)
 }
 
 /// TeenyString encodes string storage in a single machine word.
 /// The scheme is simple but effective: strings that encode into a
 /// byte slice that is 1 less byte than the machine word size can
 /// be encoded directly into the usize bits stored in the struct.
-/// A marker bit (LSB for big endian, MSB for little endian) is
-/// set to indicate that the string is stored inline.
-/// If the string is longer than this then a `Vec<u8>` is allocated
-/// from the heap and the usize holds its raw pointer address.
+/// The least significant byte of the word (the first byte for little
+/// endian, the last for big endian) holds a marker bit that indicates
+/// that the string is stored inline, along with the column width of
+/// the string; the text occupies the remaining bytes.
+/// If the string is longer than this then a `TeenyStringHeap` is
+/// allocated from the heap and the usize holds its raw pointer address.
 struct TeenyString(usize);
+
+struct TeenyStringHeap {
+    bytes: Vec<u8>,
+    width: usize,
+}
+
 impl TeenyString {
-    fn marker_mask() -> usize {
-        if cfg!(target_endian = "little") {
-            cfg_if::cfg_if! {
-                if #[cfg(target_pointer_width = "64")] {
-                    0x7f000000_00000000
-                } else if #[cfg(target_pointer_width = "32")] {
-                    0x7f000000
-                } else if #[cfg(target_pointer_width = "16")] {
-                    0x7f00
-                } else {
-                    panic!("unsupported target");
-                }
-            }
-        } else {
-            // I don't have a big endian machine to verify
-            // this on, but I think this is right!
-            0x1
-        }
-    }
-    fn is_marker_bit_set(word: usize) -> bool {
-        let mask = Self::marker_mask();
-        word & mask == mask
-    }
+    /// This bit is set in the word when the string is stored inline.
+    /// It is the least significant bit, which is always clear in a
+    /// pointer to a `TeenyStringHeap` because of its alignment, so the
+    /// encoding doesn't depend on where the heap places allocations.
+    const MARKER: usize = 0x1;
+    const MAX_INLINE_WIDTH: usize = 0x7f;
+    const MAX_INLINE_LEN: usize = std::mem::size_of::<usize>() - 1;
+    /// The index of the least significant byte within the word
+    const MARKER_IDX: usize = if cfg!(target_endian = "little") {
+        0
+    } else {
+        std::mem::size_of::<usize>() - 1
+    };
+    /// The index of the first byte of inline text within the word
+    const TEXT_IDX: usize = if cfg!(target_endian = "little") { 1 } else { 0 };
 
-    fn set_marker_bit(word: usize) -> usize {
-        word | Self::marker_mask()
+    fn is_marker_bit_set(word: usize) -> bool {
+        word & Self::MARKER == Self::MARKER
     }
 
-    pub fn from_slice(bytes: &[u8]) -> Self {
+    pub fn from_slice(bytes: &[u8], width: usize) -> Self {
         // De-fang the input text such that it has no special meaning
         // to a terminal.  All control and movement characters are rewritten
         // as a space.
-        let bytes = if bytes.is_empty() {
-            b" "
-        } else if bytes == b"\r\n" {
-            b" "
-        } else if bytes.len() == 1 && (bytes[0] < 0x20 || bytes[0] == 0x7f) {
-            b" "
+        let (bytes, width) = if bytes.is_empty()
+            || bytes == b"\r\n"
+            || (bytes.len() == 1 && (bytes[0] < 0x20 || bytes[0] == 0x7f))
+        {
+            (&b" "[..], 1)
         } else {
-            bytes
+            (bytes, width)
         };
         let len = bytes.len();
-        if len < std::mem::size_of::<usize>() {
-            let mut word = 0usize;
-            unsafe {
-                std::ptr::copy_nonoverlapping(
-                    bytes.as_ptr(),
-                    &mut word as *mut usize as *mut u8,
-                    len,
-                );
-            }
-            let word = Self::set_marker_bit(word);
-            Self(word)
+        if len <= Self::MAX_INLINE_LEN && width <= Self::MAX_INLINE_WIDTH {
+            let mut word = [0u8; std::mem::size_of::<usize>()];
+            word[Self::TEXT_IDX..Self::TEXT_IDX + len].copy_from_slice(bytes);
+            word[Self::MARKER_IDX] = Self::MARKER as u8 | ((width as u8) << 1);
+            Self(usize::from_ne_bytes(word))
         } else {
-            let vec = Box::new(bytes.to_vec());
-            let ptr = Box::into_raw(vec);
-            Self(ptr as usize)
+            let heap = Box::new(TeenyStringHeap {
+                bytes: bytes.to_vec(),
+                width,
+            });
+            Self(Box::into_raw(heap) as usize)
         }
     }
 
     pub fn from_char(c: char) -> Self {
         let mut bytes = [0u8; 8];
-        let len = c.len_utf8();
-        debug_assert!(len < std::mem::size_of_val(&bytes));
-        c.encode_utf8(&mut bytes);
-        Self::from_slice(&bytes[0..len])
+        let s = c.encode_utf8(&mut bytes);
+        let width = if s.len() == 1 {
+            1
+        } else {
+            grapheme_column_width(s)
+        };
+        Self::from_slice(s.as_bytes(), width)
+    }
+
+    /// Returns the number of cells visually occupied by the string
+    pub fn width(&self) -> usize {
+        if Self::is_marker_bit_set(self.0) {
+            (self.0.to_ne_bytes()[Self::MARKER_IDX] >> 1) as usize
+        } else {
+            let heap = self.0 as *const usize as *const TeenyStringHeap;
+            unsafe { (*heap).width }
+        }
     }
 
     pub fn as_bytes(&self) -> &[u8] {
         if Self::is_marker_bit_set(self.0) {
             let bytes = &self.0 as *const usize as *const u8;
-            let bytes =
-                unsafe { std::slice::from_raw_parts(bytes, std::mem::size_of::<usize>() - 1) };
+            let bytes = unsafe {
+                std::slice::from_raw_parts(bytes.add(Self::TEXT_IDX), Self::MAX_INLINE_LEN)
+            };
             let len = bytes
                 .iter()
                 .position(|&b| b == 0)
-                .unwrap_or(std::mem::size_of::<usize>() - 1);
+                .unwrap_or(Self::MAX_INLINE_LEN);
 
             &bytes[0..len]
         } else {
-            let vec = self.0 as *const usize as *const Vec<u8>;
-            unsafe { (*vec).as_slice() }
+            let heap = self.0 as *const usize as *const TeenyStringHeap;
+            unsafe { (*heap).bytes.as_slice() }
+        }
+    }
+}
+
+impl TeenyString {
+    /// Returns the number of bytes allocated on the heap to hold the string
+    fn heap_size(&self) -> usize {
+        if Self::is_marker_bit_set(self.0) {
+            0
+        } else {
+            let heap = self.0 as *const usize as *const TeenyStringHeap;
+            std::mem::size_of::<TeenyStringHeap>() + unsafe { (*heap).bytes.capacity() }
         }
     }
 }
@@ -543,21 +588,21 @@ impl TeenyString {
 impl Drop for TeenyString {
     fn drop(&mut self) {
         if !Self::is_marker_bit_set(self.0) {
-            let vec = unsafe { Box::from_raw(self.0 as *mut usize as *mut Vec<u8>) };
-            drop(vec);
+            let heap = unsafe { Box::from_raw(self.0 as *mut usize as *mut TeenyStringHeap) };
+            drop(heap);
         }
     }
 }
 
 impl std::clone::Clone for TeenyString {
     fn clone(&self) -> Self {
-        Self::from_slice(self.as_bytes())
+        Self::from_slice(self.as_bytes(), self.width())
     }
 }
 
 impl std::cmp::PartialEq for TeenyString {
     fn eq(&self, rhs: &Self) -> bool {
-        self.as_bytes().eq(rhs.as_bytes())
+        self.as_bytes().eq(rhs.as_bytes()) && self.width() == rhs.width()
     }
 }
 impl std::cmp::Eq for TeenyString {}
@@ -611,8 +656,22 @@ impl Cell {
     /// over.  This function technically allows for an arbitrary string to
     /// be passed but it should not be used to hold strings other than
     /// graphemes.
+    /// The width of the grapheme is computed using the default
+    /// `UnicodeWidthPolicy`.
     pub fn new_grapheme(text: &str, attrs: CellAttributes) -> Self {
-        let storage = TeenyString::from_slice(text.as_bytes());
+        let width = if text.len() == 1 {
+            1
+        } else {
+            grapheme_column_width(text)
+        };
+        Self::new_grapheme_with_width(text, width, attrs)
+    }
+
+    /// Create a new cell holding the specified grapheme, which
+    /// occupies `width` cells.  This is used when the width has been
+    /// computed using a `UnicodeWidthPolicy` other than the default.
+    pub fn new_grapheme_with_width(text: &str, width: usize, attrs: CellAttributes) -> Self {
+        let storage = TeenyString::from_slice(text.as_bytes(), width);
 
         Self {
             text: storage,
@@ -629,12 +688,12 @@ impl Cell {
 
     /// Returns the number of cells visually occupied by this grapheme
     pub fn width(&self) -> usize {
-        let s = self.str();
-        if s.len() == 1 {
-            1
-        } else {
-            grapheme_column_width(s)
-        }
+        self.text.width()
+    }
+
+    /// Returns the number of bytes allocated on the heap for this cell
+    pub(crate) fn heap_size(&self) -> usize {
+        self.text.heap_size() + self.attrs.heap_size()
     }
 
     /// Returns the attributes of the cell
@@ -647,43 +706,94 @@ impl Cell {
     }
 }
 
+/// Controls how the number of cells occupied by a grapheme is computed.
+/// Applications running in the terminal compute widths for themselves
+/// using the `wcwidth` function of the system that they are running on;
+/// if that doesn't agree with the terminal then the display is corrupted,
+/// so this allows the terminal to be configured to match.
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UnicodeWidthPolicy {
+    /// When true, emoji are treated as single width, as they were
+    /// prior to Unicode 9, which made most emoji double width.
+    pub narrow_emoji: bool,
+    /// When true, characters with an East Asian Width of Ambiguous
+    /// are treated as double width, as they are in CJK locales.
+    pub ambiguous_are_wide: bool,
+    /// When true, an emoji followed by VARIATION SELECTOR-16 (which
+    /// requests emoji presentation) is treated as double width, even
+    /// if the emoji would otherwise be single width.
+    pub vs16_is_wide: bool,
+}
+
+impl UnicodeWidthPolicy {
+    /// Returns the number of cells visually occupied by a sequence
+    /// of graphemes
+    pub fn str_width(&self, s: &str) -> usize {
+        use unicode_segmentation::UnicodeSegmentation;
+        s.graphemes(true).map(|g| self.grapheme_width(g)).sum()
+    }
+
+    /// Returns the number of cells visually occupied by a grapheme.
+    /// The input string must be a single grapheme.
+    pub fn grapheme_width(&self, s: &str) -> usize {
+        // Due to this issue:
+        // https://github.com/unicode-rs/unicode-width/issues/4
+        // we cannot simply use the unicode-width crate to compute
+        // the desired value.
+        // Let's check for emoji-ness for ourselves first
+        use xi_unicode::EmojiExt;
+        let mut emoji = false;
+        let mut vs16 = false;
+        let mut width = 0;
+        for c in s.chars() {
+            if !self.narrow_emoji && (c.is_emoji_modifier_base() || c.is_emoji_modifier()) {
+                // treat modifier sequences as double wide
+                return 2;
+            }
+            let mut char_width = if self.ambiguous_are_wide {
+                UnicodeWidthChar::width_cjk(c)
+            } else {
+                UnicodeWidthChar::width(c)
+            }
+            .unwrap_or(0);
+            if c == '\u{fe0f}' {
+                vs16 = true;
+            } else if c.is_emoji() {
+                emoji = true;
+                if self.narrow_emoji {
+                    char_width = char_width.min(1);
+                }
+            }
+            width += char_width;
+        }
+        if emoji {
+            if vs16 && self.vs16_is_wide {
+                return 2;
+            }
+            // For sequences such as "deaf man", the sum of the widths
+            // is 3 because of the widths of the component glyphs,
+            // rather than 2 for a single double width grapheme.
+            // If we saw any emoji within the characters then we assume
+            // that it can be a maximum of 2 cells in width.
+            width.min(2)
+        } else {
+            width
+        }
+    }
+}
+
 /// Returns the number of cells visually occupied by a sequence
-/// of graphemes
+/// of graphemes, using the default `UnicodeWidthPolicy`
 pub fn unicode_column_width(s: &str) -> usize {
-    use unicode_segmentation::UnicodeSegmentation;
-    s.graphemes(true).map(grapheme_column_width).sum()
+    UnicodeWidthPolicy::default().str_width(s)
 }
 
-/// Returns the number of cells visually occupied by a grapheme.
+/// Returns the number of cells visually occupied by a grapheme,
+/// using the default `UnicodeWidthPolicy`.
 /// The input string must be a single grapheme.
 pub fn grapheme_column_width(s: &str) -> usize {
-    // Due to this issue:
-    // https://github.com/unicode-rs/unicode-width/issues/4
-    // we cannot simply use the unicode-width crate to compute
-    // the desired value.
-    // Let's check for emoji-ness for ourselves first
-    use xi_unicode::EmojiExt;
-    let mut emoji = false;
-    for c in s.chars() {
-        if c.is_emoji_modifier_base() || c.is_emoji_modifier() {
-            // treat modifier sequences as double wide
-            return 2;
-        }
-        if c.is_emoji() {
-            emoji = true;
-        }
-    }
-    let width = UnicodeWidthStr::width(s);
-    if emoji {
-        // For sequences such as "deaf man", UnicodeWidthStr::width()
-        // returns 3 because of the widths of the component glyphs,
-        // rather than 2 for a single double width grapheme.
-        // If we saw any emoji within the characters then we assume
-        // that it can be a maximum of 2 cells in width.
-        width.min(2)
-    } else {
-        width
-    }
+    UnicodeWidthPolicy::default().grapheme_width(s)
 }
 
 /// Models a change in the attributes of a cell in a stream of changes.
@@ -713,8 +823,31 @@ mod test {
         let s = TeenyString::from_char('a');
         assert_eq!(s.as_bytes(), &[b'a']);
 
-        let longer = TeenyString::from_slice(b"hellothere");
+        assert_eq!(s.width(), 1);
+
+        let longer = TeenyString::from_slice(b"hellothere", 10);
         assert_eq!(longer.as_bytes(), b"hellothere");
+        assert_eq!(longer.width(), 10);
+
+        let wide = TeenyString::from_char('\u{4e00}');
+        assert_eq!(wide.as_bytes(), "\u{4e00}".as_bytes());
+        assert_eq!(wide.width(), 2);
+        assert_eq!(wide.clone().width(), 2);
+
+        // The longest strings that are stored inline, and the shortest
+        // that are not
+        let max_inline = TeenyString::MAX_INLINE_LEN;
+        for len in max_inline..=max_inline + 1 {
+            let text = vec![b'x'; len];
+            let s = TeenyString::from_slice(&text, len);
+            assert_eq!(TeenyString::is_marker_bit_set(s.0), len == max_inline);
+            assert_eq!(s.as_bytes(), &text[..]);
+            assert_eq!(s.width(), len);
+            assert_eq!(s.heap_size() == 0, len == max_inline);
+        }
+        let s = TeenyString::from_slice(b"x", TeenyString::MAX_INLINE_WIDTH + 1);
+        assert_eq!(s.width(), TeenyString::MAX_INLINE_WIDTH + 1);
+        assert_ne!(s.heap_size(), 0);
     }
 
     #[test]
@@ -791,4 +924,41 @@ mod test {
         eprintln!("font_awesome_star {}", font_awesome_star.escape_debug());
         assert_eq!(unicode_column_width(font_awesome_star), 1);
     }
+
+    #[test]
+    fn width_policy() {
+        let policy = UnicodeWidthPolicy::default();
+        let ambiguous = UnicodeWidthPolicy {
+            ambiguous_are_wide: true,
+            ..policy
+        };
+        let narrow = UnicodeWidthPolicy {
+            narrow_emoji: true,
+            ..policy
+        };
+        let vs16 = UnicodeWidthPolicy {
+            vs16_is_wide: true,
+            ..policy
+        };
+
+        // WHITE CIRCLE has ambiguous width
+        assert_eq!(policy.grapheme_width("\u{25cb}"), 1);
+        assert_eq!(ambiguous.grapheme_width("\u{25cb}"), 2);
+        assert_eq!(ambiguous.grapheme_width("a"), 1);
+
+        // FOOT was introduced as a wide emoji
+        assert_eq!(policy.grapheme_width("\u{1f9b6}"), 2);
+        assert_eq!(narrow.grapheme_width("\u{1f9b6}"), 1);
+
+        // HEAVY BLACK HEART has text presentation unless followed by VS16
+        let heart = "\u{2764}\u{fe0f}";
+        assert_eq!(policy.grapheme_width(heart), 1);
+        assert_eq!(vs16.grapheme_width(heart), 2);
+        assert_eq!(vs16.grapheme_width("\u{2764}"), 1);
+        assert_eq!(vs16.str_width("ab\u{2764}\u{fe0f}"), 4);
+
+        let cell = Cell::new_grapheme_with_width(heart, 2, CellAttributes::default());
+        assert_eq!(cell.width(), 2);
+        assert_eq!(cell.str(), heart);
+    }
 }
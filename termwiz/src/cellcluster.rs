@@ -1,3 +1,4 @@
+use crate::bidi::Direction;
 use crate::cell::{Cell, CellAttributes};
 use std::borrow::Cow;
 
@@ -10,6 +11,10 @@ use std::borrow::Cow;
 pub struct CellCluster {
     pub attrs: CellAttributes,
     pub text: String,
+    /// The direction of the text.  The text of a right-to-left
+    /// cluster is held in logical order, which is the reverse of
+    /// the order in which its cells are displayed.
+    pub direction: Direction,
     byte_to_cell_idx: Vec<usize>,
     first_cell_idx: usize,
 }
@@ -30,6 +35,29 @@ impl CellCluster {
     pub fn make_cluster<'a>(
         hint: usize,
         iter: impl Iterator<Item = (usize, &'a Cell)>,
+    ) -> Vec<CellCluster> {
+        Self::make_cluster_with_direction(hint, iter, Direction::LeftToRight)
+    }
+
+    /// Compute the list of CellClusters for a right-to-left run of
+    /// cells that has been reordered for display.
+    /// The cells are given in display order and the clusters are
+    /// returned in display order, but the text of each cluster is
+    /// in logical order so that it can be shaped.
+    pub fn make_rtl_cluster<'a>(
+        hint: usize,
+        iter: impl DoubleEndedIterator<Item = (usize, &'a Cell)>,
+    ) -> Vec<CellCluster> {
+        let mut clusters =
+            Self::make_cluster_with_direction(hint, iter.rev(), Direction::RightToLeft);
+        clusters.reverse();
+        clusters
+    }
+
+    fn make_cluster_with_direction<'a>(
+        hint: usize,
+        iter: impl Iterator<Item = (usize, &'a Cell)>,
+        direction: Direction,
     ) -> Vec<CellCluster> {
         let mut last_cluster = None;
         let mut clusters = Vec::new();
@@ -56,6 +84,7 @@ impl CellCluster {
                         normalized_attr.into_owned(),
                         cell_str,
                         cell_idx,
+                        direction,
                     ))
                 }
                 Some(mut last) => {
@@ -70,6 +99,7 @@ impl CellCluster {
                             normalized_attr.into_owned(),
                             cell_str,
                             cell_idx,
+                            direction,
                         ))
                     } else {
                         // Add to current cluster.
@@ -96,6 +126,7 @@ impl CellCluster {
                                 normalized_attr.into_owned(),
                                 cell_str,
                                 cell_idx,
+                                direction,
                             ))
                         } else {
                             last.add(cell_str, cell_idx);
@@ -115,12 +146,19 @@ impl CellCluster {
     }
 
     /// Start off a new cluster with some initial data
-    fn new(hint: usize, attrs: CellAttributes, text: &str, cell_idx: usize) -> CellCluster {
+    fn new(
+        hint: usize,
+        attrs: CellAttributes,
+        text: &str,
+        cell_idx: usize,
+        direction: Direction,
+    ) -> CellCluster {
         let mut idx = Vec::new();
-        if text.len() > 1 {
-            // Prefer to avoid pushing any index data; this saves
-            // allocating any storage until we have any cells that
-            // are multibyte
+        // Prefer to avoid pushing any index data; this saves
+        // allocating any storage until we have any cells that
+        // are multibyte.  The cell indices of a right-to-left
+        // cluster descend, so they can't be extrapolated.
+        if text.len() > 1 || direction == Direction::RightToLeft {
             for _ in 0..text.len() {
                 idx.push(cell_idx);
             }
@@ -131,6 +169,7 @@ impl CellCluster {
         CellCluster {
             attrs,
             text: storage,
+            direction,
             byte_to_cell_idx: idx,
             first_cell_idx: cell_idx,
         }
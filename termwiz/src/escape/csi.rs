@@ -27,6 +27,12 @@ pub enum CSI {
 
     Window(Window),
 
+    /// SCP - SELECT CHARACTER PATH
+    /// Selects the direction of the current paragraph.  The second
+    /// parameter selects how existing text is affected and is
+    /// ignored by wezterm.
+    SelectCharacterPath(CharacterPath, i64),
+
     /// Unknown or unspecified; should be rare and is rather
     /// large, so it is boxed and kept outside of the enum
     /// body to help reduce space usage in the common cases.
@@ -78,11 +84,33 @@ impl Display for CSI {
             CSI::Mouse(mouse) => mouse.fmt(f)?,
             CSI::Device(dev) => dev.fmt(f)?,
             CSI::Window(window) => window.fmt(f)?,
+            CSI::SelectCharacterPath(path, n) => {
+                let a = *path as u8;
+                match (a, n) {
+                    (0, 0) => write!(f, " k")?,
+                    (a, 0) => write!(f, "{} k", a)?,
+                    (a, n) => write!(f, "{};{} k", a, n)?,
+                }
+            }
         };
         Ok(())
     }
 }
 
+/// The direction selected by SCP
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+pub enum CharacterPath {
+    ImplementationDefault = 0,
+    LeftToRightOrTopToBottom = 1,
+    RightToLeftOrBottomToTop = 2,
+}
+
+impl Default for CharacterPath {
+    fn default() -> Self {
+        Self::ImplementationDefault
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
 pub enum CursorStyle {
     Default = 0,
@@ -592,7 +620,7 @@ impl Display for Mode {
                     TerminalMode::Code(mode) => mode.to_u16().ok_or_else(|| FmtError)?,
                     TerminalMode::Unspecified(mode) => *mode,
                 };
-                write!(f, "?{}{}", value, $flag)
+                write!(f, "{}{}", value, $flag)
             }};
         }
         match self {
@@ -689,6 +717,11 @@ pub enum DecPrivateModeCode {
     EnableAlternateScreen = 47,
     OptEnableAlternateScreen = 1047,
     BracketedPaste = 2004,
+    /// <https://terminal-wg.pages.freedesktop.org/bidi/recommendation/escape-sequences.html>
+    /// Mirror box drawing characters in right-to-left runs
+    MirrorBoxDrawing = 2500,
+    /// Autodetect the direction of each paragraph from its text
+    BidiAutoDetect = 2501,
     /// Applies to sixel and regis modes
     UsePrivateColorRegistersForEachGraphic = 1070,
 
@@ -713,6 +746,10 @@ pub enum TerminalModeCode {
     SendReceive = 12,
     /// https://vt100.net/docs/vt510-rm/LNM.html
     AutomaticNewline = 20,
+    /// BDSM - ECMA-48 bidirectional support mode.
+    /// When set (implicit mode) the terminal reorders text for display;
+    /// when reset (explicit mode) the application does so.
+    BiDirectionalSupportMode = 8,
     /// MS terminal cursor visibility
     ShowCursor = 25,
 }
@@ -959,6 +996,133 @@ pub enum Edit {
 
     /// REP - Repeat the preceding character n times
     Repeat(u32),
+
+    /// DECCRA - Copy Rectangular Area
+    /// https://vt100.net/docs/vt510-rm/DECCRA.html
+    CopyRectangularArea {
+        source: Rectangle,
+        source_page: OneBased,
+        dest_top: OneBased,
+        dest_left: OneBased,
+        dest_page: OneBased,
+    },
+
+    /// DECFRA - Fill Rectangular Area with the specified character
+    /// https://vt100.net/docs/vt510-rm/DECFRA.html
+    FillRectangularArea { ch: char, area: Rectangle },
+
+    /// DECERA - Erase Rectangular Area
+    /// https://vt100.net/docs/vt510-rm/DECERA.html
+    EraseRectangularArea(Rectangle),
+
+    /// DECSERA - Selective Erase Rectangular Area.
+    /// Erases only the characters that are not protected by DECSCA.
+    /// https://vt100.net/docs/vt510-rm/DECSERA.html
+    SelectiveEraseRectangularArea(Rectangle),
+
+    /// DECCARA - Change Attributes in Rectangular Area.
+    /// The extent of the change is selected by DECSACE.
+    /// https://vt100.net/docs/vt510-rm/DECCARA.html
+    ChangeAttributesInRectangularArea {
+        area: Rectangle,
+        attributes: Vec<Sgr>,
+    },
+
+    /// DECRARA - Reverse Attributes in Rectangular Area.
+    /// Each of the attributes is toggled; `Sgr::Reset` toggles
+    /// all of them.
+    /// The extent of the change is selected by DECSACE.
+    /// https://vt100.net/docs/vt510-rm/DECRARA.html
+    ReverseAttributesInRectangularArea {
+        area: Rectangle,
+        attributes: Vec<Sgr>,
+    },
+
+    /// DECSACE - Select Attribute Change Extent
+    /// https://vt100.net/docs/vt510-rm/DECSACE.html
+    SelectAttributeChangeExtent(AttributeChangeExtent),
+
+    /// DECSCA - Select Character Protection Attribute.
+    /// When true, subsequently printed characters cannot be erased
+    /// by DECSED, DECSEL or DECSERA.
+    /// https://vt100.net/docs/vt510-rm/DECSCA.html
+    SelectCharacterProtection(bool),
+
+    /// DECSED - Selective Erase in Display.
+    /// Like ED, but erases only the characters that are not
+    /// protected by DECSCA.
+    /// https://vt100.net/docs/vt510-rm/DECSED.html
+    SelectiveEraseInDisplay(EraseInDisplay),
+
+    /// DECSEL - Selective Erase in Line.
+    /// Like EL, but erases only the characters that are not
+    /// protected by DECSCA.
+    /// https://vt100.net/docs/vt510-rm/DECSEL.html
+    SelectiveEraseInLine(EraseInLine),
+}
+
+/// A rectangular area of the screen that is the subject of one of the
+/// DEC rectangular area operations.  The bounds are inclusive.
+/// `bottom` and `right` default to the last line and column of the
+/// screen, which is represented as `u32::max_value()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rectangle {
+    pub top: OneBased,
+    pub left: OneBased,
+    pub bottom: OneBased,
+    pub right: OneBased,
+}
+
+impl Rectangle {
+    /// Parse the rectangle from the first four parameters
+    fn parse(params: &[CsiParam]) -> Result<Self, ()> {
+        fn big(o: Option<&CsiParam>) -> Result<OneBased, ()> {
+            OneBased::from_esc_param_with_big_default(o.unwrap_or(&CsiParam::Integer(0)))
+        }
+        Ok(Self {
+            top: OneBased::from_optional_esc_param(params.get(0))?,
+            left: OneBased::from_optional_esc_param(params.get(1))?,
+            bottom: big(params.get(2))?,
+            right: big(params.get(3))?,
+        })
+    }
+}
+
+impl Display for Rectangle {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        write!(
+            f,
+            "{};{};{};{}",
+            self.top, self.left, self.bottom, self.right
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeChangeExtent {
+    /// DECCARA and DECRARA affect the character positions that
+    /// lie between the start and end positions, in reading order
+    Stream,
+    /// DECCARA and DECRARA affect only the character positions
+    /// that lie within the rectangle
+    Rectangle,
+}
+
+impl Default for AttributeChangeExtent {
+    fn default() -> Self {
+        Self::Stream
+    }
+}
+
+/// Emit the attribute parameters of DECCARA and DECRARA
+fn write_rectangle_attributes(f: &mut Formatter, attributes: &[Sgr]) -> Result<(), FmtError> {
+    for sgr in attributes {
+        // Sgr encodes itself as a complete SGR sequence body;
+        // we just want the parameters.
+        let sgr = sgr.to_string();
+        write!(f, ";{}", sgr.trim_end_matches('m'))?;
+    }
+    Ok(())
 }
 
 trait EncodeCSIParam {
@@ -1009,6 +1173,45 @@ impl Display for Edit {
             Edit::ScrollUp(n) => n.write_csi(f, "S")?,
             Edit::EraseInDisplay(n) => n.write_csi(f, "J")?,
             Edit::Repeat(n) => n.write_csi(f, "b")?,
+            Edit::CopyRectangularArea {
+                source,
+                source_page,
+                dest_top,
+                dest_left,
+                dest_page,
+            } => write!(
+                f,
+                "{};{};{};{};{}$v",
+                source, source_page, dest_top, dest_left, dest_page
+            )?,
+            Edit::FillRectangularArea { ch, area } => write!(f, "{};{}$x", *ch as u32, area)?,
+            Edit::EraseRectangularArea(area) => write!(f, "{}$z", area)?,
+            Edit::SelectiveEraseRectangularArea(area) => write!(f, "{}${{", area)?,
+            Edit::ChangeAttributesInRectangularArea { area, attributes } => {
+                write!(f, "{}", area)?;
+                write_rectangle_attributes(f, attributes)?;
+                write!(f, "$r")?;
+            }
+            Edit::ReverseAttributesInRectangularArea { area, attributes } => {
+                write!(f, "{}", area)?;
+                write_rectangle_attributes(f, attributes)?;
+                write!(f, "$t")?;
+            }
+            Edit::SelectAttributeChangeExtent(AttributeChangeExtent::Stream) => write!(f, "1*x")?,
+            Edit::SelectAttributeChangeExtent(AttributeChangeExtent::Rectangle) => {
+                write!(f, "2*x")?
+            }
+            Edit::SelectCharacterProtection(protect) => {
+                write!(f, "{}\"q", if *protect { 1 } else { 0 })?
+            }
+            Edit::SelectiveEraseInDisplay(n) => {
+                write!(f, "?")?;
+                n.write_csi(f, "J")?
+            }
+            Edit::SelectiveEraseInLine(n) => {
+                write!(f, "?")?;
+                n.write_csi(f, "K")?
+            }
         }
         Ok(())
     }
@@ -1522,6 +1725,7 @@ impl<'a> CSIParser<'a> {
 
             ('m', &[]) => self.sgr(params).map(CSI::Sgr),
             ('n', &[]) => self.dsr(params),
+            ('k', &[b' ']) => self.select_character_path(params),
             ('q', &[b' ']) => self.cursor_style(params),
             ('r', &[]) => self.decstbm(params),
             ('s', &[]) => self.decslrm(params),
@@ -1549,6 +1753,47 @@ impl<'a> CSIParser<'a> {
 
             ('p', &[b'!']) => Ok(CSI::Device(Box::new(Device::SoftReset))),
 
+            ('v', &[b'$']) => self.deccra(params),
+            ('x', &[b'$']) => self.decfra(params),
+            ('z', &[b'$']) => Ok(CSI::Edit(Edit::EraseRectangularArea(Rectangle::parse(
+                params,
+            )?))),
+            ('{', &[b'$']) => Ok(CSI::Edit(Edit::SelectiveEraseRectangularArea(
+                Rectangle::parse(params)?,
+            ))),
+            ('r', &[b'$']) => {
+                let (area, attributes) = Self::rectangle_attributes(params)?;
+                Ok(CSI::Edit(Edit::ChangeAttributesInRectangularArea {
+                    area,
+                    attributes,
+                }))
+            }
+            ('t', &[b'$']) => {
+                let (area, attributes) = Self::rectangle_attributes(params)?;
+                Ok(CSI::Edit(Edit::ReverseAttributesInRectangularArea {
+                    area,
+                    attributes,
+                }))
+            }
+            ('q', &[b'"']) => match params {
+                [] | [CsiParam::Integer(0)] | [CsiParam::Integer(2)] => {
+                    Ok(CSI::Edit(Edit::SelectCharacterProtection(false)))
+                }
+                [CsiParam::Integer(1)] => Ok(CSI::Edit(Edit::SelectCharacterProtection(true))),
+                _ => Err(()),
+            },
+            ('J', &[b'?']) => parse!(Edit, SelectiveEraseInDisplay, params),
+            ('K', &[b'?']) => parse!(Edit, SelectiveEraseInLine, params),
+            ('x', &[b'*']) => match params {
+                [] | [CsiParam::Integer(0)] | [CsiParam::Integer(1)] => Ok(CSI::Edit(
+                    Edit::SelectAttributeChangeExtent(AttributeChangeExtent::Stream),
+                )),
+                [CsiParam::Integer(2)] => Ok(CSI::Edit(Edit::SelectAttributeChangeExtent(
+                    AttributeChangeExtent::Rectangle,
+                ))),
+                _ => Err(()),
+            },
+
             ('h', &[b'?']) => self
                 .dec(params)
                 .map(|mode| CSI::Mode(Mode::SetDecPrivateMode(mode))),
@@ -1594,6 +1839,53 @@ impl<'a> CSIParser<'a> {
         result
     }
 
+    fn deccra(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
+        if params.len() > 8 {
+            return Err(());
+        }
+        Ok(CSI::Edit(Edit::CopyRectangularArea {
+            source: Rectangle::parse(params)?,
+            source_page: OneBased::from_optional_esc_param(params.get(4))?,
+            dest_top: OneBased::from_optional_esc_param(params.get(5))?,
+            dest_left: OneBased::from_optional_esc_param(params.get(6))?,
+            dest_page: OneBased::from_optional_esc_param(params.get(7))?,
+        }))
+    }
+
+    fn decfra(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
+        if params.len() > 5 {
+            return Err(());
+        }
+        // Only the graphic characters from the GL and GR
+        // sets are valid fill characters
+        let ch = match params.get(0) {
+            Some(CsiParam::Integer(n)) if (32..=126).contains(n) || (160..=255).contains(n) => {
+                std::char::from_u32(*n as u32).ok_or(())?
+            }
+            _ => return Err(()),
+        };
+        Ok(CSI::Edit(Edit::FillRectangularArea {
+            ch,
+            area: Rectangle::parse(&params[1..])?,
+        }))
+    }
+
+    /// Parse the rectangle and SGR attributes used by DECCARA and DECRARA
+    fn rectangle_attributes(params: &'a [CsiParam]) -> Result<(Rectangle, Vec<Sgr>), ()> {
+        let area = Rectangle::parse(params)?;
+        let attributes = if params.len() > 4 {
+            CSI::parse(&params[4..], &[], false, 'm')
+                .map(|csi| match csi {
+                    CSI::Sgr(sgr) => Ok(sgr),
+                    _ => Err(()),
+                })
+                .collect::<Result<Vec<_>, ()>>()?
+        } else {
+            vec![Sgr::Reset]
+        };
+        Ok((area, attributes))
+    }
+
     fn cursor_style(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
         if params.len() != 1 {
             Err(())
@@ -1607,6 +1899,22 @@ impl<'a> CSIParser<'a> {
         }
     }
 
+    fn select_character_path(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
+        fn path(p: &CsiParam) -> Result<CharacterPath, ()> {
+            FromPrimitive::from_i64(p.as_integer().ok_or(())?).ok_or(())
+        }
+
+        match params {
+            [] => Ok(CSI::SelectCharacterPath(CharacterPath::default(), 0)),
+            [a] => Ok(CSI::SelectCharacterPath(path(a)?, 0)),
+            [a, b] => Ok(CSI::SelectCharacterPath(
+                path(a)?,
+                b.as_integer().ok_or(())?,
+            )),
+            _ => Err(()),
+        }
+    }
+
     fn dsr(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
         if params == [CsiParam::Integer(5)] {
             Ok(self.advance_by(1, params, CSI::Device(Box::new(Device::StatusReport))))
@@ -2545,6 +2853,43 @@ mod test {
         );
     }
 
+    #[test]
+    fn bidi_modes() {
+        assert_eq!(
+            parse_int('h', &[2501], b'?', "\x1b[?2501h"),
+            vec![CSI::Mode(Mode::SetDecPrivateMode(DecPrivateMode::Code(
+                DecPrivateModeCode::BidiAutoDetect,
+            )))]
+        );
+        assert_eq!(
+            parse('l', &[8], "\x1b[8l"),
+            vec![CSI::Mode(Mode::ResetMode(TerminalMode::Code(
+                TerminalModeCode::BiDirectionalSupportMode,
+            )))]
+        );
+        assert_eq!(
+            parse_int('k', &[], b' ', "\x1b[ k"),
+            vec![CSI::SelectCharacterPath(
+                CharacterPath::ImplementationDefault,
+                0
+            )]
+        );
+        assert_eq!(
+            parse_int('k', &[2], b' ', "\x1b[2 k"),
+            vec![CSI::SelectCharacterPath(
+                CharacterPath::RightToLeftOrBottomToTop,
+                0
+            )]
+        );
+        assert_eq!(
+            parse_int('k', &[1, 1], b' ', "\x1b[1;1 k"),
+            vec![CSI::SelectCharacterPath(
+                CharacterPath::LeftToRightOrTopToBottom,
+                1
+            )]
+        );
+    }
+
     #[test]
     fn mouse() {
         assert_eq!(
@@ -2580,4 +2925,119 @@ mod test {
             )))]
         );
     }
+
+    #[test]
+    fn ansi_modes() {
+        assert_eq!(
+            parse('h', &[4], "\x1b[4h"),
+            vec![CSI::Mode(Mode::SetMode(TerminalMode::Code(
+                TerminalModeCode::Insert,
+            )))]
+        );
+        assert_eq!(
+            parse('l', &[20], "\x1b[20l"),
+            vec![CSI::Mode(Mode::ResetMode(TerminalMode::Code(
+                TerminalModeCode::AutomaticNewline,
+            )))]
+        );
+        assert_eq!(
+            parse('h', &[3], "\x1b[3h"),
+            vec![CSI::Mode(Mode::SetMode(TerminalMode::Unspecified(3)))]
+        );
+    }
+
+    #[test]
+    fn rectangular_area() {
+        let area = Rectangle {
+            top: OneBased::new(2),
+            left: OneBased::new(3),
+            bottom: OneBased::new(4),
+            right: OneBased::new(5),
+        };
+        assert_eq!(
+            parse_int('z', &[2, 3, 4, 5], b'$', "\x1b[2;3;4;5$z"),
+            vec![CSI::Edit(Edit::EraseRectangularArea(area))]
+        );
+        assert_eq!(
+            parse_int('{', &[2, 3, 4, 5], b'$', "\x1b[2;3;4;5${"),
+            vec![CSI::Edit(Edit::SelectiveEraseRectangularArea(area))]
+        );
+        assert_eq!(
+            parse_int('x', &[65, 2, 3, 4, 5], b'$', "\x1b[65;2;3;4;5$x"),
+            vec![CSI::Edit(Edit::FillRectangularArea { ch: 'A', area })]
+        );
+        assert_eq!(
+            parse_int(
+                'v',
+                &[2, 3, 4, 5, 1, 6, 7, 1],
+                b'$',
+                "\x1b[2;3;4;5;1;6;7;1$v"
+            ),
+            vec![CSI::Edit(Edit::CopyRectangularArea {
+                source: area,
+                source_page: OneBased::new(1),
+                dest_top: OneBased::new(6),
+                dest_left: OneBased::new(7),
+                dest_page: OneBased::new(1),
+            })]
+        );
+        assert_eq!(
+            parse_int('r', &[2, 3, 4, 5, 1, 24], b'$', "\x1b[2;3;4;5;1;24$r"),
+            vec![CSI::Edit(Edit::ChangeAttributesInRectangularArea {
+                area,
+                attributes: vec![
+                    Sgr::Intensity(Intensity::Bold),
+                    Sgr::Underline(Underline::None)
+                ],
+            })]
+        );
+        assert_eq!(
+            parse_int('t', &[2, 3, 4, 5], b'$', "\x1b[2;3;4;5;0$t"),
+            vec![CSI::Edit(Edit::ReverseAttributesInRectangularArea {
+                area,
+                attributes: vec![Sgr::Reset],
+            })]
+        );
+        assert_eq!(
+            parse_int('x', &[2], b'*', "\x1b[2*x"),
+            vec![CSI::Edit(Edit::SelectAttributeChangeExtent(
+                AttributeChangeExtent::Rectangle
+            ))]
+        );
+
+        // The bottom right corner defaults to the edge of the screen
+        assert_eq!(
+            parse_int('z', &[], b'$', "\x1b[1;1;4294967295;4294967295$z"),
+            vec![CSI::Edit(Edit::EraseRectangularArea(Rectangle {
+                top: OneBased::new(1),
+                left: OneBased::new(1),
+                bottom: OneBased::new(u32::max_value()),
+                right: OneBased::new(u32::max_value()),
+            }))]
+        );
+    }
+
+    #[test]
+    fn selective_erase() {
+        assert_eq!(
+            parse_int('q', &[1], b'"', "\x1b[1\"q"),
+            vec![CSI::Edit(Edit::SelectCharacterProtection(true))]
+        );
+        assert_eq!(
+            parse_int('q', &[2], b'"', "\x1b[0\"q"),
+            vec![CSI::Edit(Edit::SelectCharacterProtection(false))]
+        );
+        assert_eq!(
+            parse_int('J', &[2], b'?', "\x1b[?2J"),
+            vec![CSI::Edit(Edit::SelectiveEraseInDisplay(
+                EraseInDisplay::EraseDisplay
+            ))]
+        );
+        assert_eq!(
+            parse_int('K', &[], b'?', "\x1b[?K"),
+            vec![CSI::Edit(Edit::SelectiveEraseInLine(
+                EraseInLine::EraseToEndOfLine
+            ))]
+        );
+    }
 }
@@ -42,6 +42,10 @@ pub enum EscCode {
     SingleShiftG2 = esc!('N'),
     /// SS3 Single shift of G3 character set affects next character only
     SingleShiftG3 = esc!('O'),
+    /// LS2 Invoke the G2 character set into GL
+    LockingShiftG2 = esc!('n'),
+    /// LS3 Invoke the G3 character set into GL
+    LockingShiftG3 = esc!('o'),
     /// SPA - Start of Guarded Area
     StartOfGuardedArea = esc!('V'),
     /// EPA - End of Guarded Area
@@ -70,18 +74,145 @@ pub enum EscCode {
 
     /// Designate G0 Character Set – DEC Line Drawing
     DecLineDrawingG0 = esc!('(', '0'),
-    /// Designate G0 Character Set - UK
+    /// Designate G0 Character Set – UK
     UkCharacterSetG0 = esc!('(', 'A'),
     /// Designate G0 Character Set – US ASCII
     AsciiCharacterSetG0 = esc!('(', 'B'),
+    /// Designate G0 Character Set – DEC Supplemental
+    DecSupplementalG0 = esc!('(', '<'),
+    /// Designate G0 Character Set – DEC Technical
+    DecTechnicalG0 = esc!('(', '>'),
+    /// Designate G0 Character Set – Dutch NRCS
+    DutchCharacterSetG0 = esc!('(', '4'),
+    /// Designate G0 Character Set – Finnish NRCS
+    FinnishCharacterSetG0 = esc!('(', 'C'),
+    /// Designate G0 Character Set – French NRCS
+    FrenchCharacterSetG0 = esc!('(', 'R'),
+    /// Designate G0 Character Set – French Canadian NRCS
+    FrenchCanadianCharacterSetG0 = esc!('(', 'Q'),
+    /// Designate G0 Character Set – German NRCS
+    GermanCharacterSetG0 = esc!('(', 'K'),
+    /// Designate G0 Character Set – Italian NRCS
+    ItalianCharacterSetG0 = esc!('(', 'Y'),
+    /// Designate G0 Character Set – Norwegian/Danish NRCS
+    NorwegianDanishCharacterSetG0 = esc!('(', 'E'),
+    /// Designate G0 Character Set – Spanish NRCS
+    SpanishCharacterSetG0 = esc!('(', 'Z'),
+    /// Designate G0 Character Set – Swedish NRCS
+    SwedishCharacterSetG0 = esc!('(', 'H'),
+    /// Designate G0 Character Set – Swiss NRCS
+    SwissCharacterSetG0 = esc!('(', '='),
 
     /// Designate G1 Character Set – DEC Line Drawing
     DecLineDrawingG1 = esc!(')', '0'),
-    /// Designate G1 Character Set - UK
+    /// Designate G1 Character Set – UK
     UkCharacterSetG1 = esc!(')', 'A'),
     /// Designate G1 Character Set – US ASCII
     AsciiCharacterSetG1 = esc!(')', 'B'),
+    /// Designate G1 Character Set – DEC Supplemental
+    DecSupplementalG1 = esc!(')', '<'),
+    /// Designate G1 Character Set – DEC Technical
+    DecTechnicalG1 = esc!(')', '>'),
+    /// Designate G1 Character Set – Dutch NRCS
+    DutchCharacterSetG1 = esc!(')', '4'),
+    /// Designate G1 Character Set – Finnish NRCS
+    FinnishCharacterSetG1 = esc!(')', 'C'),
+    /// Designate G1 Character Set – French NRCS
+    FrenchCharacterSetG1 = esc!(')', 'R'),
+    /// Designate G1 Character Set – French Canadian NRCS
+    FrenchCanadianCharacterSetG1 = esc!(')', 'Q'),
+    /// Designate G1 Character Set – German NRCS
+    GermanCharacterSetG1 = esc!(')', 'K'),
+    /// Designate G1 Character Set – Italian NRCS
+    ItalianCharacterSetG1 = esc!(')', 'Y'),
+    /// Designate G1 Character Set – Norwegian/Danish NRCS
+    NorwegianDanishCharacterSetG1 = esc!(')', 'E'),
+    /// Designate G1 Character Set – Spanish NRCS
+    SpanishCharacterSetG1 = esc!(')', 'Z'),
+    /// Designate G1 Character Set – Swedish NRCS
+    SwedishCharacterSetG1 = esc!(')', 'H'),
+    /// Designate G1 Character Set – Swiss NRCS
+    SwissCharacterSetG1 = esc!(')', '='),
+    /// Designate G1 Character Set – ISO Latin-1 Supplemental
+    IsoLatin1SupplementalG1 = esc!('-', 'A'),
 
+    /// Designate G2 Character Set – DEC Line Drawing
+    DecLineDrawingG2 = esc!('*', '0'),
+    /// Designate G2 Character Set – UK
+    UkCharacterSetG2 = esc!('*', 'A'),
+    /// Designate G2 Character Set – US ASCII
+    AsciiCharacterSetG2 = esc!('*', 'B'),
+    /// Designate G2 Character Set – DEC Supplemental
+    DecSupplementalG2 = esc!('*', '<'),
+    /// Designate G2 Character Set – DEC Technical
+    DecTechnicalG2 = esc!('*', '>'),
+    /// Designate G2 Character Set – Dutch NRCS
+    DutchCharacterSetG2 = esc!('*', '4'),
+    /// Designate G2 Character Set – Finnish NRCS
+    FinnishCharacterSetG2 = esc!('*', 'C'),
+    /// Designate G2 Character Set – French NRCS
+    FrenchCharacterSetG2 = esc!('*', 'R'),
+    /// Designate G2 Character Set – French Canadian NRCS
+    FrenchCanadianCharacterSetG2 = esc!('*', 'Q'),
+    /// Designate G2 Character Set – German NRCS
+    GermanCharacterSetG2 = esc!('*', 'K'),
+    /// Designate G2 Character Set – Italian NRCS
+    ItalianCharacterSetG2 = esc!('*', 'Y'),
+    /// Designate G2 Character Set – Norwegian/Danish NRCS
+    NorwegianDanishCharacterSetG2 = esc!('*', 'E'),
+    /// Designate G2 Character Set – Spanish NRCS
+    SpanishCharacterSetG2 = esc!('*', 'Z'),
+    /// Designate G2 Character Set – Swedish NRCS
+    SwedishCharacterSetG2 = esc!('*', 'H'),
+    /// Designate G2 Character Set – Swiss NRCS
+    SwissCharacterSetG2 = esc!('*', '='),
+    /// Designate G2 Character Set – ISO Latin-1 Supplemental
+    IsoLatin1SupplementalG2 = esc!('.', 'A'),
+
+    /// Designate G3 Character Set – DEC Line Drawing
+    DecLineDrawingG3 = esc!('+', '0'),
+    /// Designate G3 Character Set – UK
+    UkCharacterSetG3 = esc!('+', 'A'),
+    /// Designate G3 Character Set – US ASCII
+    AsciiCharacterSetG3 = esc!('+', 'B'),
+    /// Designate G3 Character Set – DEC Supplemental
+    DecSupplementalG3 = esc!('+', '<'),
+    /// Designate G3 Character Set – DEC Technical
+    DecTechnicalG3 = esc!('+', '>'),
+    /// Designate G3 Character Set – Dutch NRCS
+    DutchCharacterSetG3 = esc!('+', '4'),
+    /// Designate G3 Character Set – Finnish NRCS
+    FinnishCharacterSetG3 = esc!('+', 'C'),
+    /// Designate G3 Character Set – French NRCS
+    FrenchCharacterSetG3 = esc!('+', 'R'),
+    /// Designate G3 Character Set – French Canadian NRCS
+    FrenchCanadianCharacterSetG3 = esc!('+', 'Q'),
+    /// Designate G3 Character Set – German NRCS
+    GermanCharacterSetG3 = esc!('+', 'K'),
+    /// Designate G3 Character Set – Italian NRCS
+    ItalianCharacterSetG3 = esc!('+', 'Y'),
+    /// Designate G3 Character Set – Norwegian/Danish NRCS
+    NorwegianDanishCharacterSetG3 = esc!('+', 'E'),
+    /// Designate G3 Character Set – Spanish NRCS
+    SpanishCharacterSetG3 = esc!('+', 'Z'),
+    /// Designate G3 Character Set – Swedish NRCS
+    SwedishCharacterSetG3 = esc!('+', 'H'),
+    /// Designate G3 Character Set – Swiss NRCS
+    SwissCharacterSetG3 = esc!('+', '='),
+    /// Designate G3 Character Set – ISO Latin-1 Supplemental
+    IsoLatin1SupplementalG3 = esc!('/', 'A'),
+
+    /// DECDHL - Double height line, top half
+    /// https://vt100.net/docs/vt510-rm/DECDHL.html
+    DecDoubleHeightTopHalfLine = esc!('#', '3'),
+    /// DECDHL - Double height line, bottom half
+    DecDoubleHeightBottomHalfLine = esc!('#', '4'),
+    /// DECSWL - Single width line
+    /// https://vt100.net/docs/vt510-rm/DECSWL.html
+    DecSingleWidthLine = esc!('#', '5'),
+    /// DECDWL - Double width line
+    /// https://vt100.net/docs/vt510-rm/DECDWL.html
+    DecDoubleWidthLine = esc!('#', '6'),
     /// https://vt100.net/docs/vt510-rm/DECALN.html
     DecScreenAlignmentDisplay = esc!('#', '8'),
 
@@ -183,5 +314,18 @@ mod test {
         assert_eq!(parse("(B"), Esc::Code(EscCode::AsciiCharacterSetG0));
         assert_eq!(parse(")0"), Esc::Code(EscCode::DecLineDrawingG1));
         assert_eq!(parse(")B"), Esc::Code(EscCode::AsciiCharacterSetG1));
+        assert_eq!(parse("#3"), Esc::Code(EscCode::DecDoubleHeightTopHalfLine));
+        assert_eq!(parse("#6"), Esc::Code(EscCode::DecDoubleWidthLine));
+    }
+
+    #[test]
+    fn charsets() {
+        assert_eq!(parse("*<"), Esc::Code(EscCode::DecSupplementalG2));
+        assert_eq!(parse("+>"), Esc::Code(EscCode::DecTechnicalG3));
+        assert_eq!(parse("(K"), Esc::Code(EscCode::GermanCharacterSetG0));
+        assert_eq!(parse(")="), Esc::Code(EscCode::SwissCharacterSetG1));
+        assert_eq!(parse("-A"), Esc::Code(EscCode::IsoLatin1SupplementalG1));
+        assert_eq!(parse("n"), Esc::Code(EscCode::LockingShiftG2));
+        assert_eq!(parse("o"), Esc::Code(EscCode::LockingShiftG3));
     }
 }
@@ -362,14 +362,16 @@ impl Display for SixelData {
                 write!(f, "!{}{}", repeat_count, (data + 0x3f) as char)
             }
             Self::DefineColorMapRGB { color_number, rgb } => {
-                let (r, g, b, _) = rgb.to_linear_tuple_rgba();
+                // The parser maps the percentages directly onto
+                // the sRGB color, so we do the inverse here
+                let (r, g, b, _) = rgb.to_tuple_rgba();
                 write!(
                     f,
                     "#{};2;{};{};{}",
                     color_number,
-                    (r * 100.) as u8,
-                    (g * 100.) as u8,
-                    (b * 100.0) as u8
+                    (r * 100.).round() as u8,
+                    (g * 100.).round() as u8,
+                    (b * 100.).round() as u8
                 )
             }
             Self::DefineColorMapHSL {
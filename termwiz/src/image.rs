@@ -1,15 +1,12 @@
 //! Images.
 //! This module has some helpers for modeling terminal cells that are filled
 //! with image data.
-//! We're targeting the iTerm image protocol initially, with sixel as an obvious
-//! follow up.
-// Kitty has an extensive and complex graphics protocol that seems difficult
-// to model.  Its docs are here:
-// <https://github.com/kovidgoyal/kitty/blob/master/docs/graphics-protocol.rst>
+//! The terminfo renderer can output these using the iTerm2, sixel or
+//! kitty image protocols.
 // Both iTerm2 and Sixel appear to have semantics that allow replacing the
 // contents of a single chararcter cell with image data, whereas the kitty
-// protocol appears to track the images out of band as attachments with
-// z-order.
+// protocol tracks the images out of band as attachments with z-order;
+// the renderer transmits each `ImageData` once and then places it by id.
 
 use ordered_float::NotNan;
 #[cfg(feature = "use_serde")]
@@ -39,7 +39,12 @@
 //!
 //! * `widgets` - enables the widget layout and related traits
 //! * `use_serde` - makes a number of structs serde serializable
+//! * `use_image` - decodes image data so that images can be rendered
+//!   to terminals that support sixel graphics.  This is not enabled
+//!   by default; without it, images are only rendered to terminals
+//!   that support the iTerm2 image protocol.
 
+pub mod bidi;
 pub mod caps;
 pub mod cell;
 pub mod cellcluster;
@@ -6,6 +6,8 @@ pub enum Movement {
     BackwardWord(RepeatCount),
     ForwardChar(RepeatCount),
     ForwardWord(RepeatCount),
+    BackwardLine(RepeatCount),
+    ForwardLine(RepeatCount),
     StartOfLine,
     EndOfLine,
     None,
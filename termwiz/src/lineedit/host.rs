@@ -1,4 +1,4 @@
-use crate::cell::{AttributeChange, CellAttributes};
+use crate::cell::{AttributeChange, CellAttributes, Intensity};
 use crate::input::InputEvent;
 use crate::lineedit::actions::Action;
 use crate::lineedit::{BasicHistory, History, LineEditor};
@@ -65,12 +65,51 @@ pub trait LineEditorHost {
     /// location.
     /// The `OutputElement` type allows returning graphic attribute changes
     /// as well as textual output.
+    /// The line may span multiple logical lines separated by `\n`; the
+    /// returned cursor x position is the column of the cursor relative
+    /// to the start of the logical line that contains it.
     /// The default implementation returns the line as-is with no coloring.
     fn highlight_line(&self, line: &str, cursor_position: usize) -> (Vec<OutputElement>, usize) {
-        let cursor_x_pos = crate::cell::unicode_column_width(&line[0..cursor_position]);
+        let line_start = line[0..cursor_position]
+            .rfind('\n')
+            .map(|idx| idx + 1)
+            .unwrap_or(0);
+        let cursor_x_pos = crate::cell::unicode_column_width(&line[line_start..cursor_position]);
         (vec![OutputElement::Text(line.to_owned())], cursor_x_pos)
     }
 
+    /// Given a reference to the current line being edited, return
+    /// true if it is a complete input that can be accepted.
+    /// When this returns false, the accept line action inserts a
+    /// newline at the cursor position instead of accepting the line,
+    /// allowing for multi-line input such as an unterminated block
+    /// in a REPL.
+    /// The default implementation considers every line to be complete.
+    fn is_line_complete(&self, _line: &str) -> bool {
+        true
+    }
+
+    /// Given a reference to the current line being edited and the position
+    /// of the cursor, return a hint (sometimes known as an autosuggestion)
+    /// for text that could follow the end of the line.
+    /// The hint is rendered after the line but is not part of it; moving
+    /// the cursor forwards while it is positioned at the end of the line
+    /// appends the hint to the line.
+    /// The default implementation returns no hint.
+    fn hint(&self, _line: &str, _cursor_position: usize) -> Option<String> {
+        None
+    }
+
+    /// Given a hint produced by `hint`, return the rendered form of
+    /// the hint as a sequence of `OutputElement` instances.
+    /// The default implementation renders the hint using half intensity.
+    fn render_hint(&self, hint: &str) -> Vec<OutputElement> {
+        vec![
+            OutputElement::Attribute(AttributeChange::Intensity(Intensity::Half)),
+            OutputElement::Text(hint.to_owned()),
+        ]
+    }
+
     /// Returns the history implementation
     fn history(&mut self) -> &mut dyn History;
 
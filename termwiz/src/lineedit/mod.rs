@@ -23,27 +23,44 @@
 //! Keystroke     | Action
 //! ---------     | ------
 //! Ctrl-A, Home  | Move cursor to the beginning of the line
-//! Ctrl-E, End   | Move cursor to the end of the line
+//! Ctrl-E, End   | Move cursor to the end of the line, or accept the hint
 //! Ctrl-B, Left  | Move cursor one grapheme to the left
 //! Ctrl-C        | Cancel the line editor
 //! Ctrl-D        | Cancel the line editor with an End-of-File result
-//! Ctrl-F, Right | Move cursor one grapheme to the right
+//! Ctrl-F, Right | Move cursor one grapheme to the right, or accept the hint
 //! Ctrl-H, Backspace | Delete the grapheme to the left of the cursor
 //! Delete        | Delete the grapheme to the right of the cursor
 //! Ctrl-J, Ctrl-M, Enter | Finish line editing and accept the current line
+//! Ctrl-P, Up    | Move cursor up one line, or to the previous history entry
+//! Ctrl-N, Down  | Move cursor down one line, or to the next history entry
 //! Ctrl-K        | Delete from cursor to end of line
 //! Ctrl-L        | Move the cursor to the top left, clear screen and repaint
 //! Ctrl-R        | Incremental history search mode
 //! Ctrl-W        | Delete word leading up to cursor
 //! Alt-b, Alt-Left | Move the cursor backwards one word
 //! Alt-f, Alt-Right | Move the cursor forwards one word
+//!
+//! ## Multi-line input
+//!
+//! If `LineEditorHost::is_line_complete` returns false for the current
+//! input, then the accept line action inserts a newline at the cursor
+//! rather than accepting the line.  The line movement actions and the
+//! start/end of line actions operate on the logical line containing the
+//! cursor.
+//!
+//! ## Hints
+//!
+//! `LineEditorHost::hint` can suggest text to follow the input; it is
+//! rendered after the input using `LineEditorHost::render_hint` and can
+//! be accepted by moving the cursor forwards from the end of the input.
 use crate::caps::{Capabilities, ProbeHints};
+use crate::cell::unicode_column_width;
 use crate::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
 use crate::surface::change::ChangeSequence;
 use crate::surface::{Change, Position};
 use crate::terminal::{new_terminal, Terminal};
 use crate::{bail, ensure, Result};
-use unicode_segmentation::GraphemeCursor;
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
 
 mod actions;
 mod history;
@@ -210,15 +227,42 @@ impl<'term> LineEditor<'term> {
 
             (col, row)
         }
+
+        // The cursor x position is relative to the start of the logical
+        // line that contains the cursor, so figure out where that line
+        // starts by walking over the lines that precede it.
+        let mut line_start = cursor_position_after_printing_prompt;
+        if let Some(idx) = line_to_display[0..cursor].rfind('\n') {
+            for preceding_line in line_to_display[0..idx].split('\n') {
+                let (_, row) = compute_cursor_after_printing_x_columns(
+                    line_start.0,
+                    line_start.1,
+                    unicode_column_width(preceding_line),
+                    screen_size.cols,
+                );
+                line_start = (0, row + 1);
+            }
+        }
+
         let cursor_position = compute_cursor_after_printing_x_columns(
-            cursor_position_after_printing_prompt.0,
-            cursor_position_after_printing_prompt.1,
+            line_start.0,
+            line_start.1,
             cursor_x_pos,
             screen_size.cols,
         );
 
         for ele in elements {
-            changes.add(ele);
+            changes.add(newline_to_crlf(ele));
+        }
+
+        if let EditorState::Editing = &self.state {
+            if let Some(hint) = host.hint(line_to_display, cursor) {
+                changes.add(Change::AllAttributes(Default::default()));
+                for ele in host.render_hint(&hint) {
+                    changes.add(newline_to_crlf(ele));
+                }
+                changes.add(Change::AllAttributes(Default::default()));
+            }
         }
 
         let cursor_after_line_render = changes.current_cursor_position();
@@ -391,7 +435,13 @@ impl<'term> LineEditor<'term> {
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::ApplicationUpArrow,
                 modifiers: Modifiers::NONE,
-            }) => Some(Action::HistoryPrevious),
+            }) => {
+                if self.logical_line_start(self.cursor) > 0 {
+                    Some(Action::Move(Movement::BackwardLine(1)))
+                } else {
+                    Some(Action::HistoryPrevious)
+                }
+            }
 
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('N'),
@@ -404,7 +454,13 @@ impl<'term> LineEditor<'term> {
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::ApplicationDownArrow,
                 modifiers: Modifiers::NONE,
-            }) => Some(Action::HistoryNext),
+            }) => {
+                if self.logical_line_end(self.cursor) < self.line.len() {
+                    Some(Action::Move(Movement::ForwardLine(1)))
+                } else {
+                    Some(Action::HistoryNext)
+                }
+            }
 
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('B'),
@@ -598,20 +654,93 @@ impl<'term> LineEditor<'term> {
                 }
                 position
             }
-            Movement::StartOfLine => 0,
-            Movement::EndOfLine => {
-                let mut cursor =
-                    GraphemeCursor::new(self.line.len().saturating_sub(1), self.line.len(), false);
-                if let Ok(Some(pos)) = cursor.next_boundary(&self.line, 0) {
-                    pos
-                } else {
-                    self.cursor
+            Movement::BackwardLine(rep) => {
+                let column = self.column_of(self.cursor);
+                let mut line_start = self.logical_line_start(self.cursor);
+                for _ in 0..rep {
+                    if line_start == 0 {
+                        break;
+                    }
+                    line_start = self.logical_line_start(line_start - 1);
                 }
+                self.position_at_column(line_start, column)
             }
+            Movement::ForwardLine(rep) => {
+                let column = self.column_of(self.cursor);
+                let mut line_start = self.logical_line_start(self.cursor);
+                for _ in 0..rep {
+                    let line_end = self.logical_line_end(line_start);
+                    if line_end == self.line.len() {
+                        break;
+                    }
+                    line_start = line_end + 1;
+                }
+                self.position_at_column(line_start, column)
+            }
+            Movement::StartOfLine => self.logical_line_start(self.cursor),
+            Movement::EndOfLine => self.logical_line_end(self.cursor),
             Movement::None => self.cursor,
         }
     }
 
+    /// Returns the byte index of the start of the logical line
+    /// (delimited by `\n`) that contains the specified position
+    fn logical_line_start(&self, position: usize) -> usize {
+        self.line[0..position]
+            .rfind('\n')
+            .map(|idx| idx + 1)
+            .unwrap_or(0)
+    }
+
+    /// Returns the byte index of the end of the logical line
+    /// (delimited by `\n`) that contains the specified position.
+    /// The end is either the index of the `\n` or the length of the line.
+    fn logical_line_end(&self, position: usize) -> usize {
+        self.line[position..]
+            .find('\n')
+            .map(|idx| position + idx)
+            .unwrap_or_else(|| self.line.len())
+    }
+
+    /// Returns the column of the specified position relative to the
+    /// start of the logical line that contains it
+    fn column_of(&self, position: usize) -> usize {
+        unicode_column_width(&self.line[self.logical_line_start(position)..position])
+    }
+
+    /// Returns the byte index of the grapheme at the specified column
+    /// of the logical line that starts at `line_start`, clamping to the
+    /// end of that line if it is shorter than the column.
+    fn position_at_column(&self, line_start: usize, column: usize) -> usize {
+        let line_end = self.logical_line_end(line_start);
+        let mut x = 0;
+        for (idx, grapheme) in self.line[line_start..line_end].grapheme_indices(true) {
+            let width = unicode_column_width(grapheme);
+            if x + width > column {
+                return line_start + idx;
+            }
+            x += width;
+        }
+        line_end
+    }
+
+    /// If the cursor is at the end of the line and the host has a hint
+    /// for the line, append the hint to the line.
+    /// Returns true if a hint was accepted.
+    fn accept_hint(&mut self, host: &mut dyn LineEditorHost) -> bool {
+        if self.cursor != self.line.len() || self.state != EditorState::Editing {
+            return false;
+        }
+        match host.hint(&self.line, self.cursor) {
+            Some(hint) if !hint.is_empty() => {
+                self.line.push_str(&hint);
+                self.cursor = self.line.len();
+                true
+            }
+            _ => false,
+        }
+    }
+
     fn kill_text(&mut self, kill_movement: Movement, move_movement: Movement) {
         self.clear_completion();
         let kill_pos = self.eval_movement(kill_movement);
@@ -817,7 +946,13 @@ impl<'term> LineEditor<'term> {
                 // line to be accepted, rather than the search pattern!
                 self.cancel_search_state();
 
-                self.state = EditorState::Accepted;
+                if host.is_line_complete(&self.line) {
+                    self.state = EditorState::Accepted;
+                } else {
+                    self.clear_completion();
+                    self.line.insert(self.cursor, '\n');
+                    self.cursor += 1;
+                }
             }
             Action::EndOfFile => {
                 return Err(
@@ -836,7 +971,13 @@ impl<'term> LineEditor<'term> {
             Action::Move(movement) => {
                 self.clear_completion();
                 self.cancel_search_state();
-                self.cursor = self.eval_movement(movement);
+                let accepted_hint = match movement {
+                    Movement::ForwardChar(_) | Movement::EndOfLine => self.accept_hint(host),
+                    _ => false,
+                };
+                if !accepted_hint {
+                    self.cursor = self.eval_movement(movement);
+                }
             }
 
             Action::InsertChar(rep, c) => {
@@ -975,6 +1116,18 @@ impl<'term> LineEditor<'term> {
     }
 }
 
+/// Text elements may contain `\n` to separate the logical lines of
+/// multi-line input; the terminal needs `\r\n` to return the cursor
+/// to the start of the next line.
+fn newline_to_crlf(ele: OutputElement) -> OutputElement {
+    match ele {
+        OutputElement::Text(t) if t.contains('\n') => {
+            OutputElement::Text(t.replace("\r\n", "\n").replace('\n', "\r\n"))
+        }
+        ele => ele,
+    }
+}
+
 /// Create a `Terminal` with the recommended settings for use with
 /// a `LineEditor`.
 pub fn line_editor_terminal() -> Result<impl Terminal> {
@@ -982,3 +1135,168 @@ pub fn line_editor_terminal() -> Result<impl Terminal> {
     let caps = Capabilities::new_with_hints(hints)?;
     new_terminal(caps)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::terminal::{ScreenSize, TerminalWaker};
+    use std::time::Duration;
+
+    struct FakeTerm;
+
+    impl Terminal for FakeTerm {
+        fn set_raw_mode(&mut self) -> Result<()> {
+            bail!("not implemented");
+        }
+
+        fn set_cooked_mode(&mut self) -> Result<()> {
+            bail!("not implemented");
+        }
+
+        fn enter_alternate_screen(&mut self) -> Result<()> {
+            bail!("not implemented");
+        }
+
+        fn exit_alternate_screen(&mut self) -> Result<()> {
+            bail!("not implemented");
+        }
+
+        fn get_screen_size(&mut self) -> Result<ScreenSize> {
+            Ok(ScreenSize {
+                rows: 24,
+                cols: 80,
+                xpixel: 0,
+                ypixel: 0,
+            })
+        }
+
+        fn set_screen_size(&mut self, _size: ScreenSize) -> Result<()> {
+            bail!("not implemented");
+        }
+
+        fn render(&mut self, _changes: &[Change]) -> Result<()> {
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn poll_input(&mut self, _wait: Option<Duration>) -> Result<Option<InputEvent>> {
+            bail!("not implemented");
+        }
+
+        fn waker(&self) -> TerminalWaker {
+            unimplemented!();
+        }
+    }
+
+    struct HintHost {
+        history: BasicHistory,
+        hint: &'static str,
+    }
+
+    impl LineEditorHost for HintHost {
+        fn history(&mut self) -> &mut dyn History {
+            &mut self.history
+        }
+
+        fn hint(&self, _line: &str, _cursor_position: usize) -> Option<String> {
+            Some(self.hint.to_string())
+        }
+    }
+
+    #[test]
+    fn logical_lines() {
+        let mut term = FakeTerm;
+        let mut editor = LineEditor::new(&mut term);
+        editor.set_line_and_cursor("one\ntwo\n\nthree", 0);
+
+        assert_eq!(editor.logical_line_start(0), 0);
+        assert_eq!(editor.logical_line_end(0), 3);
+        // The newline belongs to the line that it terminates
+        assert_eq!(editor.logical_line_start(3), 0);
+        assert_eq!(editor.logical_line_end(3), 3);
+        assert_eq!(editor.logical_line_start(6), 4);
+        assert_eq!(editor.logical_line_end(6), 7);
+        // An empty line
+        assert_eq!(editor.logical_line_start(8), 8);
+        assert_eq!(editor.logical_line_end(8), 8);
+        // The last line ends at the end of the input
+        assert_eq!(editor.logical_line_start(14), 9);
+        assert_eq!(editor.logical_line_end(14), 14);
+    }
+
+    #[test]
+    fn column_positions() {
+        let mut term = FakeTerm;
+        let mut editor = LineEditor::new(&mut term);
+        editor.set_line_and_cursor("héllo\n日本語\nab", 0);
+
+        assert_eq!(editor.position_at_column(0, 0), 0);
+        // The é is two bytes wide in UTF-8 but occupies a single cell
+        assert_eq!(editor.position_at_column(0, 2), 3);
+        // Columns beyond the end of the line clamp to its end
+        assert_eq!(editor.position_at_column(0, 40), 6);
+
+        // Each of these occupies two cells and three bytes
+        assert_eq!(editor.position_at_column(7, 2), 10);
+        // A column in the middle of a double width character
+        // selects that character
+        assert_eq!(editor.position_at_column(7, 3), 10);
+        assert_eq!(editor.position_at_column(7, 6), 16);
+
+        assert_eq!(editor.position_at_column(17, 1), 18);
+        assert_eq!(editor.position_at_column(17, 5), 19);
+    }
+
+    #[test]
+    fn accepting_hints() {
+        let mut term = FakeTerm;
+        let mut editor = LineEditor::new(&mut term);
+        let mut host = HintHost {
+            history: BasicHistory::default(),
+            hint: "lo",
+        };
+
+        // Hints are only accepted while editing
+        editor.line = "hel".to_string();
+        editor.cursor = 3;
+        assert!(!editor.accept_hint(&mut host));
+
+        editor.state = EditorState::Editing;
+        assert!(editor.accept_hint(&mut host));
+        assert_eq!(editor.get_line_and_cursor(), ("hello", 5));
+
+        // Nor when the cursor isn't at the end of the line
+        editor.set_line_and_cursor("hel", 1);
+        assert!(!editor.accept_hint(&mut host));
+        assert_eq!(editor.get_line_and_cursor(), ("hel", 1));
+
+        // An empty hint is not accepted
+        host.hint = "";
+        editor.cursor = 3;
+        assert!(!editor.accept_hint(&mut host));
+        assert_eq!(editor.get_line_and_cursor(), ("hel", 3));
+    }
+
+    #[test]
+    fn newlines_become_crlf() {
+        fn convert(ele: OutputElement) -> Change {
+            newline_to_crlf(ele).into()
+        }
+
+        assert_eq!(
+            convert(OutputElement::Text("one\ntwo\r\nthree".to_string())),
+            Change::Text("one\r\ntwo\r\nthree".to_string())
+        );
+        assert_eq!(
+            convert(OutputElement::Text("one".to_string())),
+            Change::Text("one".to_string())
+        );
+        assert_eq!(
+            convert(OutputElement::AllAttributes(Default::default())),
+            Change::AllAttributes(Default::default())
+        );
+    }
+}
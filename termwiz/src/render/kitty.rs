@@ -0,0 +1,219 @@
+//! Output of images using the kitty graphics protocol.
+//! Image data is transmitted to the terminal once and associated with
+//! an id, and can then be placed on the screen any number of times by
+//! referencing that id.
+//! See <https://sw.kovidgoyal.net/kitty/graphics-protocol/>
+use std::io::Write;
+
+/// The maximum size of a base64 encoded chunk of image data
+/// that may be sent in a single escape sequence
+const CHUNK_SIZE: usize = 4096;
+
+/// Describes the encoding of transmitted image data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KittyImageFormat {
+    /// The data is a PNG file
+    Png,
+    /// The data is 4 bytes per pixel of RGBA, row major
+    Rgba { width: u32, height: u32 },
+}
+
+/// Describes where to place a previously transmitted image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KittyPlacement {
+    /// The id that was used to transmit the image
+    pub image_id: u32,
+    /// Identifies the placement of the image.  Placing the same
+    /// image again with the same placement id replaces the earlier
+    /// placement rather than adding another.
+    pub placement_id: Option<u32>,
+    /// The number of cells that the placement spans horizontally
+    pub columns: u32,
+    /// The number of cells that the placement spans vertically
+    pub rows: u32,
+    /// The region of the image to display as (x, y, width, height)
+    /// measured in pixels.  `None` displays the whole image.
+    pub source: Option<(u32, u32, u32, u32)>,
+}
+
+/// Describes the image placements to be deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KittyDelete {
+    /// Delete all placements that are visible on the screen
+    AllVisible,
+    /// Delete a single placement of an image
+    Placement { image_id: u32, placement_id: u32 },
+    /// Delete all placements of an image and free its data
+    Image { image_id: u32 },
+}
+
+/// Emit the graphics command with the specified control data and payload
+fn command<W: Write>(out: &mut W, control: &str, payload: &str) -> std::io::Result<()> {
+    if payload.is_empty() {
+        write!(out, "\x1b_G{}\x1b\\", control)
+    } else {
+        write!(out, "\x1b_G{};{}\x1b\\", control, payload)
+    }
+}
+
+/// Transmit image data to the terminal and associate it with `image_id`
+/// without displaying it.
+pub fn transmit<W: Write>(
+    out: &mut W,
+    image_id: u32,
+    format: KittyImageFormat,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let format = match format {
+        KittyImageFormat::Png => "f=100".to_string(),
+        KittyImageFormat::Rgba { width, height } => format!("f=32,s={},v={}", width, height),
+    };
+    let encoded = base64::encode(data);
+    // The chunks are ASCII, so we can safely slice on any byte boundary
+    let chunks: Vec<&str> = encoded
+        .as_bytes()
+        .chunks(CHUNK_SIZE)
+        .map(|c| std::str::from_utf8(c).expect("base64 is ascii"))
+        .collect();
+
+    if chunks.is_empty() {
+        return command(out, &format!("a=t,q=2,i={},{}", image_id, format), "");
+    }
+
+    for (idx, chunk) in chunks.iter().enumerate() {
+        let more = if idx + 1 < chunks.len() { 1 } else { 0 };
+        if idx == 0 {
+            // Suppress responses with q=2 so that they don't
+            // show up as input to the application
+            command(
+                out,
+                &format!("a=t,q=2,i={},{},m={}", image_id, format, more),
+                chunk,
+            )?;
+        } else {
+            command(out, &format!("m={}", more), chunk)?;
+        }
+    }
+    Ok(())
+}
+
+/// Display a previously transmitted image at the cursor position.
+/// The cursor is not moved.
+pub fn place<W: Write>(out: &mut W, placement: &KittyPlacement) -> std::io::Result<()> {
+    let mut control = format!("a=p,q=2,C=1,i={}", placement.image_id);
+    if let Some(placement_id) = placement.placement_id {
+        control.push_str(&format!(",p={}", placement_id));
+    }
+    control.push_str(&format!(",c={},r={}", placement.columns, placement.rows));
+    if let Some((x, y, w, h)) = placement.source {
+        control.push_str(&format!(",x={},y={},w={},h={}", x, y, w, h));
+    }
+    command(out, &control, "")
+}
+
+/// Delete image placements, and possibly image data
+pub fn delete<W: Write>(out: &mut W, what: KittyDelete) -> std::io::Result<()> {
+    let control = match what {
+        KittyDelete::AllVisible => "a=d,q=2,d=a".to_string(),
+        KittyDelete::Placement {
+            image_id,
+            placement_id,
+        } => format!("a=d,q=2,d=i,i={},p={}", image_id, placement_id),
+        KittyDelete::Image { image_id } => format!("a=d,q=2,d=I,i={}", image_id),
+    };
+    command(out, &control, "")
+}
+
+/// If `data` is a PNG file, returns its (width, height) in pixels
+pub fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    // The IHDR chunk is required to be first; it starts with
+    // the length and type, followed by the width and height
+    if data.len() < 24 || !data.starts_with(SIGNATURE) || &data[12..16] != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes([data[16], data[17], data[18], data[19]]);
+    let height = u32::from_be_bytes([data[20], data[21], data[22], data[23]]);
+    Some((width, height))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn transmit_chunks() {
+        let mut out = vec![];
+        transmit(
+            &mut out,
+            1,
+            KittyImageFormat::Rgba {
+                width: 1,
+                height: 1,
+            },
+            &[255, 0, 0, 255],
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1b_Ga=t,q=2,i=1,f=32,s=1,v=1,m=0;/wAA/w==\x1b\\"
+        );
+
+        let mut out = vec![];
+        let data = vec![0u8; CHUNK_SIZE];
+        transmit(&mut out, 2, KittyImageFormat::Png, &data).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let commands: Vec<&str> = out.split("\x1b\\").filter(|s| !s.is_empty()).collect();
+        assert_eq!(commands.len(), 2);
+        assert!(commands[0].starts_with("\x1b_Ga=t,q=2,i=2,f=100,m=1;"));
+        assert!(commands[1].starts_with("\x1b_Gm=0;"));
+    }
+
+    #[test]
+    fn placement() {
+        let mut out = vec![];
+        place(
+            &mut out,
+            &KittyPlacement {
+                image_id: 3,
+                placement_id: Some(5),
+                columns: 4,
+                rows: 2,
+                source: Some((10, 20, 30, 40)),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1b_Ga=p,q=2,C=1,i=3,p=5,c=4,r=2,x=10,y=20,w=30,h=40\x1b\\"
+        );
+    }
+
+    #[test]
+    fn deletion() {
+        let mut out = vec![];
+        delete(&mut out, KittyDelete::AllVisible).unwrap();
+        delete(
+            &mut out,
+            KittyDelete::Placement {
+                image_id: 3,
+                placement_id: 5,
+            },
+        )
+        .unwrap();
+        delete(&mut out, KittyDelete::Image { image_id: 3 }).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1b_Ga=d,q=2,d=a\x1b\\\x1b_Ga=d,q=2,d=i,i=3,p=5\x1b\\\x1b_Ga=d,q=2,d=I,i=3\x1b\\"
+        );
+    }
+
+    #[test]
+    fn png_size() {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend_from_slice(&640u32.to_be_bytes());
+        data.extend_from_slice(&480u32.to_be_bytes());
+        assert_eq!(png_dimensions(&data), Some((640, 480)));
+        assert_eq!(png_dimensions(b"GIF89a"), None);
+    }
+}
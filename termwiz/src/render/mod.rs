@@ -1,3 +1,5 @@
+pub mod kitty;
+pub mod sixel;
 pub mod terminfo;
 #[cfg(windows)]
 pub mod windows;
@@ -5,4 +7,11 @@ pub mod windows;
 pub trait RenderTty: std::io::Write {
     /// Returns the (cols, rows) for the terminal
     fn get_size_in_cells(&mut self) -> crate::Result<(usize, usize)>;
+
+    /// Returns the (width, height) of a cell in pixels, if known.
+    /// This is used to size image data for protocols that are
+    /// expressed in pixels rather than cells.
+    fn get_cell_size_in_pixels(&mut self) -> crate::Result<Option<(usize, usize)>> {
+        Ok(None)
+    }
 }
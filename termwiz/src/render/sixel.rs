@@ -0,0 +1,370 @@
+//! Encoding of RGBA pixel data as sixel graphics.
+//! Sixel images are limited to a palette of colors, so the pixel data
+//! is quantized to a palette using the median cut algorithm and then
+//! mapped onto that palette using Floyd-Steinberg error diffusion.
+//! See <https://vt100.net/docs/vt3xx-gp/chapter14.html>
+use crate::color::RgbColor;
+use crate::escape::{Sixel, SixelData};
+use std::collections::HashMap;
+
+/// The maximum number of palette entries that are used by default.
+/// This is the number of color registers that xterm provides.
+pub const DEFAULT_MAX_COLORS: usize = 256;
+
+/// Pixels with an alpha value below this are treated as transparent
+const ALPHA_THRESHOLD: u8 = 128;
+
+type Rgb = [u8; 3];
+
+/// Encode `width` x `height` pixels of RGBA data (4 bytes per pixel,
+/// row major) as a sixel image that uses at most `max_colors` palette
+/// entries.  Transparent pixels are left unpainted.
+pub fn encode_sixel(width: u32, height: u32, rgba: &[u8], max_colors: usize) -> Sixel {
+    let num_pixels = width as usize * height as usize;
+    assert!(
+        rgba.len() >= num_pixels * 4,
+        "rgba data is too short for {}x{} pixels",
+        width,
+        height
+    );
+    let pixels = &rgba[0..num_pixels * 4];
+
+    let mut histogram: HashMap<Rgb, u32> = HashMap::new();
+    let mut background_is_transparent = false;
+    for px in pixels.chunks_exact(4) {
+        if px[3] < ALPHA_THRESHOLD {
+            background_is_transparent = true;
+        } else {
+            *histogram.entry([px[0], px[1], px[2]]).or_insert(0) += 1;
+        }
+    }
+
+    let palette = median_cut(histogram, max_colors.clamp(1, DEFAULT_MAX_COLORS));
+    let indices = dither(width as usize, height as usize, pixels, &palette);
+
+    let mut data = vec![];
+    for (color_number, rgb) in palette.iter().enumerate() {
+        data.push(SixelData::DefineColorMapRGB {
+            color_number: color_number as u16,
+            rgb: RgbColor::new_8bpc(rgb[0], rgb[1], rgb[2]),
+        });
+    }
+
+    let width = width as usize;
+    let height = height as usize;
+    let mut band_top = 0;
+    while band_top < height {
+        let band_bottom = (band_top + 6).min(height);
+
+        // Determine which colors are used in this band, preserving
+        // palette order so that the output is deterministic
+        let mut used = vec![false; palette.len()];
+        for y in band_top..band_bottom {
+            for idx in indices[y * width..(y + 1) * width].iter().flatten() {
+                used[*idx as usize] = true;
+            }
+        }
+
+        let mut first = true;
+        for (color_number, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+            if !first {
+                data.push(SixelData::CarriageReturn);
+            }
+            first = false;
+            data.push(SixelData::SelectColorMapEntry(color_number as u16));
+
+            let mut row: Vec<u8> = (0..width)
+                .map(|x| {
+                    let mut sixel = 0u8;
+                    for y in band_top..band_bottom {
+                        if indices[y * width + x] == Some(color_number as u8) {
+                            sixel |= 1 << (y - band_top);
+                        }
+                    }
+                    sixel
+                })
+                .collect();
+            // Trailing empty sixels have no effect, so don't emit them
+            while row.last() == Some(&0) {
+                row.pop();
+            }
+            push_run_length_encoded(&mut data, &row);
+        }
+
+        band_top = band_bottom;
+        if band_top < height {
+            data.push(SixelData::NewLine);
+        }
+    }
+
+    Sixel {
+        pan: 1,
+        pad: 1,
+        pixel_width: Some(width as u32),
+        pixel_height: Some(height as u32),
+        background_is_transparent,
+        horizontal_grid_size: None,
+        data,
+    }
+}
+
+fn push_run_length_encoded(data: &mut Vec<SixelData>, row: &[u8]) {
+    let mut x = 0;
+    while x < row.len() {
+        let value = row[x];
+        let mut run = 1;
+        while x + run < row.len() && row[x + run] == value {
+            run += 1;
+        }
+        // The repeat introducer costs more than repeating a short run
+        if run > 3 {
+            data.push(SixelData::Repeat {
+                repeat_count: run as u32,
+                data: value,
+            });
+        } else {
+            for _ in 0..run {
+                data.push(SixelData::Data(value));
+            }
+        }
+        x += run;
+    }
+}
+
+/// A set of distinct colors and their pixel counts that is
+/// subdivided by the median cut algorithm
+struct ColorBox {
+    colors: Vec<(Rgb, u32)>,
+}
+
+impl ColorBox {
+    /// Returns the channel with the largest range of values
+    /// and the size of that range
+    fn widest_channel(&self) -> (usize, u8) {
+        let mut best = (0, 0);
+        for channel in 0..3 {
+            let min = self
+                .colors
+                .iter()
+                .map(|(c, _)| c[channel])
+                .min()
+                .unwrap_or(0);
+            let max = self
+                .colors
+                .iter()
+                .map(|(c, _)| c[channel])
+                .max()
+                .unwrap_or(0);
+            if max - min > best.1 {
+                best = (channel, max - min);
+            }
+        }
+        best
+    }
+
+    /// Splits the box at the weighted median of its widest channel
+    fn split(mut self) -> (Self, Self) {
+        let (channel, _) = self.widest_channel();
+        self.colors.sort_by_key(|(c, _)| c[channel]);
+        let total: u64 = self.colors.iter().map(|(_, n)| *n as u64).sum();
+        let mut acc = 0u64;
+        let mut split_at = self.colors.len() / 2;
+        for (i, (_, n)) in self.colors.iter().enumerate() {
+            acc += *n as u64;
+            if acc * 2 >= total {
+                split_at = i + 1;
+                break;
+            }
+        }
+        let split_at = split_at.max(1).min(self.colors.len() - 1);
+        let upper = self.colors.split_off(split_at);
+        (self, Self { colors: upper })
+    }
+
+    /// Returns the weighted average color of the box
+    fn average(&self) -> Rgb {
+        let mut sums = [0u64; 3];
+        let mut total = 0u64;
+        for (c, n) in &self.colors {
+            for channel in 0..3 {
+                sums[channel] += c[channel] as u64 * *n as u64;
+            }
+            total += *n as u64;
+        }
+        let total = total.max(1);
+        [
+            ((sums[0] + total / 2) / total) as u8,
+            ((sums[1] + total / 2) / total) as u8,
+            ((sums[2] + total / 2) / total) as u8,
+        ]
+    }
+}
+
+/// Reduce the colors in the histogram to a palette of at most
+/// `max_colors` entries
+fn median_cut(histogram: HashMap<Rgb, u32>, max_colors: usize) -> Vec<Rgb> {
+    let mut colors: Vec<(Rgb, u32)> = histogram.into_iter().collect();
+    if colors.len() <= max_colors {
+        colors.sort();
+        return colors.into_iter().map(|(c, _)| c).collect();
+    }
+
+    let mut boxes = vec![ColorBox { colors }];
+    while boxes.len() < max_colors {
+        let candidate = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.colors.len() > 1)
+            .max_by_key(|(_, b)| b.widest_channel().1)
+            .map(|(idx, _)| idx);
+        let idx = match candidate {
+            Some(idx) => idx,
+            None => break,
+        };
+        let (a, b) = boxes.swap_remove(idx).split();
+        boxes.push(a);
+        boxes.push(b);
+    }
+
+    let mut palette: Vec<Rgb> = boxes.iter().map(ColorBox::average).collect();
+    palette.sort();
+    palette.dedup();
+    palette
+}
+
+fn nearest_color(palette: &[Rgb], color: Rgb) -> u8 {
+    let mut best = (0, u32::MAX);
+    for (idx, candidate) in palette.iter().enumerate() {
+        let dist: u32 = (0..3)
+            .map(|c| {
+                let d = candidate[c] as i32 - color[c] as i32;
+                (d * d) as u32
+            })
+            .sum();
+        if dist < best.1 {
+            best = (idx, dist);
+        }
+    }
+    best.0 as u8
+}
+
+/// Map each pixel to its palette index using Floyd-Steinberg error
+/// diffusion.  Transparent pixels map to `None`.
+fn dither(width: usize, height: usize, pixels: &[u8], palette: &[Rgb]) -> Vec<Option<u8>> {
+    let mut work: Vec<[i32; 3]> = pixels
+        .chunks_exact(4)
+        .map(|px| [px[0] as i32, px[1] as i32, px[2] as i32])
+        .collect();
+    let opaque: Vec<bool> = pixels
+        .chunks_exact(4)
+        .map(|px| px[3] >= ALPHA_THRESHOLD)
+        .collect();
+    let mut cache: HashMap<Rgb, u8> = HashMap::new();
+    let mut indices = vec![None; width * height];
+
+    for y in 0..height {
+        for x in 0..width {
+            let offset = y * width + x;
+            if !opaque[offset] {
+                continue;
+            }
+            let wanted = work[offset];
+            let clamped = [
+                wanted[0].clamp(0, 255) as u8,
+                wanted[1].clamp(0, 255) as u8,
+                wanted[2].clamp(0, 255) as u8,
+            ];
+            let idx = *cache
+                .entry(clamped)
+                .or_insert_with(|| nearest_color(palette, clamped));
+            indices[offset] = Some(idx);
+
+            let chosen = palette[idx as usize];
+            let error = [
+                clamped[0] as i32 - chosen[0] as i32,
+                clamped[1] as i32 - chosen[1] as i32,
+                clamped[2] as i32 - chosen[2] as i32,
+            ];
+            if error == [0, 0, 0] {
+                continue;
+            }
+
+            let mut diffuse = |dx: isize, dy: usize, factor: i32| {
+                let nx = x as isize + dx;
+                let ny = y + dy;
+                if nx < 0 || nx as usize >= width || ny >= height {
+                    return;
+                }
+                let target = ny * width + nx as usize;
+                if !opaque[target] {
+                    return;
+                }
+                for c in 0..3 {
+                    work[target][c] += error[c] * factor / 16;
+                }
+            };
+            diffuse(1, 0, 7);
+            diffuse(-1, 1, 3);
+            diffuse(0, 1, 5);
+            diffuse(1, 1, 1);
+        }
+    }
+
+    indices
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn solid(width: u32, height: u32, rgba: [u8; 4]) -> Vec<u8> {
+        (0..width * height).flat_map(|_| rgba.to_vec()).collect()
+    }
+
+    #[test]
+    fn solid_color() {
+        let pixels = solid(10, 6, [255, 0, 0, 255]);
+        let sixel = encode_sixel(10, 6, &pixels, DEFAULT_MAX_COLORS);
+        assert_eq!(sixel.to_string(), "\x1bP;0q\"1;1;10;6#0;2;100;0;0#0!10~");
+        assert_eq!(sixel.dimensions(), (10, 6));
+    }
+
+    #[test]
+    fn bands_and_colors() {
+        // Two columns: left black, right white, 7 rows tall so
+        // that the image spans two sixel bands
+        let mut pixels = vec![];
+        for _ in 0..7 {
+            pixels.extend_from_slice(&[0, 0, 0, 255, 255, 255, 255, 255]);
+        }
+        let sixel = encode_sixel(2, 7, &pixels, DEFAULT_MAX_COLORS);
+        assert_eq!(
+            sixel.to_string(),
+            "\x1bP;0q\"1;1;2;7#0;2;0;0;0#1;2;100;100;100#0~$#1?~-#0@$#1?@"
+        );
+    }
+
+    #[test]
+    fn transparency() {
+        let mut pixels = solid(3, 1, [0, 0, 255, 255]);
+        pixels[7] = 0;
+        let sixel = encode_sixel(3, 1, &pixels, DEFAULT_MAX_COLORS);
+        assert!(sixel.background_is_transparent);
+        assert_eq!(sixel.to_string(), "\x1bP;1q\"1;1;3;1#0;2;0;0;100#0@?@");
+    }
+
+    #[test]
+    fn quantize() {
+        // A gradient with more colors than the palette allows
+        let pixels: Vec<u8> = (0..=255u8).flat_map(|v| vec![v, v, v, 255]).collect();
+        let sixel = encode_sixel(256, 1, &pixels, 16);
+        let num_colors = sixel
+            .data
+            .iter()
+            .filter(|d| matches!(d, SixelData::DefineColorMapRGB { .. }))
+            .count();
+        assert!(num_colors <= 16, "{} colors", num_colors);
+        assert!(num_colors > 1, "{} colors", num_colors);
+        assert_eq!(sixel.dimensions(), (256, 1));
+    }
+}
@@ -1,21 +1,131 @@
 //! Rendering of Changes using terminfo
 use crate::caps::{Capabilities, ColorLevel};
-use crate::cell::{AttributeChange, Blink, CellAttributes, Intensity, Underline};
+use crate::cell::{
+    unicode_column_width, AttributeChange, Blink, CellAttributes, Intensity, Underline,
+};
 use crate::color::{ColorAttribute, ColorSpec};
 use crate::escape::csi::{Cursor, Edit, EraseInDisplay, EraseInLine, Sgr, CSI};
 use crate::escape::osc::{ITermDimension, ITermFileData, ITermProprietary, OperatingSystemCommand};
 use crate::escape::OneBased;
 use crate::image::TextureCoordinate;
 use crate::render::RenderTty;
-use crate::surface::{Change, CursorShape, CursorVisibility, Position};
+use crate::surface::{Change, CursorShape, CursorVisibility, Image, Position};
 use crate::Result;
+use std::collections::HashMap;
 use std::io::Write;
+use std::ops::Range;
 use terminfo::{capability as cap, Capability as TermInfoCapability};
 
+/// Transmit image data using the kitty graphics protocol.
+/// PNG data can be passed through as-is, but other formats need
+/// to be decoded first.
+/// Returns the pixel dimensions of the image, or None if the data
+/// could not be transmitted.
+fn kitty_transmit<W: Write>(image_id: u32, data: &[u8], out: &mut W) -> Result<Option<(u32, u32)>> {
+    use crate::render::kitty::{png_dimensions, transmit, KittyImageFormat};
+
+    if let Some(dims) = png_dimensions(data) {
+        transmit(out, image_id, KittyImageFormat::Png, data)?;
+        return Ok(Some(dims));
+    }
+
+    #[cfg(feature = "use_image")]
+    {
+        match ::image::load_from_memory(data) {
+            Ok(decoded) => {
+                let decoded = decoded.to_rgba8();
+                let (width, height) = decoded.dimensions();
+                transmit(
+                    out,
+                    image_id,
+                    KittyImageFormat::Rgba { width, height },
+                    decoded.as_raw(),
+                )?;
+                return Ok(Some((width, height)));
+            }
+            Err(err) => {
+                log::error!(
+                    "unable to decode image for kitty graphics output: {:#}",
+                    err
+                );
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// The number of bytes of decoded image data that we allow to be held
+/// by the terminal for images transmitted using the kitty graphics
+/// protocol.  The terminal silently evicts images once its own quota
+/// is exceeded (320MB by default in kitty), after which placing them
+/// by id would display nothing, so we stay well below that and free
+/// the least recently used images ourselves.
+const KITTY_IMAGE_STORAGE_LIMIT: usize = 64 * 1024 * 1024;
+
+/// An image that has been transmitted using the kitty graphics protocol
+struct KittyImage {
+    /// The id by which the terminal knows the image
+    image_id: u32,
+    /// The (width, height) of the image in pixels
+    dims: (u32, u32),
+    /// The generation in which the image was last placed
+    last_used: u64,
+}
+
+impl KittyImage {
+    /// The number of bytes that the terminal needs to hold the image
+    fn storage_size(&self) -> usize {
+        self.dims.0 as usize * self.dims.1 as usize * 4
+    }
+}
+
+/// A kitty image placement made at a known position on the screen
+struct KittyPlaced {
+    image_id: u32,
+    placement_id: u32,
+    x: usize,
+    y: usize,
+    columns: usize,
+    rows: usize,
+}
+
+impl KittyPlaced {
+    fn intersects(&self, x: &Range<usize>, y: &Range<usize>) -> bool {
+        self.x < x.end
+            && x.start < self.x + self.columns
+            && self.y < y.end
+            && y.start < self.y + self.rows
+    }
+}
+
+/// Returns the placement id used for an image placed at `x`, `y`.
+/// Deriving it from the position means that placing an image again at
+/// the same position replaces the earlier placement.
+fn kitty_placement_id(x: usize, y: usize) -> u32 {
+    (((y & 0x7fff) << 16) | (x & 0xffff)) as u32 + 1
+}
+
+/// The (width, height) of a cell in pixels that is assumed when rendering
+/// images in pixel based formats and the terminal doesn't report its size.
+#[cfg(feature = "use_image")]
+const FALLBACK_CELL_SIZE_IN_PIXELS: (usize, usize) = (8, 16);
+
 pub struct TerminfoRenderer {
     caps: Capabilities,
     current_attr: CellAttributes,
     pending_attr: Option<CellAttributes>,
+    /// Images that have been transmitted using the kitty graphics
+    /// protocol, keyed by the id of their `ImageData`
+    kitty_images: HashMap<usize, KittyImage>,
+    /// Incremented each time that a kitty image is placed
+    kitty_generation: u64,
+    /// The kitty image placements that are believed to be on screen
+    kitty_placements: Vec<KittyPlaced>,
+    /// The cursor position, if it is known.  This is only tracked
+    /// when using the kitty graphics protocol, so that placements can
+    /// be given a stable id and deleted when their cells are cleared.
+    cursor: Option<(usize, usize)>,
     /* TODO: we should record cursor position, shape and color here
      * so that we can optimize updating them on screen. */
 }
@@ -26,6 +136,10 @@ impl TerminfoRenderer {
             caps,
             current_attr: CellAttributes::default(),
             pending_attr: None,
+            kitty_images: HashMap::new(),
+            kitty_generation: 0,
+            kitty_placements: vec![],
+            cursor: None,
         }
     }
 
@@ -267,6 +381,319 @@ impl TerminfoRenderer {
         Ok(())
     }
 
+    /// Delete the tracked kitty placements that intersect the
+    /// specified region of the screen
+    fn kitty_delete_placements<W: RenderTty + Write>(
+        &mut self,
+        x: Range<usize>,
+        y: Range<usize>,
+        out: &mut W,
+    ) -> Result<()> {
+        use crate::render::kitty::{delete, KittyDelete};
+
+        let mut result = Ok(());
+        self.kitty_placements.retain(|placed| {
+            if result.is_err() || !placed.intersects(&x, &y) {
+                return true;
+            }
+            result = delete(
+                out,
+                KittyDelete::Placement {
+                    image_id: placed.image_id,
+                    placement_id: placed.placement_id,
+                },
+            );
+            false
+        });
+        Ok(result?)
+    }
+
+    /// Adjust the tracked kitty placements after the rows in
+    /// `first_row..first_row + region_size` scrolled up by `count`
+    /// rows, or down when `count` is negative.
+    /// Placements that were not entirely within the region, or that
+    /// scrolled out of it, are no longer tracked.
+    fn kitty_scrolled(&mut self, first_row: usize, region_size: usize, count: isize) {
+        let region = first_row as isize..(first_row + region_size) as isize;
+        self.kitty_placements.retain(|placed| {
+            let top = placed.y as isize;
+            let bottom = top + placed.rows as isize;
+            bottom <= region.start
+                || top >= region.end
+                || (top - count >= region.start
+                    && bottom - count <= region.end
+                    && top >= region.start
+                    && bottom <= region.end)
+        });
+        for placed in &mut self.kitty_placements {
+            let top = placed.y as isize;
+            if top >= region.start && top < region.end {
+                placed.y = (top - count) as usize;
+            }
+        }
+    }
+
+    /// Update the tracked cursor position for a `Change::CursorPosition`
+    fn kitty_track_cursor_position<W: RenderTty + Write>(
+        &mut self,
+        x: &Position,
+        y: &Position,
+        out: &mut W,
+    ) -> Result<()> {
+        let (cols, rows) = out.get_size_in_cells()?;
+
+        if let (Position::Absolute(0), Position::Relative(1), Some((_, cursor_y))) =
+            (x, y, self.cursor)
+        {
+            if cursor_y + 1 >= rows {
+                // This is rendered as a line feed, which scrolls the screen
+                self.kitty_scrolled(0, rows, 1);
+            }
+        }
+
+        let resolve = |pos: &Position, current: Option<usize>, size: usize| {
+            let pos = match pos {
+                Position::Absolute(n) => *n as isize,
+                Position::Relative(n) => current? as isize + *n,
+                Position::EndRelative(n) => size as isize - 1 - *n as isize,
+            };
+            Some(pos.max(0).min(size.saturating_sub(1) as isize) as usize)
+        };
+        self.cursor = match (
+            resolve(x, self.cursor.map(|(x, _)| x), cols),
+            resolve(y, self.cursor.map(|(_, y)| y), rows),
+        ) {
+            (Some(x), Some(y)) => Some((x, y)),
+            _ => None,
+        };
+        Ok(())
+    }
+
+    /// Update the tracked cursor position for a `Change::Text`,
+    /// deleting the kitty placements that the text is written over
+    fn kitty_track_text<W: RenderTty + Write>(&mut self, text: &str, out: &mut W) -> Result<()> {
+        if text.chars().any(char::is_control) {
+            // We can't tell where the cursor ends up, or whether
+            // the screen scrolled
+            self.cursor = None;
+            self.kitty_placements.clear();
+            return Ok(());
+        }
+        if let Some((x, y)) = self.cursor {
+            let width = unicode_column_width(text);
+            self.kitty_delete_placements(x..x + width, y..y + 1, out)?;
+            let (cols, _rows) = out.get_size_in_cells()?;
+            // The cursor position after writing into the last column
+            // depends on the terminal's wrapping behavior
+            self.cursor = if x + width < cols {
+                Some((x + width, y))
+            } else {
+                None
+            };
+        }
+        Ok(())
+    }
+
+    /// Render the image using the kitty graphics protocol.
+    /// The image data is transmitted the first time that it is seen,
+    /// and subsequent renders place it again by its id.
+    /// When the cursor position is known, the placement id is derived
+    /// from it so that re-rendering the image at the same position
+    /// replaces the earlier placement, and any other placements that
+    /// it overlaps are deleted.
+    /// Returns false if the image could not be rendered that way, in
+    /// which case the caller should fall back to some other method.
+    fn render_kitty_image<W: RenderTty + Write>(
+        &mut self,
+        image: &Image,
+        out: &mut W,
+    ) -> Result<bool> {
+        use crate::render::kitty::{delete, place, KittyDelete, KittyPlacement};
+
+        let data_id = image.image.id();
+        // Kitty image ids are non-zero 32-bit numbers
+        let image_id = (data_id % u32::MAX as usize) as u32 + 1;
+
+        self.kitty_generation += 1;
+        let (image_width, image_height) = match self.kitty_images.get_mut(&data_id) {
+            Some(transmitted) => {
+                transmitted.last_used = self.kitty_generation;
+                transmitted.dims
+            }
+            None => match kitty_transmit(image_id, image.image.data(), out)? {
+                Some(dims) => {
+                    self.kitty_images.insert(
+                        data_id,
+                        KittyImage {
+                            image_id,
+                            dims,
+                            last_used: self.kitty_generation,
+                        },
+                    );
+                    dims
+                }
+                None => return Ok(false),
+            },
+        };
+
+        // Free the least recently used images until we're within the
+        // storage limit.  The image that we're about to place is the
+        // most recently used and so is never freed here.
+        while self.kitty_images.len() > 1
+            && self
+                .kitty_images
+                .values()
+                .map(KittyImage::storage_size)
+                .sum::<usize>()
+                > KITTY_IMAGE_STORAGE_LIMIT
+        {
+            let lru = match self
+                .kitty_images
+                .iter()
+                .min_by_key(|(_, transmitted)| transmitted.last_used)
+            {
+                Some((lru, _)) => *lru,
+                None => break,
+            };
+            if let Some(evicted) = self.kitty_images.remove(&lru) {
+                delete(
+                    out,
+                    KittyDelete::Image {
+                        image_id: evicted.image_id,
+                    },
+                )?;
+                self.kitty_placements
+                    .retain(|placed| placed.image_id != evicted.image_id);
+            }
+        }
+
+        let source = if image.top_left == TextureCoordinate::new_f32(0.0, 0.0)
+            && image.bottom_right == TextureCoordinate::new_f32(1.0, 1.0)
+        {
+            None
+        } else {
+            let to_pixel = |coord: f32, size: u32| ((coord * size as f32).round() as u32).min(size);
+            let left = to_pixel(image.top_left.x.into_inner(), image_width);
+            let top = to_pixel(image.top_left.y.into_inner(), image_height);
+            let right = to_pixel(image.bottom_right.x.into_inner(), image_width);
+            let bottom = to_pixel(image.bottom_right.y.into_inner(), image_height);
+            Some((
+                left,
+                top,
+                right.saturating_sub(left),
+                bottom.saturating_sub(top),
+            ))
+        };
+
+        let placement_id = match self.cursor {
+            Some((x, y)) => {
+                let placement_id = kitty_placement_id(x, y);
+                // Placing with the same ids replaces the old placement,
+                // so only the others need to be deleted
+                self.kitty_placements.retain(|placed| {
+                    placed.image_id != image_id || placed.placement_id != placement_id
+                });
+                self.kitty_delete_placements(x..x + image.width, y..y + image.height, out)?;
+                self.kitty_placements.push(KittyPlaced {
+                    image_id,
+                    placement_id,
+                    x,
+                    y,
+                    columns: image.width,
+                    rows: image.height,
+                });
+                Some(placement_id)
+            }
+            None => None,
+        };
+
+        place(
+            out,
+            &KittyPlacement {
+                image_id,
+                placement_id,
+                columns: image.width as u32,
+                rows: image.height as u32,
+                source,
+            },
+        )?;
+        // The placement doesn't move the cursor; advance past the image
+        // to leave the cursor in the same place as the other image
+        // rendering methods.
+        self.cursor_right(image.width as u32, out)?;
+        if let Some((x, y)) = self.cursor {
+            let (cols, _rows) = out.get_size_in_cells()?;
+            self.cursor = Some(((x + image.width).min(cols.saturating_sub(1)), y));
+        }
+
+        Ok(true)
+    }
+
+    /// Render the image using sixel graphics.
+    /// Returns false if the image could not be rendered that way, in
+    /// which case the caller should fall back to some other method.
+    /// Decoding the image requires the `use_image` feature; without it
+    /// images are never rendered as sixel graphics.
+    #[cfg(feature = "use_image")]
+    fn render_sixel<W: RenderTty + Write>(&mut self, image: &Image, out: &mut W) -> Result<bool> {
+        use crate::escape::{Esc, EscCode};
+        use crate::render::sixel::{encode_sixel, DEFAULT_MAX_COLORS};
+
+        let decoded = match ::image::load_from_memory(image.image.data()) {
+            Ok(decoded) => decoded.to_rgba8(),
+            Err(err) => {
+                log::error!("unable to decode image for sixel output: {:#}", err);
+                return Ok(false);
+            }
+        };
+
+        // Slice out the requested region of the image
+        let (image_width, image_height) = decoded.dimensions();
+        let to_pixel = |coord: f32, size: u32| ((coord * size as f32).round() as u32).min(size);
+        let left = to_pixel(image.top_left.x.into_inner(), image_width);
+        let top = to_pixel(image.top_left.y.into_inner(), image_height);
+        let right = to_pixel(image.bottom_right.x.into_inner(), image_width);
+        let bottom = to_pixel(image.bottom_right.y.into_inner(), image_height);
+        if right <= left || bottom <= top || image.width == 0 || image.height == 0 {
+            return Ok(false);
+        }
+        let region =
+            ::image::imageops::crop_imm(&decoded, left, top, right - left, bottom - top).to_image();
+
+        // and scale it to fill the cells that it occupies
+        let (cell_width, cell_height) = out
+            .get_cell_size_in_pixels()?
+            .unwrap_or(FALLBACK_CELL_SIZE_IN_PIXELS);
+        let width = (image.width * cell_width) as u32;
+        let height = (image.height * cell_height) as u32;
+        let scaled = ::image::imageops::resize(
+            &region,
+            width,
+            height,
+            ::image::imageops::FilterType::Triangle,
+        );
+
+        let sixel = encode_sixel(width, height, scaled.as_raw(), DEFAULT_MAX_COLORS);
+
+        // Terminals disagree on where the cursor ends up after
+        // displaying a sixel image, so save and restore it and then
+        // advance past the image, leaving the cursor in the same
+        // place as the other image rendering methods.
+        write!(out, "{}", Esc::Code(EscCode::DecSaveCursorPosition))?;
+        write!(out, "{}{}", sixel, Esc::Code(EscCode::StringTerminator))?;
+        write!(out, "{}", Esc::Code(EscCode::DecRestoreCursorPosition))?;
+        self.cursor_right(image.width as u32, out)?;
+
+        Ok(true)
+    }
+
+    /// Without the `use_image` feature we can't decode the image data,
+    /// so the caller always falls back to blanking out the cells
+    #[cfg(not(feature = "use_image"))]
+    fn render_sixel<W: RenderTty + Write>(&mut self, _image: &Image, _out: &mut W) -> Result<bool> {
+        Ok(false)
+    }
+
     fn cursor_x_relative<W: RenderTty + Write>(&mut self, x: isize, out: &mut W) -> Result<()> {
         if x > 0 {
             self.cursor_right(x as u32, out)
@@ -335,6 +762,14 @@ impl TerminfoRenderer {
                     }
                     self.pending_attr = None;
 
+                    if self.caps.kitty_graphics() {
+                        crate::render::kitty::delete(
+                            out,
+                            crate::render::kitty::KittyDelete::AllVisible,
+                        )?;
+                        self.kitty_placements.clear();
+                    }
+
                     if self.current_attr.background() == ColorAttribute::Default || self.caps.bce()
                     {
                         // The erase operation respects "background color erase",
@@ -350,6 +785,7 @@ impl TerminfoRenderer {
                                 CSI::Edit(Edit::EraseInDisplay(EraseInDisplay::EraseDisplay))
                             )?;
                         }
+                        self.cursor = Some((0, 0));
                     } else {
                         // We're setting the background to a specific color, so we get to
                         // paint the whole thing.
@@ -360,6 +796,7 @@ impl TerminfoRenderer {
                         let mut buf = Vec::with_capacity(num_spaces);
                         buf.resize(num_spaces, b' ');
                         out.write_all(buf.as_slice())?;
+                        self.cursor = None;
                     }
                 }
                 Change::ClearToEndOfLine(color) => {
@@ -371,6 +808,11 @@ impl TerminfoRenderer {
                     }
                     self.pending_attr = None;
 
+                    if let (true, Some((x, y))) = (self.caps.kitty_graphics(), self.cursor) {
+                        let (cols, _rows) = out.get_size_in_cells()?;
+                        self.kitty_delete_placements(x..cols, y..y + 1, out)?;
+                    }
+
                     // FIXME: this doesn't behave correctly for terminals without bce.
                     // If we knew the current cursor position, we would be able to
                     // emit the correctly colored background for that case.
@@ -393,6 +835,12 @@ impl TerminfoRenderer {
                     }
                     self.pending_attr = None;
 
+                    if let (true, Some((x, y))) = (self.caps.kitty_graphics(), self.cursor) {
+                        let (cols, rows) = out.get_size_in_cells()?;
+                        self.kitty_delete_placements(x..cols, y..y + 1, out)?;
+                        self.kitty_delete_placements(0..cols, y + 1..rows, out)?;
+                    }
+
                     // FIXME: this doesn't behave correctly for terminals without bce.
                     // If we knew the current cursor position, we would be able to
                     // emit the correctly colored background for that case.
@@ -446,11 +894,18 @@ impl TerminfoRenderer {
                     self.pending_attr = Some(all.clone());
                 }
                 Change::Text(text) => {
+                    if self.caps.kitty_graphics() {
+                        self.kitty_track_text(text, out)?;
+                    }
                     self.flush_pending_attr(out)?;
                     out.by_ref().write_all(text.as_bytes())?;
                 }
 
                 Change::CursorPosition { x, y } => {
+                    if self.caps.kitty_graphics() {
+                        self.kitty_track_cursor_position(x, y, out)?;
+                    }
+
                     // Note: we use `cursor_up(screen_height)` to move the cursor all the way to
                     // the top of the screen when we need to absolutely position only y.
 
@@ -561,7 +1016,10 @@ impl TerminfoRenderer {
                     }
                 },
                 Change::Image(image) => {
-                    if self.caps.iterm2_image() {
+                    if self.caps.kitty_graphics() && self.render_kitty_image(image, out)? {
+                        // The image was placed using the kitty protocol
+                    } else if self.caps.iterm2_image() {
+                        self.cursor = None;
                         let data = if image.top_left == TextureCoordinate::new_f32(0.0, 0.0)
                             && image.bottom_right == TextureCoordinate::new_f32(1.0, 1.0)
                         {
@@ -589,9 +1047,11 @@ impl TerminfoRenderer {
                         ));
 
                         write!(out, "{}", osc)?;
-
-                    // TODO: } else if self.caps.sixel() {
+                    } else if self.caps.sixel() && self.render_sixel(image, out)? {
+                        // The image was rendered as sixel graphics
+                        self.cursor = None;
                     } else {
+                        self.cursor = None;
                         // Blank out the cells and move the cursor to the right spot
                         for y in 0..image.height {
                             for _ in 0..image.width {
@@ -611,6 +1071,8 @@ impl TerminfoRenderer {
                     region_size,
                     scroll_count,
                 } => {
+                    // Changing the scroll region moves the cursor
+                    self.cursor = None;
                     if *region_size > 0 {
                         if let Some(csr) = self.get_capability::<cap::ChangeScrollRegion>() {
                             let top = *first_row as u32;
@@ -618,6 +1080,11 @@ impl TerminfoRenderer {
                             let scroll_count = *scroll_count as u32;
                             csr.expand().top(top).bottom(bottom).to(out.by_ref())?;
                             if scroll_count > 0 {
+                                self.kitty_scrolled(
+                                    *first_row,
+                                    *region_size,
+                                    scroll_count as isize,
+                                );
                                 if let Some(scroll) = self.get_capability::<cap::ParmIndex>() {
                                     scroll.expand().count(scroll_count).to(out.by_ref())?
                                 } else {
@@ -641,6 +1108,8 @@ impl TerminfoRenderer {
                     region_size,
                     scroll_count,
                 } => {
+                    // Changing the scroll region moves the cursor
+                    self.cursor = None;
                     if *region_size > 0 {
                         if let Some(csr) = self.get_capability::<cap::ChangeScrollRegion>() {
                             let top = *first_row as u32;
@@ -648,6 +1117,11 @@ impl TerminfoRenderer {
                             let scroll_count = *scroll_count as u32;
                             csr.expand().top(top).bottom(bottom).to(out.by_ref())?;
                             if scroll_count > 0 {
+                                self.kitty_scrolled(
+                                    *first_row,
+                                    *region_size,
+                                    -(scroll_count as isize),
+                                );
                                 if let Some(scroll) = self.get_capability::<cap::ParmRindex>() {
                                     scroll.expand().count(scroll_count).to(out.by_ref())?
                                 } else {
@@ -1215,4 +1689,179 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn kitty_image_transmitted_once() {
+        use crate::image::ImageData;
+        use std::sync::Arc;
+
+        let caps =
+            Capabilities::new_with_hints(ProbeHints::default().kitty_graphics(Some(true))).unwrap();
+        let mut out = FakeTerm::new(caps);
+
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&40u32.to_be_bytes());
+        png.extend_from_slice(&20u32.to_be_bytes());
+        let data = Arc::new(ImageData::with_raw_data(png.into_boxed_slice()));
+        let image_id = data.id() as u32 + 1;
+
+        out.render(&[Change::Image(crate::surface::Image {
+            width: 4,
+            height: 2,
+            top_left: TextureCoordinate::new_f32(0., 0.),
+            bottom_right: TextureCoordinate::new_f32(1., 1.),
+            image: Arc::clone(&data),
+        })])
+        .unwrap();
+        out.render(&[Change::Image(crate::surface::Image {
+            width: 2,
+            height: 1,
+            top_left: TextureCoordinate::new_f32(0.5, 0.5),
+            bottom_right: TextureCoordinate::new_f32(1., 1.),
+            image: data,
+        })])
+        .unwrap();
+
+        let output = String::from_utf8(out.write.buf).unwrap();
+        assert_eq!(output.matches("a=t,").count(), 1, "{:?}", output);
+        assert!(output.contains(&format!("a=p,q=2,C=1,i={},c=4,r=2\x1b\\", image_id)));
+        assert!(output.contains(&format!(
+            "a=p,q=2,C=1,i={},c=2,r=1,x=20,y=10,w=20,h=10\x1b\\",
+            image_id
+        )));
+    }
+
+    fn fake_png(width: u32, height: u32) -> std::sync::Arc<crate::image::ImageData> {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&width.to_be_bytes());
+        png.extend_from_slice(&height.to_be_bytes());
+        std::sync::Arc::new(crate::image::ImageData::with_raw_data(
+            png.into_boxed_slice(),
+        ))
+    }
+
+    fn whole_image(
+        width: usize,
+        height: usize,
+        image: std::sync::Arc<crate::image::ImageData>,
+    ) -> Change {
+        Change::Image(crate::surface::Image {
+            width,
+            height,
+            top_left: TextureCoordinate::new_f32(0., 0.),
+            bottom_right: TextureCoordinate::new_f32(1., 1.),
+            image,
+        })
+    }
+
+    #[test]
+    fn kitty_image_placements() {
+        let caps =
+            Capabilities::new_with_hints(ProbeHints::default().kitty_graphics(Some(true))).unwrap();
+        let mut out = FakeTerm::new(caps);
+
+        let data = fake_png(40, 20);
+        let image_id = data.id() as u32 + 1;
+        let placement = format!("i={},p={}", image_id, kitty_placement_id(2, 3));
+        let move_to = |x, y| Change::CursorPosition {
+            x: Position::Absolute(x),
+            y: Position::Absolute(y),
+        };
+
+        // Placing the image again at the same position re-uses
+        // the placement id, which replaces the earlier placement
+        for _ in 0..2 {
+            out.render(&[
+                move_to(2, 3),
+                whole_image(4, 2, std::sync::Arc::clone(&data)),
+            ])
+            .unwrap();
+        }
+        let output = String::from_utf8(std::mem::take(&mut out.write.buf)).unwrap();
+        assert_eq!(output.matches(&placement).count(), 2, "{:?}", output);
+        assert!(!output.contains("a=d,"), "{:?}", output);
+
+        // Clearing cells elsewhere leaves the placement alone
+        out.render(&[move_to(6, 4), Change::ClearToEndOfLine(Default::default())])
+            .unwrap();
+        let output = String::from_utf8(std::mem::take(&mut out.write.buf)).unwrap();
+        assert!(!output.contains("a=d,"), "{:?}", output);
+
+        // but clearing the cells that it occupies deletes it
+        out.render(&[move_to(0, 4), Change::ClearToEndOfLine(Default::default())])
+            .unwrap();
+        let output = String::from_utf8(std::mem::take(&mut out.write.buf)).unwrap();
+        assert!(
+            output.contains(&format!("a=d,q=2,d=i,{}\x1b\\", placement)),
+            "{:?}",
+            output
+        );
+
+        out.render(&[Change::ClearScreen(Default::default())])
+            .unwrap();
+        let output = String::from_utf8(std::mem::take(&mut out.write.buf)).unwrap();
+        assert!(output.contains("a=d,q=2,d=a\x1b\\"), "{:?}", output);
+    }
+
+    #[test]
+    fn kitty_image_storage_evicts_least_recently_used() {
+        let caps =
+            Capabilities::new_with_hints(ProbeHints::default().kitty_graphics(Some(true))).unwrap();
+        let mut out = FakeTerm::new(caps);
+
+        // Any two of these fit within the storage limit, but not three
+        let images: Vec<_> = (0..3).map(|_| fake_png(2896, 2896)).collect();
+        let image_ids: Vec<u32> = images.iter().map(|i| i.id() as u32 + 1).collect();
+        let delete = |idx: usize| format!("a=d,q=2,d=I,i={}\x1b\\", image_ids[idx]);
+
+        out.render(&[whole_image(4, 2, std::sync::Arc::clone(&images[0]))])
+            .unwrap();
+        out.render(&[whole_image(4, 2, std::sync::Arc::clone(&images[1]))])
+            .unwrap();
+        // Using the first image again makes the second the least
+        // recently used
+        out.render(&[whole_image(4, 2, std::sync::Arc::clone(&images[0]))])
+            .unwrap();
+        let output = String::from_utf8(std::mem::take(&mut out.write.buf)).unwrap();
+        assert!(!output.contains("a=d,"), "{:?}", output);
+
+        out.render(&[whole_image(4, 2, std::sync::Arc::clone(&images[2]))])
+            .unwrap();
+        let output = String::from_utf8(std::mem::take(&mut out.write.buf)).unwrap();
+        assert!(output.contains(&delete(1)), "{:?}", output);
+        assert!(!output.contains(&delete(0)), "{:?}", output);
+        assert!(!output.contains(&delete(2)), "{:?}", output);
+
+        // The first image is still held by the terminal, so it is
+        // placed without being transmitted again
+        out.render(&[whole_image(4, 2, std::sync::Arc::clone(&images[0]))])
+            .unwrap();
+        let output = String::from_utf8(std::mem::take(&mut out.write.buf)).unwrap();
+        assert!(!output.contains("a=t,"), "{:?}", output);
+    }
+
+    #[test]
+    fn kitty_image_storage_is_bounded() {
+        let caps =
+            Capabilities::new_with_hints(ProbeHints::default().kitty_graphics(Some(true))).unwrap();
+        let mut out = FakeTerm::new(caps);
+
+        // Each of these needs the whole storage limit
+        let first = fake_png(4096, 4096);
+        let second = fake_png(4096, 4096);
+        out.render(&[whole_image(4, 2, std::sync::Arc::clone(&first))])
+            .unwrap();
+        out.render(&[whole_image(4, 2, second)]).unwrap();
+        let output = String::from_utf8(std::mem::take(&mut out.write.buf)).unwrap();
+        assert!(
+            output.contains(&format!("a=d,q=2,d=I,i={}\x1b\\", first.id() as u32 + 1)),
+            "{:?}",
+            output
+        );
+
+        // The evicted image is transmitted again when it is next used
+        out.render(&[whole_image(4, 2, first)]).unwrap();
+        let output = String::from_utf8(out.write.buf).unwrap();
+        assert_eq!(output.matches("a=t,").count(), 1, "{:?}", output);
+    }
 }
@@ -1,3 +1,4 @@
+use crate::bidi::{mirror_char, BidiMode, Direction, ParagraphDirectionHint};
 use crate::cell::{Cell, CellAttributes};
 use crate::cellcluster::CellCluster;
 use crate::hyperlink::Rule;
@@ -7,11 +8,12 @@ use bitflags::bitflags;
 use serde::{Deserialize, Serialize};
 use std::ops::Range;
 use std::sync::Arc;
+use unicode_bidi::{BidiInfo, Level};
 use unicode_segmentation::UnicodeSegmentation;
 
 bitflags! {
     #[cfg_attr(feature="use_serde", derive(Serialize, Deserialize))]
-    struct LineBits : u8 {
+    struct LineBits : u16 {
         const NONE = 0;
         /// The contents of the Line have changed and cached or
         /// derived data will need to be reassessed.
@@ -22,6 +24,27 @@ bitflags! {
         const SCANNED_IMPLICIT_HYPERLINKS = 1<<2;
         /// true if we found implicit hyperlinks in the last scan
         const HAS_IMPLICIT_HYPERLINKS = 1<<3;
+        /// The line is displayed at double width (DECDWL).
+        /// This is also set for both halves of a double height line.
+        const DOUBLE_WIDTH = 1<<4;
+        /// The line is the top half of a double height line (DECDHL)
+        const DOUBLE_HEIGHT_TOP = 1<<5;
+        /// The line is the bottom half of a double height line (DECDHL)
+        const DOUBLE_HEIGHT_BOTTOM = 1<<6;
+        /// All of the bits that make up the line rendition
+        const RENDITION = Self::DOUBLE_WIDTH.bits |
+            Self::DOUBLE_HEIGHT_TOP.bits | Self::DOUBLE_HEIGHT_BOTTOM.bits;
+        /// The line is reordered for display by the bidi algorithm
+        const BIDI_ENABLED = 1<<7;
+        /// The base direction of the line is right-to-left
+        const BIDI_RTL = 1<<8;
+        /// The base direction is autodetected from the text of the line
+        const BIDI_AUTO = 1<<9;
+        /// Box drawing characters are mirrored in right-to-left runs
+        const BIDI_MIRROR_BOX = 1<<10;
+        /// All of the bits that make up the bidi mode
+        const BIDI = Self::BIDI_ENABLED.bits | Self::BIDI_RTL.bits |
+            Self::BIDI_AUTO.bits | Self::BIDI_MIRROR_BOX.bits;
     }
 }
 
@@ -32,12 +55,155 @@ pub struct Line {
     bits: LineBits,
 }
 
+/// A compact, read-only representation of a `Line`, intended for
+/// lines that have scrolled into the scrollback and are unlikely to
+/// change again.  The text of the cells is packed into a single UTF-8
+/// string and the attributes are run-length encoded, which is a small
+/// fraction of the size of a `Vec<Cell>` for typical terminal output.
+/// Use `to_line` to materialize the `Line` again.
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedLine {
+    /// The text of each of the cells, concatenated
+    text: Box<str>,
+    /// Runs of (number of cells, number of bytes of text in each cell,
+    /// width of each cell).  The width is kept rather than recomputed
+    /// because it depends on the `UnicodeWidthPolicy` in effect when
+    /// the text was printed.
+    text_runs: Box<[(u32, u32, u8)]>,
+    /// Runs of (number of cells, attributes of each cell)
+    attr_runs: Box<[(u32, CellAttributes)]>,
+    bits: LineBits,
+}
+
+/// A `Line` that has been reordered for display by the bidi
+/// algorithm; see `Line::bidi_visual_line`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VisualLine {
+    line: Line,
+    logical: Vec<usize>,
+    /// The display columns spanned by each run, in display order
+    runs: Vec<(Range<usize>, Direction)>,
+}
+
+impl VisualLine {
+    /// Returns the cells in display order.  Characters in right-to-left
+    /// runs that have a mirrored glyph, such as brackets, are replaced
+    /// by their mirrored counterpart.
+    pub fn line(&self) -> &Line {
+        &self.line
+    }
+
+    /// Returns the logical cell index that is displayed at `visual_idx`
+    pub fn logical_idx(&self, visual_idx: usize) -> usize {
+        self.logical.get(visual_idx).copied().unwrap_or(visual_idx)
+    }
+
+    /// Returns the display column of the logical cell index `logical_idx`
+    pub fn visual_idx(&self, logical_idx: usize) -> usize {
+        self.logical
+            .iter()
+            .position(|&idx| idx == logical_idx)
+            .unwrap_or(logical_idx)
+    }
+
+    /// Break the line into clusters for shaping, in display order.
+    /// The clusters don't span runs of differing direction, and the
+    /// text of a right-to-left cluster is in logical order.
+    /// The shaper mirrors brackets itself when shaping right-to-left
+    /// text, so those are restored to their logical form here; box
+    /// drawing characters keep the mirroring applied by the line.
+    pub fn cluster(&self) -> Vec<CellCluster> {
+        let hint = self.line.cells.len();
+        let mut cells = self.line.visible_cells().peekable();
+        let mut clusters = vec![];
+        for (range, direction) in &self.runs {
+            let mut run = vec![];
+            while let Some((idx, cell)) = cells.next_if(|(idx, _)| *idx < range.end) {
+                run.push((idx, cell));
+            }
+            match direction {
+                Direction::LeftToRight => {
+                    clusters.append(&mut CellCluster::make_cluster(hint, run.into_iter()))
+                }
+                Direction::RightToLeft => {
+                    let run: Vec<(usize, Cell)> = run
+                        .into_iter()
+                        .map(|(idx, cell)| {
+                            let mut chars = cell.str().chars();
+                            match (chars.next(), chars.next()) {
+                                (Some(c), None) => match mirror_char(c, false) {
+                                    Some(c) => (idx, Cell::new(c, cell.attrs().clone())),
+                                    None => (idx, cell.clone()),
+                                },
+                                _ => (idx, cell.clone()),
+                            }
+                        })
+                        .collect();
+                    clusters.append(&mut CellCluster::make_rtl_cluster(
+                        hint,
+                        run.iter().map(|(idx, cell)| (*idx, cell)),
+                    ));
+                }
+            }
+        }
+        clusters
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum DoubleClickRange {
     Range(Range<usize>),
     RangeWithWrap(Range<usize>),
 }
 
+/// The size at which a line is displayed, as selected by the
+/// DECSWL, DECDWL and DECDHL escape sequences.
+/// Each cell of a line with a rendition other than `SingleWidth`
+/// occupies two columns of the display, so the line can hold only
+/// half as many cells as a regular line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineRendition {
+    SingleWidth,
+    DoubleWidth,
+    DoubleHeightTop,
+    DoubleHeightBottom,
+}
+
+impl LineRendition {
+    /// Returns true if the cells of the line are displayed at twice
+    /// their normal width
+    pub fn is_double_width(self) -> bool {
+        self != Self::SingleWidth
+    }
+
+    /// Returns true if the line is one half of a double height line
+    pub fn is_double_height(self) -> bool {
+        matches!(self, Self::DoubleHeightTop | Self::DoubleHeightBottom)
+    }
+
+    fn from_bits(bits: LineBits) -> Self {
+        if bits.contains(LineBits::DOUBLE_HEIGHT_TOP) {
+            Self::DoubleHeightTop
+        } else if bits.contains(LineBits::DOUBLE_HEIGHT_BOTTOM) {
+            Self::DoubleHeightBottom
+        } else if bits.contains(LineBits::DOUBLE_WIDTH) {
+            Self::DoubleWidth
+        } else {
+            Self::SingleWidth
+        }
+    }
+
+    fn to_bits(self) -> LineBits {
+        match self {
+            Self::SingleWidth => LineBits::NONE,
+            Self::DoubleWidth => LineBits::DOUBLE_WIDTH,
+            Self::DoubleHeightTop => LineBits::DOUBLE_WIDTH | LineBits::DOUBLE_HEIGHT_TOP,
+            Self::DoubleHeightBottom => LineBits::DOUBLE_WIDTH | LineBits::DOUBLE_HEIGHT_BOTTOM,
+        }
+    }
+}
+
 impl Line {
     pub fn with_width(width: usize) -> Self {
         let mut cells = Vec::with_capacity(width);
@@ -90,6 +256,7 @@ impl Line {
     pub fn wrap(mut self, width: usize) -> Vec<Self> {
         if let Some(end_idx) = self.cells.iter().rposition(|c| c.str() != " ") {
             self.cells.resize(end_idx + 1, Cell::default());
+            let bidi = self.bits & LineBits::BIDI;
 
             let mut lines: Vec<_> = self
                 .cells
@@ -97,7 +264,7 @@ impl Line {
                 .map(|chunk| {
                     let mut line = Line {
                         cells: chunk.to_vec(),
-                        bits: LineBits::DIRTY,
+                        bits: LineBits::DIRTY | bidi,
                     };
                     if line.cells.len() == width {
                         // Ensure that we don't forget that we wrapped
@@ -137,6 +304,148 @@ impl Line {
         self.bits &= !LineBits::DIRTY;
     }
 
+    /// Returns the size at which the line is displayed
+    #[inline]
+    pub fn rendition(&self) -> LineRendition {
+        LineRendition::from_bits(self.bits)
+    }
+
+    /// Change the size at which the line is displayed.
+    /// The cells are left untouched; it is the responsibility of the
+    /// caller to discard any cells that no longer fit on the display.
+    pub fn set_rendition(&mut self, rendition: LineRendition) {
+        self.bits &= !LineBits::RENDITION;
+        self.bits |= rendition.to_bits() | LineBits::DIRTY;
+    }
+
+    /// Returns the bidi mode that applies to the display of the line
+    pub fn bidi_mode(&self) -> BidiMode {
+        let rtl = self.bits.contains(LineBits::BIDI_RTL);
+        let auto = self.bits.contains(LineBits::BIDI_AUTO);
+        BidiMode {
+            enabled: self.bits.contains(LineBits::BIDI_ENABLED),
+            hint: match (rtl, auto) {
+                (false, false) => ParagraphDirectionHint::LeftToRight,
+                (true, false) => ParagraphDirectionHint::RightToLeft,
+                (false, true) => ParagraphDirectionHint::AutoLeftToRight,
+                (true, true) => ParagraphDirectionHint::AutoRightToLeft,
+            },
+            mirror_box_drawing: self.bits.contains(LineBits::BIDI_MIRROR_BOX),
+        }
+    }
+
+    /// Change the bidi mode that applies to the display of the line
+    pub fn set_bidi_mode(&mut self, mode: BidiMode) {
+        let mut bits = LineBits::NONE;
+        bits.set(LineBits::BIDI_ENABLED, mode.enabled);
+        bits.set(LineBits::BIDI_RTL, mode.hint.is_rtl());
+        bits.set(LineBits::BIDI_AUTO, mode.hint.is_auto());
+        bits.set(LineBits::BIDI_MIRROR_BOX, mode.mirror_box_drawing);
+        if (self.bits & LineBits::BIDI) != bits {
+            self.bits &= !LineBits::BIDI;
+            self.bits |= bits | LineBits::DIRTY;
+        }
+    }
+
+    /// Apply the bidi algorithm to the line, treating it as a single
+    /// paragraph, and return its cells in display order.
+    /// A line with fewer than `width` cells is padded with blanks so
+    /// that right-to-left text is aligned with the right edge of the
+    /// display.
+    /// Returns None if bidi is not enabled for the line, or if the
+    /// display order is the same as the logical order.
+    pub fn bidi_visual_line(&self, width: usize) -> Option<VisualLine> {
+        let mode = self.bidi_mode();
+        if !mode.enabled {
+            return None;
+        }
+
+        // The byte offset of each visible cell within the text of the line
+        let mut text = String::new();
+        let mut starts = vec![];
+        for (idx, cell) in self.visible_cells() {
+            starts.push((text.len(), idx));
+            text.push_str(cell.str());
+        }
+
+        let level = if mode.hint.resolve_is_rtl(&text) {
+            Level::rtl()
+        } else {
+            Level::ltr()
+        };
+        let info = BidiInfo::new(&text, Some(level));
+        if !info.has_rtl() {
+            return None;
+        }
+        if self.cells.len() < width {
+            let mut line = self.clone();
+            line.resize(width);
+            return line.bidi_visual_line(width);
+        }
+
+        // `starts` is ordered by byte offset, so the cells that begin
+        // within a run form a contiguous slice that can be located
+        // without scanning every cell for every run
+        let first_start_at_or_after =
+            |offset: usize| match starts.binary_search_by_key(&offset, |&(start, _)| start) {
+                Ok(i) | Err(i) => i,
+            };
+
+        let mut cells = Vec::with_capacity(self.cells.len());
+        let mut logical = Vec::with_capacity(self.cells.len());
+        let mut visual_runs = vec![];
+        for para in &info.paragraphs {
+            let (levels, runs) = info.visual_runs(para, para.range.clone());
+            for run in runs {
+                let rtl = levels[run.start].is_rtl();
+                let run_start = cells.len();
+                let run_starts =
+                    &starts[first_start_at_or_after(run.start)..first_start_at_or_after(run.end)];
+                let mut run_cells: Vec<usize> = run_starts.iter().map(|&(_, idx)| idx).collect();
+                if rtl {
+                    run_cells.reverse();
+                }
+
+                for idx in run_cells {
+                    let cell = &self.cells[idx];
+                    let mut chars = cell.str().chars();
+                    let mirrored = match (chars.next(), chars.next()) {
+                        (Some(c), None) if rtl => mirror_char(c, mode.mirror_box_drawing),
+                        _ => None,
+                    };
+                    cells.push(match mirrored {
+                        Some(c) => Cell::new(c, cell.attrs().clone()),
+                        None => cell.clone(),
+                    });
+                    logical.push(idx);
+
+                    // The cells that are covered by a wide cell stay
+                    // to its right
+                    for idx in idx + 1..(idx + cell.width()).min(self.cells.len()) {
+                        cells.push(self.cells[idx].clone());
+                        logical.push(idx);
+                    }
+                }
+
+                let direction = if rtl {
+                    Direction::RightToLeft
+                } else {
+                    Direction::LeftToRight
+                };
+                visual_runs.push((run_start..cells.len(), direction));
+            }
+        }
+
+        Some(VisualLine {
+            line: Line {
+                cells,
+                bits: self.bits - LineBits::BIDI,
+            },
+            logical,
+            runs: visual_runs,
+        })
+    }
+
     /// If we have any cells with an implicit hyperlink, remove the hyperlink
     /// from the cell attributes but leave the remainder of the attributes alone.
     pub fn invalidate_implicit_hyperlinks(&mut self) {
@@ -153,8 +462,9 @@ impl Line {
 
         for cell in &mut self.cells {
             let replace = match cell.attrs().hyperlink() {
-                Some(ref link) if link.is_implicit() => Some(Cell::new_grapheme(
+                Some(ref link) if link.is_implicit() => Some(Cell::new_grapheme_with_width(
                     cell.str(),
+                    cell.width(),
                     cell.attrs().clone().set_hyperlink(None).clone(),
                 )),
                 _ => None,
@@ -470,6 +780,47 @@ impl Line {
         self.cells.iter().all(|c| c.str() == " ")
     }
 
+    /// Returns an estimate of the number of bytes of memory used to
+    /// hold this line
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.cells.capacity() * std::mem::size_of::<Cell>()
+            + self.cells.iter().map(Cell::heap_size).sum::<usize>()
+    }
+
+    /// Returns a compact representation of this line; see `CompressedLine`
+    pub fn compress(&self) -> CompressedLine {
+        let mut text = String::new();
+        let mut text_runs: Vec<(u32, u32, u8)> = vec![];
+        let mut attr_runs: Vec<(u32, CellAttributes)> = vec![];
+
+        for cell in &self.cells {
+            let grapheme = cell.str();
+            text.push_str(grapheme);
+
+            let len = grapheme.len() as u32;
+            let width = cell.width() as u8;
+            match text_runs.last_mut() {
+                Some((count, run_len, run_width)) if *run_len == len && *run_width == width => {
+                    *count += 1
+                }
+                _ => text_runs.push((1, len, width)),
+            }
+
+            match attr_runs.last_mut() {
+                Some((count, attrs)) if attrs == cell.attrs() => *count += 1,
+                _ => attr_runs.push((1, cell.attrs().clone())),
+            }
+        }
+
+        CompressedLine {
+            text: text.into_boxed_str(),
+            text_runs: text_runs.into_boxed_slice(),
+            attr_runs: attr_runs.into_boxed_slice(),
+            bits: self.bits,
+        }
+    }
+
     /// Return true if the last cell in the line has the wrapped attribute,
     /// indicating that the following line is logically a part of this one.
     pub fn last_cell_was_wrapped(&self) -> bool {
@@ -573,6 +924,99 @@ impl Line {
     }
 }
 
+impl CompressedLine {
+    /// Returns the number of cells in the line
+    pub fn len(&self) -> usize {
+        self.text_runs
+            .iter()
+            .map(|&(count, _, _)| count as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    #[inline]
+    pub fn is_dirty(&self) -> bool {
+        (self.bits & LineBits::DIRTY) == LineBits::DIRTY
+    }
+
+    #[inline]
+    pub fn set_dirty(&mut self) {
+        self.bits |= LineBits::DIRTY;
+    }
+
+    #[inline]
+    pub fn clear_dirty(&mut self) {
+        self.bits &= !LineBits::DIRTY;
+    }
+
+    /// Returns the size at which the line is displayed
+    #[inline]
+    pub fn rendition(&self) -> LineRendition {
+        LineRendition::from_bits(self.bits)
+    }
+
+    /// Return true if the line consists solely of whitespace cells
+    pub fn is_whitespace(&self) -> bool {
+        // Cells are never empty, so a text of only spaces means that
+        // every cell holds a single space
+        self.text.bytes().all(|b| b == b' ')
+    }
+
+    /// Return true if the last cell in the line has the wrapped attribute,
+    /// indicating that the following line is logically a part of this one.
+    pub fn last_cell_was_wrapped(&self) -> bool {
+        self.attr_runs
+            .last()
+            .map(|(_, attrs)| attrs.wrapped())
+            .unwrap_or(false)
+    }
+
+    /// Returns an estimate of the number of bytes of memory used to
+    /// hold this line
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.text.len()
+            + self.text_runs.len() * std::mem::size_of::<(u32, u32, u8)>()
+            + self.attr_runs.len() * std::mem::size_of::<(u32, CellAttributes)>()
+            + self
+                .attr_runs
+                .iter()
+                .map(|(_, attrs)| attrs.heap_size())
+                .sum::<usize>()
+    }
+
+    /// Materializes the `Line` that this was compressed from
+    pub fn to_line(&self) -> Line {
+        let mut cells = Vec::with_capacity(self.len());
+        let mut attrs = self
+            .attr_runs
+            .iter()
+            .flat_map(|(count, attrs)| std::iter::repeat(attrs).take(*count as usize));
+        let mut text: &str = &self.text;
+
+        for &(count, len, width) in self.text_runs.iter() {
+            for _ in 0..count {
+                let (grapheme, remainder) = text.split_at(len as usize);
+                text = remainder;
+                let attrs = attrs.next().cloned().unwrap_or_default();
+                cells.push(Cell::new_grapheme_with_width(
+                    grapheme,
+                    width as usize,
+                    attrs,
+                ));
+            }
+        }
+
+        Line {
+            cells,
+            bits: self.bits,
+        }
+    }
+}
+
 impl<'a> From<&'a str> for Line {
     fn from(s: &str) -> Line {
         Line::from_text(s, &CellAttributes::default())
@@ -661,6 +1105,187 @@ mod test {
         );
     }
 
+    #[test]
+    fn compress_round_trip() {
+        let rules = vec![Rule::new(r"\b\w+://(?:[\w.-]+)\.[a-z]{2,15}\S*\b", "$0").unwrap()];
+
+        let bold = CellAttributes::default()
+            .set_intensity(crate::cell::Intensity::Bold)
+            .clone();
+        let mut line: Line = "❤ 😍 http://example.com e\u{301}".into();
+        line.overlay_text_with_attribute(2, "bold", bold);
+        line.scan_and_create_hyperlinks(&rules);
+        line.set_last_cell_was_wrapped(true);
+        line.clear_dirty();
+
+        let compressed = line.compress();
+        assert_eq!(compressed.len(), line.cells().len());
+        assert!(!compressed.is_dirty());
+        assert!(compressed.last_cell_was_wrapped());
+        assert!(!compressed.is_whitespace());
+        assert!(compressed.memory_usage() < line.memory_usage());
+        assert_eq!(compressed.to_line(), line);
+
+        let blank = Line::with_width(80);
+        let compressed = blank.compress();
+        assert!(compressed.is_whitespace());
+        assert!(compressed.is_dirty());
+        assert_eq!(compressed.to_line(), blank);
+    }
+
+    #[test]
+    fn compress_preserves_width_policy() {
+        use crate::cell::UnicodeWidthPolicy;
+        let policy = UnicodeWidthPolicy {
+            ambiguous_are_wide: true,
+            vs16_is_wide: true,
+            ..UnicodeWidthPolicy::default()
+        };
+
+        let rules = vec![Rule::new(r"\bx+\b", "$0").unwrap()];
+        let mut line = Line::with_width(0);
+        for g in &["\u{25cb}", "x", "\u{2764}\u{fe0f}", "x"] {
+            let width = policy.grapheme_width(g);
+            line.cells
+                .push(Cell::new_grapheme_with_width(g, width, Default::default()));
+            for _ in 1..width {
+                line.cells.push(Cell::new(' ', Default::default()));
+            }
+        }
+        let widths = |line: &Line| {
+            line.visible_cells()
+                .map(|(_, cell)| cell.width())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(widths(&line), vec![2, 1, 2, 1]);
+
+        let restored = line.compress().to_line();
+        assert_eq!(widths(&restored), vec![2, 1, 2, 1]);
+        assert_eq!(restored, line);
+
+        line.scan_and_create_hyperlinks(&rules);
+        assert!(line.has_hyperlink());
+        line.invalidate_implicit_hyperlinks();
+        assert!(!line.has_hyperlink());
+        assert_eq!(widths(&line), vec![2, 1, 2, 1]);
+    }
+
+    #[test]
+    fn rendition() {
+        let mut line: Line = "hello".into();
+        assert_eq!(line.rendition(), LineRendition::SingleWidth);
+        line.clear_dirty();
+
+        line.set_rendition(LineRendition::DoubleHeightTop);
+        assert!(line.is_dirty());
+        assert_eq!(line.rendition(), LineRendition::DoubleHeightTop);
+        assert!(line.rendition().is_double_width());
+        assert_eq!(line.compress().rendition(), LineRendition::DoubleHeightTop);
+        assert_eq!(line.compress().to_line(), line);
+
+        line.set_rendition(LineRendition::DoubleWidth);
+        assert_eq!(line.rendition(), LineRendition::DoubleWidth);
+        assert!(!line.rendition().is_double_height());
+
+        line.resize_and_clear(5);
+        assert_eq!(line.rendition(), LineRendition::SingleWidth);
+    }
+
+    #[test]
+    fn bidi() {
+        let bidi = |text: &str, hint| {
+            let mut line: Line = text.into();
+            line.set_bidi_mode(BidiMode {
+                enabled: true,
+                hint,
+                mirror_box_drawing: false,
+            });
+            line.bidi_visual_line(0)
+        };
+
+        let line: Line = "ab אבג".into();
+        assert_eq!(line.bidi_mode(), BidiMode::default());
+        assert_eq!(line.bidi_visual_line(0), None);
+        assert_eq!(bidi("hello", ParagraphDirectionHint::LeftToRight), None);
+
+        let visual = bidi("ab אבג cd", ParagraphDirectionHint::LeftToRight).unwrap();
+        assert_eq!(visual.line().as_str(), "ab גבא cd");
+        assert_eq!(visual.logical_idx(3), 5);
+        assert_eq!(visual.visual_idx(3), 5);
+        assert_eq!(visual.logical_idx(7), 7);
+
+        let visual = bidi("אבג (ab)", ParagraphDirectionHint::RightToLeft).unwrap();
+        assert_eq!(visual.line().as_str(), "(ab) גבא");
+
+        let visual = bidi("אבג (ab)", ParagraphDirectionHint::AutoLeftToRight).unwrap();
+        assert_eq!(visual.line().as_str(), "(ab) גבא");
+
+        // A wide cell keeps its spacer to its right
+        let visual = bidi("中ב", ParagraphDirectionHint::RightToLeft).unwrap();
+        assert_eq!(visual.line().as_str(), "ב中");
+        assert_eq!(visual.logical_idx(0), 2);
+        assert_eq!(visual.logical_idx(1), 0);
+        assert_eq!(visual.logical_idx(2), 1);
+
+        let mut line: Line = "┌א".into();
+        line.set_bidi_mode(BidiMode {
+            enabled: true,
+            hint: ParagraphDirectionHint::RightToLeft,
+            mirror_box_drawing: true,
+        });
+        assert_eq!(line.bidi_visual_line(0).unwrap().line().as_str(), "א┐");
+        assert_eq!(line.bidi_visual_line(4).unwrap().line().as_str(), "  א┐");
+        assert_eq!(line.compress().to_line(), line);
+    }
+
+    #[test]
+    fn bidi_clusters() {
+        let mut line: Line = "ab (אבג) cd".into();
+        line.set_bidi_mode(BidiMode {
+            enabled: true,
+            hint: ParagraphDirectionHint::LeftToRight,
+            mirror_box_drawing: false,
+        });
+        let visual = line.bidi_visual_line(0).unwrap();
+        assert_eq!(visual.line().as_str(), "ab (גבא) cd");
+
+        let clusters = visual.cluster();
+        let summary: Vec<(&str, Direction, Vec<usize>)> = clusters
+            .iter()
+            .map(|c| {
+                let cells = (0..c.text.len()).map(|i| c.byte_to_cell_idx(i)).collect();
+                (c.text.as_str(), c.direction, cells)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("ab (", Direction::LeftToRight, vec![0, 1, 2, 3]),
+                ("אבג", Direction::RightToLeft, vec![6, 6, 5, 5, 4, 4]),
+                (") cd", Direction::LeftToRight, vec![7, 8, 9, 10]),
+            ]
+        );
+
+        // Brackets in a right-to-left run are left for the shaper to
+        // mirror, and the cell indices of single byte text descend
+        let mut line: Line = "א(b".into();
+        line.set_bidi_mode(BidiMode {
+            enabled: true,
+            hint: ParagraphDirectionHint::RightToLeft,
+            mirror_box_drawing: false,
+        });
+        let visual = line.bidi_visual_line(0).unwrap();
+        assert_eq!(visual.line().as_str(), "b)א");
+        let clusters = visual.cluster();
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].text, "b");
+        assert_eq!(clusters[0].direction, Direction::LeftToRight);
+        assert_eq!(clusters[1].text, "א(");
+        assert_eq!(clusters[1].direction, Direction::RightToLeft);
+        assert_eq!(clusters[1].byte_to_cell_idx(0), 2);
+        assert_eq!(clusters[1].byte_to_cell_idx(2), 1);
+    }
+
     #[test]
     fn double_click_range_bounds() {
         let line: Line = "hello".into();
@@ -1,4 +1,4 @@
-use crate::cell::{AttributeChange, Cell, CellAttributes};
+use crate::cell::{AttributeChange, Cell, CellAttributes, UnicodeWidthPolicy};
 use crate::color::ColorAttribute;
 use crate::image::ImageCell;
 use ordered_float::NotNan;
@@ -112,6 +112,7 @@ pub struct Surface {
     cursor_visibility: CursorVisibility,
     cursor_color: ColorAttribute,
     title: String,
+    width_policy: UnicodeWidthPolicy,
 }
 
 #[derive(Default)]
@@ -217,6 +218,18 @@ impl Surface {
         &self.title
     }
 
+    /// Returns the policy used to compute the width of printed graphemes
+    pub fn width_policy(&self) -> UnicodeWidthPolicy {
+        self.width_policy
+    }
+
+    /// Set the policy used to compute the width of graphemes printed
+    /// from this point onwards; text that was already printed retains
+    /// the width that it was given at the time it was printed.
+    pub fn set_width_policy(&mut self, policy: UnicodeWidthPolicy) {
+        self.width_policy = policy;
+    }
+
     /// Resize the Surface to the specified width and height.
     /// If the width and/or height are smaller than previously, the rows and/or
     /// columns are truncated.  If the width and/or height are larger than
@@ -439,7 +452,11 @@ impl Surface {
                 self.xpos = 0;
             }
 
-            let cell = Cell::new_grapheme(g, self.attributes.clone());
+            let cell = Cell::new_grapheme_with_width(
+                g,
+                self.width_policy.grapheme_width(g),
+                self.attributes.clone(),
+            );
             // the max(1) here is to ensure that we advance to the next cell
             // position for zero-width graphemes.  We want to make sure that
             // they occupy a cell so that we can re-emit them when we output them.
@@ -1572,6 +1589,22 @@ mod test {
         assert_eq!(s.screen_chars_to_string(), "A\u{200b}B \n");
     }
 
+    #[test]
+    fn width_policy() {
+        let mut s = Surface::new(4, 2);
+        s.set_width_policy(crate::cell::UnicodeWidthPolicy {
+            ambiguous_are_wide: true,
+            ..Default::default()
+        });
+        // WHITE CIRCLE has ambiguous width
+        s.add_change("\u{25cb}x\r\n");
+        s.set_width_policy(Default::default());
+        s.add_change("\u{25cb}x");
+        assert_eq!(s.screen_chars_to_string(), "\u{25cb}x \n\u{25cb}x  \n");
+        assert_eq!(s.screen_cells()[0][0].width(), 2);
+        assert_eq!(s.screen_cells()[1][0].width(), 1);
+    }
+
     #[test]
     fn images() {
         // a dummy image blob with nonsense content
@@ -126,6 +126,17 @@ impl RenderTty for TtyWriteHandle {
         let size = self.get_size()?;
         Ok((size.ws_col as usize, size.ws_row as usize))
     }
+
+    fn get_cell_size_in_pixels(&mut self) -> Result<Option<(usize, usize)>> {
+        let size = self.get_size()?;
+        if size.ws_col == 0 || size.ws_row == 0 || size.ws_xpixel == 0 || size.ws_ypixel == 0 {
+            return Ok(None);
+        }
+        Ok(Some((
+            (size.ws_xpixel / size.ws_col) as usize,
+            (size.ws_ypixel / size.ws_row) as usize,
+        )))
+    }
 }
 
 impl UnixTty for TtyWriteHandle {
@@ -1008,4 +1008,13 @@ impl Client {
         SearchScrollbackResponse
     );
     rpc!(kill_pane, KillPane, UnitResponse);
+    rpc!(swap_panes, SwapPanes, UnitResponse);
+    rpc!(rotate_panes, RotatePanes, UnitResponse);
+    rpc!(
+        move_pane_to_new_tab,
+        MovePaneToNewTab,
+        MovePaneToNewTabResponse
+    );
+    rpc!(move_pane_to_tab, MovePaneToTab, UnitResponse);
+    rpc!(export_pane, ExportPane, ExportPaneResponse);
 }
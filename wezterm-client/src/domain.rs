@@ -2,8 +2,10 @@ use crate::client::Client;
 use crate::pane::ClientPane;
 use anyhow::{anyhow, bail};
 use async_trait::async_trait;
-use codec::{ListPanesResponse, Spawn, SplitPane};
-use config::keyassignment::SpawnTabDomain;
+use codec::{
+    ListPanesResponse, MovePaneToNewTab, MovePaneToTab, RotatePanes, Spawn, SplitPane, SwapPanes,
+};
+use config::keyassignment::{PaneDirection, RotationDirection, SpawnTabDomain};
 use config::{SshDomain, TlsDomainClient, UnixDomain};
 use mux::connui::ConnectionUI;
 use mux::domain::{alloc_domain_id, Domain, DomainId, DomainState};
@@ -204,6 +206,40 @@ impl ClientDomain {
         inner.remote_to_local_pane_id(remote_pane_id)
     }
 
+    /// Resolves a local pane id to the id of its counterpart in the
+    /// remote mux
+    fn remote_pane_id(&self, pane_id: PaneId) -> anyhow::Result<PaneId> {
+        let mux = Mux::get().unwrap();
+        let pane = mux
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("pane_id {} is invalid", pane_id))?;
+        if pane.domain_id() != self.local_domain_id {
+            bail!(
+                "pane_id {} is not in domain {}",
+                pane_id,
+                self.local_domain_id
+            );
+        }
+        let pane = pane
+            .downcast_ref::<ClientPane>()
+            .ok_or_else(|| anyhow!("pane_id {} is not a ClientPane", pane_id))?;
+        Ok(pane.remote_pane_id)
+    }
+
+    /// Called after the remote mux has moved a pane to a different tab.
+    /// The pane is removed from its local tab, without killing it, so that
+    /// the resync places it only in the tab that it now belongs to.
+    async fn resync_after_moving_pane(&self, pane_id: PaneId) -> anyhow::Result<()> {
+        let mux = Mux::get().unwrap();
+        if let Ok(tab) = mux.tab_containing_pane(pane_id) {
+            tab.remove_pane(pane_id);
+        }
+        self.resync().await?;
+        // Remove the local tab if the pane was the only pane in it
+        mux.prune_dead_windows();
+        Ok(())
+    }
+
     pub fn get_client_inner_for_domain(domain_id: DomainId) -> anyhow::Result<Arc<ClientInner>> {
         let mux = Mux::get().unwrap();
         let domain = mux
@@ -253,6 +289,7 @@ impl ClientDomain {
             };
 
             if let Some((remote_window_id, remote_tab_id)) = tabroot.window_and_tab_ids() {
+                let workspace = tabroot.workspace().map(|w| w.to_string());
                 let tab;
 
                 if let Some(tab_id) = inner.remote_to_local_tab_id(remote_tab_id) {
@@ -278,7 +315,13 @@ impl ClientDomain {
                 tab.sync_with_pane_tree(root_size, tabroot, |entry| {
                     if let Some(pane_id) = inner.remote_to_local_pane_id(entry.pane_id) {
                         match mux.get_pane(pane_id) {
-                            Some(pane) => pane,
+                            Some(pane) => {
+                                // The pane may have been moved to a different tab
+                                if let Some(client_pane) = pane.downcast_ref::<ClientPane>() {
+                                    client_pane.set_remote_tab_id(entry.tab_id);
+                                }
+                                pane
+                            }
                             None => {
                                 // We likely decided that we hit EOF on the tab and
                                 // removed it from the mux.  Let's add it back, but
@@ -314,14 +357,19 @@ impl ClientDomain {
                 });
 
                 if let Some(local_window_id) = inner.remote_to_local_window(remote_window_id) {
-                    let mut window = mux
-                        .get_window_mut(local_window_id)
-                        .expect("no such window!?");
-                    if window.idx_by_id(tab.tab_id()).is_none() {
-                        window.push(&tab);
+                    {
+                        let mut window = mux
+                            .get_window_mut(local_window_id)
+                            .expect("no such window!?");
+                        if window.idx_by_id(tab.tab_id()).is_none() {
+                            window.push(&tab);
+                        }
+                    }
+                    if let Some(workspace) = workspace.as_ref() {
+                        mux.set_window_workspace(local_window_id, workspace);
                     }
                 } else {
-                    let local_window_id = mux.new_empty_window();
+                    let local_window_id = mux.new_empty_window(workspace);
                     inner.record_remote_to_local_window_mapping(remote_window_id, *local_window_id);
                     mux.add_tab_to_window(&tab, *local_window_id)?;
                 }
@@ -377,6 +425,10 @@ impl Domain for ClientDomain {
         let inner = self
             .inner()
             .ok_or_else(|| anyhow!("domain is not attached"))?;
+        let workspace = Mux::get()
+            .unwrap()
+            .get_window(window)
+            .map(|w| w.get_workspace().to_string());
         let result = inner
             .client
             .spawn(Spawn {
@@ -385,6 +437,7 @@ impl Domain for ClientDomain {
                 size,
                 command,
                 command_dir,
+                workspace,
             })
             .await?;
 
@@ -467,6 +520,87 @@ impl Domain for ClientDomain {
         Ok(pane)
     }
 
+    async fn swap_panes(&self, pane_id: PaneId, other_pane_id: PaneId) -> anyhow::Result<()> {
+        let inner = self
+            .inner()
+            .ok_or_else(|| anyhow!("domain is not attached"))?;
+        inner
+            .client
+            .swap_panes(SwapPanes {
+                pane_id: self.remote_pane_id(pane_id)?,
+                other_pane_id: self.remote_pane_id(other_pane_id)?,
+            })
+            .await?;
+        self.resync().await
+    }
+
+    async fn rotate_panes(
+        &self,
+        pane_id: PaneId,
+        direction: RotationDirection,
+    ) -> anyhow::Result<()> {
+        let inner = self
+            .inner()
+            .ok_or_else(|| anyhow!("domain is not attached"))?;
+        inner
+            .client
+            .rotate_panes(RotatePanes {
+                pane_id: self.remote_pane_id(pane_id)?,
+                direction,
+            })
+            .await?;
+        self.resync().await
+    }
+
+    async fn move_pane_to_new_tab(
+        &self,
+        pane_id: PaneId,
+        window_id: Option<WindowId>,
+    ) -> anyhow::Result<(Rc<Tab>, WindowId)> {
+        let inner = self
+            .inner()
+            .ok_or_else(|| anyhow!("domain is not attached"))?;
+        let result = inner
+            .client
+            .move_pane_to_new_tab(MovePaneToNewTab {
+                pane_id: self.remote_pane_id(pane_id)?,
+                window_id: window_id.and_then(|w| inner.local_to_remote_window(w)),
+            })
+            .await?;
+
+        self.resync_after_moving_pane(pane_id).await?;
+
+        let mux = Mux::get().unwrap();
+        let tab = inner
+            .remote_to_local_tab_id(result.tab_id)
+            .and_then(|tab_id| mux.get_tab(tab_id))
+            .ok_or_else(|| anyhow!("remote tab {} has no local tab", result.tab_id))?;
+        let window_id = inner
+            .remote_to_local_window(result.window_id)
+            .ok_or_else(|| anyhow!("remote window {} has no local window", result.window_id))?;
+        Ok((tab, window_id))
+    }
+
+    async fn move_pane_to_tab(
+        &self,
+        pane_id: PaneId,
+        target_pane_id: PaneId,
+        direction: PaneDirection,
+    ) -> anyhow::Result<()> {
+        let inner = self
+            .inner()
+            .ok_or_else(|| anyhow!("domain is not attached"))?;
+        inner
+            .client
+            .move_pane_to_tab(MovePaneToTab {
+                pane_id: self.remote_pane_id(pane_id)?,
+                target_pane_id: self.remote_pane_id(target_pane_id)?,
+                direction,
+            })
+            .await?;
+        self.resync_after_moving_pane(pane_id).await
+    }
+
     async fn attach(&self) -> anyhow::Result<()> {
         let domain_id = self.local_domain_id;
         let config = self.config.clone();
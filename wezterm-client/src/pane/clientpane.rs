@@ -28,7 +28,7 @@ pub struct ClientPane {
     client: Arc<ClientInner>,
     local_pane_id: PaneId,
     pub remote_pane_id: PaneId,
-    pub remote_tab_id: TabId,
+    remote_tab_id: RefCell<TabId>,
     pub renderable: RefCell<RenderableState>,
     palette: RefCell<ColorPalette>,
     writer: RefCell<PaneWriter>,
@@ -86,7 +86,7 @@ impl ClientPane {
             mouse,
             remote_pane_id,
             local_pane_id,
-            remote_tab_id,
+            remote_tab_id: RefCell::new(remote_tab_id),
             renderable: RefCell::new(render),
             writer: RefCell::new(writer),
             palette: RefCell::new(palette),
@@ -96,6 +96,11 @@ impl ClientPane {
         }
     }
 
+    /// Called when the remote mux has moved this pane into a different tab
+    pub fn set_remote_tab_id(&self, remote_tab_id: TabId) {
+        *self.remote_tab_id.borrow_mut() = remote_tab_id;
+    }
+
     pub fn process_unilateral(&self, pdu: Pdu) -> anyhow::Result<()> {
         match pdu {
             Pdu::GetPaneRenderChangesResponse(delta) => {
@@ -229,7 +234,7 @@ impl Pane for ClientPane {
         let mut inner = render.inner.borrow_mut();
         let client = Arc::clone(&self.client);
         let remote_pane_id = self.remote_pane_id;
-        let remote_tab_id = self.remote_tab_id;
+        let remote_tab_id = *self.remote_tab_id.borrow();
         // Invalidate any cached rows on a resize
         inner.make_all_stale();
         promise::spawn::spawn(async move {
@@ -262,7 +267,7 @@ impl Pane for ClientPane {
 
             let client = Arc::clone(&self.client);
             let remote_pane_id = self.remote_pane_id;
-            let remote_tab_id = self.remote_tab_id;
+            let remote_tab_id = *self.remote_tab_id.borrow();
             promise::spawn::spawn(async move {
                 client
                     .client
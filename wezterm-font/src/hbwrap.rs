@@ -128,6 +128,15 @@ impl Buffer {
         }
     }
 
+    /// Fill in the script, direction and language of the buffer that
+    /// have not already been set, based on its contents.
+    /// This is only valid after the text has been added.
+    pub fn guess_segment_properties(&mut self) {
+        unsafe {
+            hb_buffer_guess_segment_properties(self.buf);
+        }
+    }
+
     #[allow(dead_code)]
     pub fn add(&mut self, codepoint: hb_codepoint_t, cluster: u32) {
         unsafe {
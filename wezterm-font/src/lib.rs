@@ -31,7 +31,7 @@ pub mod units;
 pub mod fcwrap;
 
 pub use crate::rasterizer::RasterizedGlyph;
-pub use crate::shaper::{FallbackIdx, FontMetrics, GlyphInfo};
+pub use crate::shaper::{Direction, FallbackIdx, FontMetrics, GlyphInfo};
 
 #[derive(Debug, Error)]
 #[error("Font fallback recalculated")]
@@ -81,6 +81,7 @@ impl LoadedFont {
     pub fn shape<F: FnOnce() + Send + Sync + 'static, FS: FnOnce(&mut Vec<char>)>(
         &self,
         text: &str,
+        direction: Direction,
         completion: F,
         filter_out_synthetic: FS,
     ) -> anyhow::Result<Vec<GlyphInfo>> {
@@ -99,10 +100,10 @@ impl LoadedFont {
             }
         }
 
-        let result = self
-            .shaper
-            .borrow()
-            .shape(text, self.font_size, self.dpi, &mut no_glyphs);
+        let result =
+            self.shaper
+                .borrow()
+                .shape(text, direction, self.font_size, self.dpi, &mut no_glyphs);
 
         filter_out_synthetic(&mut no_glyphs);
 
@@ -1,5 +1,5 @@
 use crate::parser::*;
-use crate::shaper::{FallbackIdx, FontMetrics, FontShaper, GlyphInfo};
+use crate::shaper::{Direction, FallbackIdx, FontMetrics, FontShaper, GlyphInfo};
 use crate::units::*;
 use allsorts::binary::read::{ReadScope, ReadScopeOwned};
 use allsorts::font_data_impl::read_cmap_subtable;
@@ -13,7 +13,7 @@ use allsorts::tables::{
 };
 use allsorts::tag;
 use anyhow::{anyhow, bail, Context};
-use termwiz::cell::unicode_column_width;
+use termwiz::cell::UnicodeWidthPolicy;
 use tinyvec::*;
 use unicode_general_category::{get_general_category, GeneralCategory};
 
@@ -245,6 +245,7 @@ impl AllsortsParsedFont {
         lang: u32,
         point_size: f64,
         dpi: u32,
+        width_policy: &UnicodeWidthPolicy,
     ) -> anyhow::Result<Vec<MaybeShaped>> {
         #[derive(Debug)]
         enum Run {
@@ -407,7 +408,7 @@ impl AllsortsParsedFont {
 
                         let text = reverse_engineer_glyph_text(&glyph_info.glyph);
                         let text_len = text.len();
-                        let num_cells = unicode_column_width(&text);
+                        let num_cells = width_policy.str_width(&text);
 
                         let pixel_scale =
                             (dpi as f64 / 72.) * point_size / self.units_per_em as f64;
@@ -443,10 +444,11 @@ impl AllsortsParsedFont {
 
 pub struct AllsortsShaper {
     fonts: Vec<Option<AllsortsParsedFont>>,
+    width_policy: UnicodeWidthPolicy,
 }
 
 impl AllsortsShaper {
-    pub fn new(_: &config::ConfigHandle, handles: &[ParsedFont]) -> anyhow::Result<Self> {
+    pub fn new(config: &config::ConfigHandle, handles: &[ParsedFont]) -> anyhow::Result<Self> {
         let mut fonts = vec![];
         let mut success = false;
         for handle in handles {
@@ -464,7 +466,10 @@ impl AllsortsShaper {
         if !success {
             bail!("failed to load any fonts in this fallback set!?");
         }
-        Ok(Self { fonts })
+        Ok(Self {
+            fonts,
+            width_policy: config.unicode_width_policy(),
+        })
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -531,8 +536,16 @@ impl AllsortsShaper {
             }
         }
 
-        let first_pass =
-            font.shape_text(s, slice_index, font_index, script, lang, font_size, dpi)?;
+        let first_pass = font.shape_text(
+            s,
+            slice_index,
+            font_index,
+            script,
+            lang,
+            font_size,
+            dpi,
+            &self.width_policy,
+        )?;
 
         let mut item_iter = first_pass.into_iter();
         while let Some(item) = item_iter.next() {
@@ -567,6 +580,7 @@ impl FontShaper for AllsortsShaper {
     fn shape(
         &self,
         text: &str,
+        direction: Direction,
         size: f64,
         dpi: u32,
         no_glyphs: &mut Vec<char>,
@@ -576,6 +590,9 @@ impl FontShaper for AllsortsShaper {
         let lang = allsorts::tag::DFLT;
         self.shape_into(0, text, 0, script, lang, size, dpi, &mut results, no_glyphs)?;
         // log::error!("shape {} into {:?}", text, results);
+        if direction == Direction::RightToLeft {
+            results.reverse();
+        }
         Ok(results)
     }
 
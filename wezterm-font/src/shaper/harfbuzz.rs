@@ -1,7 +1,7 @@
 use crate::ftwrap;
 use crate::hbwrap as harfbuzz;
 use crate::parser::ParsedFont;
-use crate::shaper::{FallbackIdx, FontMetrics, FontShaper, GlyphInfo};
+use crate::shaper::{Direction, FallbackIdx, FontMetrics, FontShaper, GlyphInfo};
 use crate::units::*;
 use anyhow::{anyhow, Context};
 use config::ConfigHandle;
@@ -9,7 +9,7 @@ use log::error;
 use ordered_float::NotNan;
 use std::cell::{RefCell, RefMut};
 use std::collections::HashMap;
-use termwiz::cell::unicode_column_width;
+use termwiz::cell::UnicodeWidthPolicy;
 use thiserror::Error;
 use unicode_segmentation::UnicodeSegmentation;
 
@@ -31,8 +31,13 @@ impl<'a> std::fmt::Debug for Info<'a> {
     }
 }
 
-fn make_glyphinfo(text: &str, font_idx: usize, info: &Info) -> GlyphInfo {
-    let num_cells = unicode_column_width(text) as u8;
+fn make_glyphinfo(
+    text: &str,
+    font_idx: usize,
+    info: &Info,
+    width_policy: &UnicodeWidthPolicy,
+) -> GlyphInfo {
+    let num_cells = width_policy.str_width(text) as u8;
     let is_space = text == " ";
     GlyphInfo {
         #[cfg(debug_assertions)]
@@ -69,6 +74,7 @@ pub struct HarfbuzzShaper {
     metrics: RefCell<HashMap<MetricsKey, FontMetrics>>,
     features: Vec<harfbuzz::hb_feature_t>,
     lang: harfbuzz::hb_language_t,
+    width_policy: UnicodeWidthPolicy,
 }
 
 #[derive(Error, Debug)]
@@ -128,6 +134,7 @@ impl HarfbuzzShaper {
             metrics: RefCell::new(HashMap::new()),
             features,
             lang,
+            width_policy: config.unicode_width_policy(),
         })
     }
 
@@ -159,19 +166,33 @@ impl HarfbuzzShaper {
         }
     }
 
+    /// Shape `s`, returning the glyphs in logical order
     fn do_shape(
         &self,
         font_idx: FallbackIdx,
         s: &str,
+        direction: Direction,
         font_size: f64,
         dpi: u32,
         no_glyphs: &mut Vec<char>,
     ) -> anyhow::Result<Vec<GlyphInfo>> {
         let mut buf = harfbuzz::Buffer::new()?;
-        buf.set_script(harfbuzz::hb_script_t::HB_SCRIPT_LATIN);
-        buf.set_direction(harfbuzz::hb_direction_t::HB_DIRECTION_LTR);
         buf.set_language(self.lang);
-        buf.add_str(s);
+        match direction {
+            Direction::LeftToRight => {
+                buf.set_script(harfbuzz::hb_script_t::HB_SCRIPT_LATIN);
+                buf.set_direction(harfbuzz::hb_direction_t::HB_DIRECTION_LTR);
+                buf.add_str(s);
+            }
+            Direction::RightToLeft => {
+                // The script selects the shaper that applies the
+                // joining behavior of eg: Arabic, so it is taken
+                // from the text
+                buf.set_direction(harfbuzz::hb_direction_t::HB_DIRECTION_RTL);
+                buf.add_str(s);
+                buf.guess_segment_properties();
+            }
+        }
         buf.set_cluster_level(
             harfbuzz::hb_buffer_cluster_level_t::HB_BUFFER_CLUSTER_LEVEL_MONOTONE_GRAPHEMES,
         );
@@ -209,8 +230,17 @@ impl HarfbuzzShaper {
             }
         }
 
-        let hb_infos = buf.glyph_infos();
-        let positions = buf.glyph_positions();
+        // The glyphs of right-to-left text are produced in display
+        // order; put them back into logical order so that their
+        // clusters ascend
+        let mut glyphs: Vec<_> = buf
+            .glyph_infos()
+            .iter()
+            .zip(buf.glyph_positions().iter())
+            .collect();
+        if direction == Direction::RightToLeft {
+            glyphs.reverse();
+        }
 
         let mut cluster = Vec::with_capacity(s.len());
 
@@ -227,7 +257,7 @@ impl HarfbuzzShaper {
         // and they're handy to have for debugging
         // purposes too.
         let mut info_clusters: Vec<Vec<Info>> = Vec::with_capacity(s.len());
-        let mut info_iter = hb_infos.iter().zip(positions.iter()).peekable();
+        let mut info_iter = glyphs.into_iter().peekable();
         while let Some((info, pos)) = info_iter.next() {
             let next_pos = info_iter
                 .peek()
@@ -283,14 +313,22 @@ impl HarfbuzzShaper {
                 }
                 */
 
-                let mut shape = match self.do_shape(font_idx + 1, substr, font_size, dpi, no_glyphs)
-                {
-                    Ok(shape) => Ok(shape),
-                    Err(e) => {
-                        error!("{:?} for {:?}", e, substr);
-                        self.do_shape(0, &make_question_string(substr), font_size, dpi, no_glyphs)
-                    }
-                }?;
+                let mut shape =
+                    match self.do_shape(font_idx + 1, substr, direction, font_size, dpi, no_glyphs)
+                    {
+                        Ok(shape) => Ok(shape),
+                        Err(e) => {
+                            error!("{:?} for {:?}", e, substr);
+                            self.do_shape(
+                                0,
+                                &make_question_string(substr),
+                                direction,
+                                font_size,
+                                dpi,
+                                no_glyphs,
+                            )
+                        }
+                    }?;
 
                 // Fixup the cluster member to match our current offset
                 for mut info in &mut shape {
@@ -323,9 +361,9 @@ impl HarfbuzzShaper {
 
                 let glyph = if len > 0 {
                     let text = &substr[next_idx..next_idx + len];
-                    make_glyphinfo(text, font_idx, info)
+                    make_glyphinfo(text, font_idx, info, &self.width_policy)
                 } else {
-                    make_glyphinfo("__", font_idx, info)
+                    make_glyphinfo("__", font_idx, info, &self.width_policy)
                 };
 
                 if glyph.x_advance != PixelLength::new(0.0) {
@@ -365,13 +403,19 @@ impl FontShaper for HarfbuzzShaper {
     fn shape(
         &self,
         text: &str,
+        direction: Direction,
         size: f64,
         dpi: u32,
         no_glyphs: &mut Vec<char>,
     ) -> anyhow::Result<Vec<GlyphInfo>> {
         log::trace!("shape {} `{}`", text.len(), text);
         let start = std::time::Instant::now();
-        let result = self.do_shape(0, text, size, dpi, no_glyphs);
+        let mut result = self.do_shape(0, text, direction, size, dpi, no_glyphs);
+        if direction == Direction::RightToLeft {
+            if let Ok(glyphs) = &mut result {
+                glyphs.reverse();
+            }
+        }
         metrics::histogram!("shape.harfbuzz", start.elapsed());
         /*
         if let Ok(glyphs) = &result {
@@ -516,7 +560,9 @@ mod test {
         let shaper = HarfbuzzShaper::new(&config, &[handle]).unwrap();
         {
             let mut no_glyphs = vec![];
-            let info = shaper.shape("abc", 10., 72, &mut no_glyphs).unwrap();
+            let info = shaper
+                .shape("abc", Direction::LeftToRight, 10., 72, &mut no_glyphs)
+                .unwrap();
             assert!(no_glyphs.is_empty(), "{:?}", no_glyphs);
             assert_eq!(
                 info,
@@ -563,9 +609,29 @@ mod test {
                 ]
             );
         }
+        {
+            // Right-to-left text is returned in display order, with
+            // the clusters still referring to the logical text
+            let mut no_glyphs = vec![];
+            let ltr = shaper
+                .shape("abc", Direction::LeftToRight, 10., 72, &mut no_glyphs)
+                .unwrap();
+            let mut rtl = shaper
+                .shape("abc", Direction::RightToLeft, 10., 72, &mut no_glyphs)
+                .unwrap();
+            assert!(no_glyphs.is_empty(), "{:?}", no_glyphs);
+            assert_eq!(
+                rtl.iter().map(|info| info.cluster).collect::<Vec<_>>(),
+                vec![2, 1, 0]
+            );
+            rtl.reverse();
+            assert_eq!(rtl, ltr);
+        }
         {
             let mut no_glyphs = vec![];
-            let info = shaper.shape("<", 10., 72, &mut no_glyphs).unwrap();
+            let info = shaper
+                .shape("<", Direction::LeftToRight, 10., 72, &mut no_glyphs)
+                .unwrap();
             assert!(no_glyphs.is_empty(), "{:?}", no_glyphs);
             assert_eq!(
                 info,
@@ -588,7 +654,9 @@ mod test {
             // This is a ligatured sequence, but you wouldn't know
             // from this info :-/
             let mut no_glyphs = vec![];
-            let info = shaper.shape("<-", 10., 72, &mut no_glyphs).unwrap();
+            let info = shaper
+                .shape("<-", Direction::LeftToRight, 10., 72, &mut no_glyphs)
+                .unwrap();
             assert!(no_glyphs.is_empty(), "{:?}", no_glyphs);
             assert_eq!(
                 info,
@@ -624,7 +692,9 @@ mod test {
         }
         {
             let mut no_glyphs = vec![];
-            let info = shaper.shape("<--", 10., 72, &mut no_glyphs).unwrap();
+            let info = shaper
+                .shape("<--", Direction::LeftToRight, 10., 72, &mut no_glyphs)
+                .unwrap();
             assert!(no_glyphs.is_empty(), "{:?}", no_glyphs);
             assert_eq!(
                 info,
@@ -4,6 +4,8 @@ use crate::units::PixelLength;
 pub mod allsorts;
 pub mod harfbuzz;
 
+pub use termwiz::bidi::Direction;
+
 /// Holds information about a shaped glyph
 #[derive(Clone, Debug, PartialEq)]
 pub struct GlyphInfo {
@@ -62,10 +64,15 @@ pub struct FontMetrics {
 }
 
 pub trait FontShaper {
-    /// Shape text and return a vector of GlyphInfo
+    /// Shape text and return a vector of GlyphInfo.
+    /// `text` is in logical order and `direction` is the direction
+    /// in which it is laid out.  The glyphs are returned in display
+    /// order, so the glyphs of right-to-left text are in the reverse
+    /// of their logical order.
     fn shape(
         &self,
         text: &str,
+        direction: Direction,
         size: f64,
         dpi: u32,
         no_glyphs: &mut Vec<char>,
@@ -1,5 +1,6 @@
 use config::SshParameters;
 use std::ffi::OsString;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 /// Helper for parsing config overrides
@@ -20,6 +21,14 @@ pub fn name_equals_value(arg: &str) -> Result<(String, String), String> {
     }
 }
 
+/// Helper for parsing a non-negative, finite number
+pub fn non_negative_number(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(value) if value.is_finite() && value >= 0. => Ok(value),
+        _ => Err(format!("Expected a non-negative number, but got {}", arg)),
+    }
+}
+
 #[derive(Debug, StructOpt, Default, Clone)]
 pub struct StartCommand {
     /// If true, do not connect to domains marked as connect_automatically
@@ -119,3 +128,45 @@ pub struct LsFontsCommand {
     #[structopt(long = "text", conflicts_with = "list-system")]
     pub text: Option<String>,
 }
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct RecordCommand {
+    /// The file to which the recording will be written.
+    /// The default is `wezterm-recording-YYYYmmdd-HHMMSS.cast`
+    /// in the current directory.
+    #[structopt(long = "output", short = "o", parse(from_os_str))]
+    pub output: Option<PathBuf>,
+
+    /// Also record the input that is sent to the program.
+    /// Be careful with this option; it will capture any passwords
+    /// that you type!
+    #[structopt(long = "input")]
+    pub input: bool,
+
+    /// Specify the current working directory for the recorded program
+    #[structopt(long = "cwd", parse(from_os_str))]
+    pub cwd: Option<OsString>,
+
+    /// Instead of executing your shell, record PROG.
+    /// For example: `wezterm record -- htop` will record
+    /// a session of htop.
+    #[structopt(parse(from_os_str))]
+    pub prog: Vec<OsString>,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct ReplayCommand {
+    /// Playback speed multiplier; 2 plays back twice as fast
+    /// as the session was recorded.
+    #[structopt(long = "speed", default_value = "1", parse(try_from_str = non_negative_number))]
+    pub speed: f64,
+
+    /// Limit pauses between events in the recording to
+    /// the specified number of seconds
+    #[structopt(long = "max-idle", parse(try_from_str = non_negative_number))]
+    pub max_idle: Option<f64>,
+
+    /// The asciicast file to replay
+    #[structopt(parse(from_os_str))]
+    pub file: PathBuf,
+}
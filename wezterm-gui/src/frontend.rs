@@ -1,15 +1,26 @@
+use crate::scripting::events::{emit_pane_event, PaneEvent, PaneExitStatus};
 use crate::TermWindow;
 use ::window::*;
 use anyhow::Error;
 pub use config::FrontEndSelection;
+use mux::pane::PaneId;
+use mux::window::WindowId as MuxWindowId;
 use mux::{Mux, MuxNotification};
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use wezterm_term::Alert;
 use wezterm_toast_notification::*;
 
 pub struct GuiFrontEnd {
     connection: Rc<Connection>,
+    /// The mux windows that are currently shown by a gui window
+    known_windows: RefCell<HashSet<MuxWindowId>>,
+    /// The workspace whose windows are in `known_windows`
+    known_workspace: RefCell<Option<String>>,
+    /// The most recently seen title of each pane, used to tell
+    /// whether a TitleMaybeChanged alert really changed the title
+    pane_titles: RefCell<HashMap<PaneId, String>>,
 }
 
 impl Drop for GuiFrontEnd {
@@ -21,28 +32,43 @@ impl Drop for GuiFrontEnd {
 impl GuiFrontEnd {
     pub fn try_new() -> anyhow::Result<Rc<GuiFrontEnd>> {
         let connection = Connection::init()?;
-        let front_end = Rc::new(GuiFrontEnd { connection });
+        let front_end = Rc::new(GuiFrontEnd {
+            connection,
+            known_windows: RefCell::new(HashSet::new()),
+            known_workspace: RefCell::new(None),
+            pane_titles: RefCell::new(HashMap::new()),
+        });
         let mux = Mux::get().expect("mux started and running on main thread");
         let fe = Rc::downgrade(&front_end);
         mux.subscribe(move |n| {
-            if let Some(_fe) = fe.upgrade() {
+            if let Some(fe) = fe.upgrade() {
                 match n {
-                    MuxNotification::WindowCreated(mux_window_id) => {
-                        promise::spawn::spawn(async move {
-                            if let Err(err) = TermWindow::new_window(mux_window_id).await {
-                                log::error!("Failed to create window: {:#}", err);
-                                let mux = Mux::get().expect("subscribe to trigger on main thread");
-                                mux.kill_window(mux_window_id);
-                            }
-                            anyhow::Result::<()>::Ok(())
-                        })
-                        .detach();
+                    MuxNotification::WindowCreated(_)
+                    | MuxNotification::WindowRemoved(_)
+                    | MuxNotification::WindowWorkspaceChanged(_)
+                    | MuxNotification::ActiveWorkspaceChanged(_) => {
+                        fe.schedule_reconcile_workspace();
+                    }
+                    MuxNotification::PaneRemoved(pane_id) => {
+                        fe.pane_titles.borrow_mut().remove(&pane_id);
                     }
-                    MuxNotification::WindowRemoved(_) => {}
-                    MuxNotification::PaneRemoved(_) => {}
                     MuxNotification::WindowInvalidated(_) => {}
                     MuxNotification::PaneOutput(_) => {}
-                    MuxNotification::PaneAdded(_) => {}
+                    MuxNotification::PaneAdded(pane_id) => {
+                        emit_tab_pane_event(pane_id, PaneEvent::Spawned);
+                    }
+                    MuxNotification::PaneExited { pane_id, status } => {
+                        // Only a LocalPane reports its exit, and it has
+                        // usually been pruned from its tab by now, so
+                        // this one is not filtered
+                        emit_pane_event(
+                            pane_id,
+                            PaneEvent::Exited(PaneExitStatus {
+                                success: status.success(),
+                                exit_code: status.exit_code(),
+                            }),
+                        );
+                    }
                     MuxNotification::Alert {
                         pane_id: _,
                         alert:
@@ -60,20 +86,39 @@ impl GuiFrontEnd {
                         persistent_toast_notification(title, message);
                     }
                     MuxNotification::Alert {
-                        pane_id: _,
+                        pane_id,
                         alert: Alert::Bell,
                     } => {
                         // persistent_toast_notification("Ding!", "This is the bell");
                         log::info!("Ding! (this is the bell)");
+                        emit_tab_pane_event(pane_id, PaneEvent::Bell);
                     }
                     MuxNotification::Alert {
                         pane_id: _,
                         alert: Alert::PaletteChanged,
-                    }
-                    | MuxNotification::Alert {
-                        pane_id: _,
-                        alert: Alert::TitleMaybeChanged,
                     } => {}
+                    MuxNotification::Alert {
+                        pane_id,
+                        alert: Alert::TitleMaybeChanged,
+                    } => {
+                        fe.check_pane_title(pane_id);
+                    }
+                    MuxNotification::Alert {
+                        pane_id,
+                        alert: Alert::CurrentWorkingDirectoryChanged,
+                    } => {
+                        let mux = Mux::get().expect("subscribe to trigger on main thread");
+                        if let Some(pane) = mux.get_pane(pane_id) {
+                            let cwd = pane.get_current_working_dir().map(|u| u.to_string());
+                            emit_tab_pane_event(pane_id, PaneEvent::CwdChanged(cwd));
+                        }
+                    }
+                    MuxNotification::Alert {
+                        pane_id,
+                        alert: Alert::SetUserVar { name, value },
+                    } => {
+                        emit_tab_pane_event(pane_id, PaneEvent::UserVarChanged { name, value });
+                    }
                     MuxNotification::Empty => {
                         if mux::activity::Activity::count() == 0 {
                             log::trace!("Mux is now empty, terminate gui");
@@ -89,6 +134,87 @@ impl GuiFrontEnd {
         Ok(front_end)
     }
 
+    /// Emits the pane-title-changed event if the title of the pane
+    /// differs from the one that was last seen
+    fn check_pane_title(&self, pane_id: PaneId) {
+        let mux = Mux::get().expect("subscribe to trigger on main thread");
+        let title = match mux.get_pane(pane_id) {
+            Some(pane) => pane.get_title(),
+            None => return,
+        };
+        let mut pane_titles = self.pane_titles.borrow_mut();
+        if pane_titles.get(&pane_id) == Some(&title) {
+            return;
+        }
+        pane_titles.insert(pane_id, title.clone());
+        emit_tab_pane_event(pane_id, PaneEvent::TitleChanged(title));
+    }
+
+    fn schedule_reconcile_workspace(self: &Rc<Self>) {
+        let fe = Rc::downgrade(self);
+        promise::spawn::spawn(async move {
+            if let Some(fe) = fe.upgrade() {
+                fe.reconcile_workspace();
+            }
+        })
+        .detach();
+    }
+
+    /// Creates gui windows for the mux windows in the active workspace
+    /// that don't yet have one.  The gui windows for mux windows in
+    /// other workspaces close themselves when the workspace changes.
+    /// If the last window in the active workspace was closed or moved
+    /// away, switches to the first of the remaining workspaces.
+    fn reconcile_workspace(&self) {
+        let mux = Mux::get().expect("mux started and running on main thread");
+        let workspace = mux.active_workspace();
+        let mut known_windows = self.known_windows.borrow_mut();
+
+        let was_shown = self.known_workspace.borrow().as_deref() == Some(workspace.as_str())
+            && !known_windows.is_empty();
+        *self.known_workspace.borrow_mut() = Some(workspace.clone());
+
+        known_windows.retain(|&mux_window_id| {
+            mux.get_window(mux_window_id)
+                .map_or(false, |w| w.get_workspace() == workspace)
+        });
+
+        let windows = mux.iter_windows_in_workspace(&workspace);
+        if was_shown && windows.is_empty() {
+            // Nothing would be left on screen.  If there are no windows
+            // in any workspace then the mux is empty, and we'll terminate
+            // when it tells us so.
+            drop(known_windows);
+            if let Some(other) = mux.iter_workspaces().into_iter().next() {
+                log::trace!(
+                    "workspace {} is now empty, switching to {}",
+                    workspace,
+                    other
+                );
+                mux.set_active_workspace(&other);
+            }
+            return;
+        }
+
+        for mux_window_id in windows {
+            // Windows are announced once their first tab has been added;
+            // don't show a window that is still being built
+            let is_empty = mux.get_window(mux_window_id).map_or(true, |w| w.is_empty());
+            if is_empty || !known_windows.insert(mux_window_id) {
+                continue;
+            }
+            promise::spawn::spawn(async move {
+                if let Err(err) = TermWindow::new_window(mux_window_id).await {
+                    log::error!("Failed to create window: {:#}", err);
+                    let mux = Mux::get().expect("subscribe to trigger on main thread");
+                    mux.kill_window(mux_window_id);
+                }
+                anyhow::Result::<()>::Ok(())
+            })
+            .detach();
+        }
+    }
+
     pub fn run_forever(&self) -> anyhow::Result<()> {
         self.connection.run_message_loop()
     }
@@ -98,6 +224,16 @@ thread_local! {
     static FRONT_END: RefCell<Option<Rc<GuiFrontEnd>>> = RefCell::new(None);
 }
 
+/// Overlays such as the launcher and the command palette are panes
+/// that are not placed in any tab; the lua pane events are only
+/// emitted for the panes that are
+fn emit_tab_pane_event(pane_id: PaneId, event: PaneEvent) {
+    let mux = Mux::get().expect("subscribe to trigger on main thread");
+    if mux.tab_containing_pane(pane_id).is_ok() {
+        emit_pane_event(pane_id, event);
+    }
+}
+
 pub fn front_end() -> Option<Rc<GuiFrontEnd>> {
     let mut res = None;
     FRONT_END.with(|f| {
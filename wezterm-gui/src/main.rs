@@ -3,18 +3,25 @@
 
 use crate::frontend::front_end;
 use ::window::*;
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use mux::activity::Activity;
+use mux::asciicast::{duration_from_secs, read_recording, Event, ReplayTiming};
 use mux::domain::{Domain, LocalDomain};
+use mux::localpane::LocalPane;
+use mux::termwiztermtab::TermWizTerminal;
 use mux::Mux;
 use portable_pty::cmdbuilder::CommandBuilder;
+use portable_pty::PtySize;
 use promise::spawn::block_on;
 use std::ffi::OsString;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
 use structopt::StructOpt;
 use termwiz::cell::CellAttributes;
-use termwiz::surface::Line;
+use termwiz::input::InputEvent;
+use termwiz::surface::{Change, Line};
+use termwiz::terminal::Terminal;
 use wezterm_client::domain::{ClientDomain, ClientDomainConfig};
 use wezterm_gui_subcommands::*;
 use wezterm_ssh::*;
@@ -90,6 +97,15 @@ enum SubCommand {
 
     #[structopt(name = "ls-fonts", about = "Display information about fonts")]
     LsFonts(LsFontsCommand),
+
+    #[structopt(
+        name = "record",
+        about = "Record a terminal session in asciicast format"
+    )]
+    Record(RecordCommand),
+
+    #[structopt(name = "replay", about = "Replay an asciicast recording")]
+    Replay(ReplayCommand),
 }
 
 async fn async_run_ssh(opts: SshCommand) -> anyhow::Result<()> {
@@ -137,7 +153,7 @@ async fn async_run_ssh(opts: SshCommand) -> anyhow::Result<()> {
     let local_domain: Arc<dyn Domain> = Arc::new(LocalDomain::new("local")?);
     mux.add_domain(&local_domain);
 
-    let window_id = mux.new_empty_window();
+    let window_id = mux.new_empty_window(None);
     let _tab = domain
         .spawn(config.initial_size(), cmd, None, *window_id)
         .await?;
@@ -184,7 +200,7 @@ fn run_serial(config: config::ConfigHandle, opts: &SerialCommand) -> anyhow::Res
     block_on(domain.attach())?; // FIXME: blocking
 
     {
-        let window_id = mux.new_empty_window();
+        let window_id = mux.new_empty_window(None);
         // FIXME: blocking
         let _tab = block_on(domain.spawn(config.initial_size(), None, None, *window_id))?;
     }
@@ -193,6 +209,102 @@ fn run_serial(config: config::ConfigHandle, opts: &SerialCommand) -> anyhow::Res
     gui.run_forever()
 }
 
+fn run_record(config: config::ConfigHandle, opts: RecordCommand) -> anyhow::Result<()> {
+    let output = match opts.output {
+        Some(output) => output,
+        None => PathBuf::from(
+            chrono::Local::now()
+                .format("wezterm-recording-%Y%m%d-%H%M%S.cast")
+                .to_string(),
+        ),
+    };
+
+    let prog = opts.prog.iter().map(|s| s.as_os_str()).collect::<Vec<_>>();
+    let mut builder = config.build_prog(if prog.is_empty() { None } else { Some(prog) })?;
+    if let Some(cwd) = opts.cwd {
+        builder.cwd(cwd);
+    }
+
+    let domain: Arc<dyn Domain> = Arc::new(LocalDomain::new("local")?);
+    let mux = Rc::new(mux::Mux::new(Some(domain.clone())));
+    Mux::set_mux(&mux);
+    crate::update::load_last_release_info_and_set_banner();
+
+    let gui = crate::frontend::try_new()?;
+    block_on(domain.attach())?; // FIXME: blocking
+
+    {
+        let window_id = mux.new_empty_window(None);
+        // FIXME: blocking
+        let tab = block_on(domain.spawn(config.initial_size(), Some(builder), None, *window_id))?;
+        let pane = tab
+            .get_active_pane()
+            .ok_or_else(|| anyhow!("newly spawned tab has no pane"))?;
+        let pane = pane
+            .downcast_ref::<LocalPane>()
+            .ok_or_else(|| anyhow!("newly spawned pane is not a LocalPane"))?;
+        pane.start_recording(&output, opts.input)?;
+    }
+
+    maybe_show_configuration_error_window();
+    gui.run_forever()
+}
+
+fn replay_recording(
+    mut term: TermWizTerminal,
+    events: Vec<Event>,
+    timing: ReplayTiming,
+) -> anyhow::Result<()> {
+    term.no_grab_mouse_in_raw_mode();
+    if mux::asciicast::replay(&mut term, &events, timing)? {
+        term.render(&[
+            Change::AllAttributes(CellAttributes::default()),
+            Change::Text("\r\n[replay finished; press any key to close]".to_string()),
+        ])?;
+        loop {
+            if let Some(InputEvent::Key(_)) = term.poll_input(None)? {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_replay(opts: ReplayCommand) -> anyhow::Result<()> {
+    let file = std::fs::File::open(&opts.file)
+        .with_context(|| format!("opening {}", opts.file.display()))?;
+    let (header, events) = read_recording(std::io::BufReader::new(file))
+        .with_context(|| format!("reading {}", opts.file.display()))?;
+    let timing = ReplayTiming {
+        speed: opts.speed,
+        max_idle: opts.max_idle.map(duration_from_secs),
+    };
+    let size = PtySize {
+        rows: header.height.max(1) as u16,
+        cols: header.width.max(1) as u16,
+        pixel_width: 0,
+        pixel_height: 0,
+    };
+
+    let mux = Rc::new(mux::Mux::new(None));
+    Mux::set_mux(&mux);
+
+    let gui = crate::frontend::try_new()?;
+
+    promise::spawn::spawn(async move {
+        let result =
+            mux::termwiztermtab::run(size, move |term| replay_recording(term, events, timing))
+                .await;
+        if let Err(err) = result {
+            terminate_with_error(err);
+        }
+    })
+    .detach();
+
+    maybe_show_configuration_error_window();
+    gui.run_forever()
+}
+
 fn client_domains(config: &config::ConfigHandle) -> Vec<ClientDomainConfig> {
     let mut domains = vec![];
     for unix_dom in &config.unix_domains {
@@ -271,7 +383,7 @@ async fn spawn_tab_in_default_domain_if_mux_is_empty(
     }
 
     let config = config::configuration();
-    let window_id = mux.new_empty_window();
+    let window_id = mux.new_empty_window(None);
     let _tab = domain
         .spawn(config.initial_size(), cmd, None, *window_id)
         .await?;
@@ -437,7 +549,9 @@ pub fn run_ls_fonts(config: config::ConfigHandle, cmd: &LsFontsCommand) -> anyho
             let style = font_config.match_style(&config, &cluster.attrs);
             let font = font_config.resolve_font(style)?;
             let handles = font.clone_handles();
-            let infos = font.shape(&cluster.text, || {}, |_| {}).unwrap();
+            let infos = font
+                .shape(&cluster.text, cluster.direction, || {}, |_| {})
+                .unwrap();
 
             for info in infos {
                 let parsed = &handles[info.font_idx];
@@ -609,5 +723,7 @@ fn run() -> anyhow::Result<()> {
         SubCommand::Serial(serial) => run_serial(config, &serial),
         SubCommand::Connect(connect) => run_mux_client(config, &connect),
         SubCommand::LsFonts(cmd) => run_ls_fonts(config, &cmd),
+        SubCommand::Record(cmd) => run_record(config, cmd),
+        SubCommand::Replay(cmd) => run_replay(cmd),
     }
 }
@@ -0,0 +1,205 @@
+//! The color scheme picker lists the built-in and user color schemes.
+//! Typing filters the list by name with a fuzzy match.  As the
+//! selection moves, the selected scheme is applied to the window via
+//! its config overrides so that it can be previewed; Enter keeps it
+//! while Escape restores the colors that were in effect before.
+use super::fuzzy::{highlight, rank, run_picker, Candidate, PickerDelegate};
+use crate::termwindow::TermWindowNotif;
+use config::Palette;
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::cell::{AttributeChange, CellAttributes};
+use termwiz::color::ColorAttribute;
+use termwiz::input::Modifiers;
+use termwiz::surface::Change;
+use window::WindowOps;
+
+/// Sample text shown in the foreground color of the scheme
+const SAMPLE: &str = " Aa ";
+
+/// The width of the swatch that follows the name of each scheme:
+/// the sample text followed by two cells for each of the 16 colors
+const SWATCH_WIDTH: usize = 4 + 1 + 16 * 2;
+
+fn filter_schemes(schemes: &[(String, Palette)], filter: &str) -> Vec<Candidate> {
+    // The schemes are already sorted by name, so there is no
+    // recency ordering to apply
+    rank(
+        schemes.iter().map(|(name, _)| name.as_str()),
+        filter,
+        |_| None,
+    )
+}
+
+/// Returns the changes that render a swatch for `palette`: some sample
+/// text in its foreground and background colors, followed by its
+/// ansi and bright colors
+fn swatch(palette: &Palette) -> Vec<Change> {
+    let color = |c: Option<config::RgbColor>| match c {
+        Some(c) => ColorAttribute::TrueColorWithDefaultFallback(c),
+        None => ColorAttribute::Default,
+    };
+    let mut changes = vec![
+        AttributeChange::Foreground(color(palette.foreground)).into(),
+        AttributeChange::Background(color(palette.background)).into(),
+        Change::Text(SAMPLE.to_string()),
+        Change::AllAttributes(CellAttributes::default()),
+        Change::Text(" ".to_string()),
+    ];
+    for colors in [palette.ansi, palette.brights].iter() {
+        for idx in 0..8 {
+            changes.push(
+                AttributeChange::Background(color(colors.as_ref().map(|colors| colors[idx])))
+                    .into(),
+            );
+            changes.push(Change::Text("  ".to_string()));
+        }
+    }
+    changes.push(Change::AllAttributes(CellAttributes::default()));
+    changes
+}
+
+/// Returns `overrides` with `color_scheme` set to `name`.
+/// `colors` is removed, as the scheme should be shown as-is.
+fn overrides_with_scheme(overrides: &serde_json::Value, name: &str) -> serde_json::Value {
+    let mut overrides = match overrides {
+        serde_json::Value::Object(map) => map.clone(),
+        _ => serde_json::Map::new(),
+    };
+    overrides.remove("colors");
+    overrides.insert(
+        "color_scheme".to_string(),
+        serde_json::Value::String(name.to_string()),
+    );
+    serde_json::Value::Object(overrides)
+}
+
+struct ColorSchemePicker {
+    schemes: Vec<(String, Palette)>,
+    /// The scheme that was configured when the picker was shown
+    current: Option<String>,
+    overrides: serde_json::Value,
+    window: ::window::Window,
+    /// The scheme that is currently being previewed, if any
+    previewing: Option<usize>,
+    /// Set when a scheme is chosen, so that it is kept rather than
+    /// restoring the prior colors
+    keep: bool,
+}
+
+impl ColorSchemePicker {
+    fn preview(&mut self, idx: usize) {
+        if self.previewing != Some(idx) {
+            self.previewing.replace(idx);
+            self.window
+                .notify(TermWindowNotif::SetConfigOverrides(overrides_with_scheme(
+                    &self.overrides,
+                    &self.schemes[idx].0,
+                )));
+        }
+    }
+}
+
+impl PickerDelegate for ColorSchemePicker {
+    type Row = Candidate;
+
+    fn filter(&self, filter: &str) -> Vec<Candidate> {
+        filter_schemes(&self.schemes, filter)
+    }
+
+    fn initial_selection(&self, _rows: &[Candidate]) -> usize {
+        self.current
+            .as_ref()
+            .and_then(|current| self.schemes.iter().position(|(name, _)| name == current))
+            .unwrap_or(0)
+    }
+
+    fn render_row(&self, candidate: &Candidate, _is_selected: bool, width: usize) -> Vec<Change> {
+        let (name, palette) = &self.schemes[candidate.idx];
+        let name_width = width.saturating_sub(SWATCH_WIDTH + 2).max(1);
+
+        let mut changes = vec![Change::Text(" ".to_string())];
+        let (text, text_width) = highlight(name, &candidate.positions, name_width - 1);
+        changes.extend(text);
+        changes.push(Change::Text(" ".repeat(name_width - text_width)));
+
+        // Only the name is shown in reverse video when selected
+        changes.push(AttributeChange::Reverse(false).into());
+        changes.push(Change::Text(" ".to_string()));
+        if width > SWATCH_WIDTH {
+            changes.extend(swatch(palette));
+        }
+        changes
+    }
+
+    fn selection_changed(&mut self, candidate: &Candidate) {
+        self.preview(candidate.idx);
+    }
+
+    fn accept(&mut self, candidate: &Candidate, _mods: Modifiers, _filter: &str) {
+        // Enter keeps whatever is selected, even if the selection
+        // was never moved to it and so isn't being previewed yet
+        self.preview(candidate.idx);
+        self.keep = true;
+    }
+}
+
+pub fn color_scheme_picker(
+    mut term: TermWizTerminal,
+    schemes: Vec<(String, Palette)>,
+    current: Option<String>,
+    overrides: serde_json::Value,
+    window: ::window::Window,
+) -> anyhow::Result<()> {
+    let mut picker = ColorSchemePicker {
+        schemes,
+        current,
+        overrides,
+        window,
+        previewing: None,
+        keep: false,
+    };
+    run_picker(
+        &mut term,
+        "Color Schemes",
+        "Type to filter, move to preview, Enter to keep the scheme, Escape to cancel",
+        &mut picker,
+    )?;
+
+    if !picker.keep && picker.previewing.is_some() {
+        picker
+            .window
+            .notify(TermWindowNotif::SetConfigOverrides(picker.overrides));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn overrides() {
+        // Other overrides are retained, but explicit colors are
+        // removed so that the scheme is shown as-is
+        let overrides = json!({
+            "font_size": 14.0,
+            "colors": {"foreground": "red"},
+            "color_scheme": "Dracula",
+        });
+        assert_eq!(
+            overrides_with_scheme(&overrides, "Builtin Dark"),
+            json!({
+                "font_size": 14.0,
+                "color_scheme": "Builtin Dark",
+            })
+        );
+
+        // There may not be any overrides yet
+        assert_eq!(
+            overrides_with_scheme(&serde_json::Value::Null, "Builtin Dark"),
+            json!({"color_scheme": "Builtin Dark"})
+        );
+    }
+}
@@ -3,6 +3,7 @@ use chrono::prelude::*;
 use log::Level;
 use luahelper::ValueWrapper;
 use mlua::Value;
+use mux::pane::PaneId;
 use mux::termwiztermtab::TermWizTerminal;
 use termwiz::cell::{AttributeChange, CellAttributes, Intensity};
 use termwiz::color::AnsiColor;
@@ -10,6 +11,7 @@ use termwiz::input::{InputEvent, KeyCode, KeyEvent};
 use termwiz::lineedit::*;
 use termwiz::surface::Change;
 use termwiz::terminal::Terminal;
+use wezterm_term::MemoryUsage;
 
 struct LuaReplHost {
     history: BasicHistory,
@@ -42,12 +44,20 @@ impl LineEditorHost for LuaReplHost {
         }
     }
 
+    fn is_line_complete(&self, line: &str) -> bool {
+        !matches!(
+            compile_line(&self.lua, line),
+            Err(mlua::Error::SyntaxError {
+                incomplete_input: true,
+                ..
+            })
+        )
+    }
+
     fn render_preview(&self, line: &str) -> Vec<OutputElement> {
-        let expr = format!("return {}", line);
         let mut preview = vec![];
 
-        let chunk = self.lua.load(&expr);
-        match chunk.into_function() {
+        match compile_line(&self.lua, line) {
             Ok(_) => {}
             Err(err) => {
                 let text = match &err {
@@ -65,7 +75,105 @@ impl LineEditorHost for LuaReplHost {
     }
 }
 
-pub fn show_debug_overlay(mut term: TermWizTerminal, gui_win: GuiWin) -> anyhow::Result<()> {
+/// Compiles the line entered into the repl.  The line is first treated
+/// as an expression so that its value can be shown, falling back to
+/// treating it as a statement such as `x = 1` or `for i=1,3 do`.
+/// If either form needs more input, the incomplete input error is
+/// returned so that the editor can continue on the next line.
+fn compile_line<'lua>(lua: &'lua mlua::Lua, line: &str) -> mlua::Result<mlua::Function<'lua>> {
+    let expr = format!("return {}", line);
+    let expr_err = match lua.load(&expr).into_function() {
+        Ok(func) => return Ok(func),
+        Err(err) => err,
+    };
+    match lua.load(line).into_function() {
+        Ok(func) => Ok(func),
+        Err(
+            err @ mlua::Error::SyntaxError {
+                incomplete_input: true,
+                ..
+            },
+        ) => Err(err),
+        Err(err) => match expr_err {
+            mlua::Error::SyntaxError {
+                incomplete_input: true,
+                ..
+            } => Err(expr_err),
+            _ => Err(err),
+        },
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Describes the lines held in `usage` and the memory that they use
+fn describe_usage(usage: &MemoryUsage) -> String {
+    let mut result = format!(
+        "{} lines, {} compressed, {}",
+        usage.lines,
+        usage.compressed_lines,
+        format_bytes(usage.bytes)
+    );
+    if usage.disk_lines > 0 {
+        result.push_str(&format!(
+            ", {} lines on disk using {}",
+            usage.disk_lines,
+            format_bytes(usage.disk_bytes)
+        ));
+    }
+    result
+}
+
+/// Renders a summary of the memory used to hold the lines of each
+/// of the local panes, followed by the total
+fn render_memory_usage(
+    term: &mut TermWizTerminal,
+    memory_usage: &[(PaneId, String, MemoryUsage)],
+) -> termwiz::Result<()> {
+    if memory_usage.is_empty() {
+        return Ok(());
+    }
+
+    let mut changes = vec![
+        AttributeChange::Intensity(Intensity::Bold).into(),
+        Change::Text("Scrollback memory usage:\r\n".to_string()),
+        Change::AllAttributes(CellAttributes::default()),
+    ];
+    let mut total = MemoryUsage::default();
+    for (pane_id, title, usage) in memory_usage {
+        changes.push(Change::Text(format!(
+            "  pane {} ({}): {}\r\n",
+            pane_id,
+            title,
+            describe_usage(usage)
+        )));
+        total = total + *usage;
+    }
+    changes.push(Change::Text(format!(
+        "  total: {}\r\n",
+        describe_usage(&total)
+    )));
+    term.render(&changes)
+}
+
+pub fn show_debug_overlay(
+    mut term: TermWizTerminal,
+    gui_win: GuiWin,
+    memory_usage: Vec<(PaneId, String, MemoryUsage)>,
+) -> anyhow::Result<()> {
     term.no_grab_mouse_in_raw_mode();
 
     let lua = config::Config::load()?
@@ -81,6 +189,7 @@ pub fn show_debug_overlay(mut term: TermWizTerminal, gui_win: GuiWin) -> anyhow:
     };
 
     term.render(&[Change::Title("Debug".to_string())])?;
+    render_memory_usage(&mut term, &memory_usage)?;
 
     fn print_new_log_entries(
         term: &mut TermWizTerminal,
@@ -139,9 +248,7 @@ pub fn show_debug_overlay(mut term: TermWizTerminal, gui_win: GuiWin) -> anyhow:
             }
             host.history().add(&line);
 
-            let expr = format!("return {}", line);
-            let chunk = host.lua.load(&expr);
-            match chunk.eval::<Value>() {
+            match compile_line(&host.lua, &line).and_then(|func| func.call::<_, Value>(())) {
                 Ok(result) => {
                     let text = format!("{:?}", ValueWrapper(result));
                     term.render(&[Change::Text(format!("{}\r\n", text.replace("\n", "\r\n")))])?;
@@ -162,3 +269,36 @@ pub fn show_debug_overlay(mut term: TermWizTerminal, gui_win: GuiWin) -> anyhow:
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn is_incomplete(lua: &mlua::Lua, line: &str) -> bool {
+        matches!(
+            compile_line(lua, line),
+            Err(mlua::Error::SyntaxError {
+                incomplete_input: true,
+                ..
+            })
+        )
+    }
+
+    #[test]
+    fn compile_repl_lines() {
+        let lua = mlua::Lua::new();
+
+        let value: i64 = compile_line(&lua, "1 + 2").unwrap().call(()).unwrap();
+        assert_eq!(value, 3);
+        compile_line(&lua, "x = 1").unwrap();
+        compile_line(&lua, "for i=1,3 do end").unwrap();
+
+        assert!(is_incomplete(&lua, "for i=1,3 do"));
+        assert!(is_incomplete(&lua, "function foo()"));
+        assert!(is_incomplete(&lua, "1 +"));
+        assert!(is_incomplete(&lua, "{1,"));
+
+        assert!(compile_line(&lua, "x = = 1").is_err());
+        assert!(!is_incomplete(&lua, "x = = 1"));
+    }
+}
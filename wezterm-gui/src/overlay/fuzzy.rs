@@ -0,0 +1,532 @@
+//! A small fuzzy matcher for filtering the entries shown in overlays.
+//! A pattern matches some text if all of the characters in the pattern
+//! appear in the text in the same order, ignoring case.  Matches are
+//! scored so that consecutive runs of characters and characters at the
+//! start of words rank higher than scattered matches.
+//! This module also holds the ranking and highlighting helpers, and the
+//! picker that presents a filterable list, that are shared by the
+//! overlays that use it.
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::cell::{unicode_column_width, AttributeChange, CellAttributes, Intensity, Underline};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+
+/// Describes a successful match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    /// Higher is better
+    pub score: i64,
+    /// The char (not byte!) indices of the text that matched the pattern
+    pub positions: Vec<usize>,
+}
+
+const MATCH_SCORE: i64 = 16;
+const CONSECUTIVE_BONUS: i64 = 24;
+const WORD_START_BONUS: i64 = 20;
+const GAP_PENALTY: i64 = 1;
+const MAX_LEADING_PENALTY: i64 = 12;
+
+fn is_word_start(chars: &[char], idx: usize) -> bool {
+    if idx == 0 {
+        return true;
+    }
+    let prior = chars[idx - 1];
+    let c = chars[idx];
+    !prior.is_alphanumeric() || (prior.is_lowercase() && c.is_uppercase())
+}
+
+fn chars_equal(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+/// Match `pattern` against `text`.  An empty pattern matches
+/// everything with a score of zero.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let pattern: Vec<char> = pattern.chars().filter(|c| !c.is_whitespace()).collect();
+    let chars: Vec<char> = text.chars().collect();
+
+    if pattern.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: vec![],
+        });
+    }
+
+    // Each pattern character matches its first occurrence, unless
+    // there is a word start or a continuation of the current run
+    // further along that still leaves room for the rest of the pattern
+    let mut positions = Vec::with_capacity(pattern.len());
+    let mut text_idx = 0;
+    for (pat_idx, &p) in pattern.iter().enumerate() {
+        let remaining = &pattern[pat_idx + 1..];
+        let mut found = None;
+        let mut idx = text_idx;
+        while idx < chars.len() {
+            if chars_equal(chars[idx], p) {
+                if found.is_none() {
+                    found = Some(idx);
+                }
+                // Prefer a later word start or a position that continues
+                // a run, as long as the rest of the pattern still fits
+                let continues_run = positions.last().map(|&last| last + 1 == idx) == Some(true);
+                if continues_run || is_word_start(&chars, idx) {
+                    if can_match(remaining, &chars[idx + 1..]) {
+                        found = Some(idx);
+                    }
+                    break;
+                }
+            }
+            idx += 1;
+        }
+        let idx = found?;
+        positions.push(idx);
+        text_idx = idx + 1;
+    }
+
+    let mut score = 0;
+    let mut prior: Option<usize> = None;
+    for &idx in &positions {
+        score += MATCH_SCORE;
+        if is_word_start(&chars, idx) {
+            score += WORD_START_BONUS;
+        }
+        match prior {
+            Some(p) if p + 1 == idx => score += CONSECUTIVE_BONUS,
+            Some(p) => score -= GAP_PENALTY * (idx - p - 1) as i64,
+            None => score -= (GAP_PENALTY * idx as i64).min(MAX_LEADING_PENALTY),
+        }
+        prior = Some(idx);
+    }
+
+    Some(FuzzyMatch { score, positions })
+}
+
+/// An entry that matches the filter
+#[derive(Debug, PartialEq)]
+pub struct Candidate {
+    /// Index into the list of entries
+    pub idx: usize,
+    /// The char positions of the label that matched the filter
+    pub positions: Vec<usize>,
+}
+
+/// Returns the labels that match `filter`, best matches first.
+/// `recency` returns the position of the entry with the specified index
+/// in the list of recently used entries, if any; recently used entries
+/// are ranked ahead of
+/// others with the same score.  When the filter is empty every entry
+/// has the same score, so the recently used entries are listed first.
+pub fn rank<'a, I, F>(labels: I, filter: &str, recency: F) -> Vec<Candidate>
+where
+    I: IntoIterator<Item = &'a str>,
+    F: Fn(usize) -> Option<usize>,
+{
+    let mut matches: Vec<(i64, usize, Candidate)> = labels
+        .into_iter()
+        .enumerate()
+        .filter_map(|(idx, label)| {
+            let m = fuzzy_match(filter, label)?;
+            Some((
+                m.score,
+                recency(idx).unwrap_or(usize::MAX),
+                Candidate {
+                    idx,
+                    positions: m.positions,
+                },
+            ))
+        })
+        .collect();
+    matches.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then(a.1.cmp(&b.1))
+            .then(a.2.idx.cmp(&b.2.idx))
+    });
+    matches.into_iter().map(|(_, _, c)| c).collect()
+}
+
+/// The number of entries to remember in a recently used list
+const MAX_RECENT: usize = 32;
+
+/// Move `item` to the front of the `recent` list
+pub fn record_recent<T: PartialEq>(recent: &mut Vec<T>, item: T) {
+    recent.retain(|r| *r != item);
+    recent.insert(0, item);
+    recent.truncate(MAX_RECENT);
+}
+
+/// Produces the changes to render `text` with the characters at
+/// `positions` highlighted, truncated so that it occupies no more than
+/// `max_width` cells.  Returns the changes and the width that they occupy.
+pub fn highlight(text: &str, positions: &[usize], max_width: usize) -> (Vec<Change>, usize) {
+    let mut changes = vec![];
+    let mut width = 0;
+    for (idx, c) in text.chars().enumerate() {
+        let s = c.to_string();
+        let c_width = unicode_column_width(&s);
+        if width + c_width > max_width {
+            break;
+        }
+        width += c_width;
+        let matched = positions.contains(&idx);
+        if matched {
+            changes.push(AttributeChange::Intensity(Intensity::Bold).into());
+            changes.push(AttributeChange::Underline(Underline::Single).into());
+        }
+        changes.push(Change::Text(s));
+        if matched {
+            changes.push(AttributeChange::Intensity(Intensity::Normal).into());
+            changes.push(AttributeChange::Underline(Underline::None).into());
+        }
+    }
+    (changes, width)
+}
+
+/// The number of rows used by the heading and the filter text
+const HEADER_ROWS: usize = 2;
+
+/// Supplies the rows that are shown by `run_picker`, and acts on
+/// the row that is chosen
+pub trait PickerDelegate {
+    type Row;
+
+    /// Returns the rows that match `filter`, best matches first
+    fn filter(&self, filter: &str) -> Vec<Self::Row>;
+
+    /// Returns the index of the row to select when the picker is shown
+    fn initial_selection(&self, _rows: &[Self::Row]) -> usize {
+        0
+    }
+
+    /// Produces the changes that render `row` into a line that is
+    /// `width` cells wide.  The selected row starts out in reverse
+    /// video, and the attributes are reset after each row.
+    fn render_row(&self, row: &Self::Row, is_selected: bool, width: usize) -> Vec<Change>;
+
+    /// Called when `row` is chosen by pressing Enter or by clicking
+    /// on it, along with the modifiers that were held and the filter
+    fn accept(&mut self, row: &Self::Row, mods: Modifiers, filter: &str);
+
+    /// Called when the selection moves to `row`
+    fn selection_changed(&mut self, _row: &Self::Row) {}
+
+    /// Allows handling `key` before the picker does.  Returns true
+    /// if the key was handled and the picker should be closed.
+    fn shortcut(&mut self, _key: &KeyEvent, _filter: &str) -> bool {
+        false
+    }
+}
+
+fn visible_rows(term: &mut TermWizTerminal) -> termwiz::Result<usize> {
+    Ok(term
+        .get_screen_size()?
+        .rows
+        .saturating_sub(HEADER_ROWS)
+        .max(1))
+}
+
+fn render_picker<D: PickerDelegate>(
+    heading: &str,
+    filter: &str,
+    delegate: &D,
+    rows: &[D::Row],
+    active_idx: usize,
+    top_row: usize,
+    term: &mut TermWizTerminal,
+) -> termwiz::Result<()> {
+    let size = term.get_screen_size()?;
+    let visible_rows = size.rows.saturating_sub(HEADER_ROWS);
+
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(0),
+        },
+        Change::Text(format!("{}\r\n", heading)),
+        AttributeChange::Intensity(Intensity::Bold).into(),
+        Change::Text(format!("> {}\r\n", filter)),
+        Change::AllAttributes(CellAttributes::default()),
+    ];
+
+    for (idx, row) in rows.iter().enumerate().skip(top_row).take(visible_rows) {
+        let is_selected = idx == active_idx;
+        if is_selected {
+            changes.push(AttributeChange::Reverse(true).into());
+        }
+        changes.extend(delegate.render_row(row, is_selected, size.cols));
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+        changes.push(Change::Text("\r\n".to_string()));
+    }
+
+    changes.push(Change::CursorPosition {
+        x: Position::Absolute(2 + unicode_column_width(filter)),
+        y: Position::Absolute(1),
+    });
+
+    term.render(&changes)?;
+    term.flush()
+}
+
+/// Shows the rows supplied by `delegate` beneath `heading`.  Typing
+/// filters the rows; the selection is moved with the arrow keys,
+/// CTRL-p/CTRL-n, PageUp/PageDown or the mouse, and Enter or a left
+/// click accepts the selected row.  Escape, or clicking any other
+/// mouse button, cancels.  Returns once the picker has been closed.
+pub fn run_picker<D: PickerDelegate>(
+    term: &mut TermWizTerminal,
+    title: &str,
+    heading: &str,
+    delegate: &mut D,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title(title.to_string())])?;
+
+    let mut filter = String::new();
+    let mut rows = delegate.filter(&filter);
+    let mut active_idx = delegate
+        .initial_selection(&rows)
+        .min(rows.len().saturating_sub(1));
+    let mut top_row = (active_idx + 1).saturating_sub(visible_rows(term)?);
+
+    render_picker(heading, &filter, delegate, &rows, active_idx, top_row, term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        let visible_rows = visible_rows(term)?;
+        let prior_idx = active_idx;
+        let mut filter_changed = false;
+
+        match event {
+            InputEvent::Key(ref key) if delegate.shortcut(key, &filter) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                modifiers,
+            }) => {
+                if let Some(row) = rows.get(active_idx) {
+                    delegate.accept(row, modifiers, &filter);
+                }
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('p'),
+                modifiers: Modifiers::CTRL,
+            }) => {
+                active_idx = active_idx.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('n'),
+                modifiers: Modifiers::CTRL,
+            }) => {
+                active_idx = (active_idx + 1).min(rows.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageUp,
+                ..
+            }) => {
+                active_idx = active_idx.saturating_sub(visible_rows);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageDown,
+                ..
+            }) => {
+                active_idx = (active_idx + visible_rows).min(rows.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            }) => {
+                filter_changed = filter.pop().is_some();
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('u'),
+                modifiers: Modifiers::CTRL,
+            }) => {
+                filter_changed = !filter.is_empty();
+                filter.clear();
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::SHIFT,
+            }) => {
+                filter.push(c);
+                filter_changed = true;
+            }
+            InputEvent::Mouse(MouseEvent { mouse_buttons, .. })
+                if mouse_buttons.contains(MouseButtons::VERT_WHEEL) =>
+            {
+                // The wheel moves the selection, scrolling the
+                // list to keep it in view
+                if mouse_buttons.contains(MouseButtons::WHEEL_POSITIVE) {
+                    active_idx = active_idx.saturating_sub(1);
+                } else {
+                    active_idx = (active_idx + 1).min(rows.len().saturating_sub(1));
+                }
+            }
+            InputEvent::Mouse(MouseEvent { mouse_buttons, .. })
+                if mouse_buttons.contains(MouseButtons::HORZ_WHEEL) => {}
+            InputEvent::Mouse(MouseEvent {
+                y,
+                mouse_buttons,
+                modifiers,
+                ..
+            }) => {
+                let y = y as usize;
+                if y >= HEADER_ROWS && top_row + y - HEADER_ROWS < rows.len() {
+                    active_idx = top_row + y - HEADER_ROWS;
+
+                    if mouse_buttons == MouseButtons::LEFT {
+                        delegate.accept(&rows[active_idx], modifiers, &filter);
+                        break;
+                    }
+                }
+                if mouse_buttons != MouseButtons::NONE {
+                    // Treat any other mouse button as cancel
+                    break;
+                }
+            }
+            _ => {}
+        }
+
+        if filter_changed {
+            rows = delegate.filter(&filter);
+            active_idx = 0;
+        }
+
+        if filter_changed || active_idx != prior_idx {
+            if let Some(row) = rows.get(active_idx) {
+                delegate.selection_changed(row);
+            }
+        }
+
+        // Keep the active row in view
+        if active_idx < top_row {
+            top_row = active_idx;
+        } else if active_idx >= top_row + visible_rows {
+            top_row = active_idx + 1 - visible_rows;
+        }
+
+        render_picker(heading, &filter, delegate, &rows, active_idx, top_row, term)?;
+    }
+
+    Ok(())
+}
+
+/// Returns true if all of `pattern` appears in order in `chars`
+fn can_match(pattern: &[char], chars: &[char]) -> bool {
+    let mut chars = chars.iter();
+    pattern.iter().all(|&p| chars.any(|&c| chars_equal(c, p)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty_pattern() {
+        assert_eq!(
+            fuzzy_match("", "anything"),
+            Some(FuzzyMatch {
+                score: 0,
+                positions: vec![]
+            })
+        );
+    }
+
+    #[test]
+    fn subsequence() {
+        assert_eq!(fuzzy_match("xyz", "abc"), None);
+        assert_eq!(fuzzy_match("ba", "abc"), None);
+        assert_eq!(
+            fuzzy_match("ac", "abc").map(|m| m.positions),
+            Some(vec![0, 2])
+        );
+        assert_eq!(
+            fuzzy_match("ABC", "abc").map(|m| m.positions),
+            Some(vec![0, 1, 2])
+        );
+    }
+
+    #[test]
+    fn prefers_word_starts() {
+        assert_eq!(
+            fuzzy_match("nt", "New Tab").map(|m| m.positions),
+            Some(vec![0, 4])
+        );
+        assert_eq!(
+            fuzzy_match("cm", "ActivateCopyMode").map(|m| m.positions),
+            Some(vec![8, 12])
+        );
+        assert_eq!(
+            fuzzy_match("ab", "xab ab").map(|m| m.positions),
+            Some(vec![4, 5])
+        );
+        // The word start is skipped if taking it would mean that
+        // the rest of the pattern can no longer match
+        assert_eq!(
+            fuzzy_match("ab", "xab a").map(|m| m.positions),
+            Some(vec![1, 2])
+        );
+    }
+
+    #[test]
+    fn rank_by_recency() {
+        let labels = ["New Tab", "New Window", "Quit"];
+        let recent = ["Quit", "New Window"];
+        let recency = |idx: usize| recent.iter().position(|r| *r == labels[idx]);
+        let order = |filter| -> Vec<usize> {
+            rank(labels.iter().copied(), filter, recency)
+                .into_iter()
+                .map(|c| c.idx)
+                .collect()
+        };
+        assert_eq!(order(""), vec![2, 1, 0]);
+        // Recency breaks ties between equally good matches
+        assert_eq!(order("new"), vec![1, 0]);
+        assert_eq!(
+            rank(labels.iter().copied(), "new", |_| None)
+                .into_iter()
+                .map(|c| c.idx)
+                .collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn record() {
+        let mut recent = vec![1, 2, 3];
+        record_recent(&mut recent, 3);
+        assert_eq!(recent, vec![3, 1, 2]);
+        record_recent(&mut recent, 4);
+        assert_eq!(recent, vec![4, 3, 1, 2]);
+    }
+
+    #[test]
+    fn ranking() {
+        let score = |pat, text| fuzzy_match(pat, text).unwrap().score;
+        // consecutive beats scattered
+        assert!(score("tab", "New Tab") > score("tab", "Toggle Full Screen and Bar"));
+        // word starts beat mid-word matches
+        assert!(score("sp", "Split Pane") > score("sp", "Inspect"));
+    }
+}
@@ -5,6 +5,9 @@
 //! be rendered as a popup/context menu if the system supports it; at the
 //! time of writing our window layer doesn't provide an API for context
 //! menus.
+//! Typing filters the list with a fuzzy match; the most recently
+//! launched entries are listed first.
+use super::fuzzy::{highlight, rank, record_recent, run_picker, Candidate, PickerDelegate};
 use crate::termwindow::clipboard::ClipboardHelper;
 use crate::termwindow::spawn::SpawnWhere;
 use crate::termwindow::TermWindow;
@@ -12,16 +15,14 @@ use anyhow::anyhow;
 use config::configuration;
 use config::keyassignment::{SpawnCommand, SpawnTabDomain};
 use mux::domain::{DomainId, DomainState};
-use mux::tab::TabId;
+use mux::tab::{SplitDirection, TabId};
 use mux::termwiztermtab::TermWizTerminal;
 use mux::window::WindowId;
 use mux::Mux;
 use portable_pty::PtySize;
-use termwiz::cell::{AttributeChange, CellAttributes};
-use termwiz::color::ColorAttribute;
-use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
-use termwiz::surface::{Change, Position};
-use termwiz::terminal::Terminal;
+use std::sync::Mutex;
+use termwiz::input::Modifiers;
+use termwiz::surface::Change;
 
 #[derive(Clone)]
 enum Entry {
@@ -98,6 +99,122 @@ fn enumerate_wsl_entries(entries: &mut Vec<Entry>) -> anyhow::Result<()> {
     Ok(())
 }
 
+lazy_static::lazy_static! {
+    /// The labels of the most recently launched entries, most recent first.
+    /// This is shared by all windows.
+    static ref RECENT: Mutex<Vec<String>> = Mutex::new(vec![]);
+}
+
+/// Returns the entries that match `filter`, best matches first
+fn filter_entries(entries: &[Entry], filter: &str, recent: &[String]) -> Vec<Candidate> {
+    rank(entries.iter().map(Entry::label), filter, |idx| {
+        recent.iter().position(|r| r == entries[idx].label())
+    })
+}
+
+/// Decide where to spawn based on the modifiers that were held
+/// when the entry was selected: SHIFT spawns into a new window,
+/// CTRL splits the current pane horizontally and CTRL+SHIFT
+/// splits it vertically.
+fn spawn_where_for_mods(default: SpawnWhere, mods: Modifiers) -> SpawnWhere {
+    let ctrl = mods.contains(Modifiers::CTRL);
+    let shift = mods.contains(Modifiers::SHIFT);
+    match (ctrl, shift) {
+        (true, true) => SpawnWhere::SplitPane(SplitDirection::Vertical),
+        (true, false) => SpawnWhere::SplitPane(SplitDirection::Horizontal),
+        (false, true) => SpawnWhere::NewWindow,
+        (false, false) => default,
+    }
+}
+
+fn launch(
+    entry: &Entry,
+    mods: Modifiers,
+    size: PtySize,
+    mux_window_id: WindowId,
+    clipboard: ClipboardHelper,
+) {
+    record_recent(&mut RECENT.lock().unwrap(), entry.label().to_string());
+    match entry.clone() {
+        Entry::Spawn {
+            command,
+            spawn_where,
+            ..
+        } => {
+            let spawn_where = spawn_where_for_mods(spawn_where, mods);
+            let size = if spawn_where == SpawnWhere::NewWindow {
+                configuration().initial_size()
+            } else {
+                size
+            };
+            promise::spawn::spawn_into_main_thread(async move {
+                TermWindow::spawn_command_impl(
+                    &command,
+                    spawn_where,
+                    size,
+                    mux_window_id,
+                    clipboard,
+                );
+            })
+            .detach();
+        }
+        Entry::Attach { domain, .. } => {
+            promise::spawn::spawn_into_main_thread(async move {
+                // We can't inline do_domain_attach here directly
+                // because the compiler would then want its body
+                // to be Send :-/
+                do_domain_attach(domain);
+            })
+            .detach();
+        }
+    }
+}
+
+struct Launcher {
+    entries: Vec<Entry>,
+    /// The index of the entry to select initially
+    preselect: usize,
+    size: PtySize,
+    mux_window_id: WindowId,
+    clipboard: ClipboardHelper,
+}
+
+impl PickerDelegate for Launcher {
+    type Row = Candidate;
+
+    fn filter(&self, filter: &str) -> Vec<Candidate> {
+        filter_entries(&self.entries, filter, &RECENT.lock().unwrap())
+    }
+
+    fn initial_selection(&self, rows: &[Candidate]) -> usize {
+        rows.iter()
+            .position(|c| c.idx == self.preselect)
+            .unwrap_or(0)
+    }
+
+    fn render_row(&self, candidate: &Candidate, _is_selected: bool, width: usize) -> Vec<Change> {
+        let avail = width.saturating_sub(2);
+        let (mut changes, label_width) = highlight(
+            self.entries[candidate.idx].label(),
+            &candidate.positions,
+            avail,
+        );
+        changes.insert(0, Change::Text(" ".to_string()));
+        changes.push(Change::Text(" ".repeat(avail - label_width + 1)));
+        changes
+    }
+
+    fn accept(&mut self, candidate: &Candidate, mods: Modifiers, _filter: &str) {
+        launch(
+            &self.entries[candidate.idx],
+            mods,
+            self.size,
+            self.mux_window_id,
+            self.clipboard.clone(),
+        );
+    }
+}
+
 pub fn launcher(
     _tab_id: TabId,
     domain_id_of_current_tab: DomainId,
@@ -107,11 +224,9 @@ pub fn launcher(
     clipboard: ClipboardHelper,
     size: PtySize,
 ) -> anyhow::Result<()> {
-    let mut active_idx = 0;
+    let mut preselect = 0;
     let mut entries = vec![];
 
-    term.set_raw_mode()?;
-
     let config = configuration();
 
     // Pull in the user defined entries from the launch_menu
@@ -158,140 +273,23 @@ pub fn launcher(
         // at the time that the launcher was set up, so that pressing
         // Enter immediately afterwards spawns a tab in the same domain.
         if *domain_id == domain_id_of_current_tab {
-            active_idx = entries.len();
+            preselect = entries.len();
         }
         entries.push(entry);
     }
 
-    fn render(
-        active_idx: usize,
-        entries: &[Entry],
-        term: &mut TermWizTerminal,
-    ) -> termwiz::Result<()> {
-        let mut changes = vec![
-            Change::ClearScreen(ColorAttribute::Default),
-            Change::CursorPosition {
-                x: Position::Absolute(0),
-                y: Position::Absolute(0),
-            },
-            Change::Text(
-                "Select an item and press Enter to launch it.  \
-                Press Escape to cancel\r\n"
-                    .to_string(),
-            ),
-            Change::AllAttributes(CellAttributes::default()),
-        ];
-
-        for (idx, entry) in entries.iter().enumerate() {
-            if idx == active_idx {
-                changes.push(AttributeChange::Reverse(true).into());
-            }
-
-            changes.push(Change::Text(format!(" {} \r\n", entry.label())));
-
-            if idx == active_idx {
-                changes.push(AttributeChange::Reverse(false).into());
-            }
-        }
-        term.render(&changes)
-    }
-
-    term.render(&[Change::Title("Launcher".to_string())])?;
-    render(active_idx, &entries, &mut term)?;
-
-    fn launch(
-        active_idx: usize,
-        entries: &[Entry],
-        size: PtySize,
-        mux_window_id: WindowId,
-        clipboard: ClipboardHelper,
-    ) {
-        match entries[active_idx].clone() {
-            Entry::Spawn {
-                command,
-                spawn_where,
-                ..
-            } => {
-                promise::spawn::spawn_into_main_thread(async move {
-                    TermWindow::spawn_command_impl(
-                        &command,
-                        spawn_where,
-                        size,
-                        mux_window_id,
-                        clipboard,
-                    );
-                })
-                .detach();
-            }
-            Entry::Attach { domain, .. } => {
-                promise::spawn::spawn_into_main_thread(async move {
-                    // We can't inline do_domain_attach here directly
-                    // because the compiler would then want its body
-                    // to be Send :-/
-                    do_domain_attach(domain);
-                })
-                .detach();
-            }
-        }
-    }
-
-    while let Ok(Some(event)) = term.poll_input(None) {
-        match event {
-            InputEvent::Key(KeyEvent {
-                key: KeyCode::Char('k'),
-                ..
-            })
-            | InputEvent::Key(KeyEvent {
-                key: KeyCode::UpArrow,
-                ..
-            }) => {
-                active_idx = active_idx.saturating_sub(1);
-            }
-            InputEvent::Key(KeyEvent {
-                key: KeyCode::Char('j'),
-                ..
-            })
-            | InputEvent::Key(KeyEvent {
-                key: KeyCode::DownArrow,
-                ..
-            }) => {
-                active_idx = (active_idx + 1).min(entries.len() - 1);
-            }
-            InputEvent::Key(KeyEvent {
-                key: KeyCode::Escape,
-                ..
-            }) => {
-                break;
-            }
-            InputEvent::Mouse(MouseEvent {
-                y, mouse_buttons, ..
-            }) => {
-                if y > 0 && y as usize <= entries.len() {
-                    active_idx = y as usize - 1;
-
-                    if mouse_buttons == MouseButtons::LEFT {
-                        launch(active_idx, &entries, size, mux_window_id, clipboard);
-                        break;
-                    }
-                }
-                if mouse_buttons != MouseButtons::NONE {
-                    // Treat any other mouse button as cancel
-                    break;
-                }
-            }
-            InputEvent::Key(KeyEvent {
-                key: KeyCode::Enter,
-                ..
-            }) => {
-                launch(active_idx, &entries, size, mux_window_id, clipboard);
-                break;
-            }
-            _ => {}
-        }
-        render(active_idx, &entries, &mut term)?;
-    }
-
-    Ok(())
+    run_picker(
+        &mut term,
+        "Launcher",
+        "Enter: launch  Shift: new window  Ctrl: split right  Ctrl+Shift: split down",
+        &mut Launcher {
+            entries,
+            preselect,
+            size,
+            mux_window_id,
+            clipboard,
+        },
+    )
 }
 
 fn do_domain_attach(domain: DomainId) {
@@ -304,3 +302,37 @@ fn do_domain_attach(domain: DomainId) {
     })
     .detach();
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spawn_where() {
+        for default in &[
+            SpawnWhere::NewTab,
+            SpawnWhere::SplitPane(SplitDirection::Vertical),
+        ] {
+            let default = *default;
+            assert_eq!(spawn_where_for_mods(default, Modifiers::NONE), default);
+            assert_eq!(
+                spawn_where_for_mods(default, Modifiers::SHIFT),
+                SpawnWhere::NewWindow
+            );
+            assert_eq!(
+                spawn_where_for_mods(default, Modifiers::CTRL),
+                SpawnWhere::SplitPane(SplitDirection::Horizontal)
+            );
+            assert_eq!(
+                spawn_where_for_mods(default, Modifiers::CTRL | Modifiers::SHIFT),
+                SpawnWhere::SplitPane(SplitDirection::Vertical)
+            );
+            // Other modifiers don't change where the entry is spawned
+            assert_eq!(spawn_where_for_mods(default, Modifiers::ALT), default);
+            assert_eq!(
+                spawn_where_for_mods(default, Modifiers::ALT | Modifiers::SHIFT),
+                SpawnWhere::NewWindow
+            );
+        }
+    }
+}
@@ -6,14 +6,20 @@ use portable_pty::PtySize;
 use std::pin::Pin;
 use std::rc::Rc;
 
+mod colorschemes;
 mod confirm_close_pane;
 mod copy;
 mod debug;
+mod fuzzy;
 mod launcher;
+mod palette;
+mod paneselect;
 mod quickselect;
 mod search;
 mod tabnavigator;
+mod workspaces;
 
+pub use colorschemes::color_scheme_picker;
 pub use confirm_close_pane::confirm_close_pane;
 pub use confirm_close_pane::confirm_close_tab;
 pub use confirm_close_pane::confirm_close_window;
@@ -21,9 +27,12 @@ pub use confirm_close_pane::confirm_quit_program;
 pub use copy::CopyOverlay;
 pub use debug::show_debug_overlay;
 pub use launcher::launcher;
+pub use palette::{command_palette, command_palette_entries};
+pub use paneselect::{pane_select, PaneSnapshot};
 pub use quickselect::QuickSelectOverlay;
 pub use search::SearchOverlay;
 pub use tabnavigator::tab_navigator;
+pub use workspaces::{workspace_picker, WorkspaceEntry};
 
 pub fn start_overlay<T, F>(
     term_window: &TermWindow,
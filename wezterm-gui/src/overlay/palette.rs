@@ -0,0 +1,524 @@
+//! The command palette lists the actions that can be performed in a
+//! window, along with the keys that are bound to them, and allows
+//! running any of them by typing a fuzzy search for its name.
+use super::fuzzy::{highlight, rank, record_recent, run_picker, Candidate, PickerDelegate};
+use crate::termwindow::TermWindowNotif;
+use config::keyassignment::{
+    ClipboardCopyDestination, ClipboardPasteSource, ExportFormat, ExportScope, InputMap,
+    KeyAssignment, PaneDirection, PaneSelectMode, Pattern, RotationDirection, ScrollbackEraseMode,
+    SelectionMode, SpawnCommand, SpawnTabDomain,
+};
+use config::{describe_key, ConfigHandle};
+use mux::pane::PaneId;
+use mux::termwiztermtab::TermWizTerminal;
+use std::sync::Mutex;
+use termwiz::cell::{unicode_column_width, AttributeChange, Intensity};
+use termwiz::input::Modifiers;
+use termwiz::surface::Change;
+use window::WindowOps;
+
+lazy_static::lazy_static! {
+    /// The labels of the most recently run entries, most recent first.
+    /// This is shared by all windows.
+    static ref RECENT: Mutex<Vec<String>> = Mutex::new(vec![]);
+}
+
+#[derive(Debug, Clone)]
+pub struct PaletteEntry {
+    pub label: String,
+    /// Describes the key(s) bound to the action, if any
+    pub keys: Option<String>,
+    pub action: KeyAssignment,
+}
+
+/// The actions that are always listed in the palette, whether or
+/// not they are bound to a key.  Actions that take parameters are
+/// listed with a commonly useful parameter.
+fn builtin_actions() -> Vec<KeyAssignment> {
+    use KeyAssignment::*;
+    vec![
+        SpawnTab(SpawnTabDomain::CurrentPaneDomain),
+        SpawnWindow,
+        ToggleFullScreen,
+        CopyTo(ClipboardCopyDestination::Clipboard),
+        PasteFrom(ClipboardPasteSource::Clipboard),
+        PasteFrom(ClipboardPasteSource::PrimarySelection),
+        ActivateTabRelative(1),
+        ActivateTabRelative(-1),
+        ActivateLastTab,
+        MoveTabRelative(-1),
+        MoveTabRelative(1),
+        CloseCurrentTab { confirm: true },
+        ShowTabNavigator,
+        ShowLauncher,
+        IncreaseFontSize,
+        DecreaseFontSize,
+        ResetFontSize,
+        ResetFontAndWindowSize,
+        ScrollByPage(-1),
+        ScrollByPage(1),
+        ScrollByLine(-1),
+        ScrollByLine(1),
+        ScrollToPrompt(-1),
+        ScrollToPrompt(1),
+        ClearScrollback(ScrollbackEraseMode::ScrollbackOnly),
+        ClearScrollback(ScrollbackEraseMode::ScrollbackAndViewport),
+        Search(Pattern::CaseSensitiveString("".into())),
+        ActivateCopyMode,
+        QuickSelect,
+        SplitHorizontal(SpawnCommand {
+            domain: SpawnTabDomain::CurrentPaneDomain,
+            ..Default::default()
+        }),
+        SplitVertical(SpawnCommand {
+            domain: SpawnTabDomain::CurrentPaneDomain,
+            ..Default::default()
+        }),
+        ActivatePaneDirection(PaneDirection::Left),
+        ActivatePaneDirection(PaneDirection::Right),
+        ActivatePaneDirection(PaneDirection::Up),
+        ActivatePaneDirection(PaneDirection::Down),
+        TogglePaneZoomState,
+        SwapPaneDirection(PaneDirection::Left),
+        SwapPaneDirection(PaneDirection::Right),
+        SwapPaneDirection(PaneDirection::Up),
+        SwapPaneDirection(PaneDirection::Down),
+        RotatePanes(RotationDirection::Clockwise),
+        RotatePanes(RotationDirection::CounterClockwise),
+        MovePaneToNewTab,
+        MovePaneToNewWindow,
+        PaneSelect {
+            mode: PaneSelectMode::Activate,
+            alphabet: None,
+        },
+        PaneSelect {
+            mode: PaneSelectMode::SwapWithActive,
+            alphabet: None,
+        },
+        ShowWorkspacePicker,
+        SwitchToWorkspace {
+            name: None,
+            spawn: None,
+        },
+        SwitchWorkspaceRelative(1),
+        SwitchWorkspaceRelative(-1),
+        ShowColorSchemePicker,
+        ExportScrollback {
+            format: ExportFormat::Text,
+            scope: ExportScope::Scrollback,
+            path: None,
+        },
+        ExportScrollback {
+            format: ExportFormat::Html,
+            scope: ExportScope::Scrollback,
+            path: None,
+        },
+        ExportScrollback {
+            format: ExportFormat::Html,
+            scope: ExportScope::Selection,
+            path: None,
+        },
+        CloseCurrentPane { confirm: true },
+        PopKeyTable,
+        ClearKeyTableStack,
+        ReloadConfiguration,
+        ShowDebugOverlay,
+        Hide,
+        HideApplication,
+        QuitApplication,
+    ]
+}
+
+/// Describes a relative movement of `n` steps, using `one_back` and
+/// `one_forward` for the common single steps
+fn relative(n: isize, one_back: &str, one_forward: &str, unit: &str) -> String {
+    match n {
+        -1 => one_back.to_string(),
+        1 => one_forward.to_string(),
+        n if n < 0 => format!("{} ({} {})", one_back, -n, unit),
+        n => format!("{} ({} {})", one_forward, n, unit),
+    }
+}
+
+/// Describes the command run by a spawn action, if it isn't the
+/// default program of the domain
+fn spawn_suffix(cmd: &SpawnCommand) -> String {
+    match (&cmd.label, &cmd.args) {
+        (Some(label), _) => format!(": {}", label),
+        (None, Some(args)) => format!(": {}", args.join(" ")),
+        (None, None) => String::new(),
+    }
+}
+
+/// Returns a descriptive name for `action`, for display in the palette
+pub fn action_label(action: &KeyAssignment) -> String {
+    use KeyAssignment::*;
+    match action {
+        SpawnTab(SpawnTabDomain::CurrentPaneDomain) => "New Tab".to_string(),
+        SpawnTab(SpawnTabDomain::DefaultDomain) => "New Tab in the Default Domain".to_string(),
+        SpawnTab(SpawnTabDomain::DomainName(name)) => format!("New Tab in Domain {}", name),
+        SpawnWindow => "New Window".to_string(),
+        ToggleFullScreen => "Toggle Full Screen".to_string(),
+        Copy => "Copy".to_string(),
+        CopyTo(ClipboardCopyDestination::Clipboard) => "Copy to Clipboard".to_string(),
+        CopyTo(ClipboardCopyDestination::PrimarySelection) => {
+            "Copy to Primary Selection".to_string()
+        }
+        CopyTo(ClipboardCopyDestination::ClipboardAndPrimarySelection) => {
+            "Copy to Clipboard and Primary Selection".to_string()
+        }
+        Paste => "Paste".to_string(),
+        PastePrimarySelection | PasteFrom(ClipboardPasteSource::PrimarySelection) => {
+            "Paste Primary Selection".to_string()
+        }
+        PasteFrom(ClipboardPasteSource::Clipboard) => "Paste from Clipboard".to_string(),
+        ActivateTabRelative(n) => relative(*n, "Previous Tab", "Next Tab", "tabs"),
+        IncreaseFontSize => "Increase Font Size".to_string(),
+        DecreaseFontSize => "Decrease Font Size".to_string(),
+        ResetFontSize => "Reset Font Size".to_string(),
+        ResetFontAndWindowSize => "Reset Font and Window Size".to_string(),
+        ActivateTab(-1) => "Activate Rightmost Tab".to_string(),
+        ActivateTab(n) if *n < 0 => format!("Activate Tab {} from the Right", -n),
+        ActivateTab(n) => format!("Activate Tab {}", n + 1),
+        ActivateLastTab => "Last Active Tab".to_string(),
+        SendString(text) => format!("Send {:?}", text),
+        Nop => "Do Nothing".to_string(),
+        DisableDefaultAssignment => "Disable Default Assignment".to_string(),
+        Hide => "Hide Window".to_string(),
+        Show => "Show Window".to_string(),
+        CloseCurrentTab { confirm: true } => "Close Tab".to_string(),
+        CloseCurrentTab { confirm: false } => "Close Tab Without Confirmation".to_string(),
+        ReloadConfiguration => "Reload Configuration".to_string(),
+        MoveTabRelative(n) => relative(*n, "Move Tab Left", "Move Tab Right", "places"),
+        MoveTab(n) => format!("Move Tab to Position {}", n + 1),
+        ScrollByPage(n) => relative(*n, "Scroll Up One Page", "Scroll Down One Page", "pages"),
+        ScrollByLine(n) => relative(*n, "Scroll Up One Line", "Scroll Down One Line", "lines"),
+        ScrollToPrompt(n) => relative(
+            *n,
+            "Scroll to Previous Prompt",
+            "Scroll to Next Prompt",
+            "prompts",
+        ),
+        ShowTabNavigator => "Show Tab Navigator".to_string(),
+        ShowDebugOverlay => "Show Debug Overlay".to_string(),
+        HideApplication => "Hide Application".to_string(),
+        QuitApplication => "Quit".to_string(),
+        SpawnCommandInNewTab(cmd) => format!("New Tab{}", spawn_suffix(cmd)),
+        SpawnCommandInNewWindow(cmd) => format!("New Window{}", spawn_suffix(cmd)),
+        SplitHorizontal(cmd) => format!("Split Pane Horizontally{}", spawn_suffix(cmd)),
+        SplitVertical(cmd) => format!("Split Pane Vertically{}", spawn_suffix(cmd)),
+        ShowLauncher => "Show Launcher".to_string(),
+        ClearScrollback(ScrollbackEraseMode::ScrollbackOnly) => "Clear Scrollback".to_string(),
+        ClearScrollback(ScrollbackEraseMode::ScrollbackAndViewport) => {
+            "Clear Scrollback and Viewport".to_string()
+        }
+        Search(pattern) if pattern.is_empty() => "Search Scrollback".to_string(),
+        Search(Pattern::CaseSensitiveString(s)) => format!("Search for {:?}", s),
+        Search(Pattern::CaseInSensitiveString(s)) => {
+            format!("Search for {:?}, Ignoring Case", s)
+        }
+        Search(Pattern::Regex(s)) => format!("Search for the Regex {:?}", s),
+        ActivateCopyMode => "Activate Copy Mode".to_string(),
+        SelectTextAtMouseCursor(mode) => format!(
+            "Select {} at Mouse Cursor",
+            match mode {
+                SelectionMode::Cell => "Text",
+                SelectionMode::Word => "Word",
+                SelectionMode::Line => "Line",
+                SelectionMode::SemanticZone => "Semantic Zone",
+            }
+        ),
+        ExtendSelectionToMouseCursor(_) => "Extend Selection to Mouse Cursor".to_string(),
+        OpenLinkAtMouseCursor => "Open Link at Mouse Cursor".to_string(),
+        CompleteSelection(_) => "Complete Selection".to_string(),
+        CompleteSelectionOrOpenLinkAtMouseCursor(_) => {
+            "Complete Selection or Open Link at Mouse Cursor".to_string()
+        }
+        StartWindowDrag => "Start Window Drag".to_string(),
+        AdjustPaneSize(direction, n) => format!("Resize Pane {:?} by {}", direction, n),
+        ActivatePaneDirection(direction) => format!("Activate Pane {:?}", direction),
+        TogglePaneZoomState => "Toggle Pane Zoom".to_string(),
+        CloseCurrentPane { confirm: true } => "Close Pane".to_string(),
+        CloseCurrentPane { confirm: false } => "Close Pane Without Confirmation".to_string(),
+        EmitEvent(name) => format!("Emit Event {}", name),
+        QuickSelect => "Quick Select".to_string(),
+        ActivateKeyTable { name, .. } => format!("Activate Key Table {}", name),
+        PopKeyTable => "Pop Key Table".to_string(),
+        ClearKeyTableStack => "Clear Key Table Stack".to_string(),
+        ActivateCommandPalette => "Show Command Palette".to_string(),
+        SwapPaneDirection(direction) => format!("Swap Pane {:?}", direction),
+        RotatePanes(RotationDirection::Clockwise) => "Rotate Panes Clockwise".to_string(),
+        RotatePanes(RotationDirection::CounterClockwise) => {
+            "Rotate Panes Counter-Clockwise".to_string()
+        }
+        MovePaneToNewTab => "Move Pane to New Tab".to_string(),
+        MovePaneToNewWindow => "Move Pane to New Window".to_string(),
+        MovePaneToTab {
+            tab_index,
+            direction,
+        } => format!("Move Pane to Tab {}, {:?}", tab_index + 1, direction),
+        PaneSelect {
+            mode: PaneSelectMode::Activate,
+            ..
+        } => "Select Pane".to_string(),
+        PaneSelect {
+            mode: PaneSelectMode::SwapWithActive,
+            ..
+        } => "Swap Active Pane with Selected Pane".to_string(),
+        SwitchToWorkspace { name: None, .. } => "New Workspace".to_string(),
+        SwitchToWorkspace {
+            name: Some(name), ..
+        } => format!("Switch to Workspace {}", name),
+        SwitchWorkspaceRelative(n) => {
+            relative(*n, "Previous Workspace", "Next Workspace", "workspaces")
+        }
+        ShowWorkspacePicker => "Switch Workspace".to_string(),
+        ShowColorSchemePicker => "Preview Color Schemes".to_string(),
+        ExportScrollback {
+            format,
+            scope,
+            path,
+        } => {
+            let scope = match scope {
+                ExportScope::Scrollback => "Scrollback".to_string(),
+                ExportScope::Selection => "Selection".to_string(),
+                ExportScope::LastSemanticZone(zone) => format!("Last {:?}", zone),
+            };
+            let format = match format {
+                ExportFormat::Text => "Text",
+                ExportFormat::Ansi => "ANSI",
+                ExportFormat::Html => "HTML",
+            };
+            match path {
+                Some(path) => format!("Export {} as {} to {}", scope, format, path.display()),
+                None => format!("Export {} as {}", scope, format),
+            }
+        }
+    }
+}
+
+/// Assemble the entries to show in the palette: the built-in actions,
+/// any other actions that have key bindings and the entries defined by
+/// the `command_palette_entries` configuration
+pub fn command_palette_entries(input_map: &InputMap, config: &ConfigHandle) -> Vec<PaletteEntry> {
+    let mut actions: Vec<(String, KeyAssignment)> = builtin_actions()
+        .into_iter()
+        .map(|action| (action_label(&action), action))
+        .collect();
+
+    let mut other_actions: Vec<(String, KeyAssignment)> = input_map
+        .bound_actions()
+        .into_iter()
+        .filter(|action| !actions.iter().any(|(_, a)| a == action))
+        .map(|action| (action_label(&action), action))
+        .collect();
+    other_actions.sort_by(|a, b| a.0.cmp(&b.0));
+    actions.append(&mut other_actions);
+
+    for entry in &config.command_palette_entries {
+        actions.push((entry.label.clone(), entry.action.clone()));
+    }
+
+    let sequences = config.key_sequence_bindings();
+
+    actions
+        .into_iter()
+        .filter(|(_, action)| {
+            !matches!(
+                action,
+                KeyAssignment::ActivateCommandPalette
+                    | KeyAssignment::Nop
+                    | KeyAssignment::DisableDefaultAssignment
+            )
+        })
+        .map(|(label, action)| {
+            let mut keys: Vec<String> = input_map
+                .keys_for_action(&action)
+                .iter()
+                .map(|(key, mods)| describe_key(key, *mods))
+                .collect();
+            for (sequence, a) in &sequences {
+                if *a == action {
+                    keys.push(
+                        sequence
+                            .iter()
+                            .map(|(key, mods)| describe_key(key, *mods))
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                    );
+                }
+            }
+            PaletteEntry {
+                label,
+                keys: if keys.is_empty() {
+                    None
+                } else {
+                    Some(keys.join(", "))
+                },
+                action,
+            }
+        })
+        .collect()
+}
+
+/// Returns the entries that match `filter`, best matches first
+fn filter_entries(entries: &[PaletteEntry], filter: &str, recent: &[String]) -> Vec<Candidate> {
+    rank(
+        entries.iter().map(|entry| entry.label.as_str()),
+        filter,
+        |idx| recent.iter().position(|r| *r == entries[idx].label),
+    )
+}
+
+struct CommandPalette {
+    entries: Vec<PaletteEntry>,
+    window: ::window::Window,
+    pane_id: PaneId,
+}
+
+impl PickerDelegate for CommandPalette {
+    type Row = Candidate;
+
+    fn filter(&self, filter: &str) -> Vec<Candidate> {
+        filter_entries(&self.entries, filter, &RECENT.lock().unwrap())
+    }
+
+    fn render_row(&self, candidate: &Candidate, is_selected: bool, width: usize) -> Vec<Change> {
+        let entry = &self.entries[candidate.idx];
+        let keys = entry.keys.as_deref().unwrap_or("");
+        let keys_width = unicode_column_width(keys);
+
+        // Leave room for the key description on the right, along
+        // with a space of padding on either side of the label
+        let avail = width.saturating_sub(keys_width + 3);
+
+        let mut changes = vec![Change::Text(" ".to_string())];
+        let (label, label_width) = highlight(&entry.label, &candidate.positions, avail);
+        changes.extend(label);
+
+        let padding = width.saturating_sub(label_width + keys_width + 2);
+        changes.push(Change::Text(" ".repeat(padding)));
+        if !is_selected {
+            changes.push(AttributeChange::Intensity(Intensity::Half).into());
+        }
+        changes.push(Change::Text(format!("{} ", keys)));
+        changes
+    }
+
+    fn accept(&mut self, candidate: &Candidate, _mods: Modifiers, _filter: &str) {
+        let entry = &self.entries[candidate.idx];
+        record_recent(&mut RECENT.lock().unwrap(), entry.label.clone());
+        self.window.notify(TermWindowNotif::PerformAssignment {
+            pane_id: self.pane_id,
+            assignment: entry.action.clone(),
+        });
+    }
+}
+
+pub fn command_palette(
+    mut term: TermWizTerminal,
+    entries: Vec<PaletteEntry>,
+    window: ::window::Window,
+    pane_id: PaneId,
+) -> anyhow::Result<()> {
+    run_picker(
+        &mut term,
+        "Command Palette",
+        "Type to filter, Enter to run the selected action, Escape to cancel",
+        &mut CommandPalette {
+            entries,
+            window,
+            pane_id,
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(label: &str) -> PaletteEntry {
+        PaletteEntry {
+            label: label.to_string(),
+            keys: None,
+            action: KeyAssignment::Nop,
+        }
+    }
+
+    fn labels(entries: &[PaletteEntry], candidates: &[Candidate]) -> Vec<String> {
+        candidates
+            .iter()
+            .map(|c| entries[c.idx].label.clone())
+            .collect()
+    }
+
+    #[test]
+    fn recent_first() {
+        let entries = vec![entry("New Tab"), entry("New Window"), entry("Quit")];
+
+        let candidates = filter_entries(&entries, "", &[]);
+        assert_eq!(
+            labels(&entries, &candidates),
+            vec!["New Tab", "New Window", "Quit"]
+        );
+
+        let recent = vec!["Quit".to_string(), "New Window".to_string()];
+        let candidates = filter_entries(&entries, "", &recent);
+        assert_eq!(
+            labels(&entries, &candidates),
+            vec!["Quit", "New Window", "New Tab"]
+        );
+
+        // Recency breaks ties between equally good matches
+        let candidates = filter_entries(&entries, "new", &recent);
+        assert_eq!(labels(&entries, &candidates), vec!["New Window", "New Tab"]);
+    }
+
+    #[test]
+    fn action_labels() {
+        let labels: Vec<String> = builtin_actions().iter().map(action_label).collect();
+        for (idx, label) in labels.iter().enumerate() {
+            assert!(
+                !labels[idx + 1..].contains(label),
+                "{} is used for more than one action",
+                label
+            );
+        }
+
+        assert_eq!(
+            action_label(&KeyAssignment::ActivateTabRelative(-2)),
+            "Previous Tab (2 tabs)"
+        );
+        assert_eq!(
+            action_label(&KeyAssignment::ActivateTab(0)),
+            "Activate Tab 1"
+        );
+        assert_eq!(
+            action_label(&KeyAssignment::SendString("\x1b".into())),
+            "Send \"\\u{1b}\""
+        );
+        assert_eq!(
+            action_label(&KeyAssignment::SplitVertical(SpawnCommand {
+                args: Some(vec!["top".into()]),
+                ..Default::default()
+            })),
+            "Split Pane Vertically: top"
+        );
+        assert_eq!(
+            action_label(&KeyAssignment::ExportScrollback {
+                format: ExportFormat::Ansi,
+                scope: ExportScope::Selection,
+                path: None,
+            }),
+            "Export Selection as ANSI"
+        );
+    }
+
+    #[test]
+    fn best_match_first() {
+        let entries = vec![entry("Toggle Full Screen"), entry("Next Tab")];
+        let candidates = filter_entries(&entries, "tab", &[]);
+        assert_eq!(labels(&entries, &candidates), vec!["Next Tab"]);
+        assert_eq!(candidates[0].positions, vec![5, 6, 7]);
+    }
+}
@@ -0,0 +1,248 @@
+//! The pane select overlay shows the panes of the tab with a short
+//! label over each of them.  Typing a label activates that pane, or
+//! swaps it with the active pane, depending on the mode.
+use super::quickselect::compute_labels_for_alphabet;
+use anyhow::anyhow;
+use config::keyassignment::PaneSelectMode;
+use mux::pane::PaneId;
+use mux::tab::TabId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use termwiz::cell::{unicode_column_width, AttributeChange, CellAttributes, Intensity};
+use termwiz::color::{AnsiColor, ColorAttribute};
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, Line, Position};
+use termwiz::terminal::Terminal;
+
+/// A copy of the visible portion of a pane, along with its
+/// position in the tab, captured when the overlay is started
+pub struct PaneSnapshot {
+    pub pane_id: PaneId,
+    pub left: usize,
+    pub top: usize,
+    pub width: usize,
+    pub height: usize,
+    pub lines: Vec<Line>,
+}
+
+impl PaneSnapshot {
+    fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.left && x < self.left + self.width && y >= self.top && y < self.top + self.height
+    }
+}
+
+fn render(
+    panes: &[PaneSnapshot],
+    labels: &[String],
+    selection: &str,
+    term: &mut TermWizTerminal,
+) -> termwiz::Result<()> {
+    let size = term.get_screen_size()?;
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorVisibility(termwiz::surface::CursorVisibility::Hidden),
+    ];
+
+    for pane in panes {
+        // The contents of the pane are shown dimmed so that the
+        // labels stand out
+        for (row, line) in pane.lines.iter().take(pane.height).enumerate() {
+            let mut line = line.clone();
+            line.resize(pane.width);
+            for cell in line.cells_mut_for_attr_changes_only() {
+                cell.attrs_mut().set_intensity(Intensity::Half);
+            }
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(pane.left),
+                y: Position::Absolute(pane.top + row),
+            });
+            changes.extend(line.changes(&CellAttributes::default()));
+        }
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+
+        // Draw the split separators to the right of and below the pane
+        if pane.left + pane.width < size.cols {
+            for row in pane.top..(pane.top + pane.height).min(size.rows) {
+                changes.push(Change::CursorPosition {
+                    x: Position::Absolute(pane.left + pane.width),
+                    y: Position::Absolute(row),
+                });
+                changes.push(Change::Text("\u{2502}".to_string()));
+            }
+        }
+        if pane.top + pane.height < size.rows {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(pane.left),
+                y: Position::Absolute(pane.top + pane.height),
+            });
+            changes.push(Change::Text("\u{2500}".repeat(pane.width)));
+        }
+    }
+
+    for (pane, label) in panes.iter().zip(labels.iter()) {
+        if !label.starts_with(selection) {
+            continue;
+        }
+
+        // Center a box holding the label in the pane
+        let text = format!(" {} ", label.to_uppercase());
+        let width = unicode_column_width(&text);
+        let x = pane.left + pane.width.saturating_sub(width) / 2;
+        let y = pane.top + pane.height.saturating_sub(3) / 2;
+        changes.push(AttributeChange::Intensity(Intensity::Bold).into());
+        changes.push(AttributeChange::Foreground(AnsiColor::Black.into()).into());
+        changes.push(AttributeChange::Background(AnsiColor::Yellow.into()).into());
+        for row in 0..3.min(pane.height) {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(x),
+                y: Position::Absolute(y + row),
+            });
+            if row == 1 || pane.height < 3 {
+                changes.push(Change::Text(text.clone()));
+            } else {
+                changes.push(Change::Text(" ".repeat(width)));
+            }
+        }
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+    }
+
+    term.render(&changes)?;
+    term.flush()
+}
+
+fn select_pane(tab_id: TabId, pane_id: PaneId, mode: PaneSelectMode) {
+    promise::spawn::spawn_into_main_thread(async move {
+        let mux = Mux::get().unwrap();
+        let tab = mux
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("no such tab {}", tab_id))?;
+        let pane = mux
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+        match mode {
+            PaneSelectMode::Activate => tab.set_active_pane(&pane),
+            PaneSelectMode::SwapWithActive => {
+                let active = tab
+                    .get_active_pane()
+                    .ok_or_else(|| anyhow!("tab {} has no active pane", tab_id))?;
+                if active.pane_id() != pane_id {
+                    let domain = mux
+                        .get_domain(active.domain_id())
+                        .ok_or_else(|| anyhow!("no domain for pane {}", active.pane_id()))?;
+                    let active_id = active.pane_id();
+                    promise::spawn::spawn(async move {
+                        if let Err(err) = domain.swap_panes(active_id, pane_id).await {
+                            log::error!("Failed to swap panes: {:#}", err);
+                        }
+                    })
+                    .detach();
+                }
+            }
+        }
+        anyhow::Result::<()>::Ok(())
+    })
+    .detach();
+}
+
+/// Restores the zoom that was removed in order to show every pane
+fn restore_zoom(tab_id: TabId) {
+    promise::spawn::spawn_into_main_thread(async move {
+        let mux = Mux::get().unwrap();
+        if let Some(tab) = mux.get_tab(tab_id) {
+            tab.set_zoomed(true);
+        }
+    })
+    .detach();
+}
+
+/// `was_zoomed` indicates that the tab was zoomed before the overlay
+/// unzoomed it; the zoom is restored if no pane is selected.
+pub fn pane_select(
+    tab_id: TabId,
+    mut term: TermWizTerminal,
+    panes: Vec<PaneSnapshot>,
+    alphabet: String,
+    mode: PaneSelectMode,
+    was_zoomed: bool,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    term.render(&[Change::Title(
+        match mode {
+            PaneSelectMode::Activate => "Select a pane to activate",
+            PaneSelectMode::SwapWithActive => "Select a pane to swap with the active pane",
+        }
+        .to_string(),
+    )])?;
+
+    let labels = compute_labels_for_alphabet(&alphabet, panes.len());
+    let mut selection = String::new();
+    let mut selected = false;
+
+    render(&panes, &labels, &selection, &mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            }) => {
+                selection.pop();
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::SHIFT,
+            }) => {
+                selection.extend(c.to_lowercase());
+                if let Some(idx) = labels.iter().position(|label| *label == selection) {
+                    select_pane(tab_id, panes[idx].pane_id, mode);
+                    selected = true;
+                    break;
+                }
+                if !labels.iter().any(|label| label.starts_with(&selection)) {
+                    // Not the start of any label; start over
+                    selection.clear();
+                }
+            }
+            InputEvent::Mouse(MouseEvent {
+                x,
+                y,
+                mouse_buttons,
+                ..
+            }) => {
+                if mouse_buttons == MouseButtons::LEFT {
+                    if let Some(pane) = panes
+                        .iter()
+                        .find(|pane| pane.contains(x as usize, y as usize))
+                    {
+                        select_pane(tab_id, pane.pane_id, mode);
+                        selected = true;
+                    }
+                    break;
+                }
+                if mouse_buttons != MouseButtons::NONE {
+                    // Treat any other mouse button as cancel
+                    break;
+                }
+            }
+            _ => {}
+        }
+
+        render(&panes, &labels, &selection, &mut term)?;
+    }
+
+    if was_zoomed && !selected {
+        restore_zoom(tab_id);
+    }
+
+    Ok(())
+}
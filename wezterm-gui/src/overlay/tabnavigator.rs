@@ -1,159 +1,129 @@
+//! The tab navigator lists the tabs in the window and allows activating
+//! one of them.  Typing filters the list by tab title with a fuzzy match;
+//! the tabs most recently activated from the navigator are listed first.
+use super::fuzzy::{highlight, rank, record_recent, run_picker, Candidate, PickerDelegate};
 use anyhow::anyhow;
 use mux::tab::TabId;
 use mux::termwiztermtab::TermWizTerminal;
 use mux::window::WindowId;
 use mux::Mux;
-use termwiz::cell::{AttributeChange, CellAttributes};
-use termwiz::color::ColorAttribute;
-use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
-use termwiz::surface::{Change, Position};
-use termwiz::terminal::Terminal;
+use std::sync::Mutex;
+use termwiz::cell::unicode_column_width;
+use termwiz::input::{KeyCode, KeyEvent, Modifiers};
+use termwiz::surface::Change;
 
-pub fn tab_navigator(
-    tab_id: TabId,
-    mut term: TermWizTerminal,
-    tab_list: Vec<(String, TabId, usize)>,
-    mux_window_id: WindowId,
-) -> anyhow::Result<()> {
-    let mut active_tab_idx = tab_list
-        .iter()
-        .position(|(_title, id, _)| *id == tab_id)
-        .unwrap_or(0);
+lazy_static::lazy_static! {
+    /// The tabs most recently activated via the navigator, most
+    /// recent first.  This is shared by all windows.
+    static ref RECENT: Mutex<Vec<TabId>> = Mutex::new(vec![]);
+}
 
-    term.set_raw_mode()?;
+/// Returns the tabs whose titles match `filter`, best matches first
+fn filter_tabs(
+    tab_list: &[(String, TabId, usize)],
+    filter: &str,
+    recent: &[TabId],
+) -> Vec<Candidate> {
+    rank(
+        tab_list.iter().map(|(title, _, _)| title.as_str()),
+        filter,
+        |idx| recent.iter().position(|id| *id == tab_list[idx].1),
+    )
+}
 
-    fn render(
-        active_tab_idx: usize,
-        tab_list: &[(String, TabId, usize)],
-        term: &mut TermWizTerminal,
-    ) -> termwiz::Result<()> {
-        // let dims = term.get_screen_size()?;
-        let mut changes = vec![
-            Change::ClearScreen(ColorAttribute::Default),
-            Change::CursorPosition {
-                x: Position::Absolute(0),
-                y: Position::Absolute(0),
-            },
-            Change::Text(
-                "Select a tab and press Enter to activate it.  Press Escape to cancel\r\n"
-                    .to_string(),
-            ),
-            Change::AllAttributes(CellAttributes::default()),
-        ];
+fn select_tab_by_idx(
+    idx: usize,
+    mux_window_id: WindowId,
+    tab_list: &[(String, TabId, usize)],
+) -> bool {
+    if idx >= tab_list.len() {
+        false
+    } else {
+        record_recent(&mut RECENT.lock().unwrap(), tab_list[idx].1);
+        promise::spawn::spawn_into_main_thread(async move {
+            let mux = Mux::get().unwrap();
+            let mut window = mux
+                .get_window_mut(mux_window_id)
+                .ok_or_else(|| anyhow!("no such window"))?;
 
-        for (idx, (title, _tab_id, num_panes)) in tab_list.iter().enumerate() {
-            if idx == active_tab_idx {
-                changes.push(AttributeChange::Reverse(true).into());
-            }
+            window.save_and_then_set_active(idx);
+            anyhow::Result::<()>::Ok(())
+        })
+        .detach();
+        true
+    }
+}
 
-            changes.push(Change::Text(format!(
-                " {}. {}. {} panes\r\n",
-                idx + 1,
-                title,
-                num_panes
-            )));
+struct TabNavigator {
+    /// The tab that was active when the navigator was shown
+    tab_id: TabId,
+    tab_list: Vec<(String, TabId, usize)>,
+    mux_window_id: WindowId,
+}
 
-            if idx == active_tab_idx {
-                changes.push(AttributeChange::Reverse(false).into());
-            }
-        }
+impl PickerDelegate for TabNavigator {
+    type Row = Candidate;
 
-        term.render(&changes)?;
-        term.flush()
+    fn filter(&self, filter: &str) -> Vec<Candidate> {
+        filter_tabs(&self.tab_list, filter, &RECENT.lock().unwrap())
     }
 
-    term.render(&[Change::Title("Tab Navigator".to_string())])?;
+    fn initial_selection(&self, rows: &[Candidate]) -> usize {
+        rows.iter()
+            .position(|c| self.tab_list[c.idx].1 == self.tab_id)
+            .unwrap_or(0)
+    }
 
-    render(active_tab_idx, &tab_list, &mut term)?;
+    fn render_row(&self, candidate: &Candidate, _is_selected: bool, width: usize) -> Vec<Change> {
+        let (title, _tab_id, num_panes) = &self.tab_list[candidate.idx];
 
-    fn select_tab_by_idx(
-        idx: usize,
-        mux_window_id: WindowId,
-        tab_list: &Vec<(String, TabId, usize)>,
-    ) -> bool {
-        if idx >= tab_list.len() {
-            false
-        } else {
-            promise::spawn::spawn_into_main_thread(async move {
-                let mux = Mux::get().unwrap();
-                let mut window = mux
-                    .get_window_mut(mux_window_id)
-                    .ok_or_else(|| anyhow!("no such window"))?;
+        // The number is the position of the tab in the tab bar,
+        // regardless of how the list is currently ordered
+        let prefix = format!(" {}. ", candidate.idx + 1);
+        let suffix = format!(". {} panes ", num_panes);
+        let avail =
+            width.saturating_sub(unicode_column_width(&prefix) + unicode_column_width(&suffix));
+        let mut changes = vec![Change::Text(prefix)];
+        let (title, _width) = highlight(title, &candidate.positions, avail);
+        changes.extend(title);
+        changes.push(Change::Text(suffix));
+        changes
+    }
 
-                window.save_and_then_set_active(idx);
-                anyhow::Result::<()>::Ok(())
-            })
-            .detach();
-            true
-        }
+    fn accept(&mut self, candidate: &Candidate, _mods: Modifiers, _filter: &str) {
+        select_tab_by_idx(candidate.idx, self.mux_window_id, &self.tab_list);
     }
 
-    while let Ok(Some(event)) = term.poll_input(None) {
-        match event {
-            InputEvent::Key(KeyEvent {
-                key: KeyCode::Char('k'),
-                ..
-            })
-            | InputEvent::Key(KeyEvent {
-                key: KeyCode::UpArrow,
-                ..
-            }) => {
-                active_tab_idx = active_tab_idx.saturating_sub(1);
-            }
-            InputEvent::Key(KeyEvent {
-                key: KeyCode::Char('j'),
-                ..
-            })
-            | InputEvent::Key(KeyEvent {
-                key: KeyCode::DownArrow,
-                ..
-            }) => {
-                active_tab_idx = (active_tab_idx + 1).min(tab_list.len() - 1);
-            }
-            InputEvent::Key(KeyEvent {
-                key: KeyCode::Escape,
-                ..
-            }) => {
-                break;
-            }
-            InputEvent::Key(KeyEvent {
+    fn shortcut(&mut self, key: &KeyEvent, filter: &str) -> bool {
+        match key {
+            KeyEvent {
                 key: KeyCode::Char(c),
-                ..
-            }) => {
-                if c >= '1' && c <= '9' {
-                    let idx = c as u8 - '1' as u8;
-                    if select_tab_by_idx(idx as usize, mux_window_id, &tab_list) {
-                        break;
-                    }
-                }
-            }
-            InputEvent::Mouse(MouseEvent {
-                y, mouse_buttons, ..
-            }) => {
-                if y > 0 && y as usize <= tab_list.len() {
-                    active_tab_idx = y as usize - 1;
-
-                    if mouse_buttons == MouseButtons::LEFT {
-                        select_tab_by_idx(active_tab_idx, mux_window_id, &tab_list);
-                        break;
-                    }
-                }
-                if mouse_buttons != MouseButtons::NONE {
-                    // Treat any other mouse button as cancel
-                    break;
-                }
+                modifiers: Modifiers::NONE,
+            } if filter.is_empty() && ('1'..='9').contains(c) => {
+                // With no filter, the digits activate the tab
+                // with that number directly
+                let idx = *c as u8 - b'1';
+                select_tab_by_idx(idx as usize, self.mux_window_id, &self.tab_list)
             }
-            InputEvent::Key(KeyEvent {
-                key: KeyCode::Enter,
-                ..
-            }) => {
-                select_tab_by_idx(active_tab_idx, mux_window_id, &tab_list);
-                break;
-            }
-            _ => {}
+            _ => false,
         }
-        render(active_tab_idx, &tab_list, &mut term)?;
     }
+}
 
-    Ok(())
+pub fn tab_navigator(
+    tab_id: TabId,
+    mut term: TermWizTerminal,
+    tab_list: Vec<(String, TabId, usize)>,
+    mux_window_id: WindowId,
+) -> anyhow::Result<()> {
+    run_picker(
+        &mut term,
+        "Tab Navigator",
+        "Type to filter, Enter to activate the selected tab, Escape to cancel",
+        &mut TabNavigator {
+            tab_id,
+            tab_list,
+            mux_window_id,
+        },
+    )
 }
@@ -0,0 +1,114 @@
+//! The workspace picker lists the workspaces and allows switching to
+//! one of them.  Typing filters the list by name with a fuzzy match;
+//! if the typed name doesn't match an existing workspace then it is
+//! offered as the name of a new workspace.
+use super::fuzzy::{highlight, rank, run_picker, Candidate, PickerDelegate};
+use crate::termwindow::TermWindowNotif;
+use config::keyassignment::KeyAssignment;
+use mux::pane::PaneId;
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::cell::unicode_column_width;
+use termwiz::input::Modifiers;
+use termwiz::surface::Change;
+use window::WindowOps;
+
+pub struct WorkspaceEntry {
+    pub name: String,
+    pub num_windows: usize,
+    pub is_active: bool,
+}
+
+/// A row in the picker
+enum Row {
+    /// An existing workspace
+    Existing(Candidate),
+    /// Create a new workspace with the name that was typed
+    New(String),
+}
+
+fn filter_workspaces(workspaces: &[WorkspaceEntry], filter: &str) -> Vec<Row> {
+    // The workspaces are already sorted by name, so there is no
+    // recency ordering to apply
+    let mut rows: Vec<Row> = rank(workspaces.iter().map(|w| w.name.as_str()), filter, |_| None)
+        .into_iter()
+        .map(Row::Existing)
+        .collect();
+    if !filter.is_empty() && !workspaces.iter().any(|w| w.name == filter) {
+        rows.push(Row::New(filter.to_string()));
+    }
+    rows
+}
+
+struct WorkspacePicker {
+    workspaces: Vec<WorkspaceEntry>,
+    window: ::window::Window,
+    pane_id: PaneId,
+}
+
+impl PickerDelegate for WorkspacePicker {
+    type Row = Row;
+
+    fn filter(&self, filter: &str) -> Vec<Row> {
+        filter_workspaces(&self.workspaces, filter)
+    }
+
+    fn initial_selection(&self, _rows: &[Row]) -> usize {
+        self.workspaces
+            .iter()
+            .position(|w| w.is_active)
+            .unwrap_or(0)
+    }
+
+    fn render_row(&self, row: &Row, _is_selected: bool, width: usize) -> Vec<Change> {
+        match row {
+            Row::Existing(candidate) => {
+                let entry = &self.workspaces[candidate.idx];
+                let prefix = if entry.is_active { " * " } else { "   " };
+                let suffix = format!(". {} windows ", entry.num_windows);
+                let avail = width
+                    .saturating_sub(unicode_column_width(prefix) + unicode_column_width(&suffix));
+                let mut changes = vec![Change::Text(prefix.to_string())];
+                let (name, _width) = highlight(&entry.name, &candidate.positions, avail);
+                changes.extend(name);
+                changes.push(Change::Text(suffix));
+                changes
+            }
+            Row::New(name) => vec![Change::Text(format!(
+                "   Create new workspace \"{}\" ",
+                name
+            ))],
+        }
+    }
+
+    fn accept(&mut self, row: &Row, _mods: Modifiers, _filter: &str) {
+        let name = match row {
+            Row::Existing(candidate) => self.workspaces[candidate.idx].name.clone(),
+            Row::New(name) => name.clone(),
+        };
+        self.window.notify(TermWindowNotif::PerformAssignment {
+            pane_id: self.pane_id,
+            assignment: KeyAssignment::SwitchToWorkspace {
+                name: Some(name),
+                spawn: None,
+            },
+        });
+    }
+}
+
+pub fn workspace_picker(
+    mut term: TermWizTerminal,
+    workspaces: Vec<WorkspaceEntry>,
+    window: ::window::Window,
+    pane_id: PaneId,
+) -> anyhow::Result<()> {
+    run_picker(
+        &mut term,
+        "Workspaces",
+        "Type to filter or to name a new workspace, Enter to switch, Escape to cancel",
+        &mut WorkspacePicker {
+            workspaces,
+            window,
+            pane_id,
+        },
+    )
+}
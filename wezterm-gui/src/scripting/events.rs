@@ -0,0 +1,84 @@
+//! Emits the lua events that describe the lifecycle of panes.
+//! Each handler receives the pane and the tab that contains it
+//! (or nil if the pane isn't part of a tab), followed by any
+//! arguments that are specific to the event.
+use super::pane::PaneObject;
+use super::tab::TabObject;
+use luahelper::impl_lua_conversion;
+use mux::pane::PaneId;
+use mux::Mux;
+use serde::*;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaneExitStatus {
+    pub success: bool,
+    pub exit_code: u32,
+}
+impl_lua_conversion!(PaneExitStatus);
+
+#[derive(Debug, Clone)]
+pub enum PaneEvent {
+    Spawned,
+    Exited(PaneExitStatus),
+    CwdChanged(Option<String>),
+    TitleChanged(String),
+    Bell,
+    UserVarChanged { name: String, value: String },
+}
+
+impl PaneEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Spawned => "pane-spawned",
+            Self::Exited(_) => "pane-exited",
+            Self::CwdChanged(_) => "pane-cwd-changed",
+            Self::TitleChanged(_) => "pane-title-changed",
+            Self::Bell => "bell",
+            Self::UserVarChanged { .. } => "user-var-changed",
+        }
+    }
+}
+
+async fn do_pane_event(
+    lua: Option<Rc<mlua::Lua>>,
+    pane_id: PaneId,
+    event: PaneEvent,
+) -> anyhow::Result<()> {
+    let lua = match lua {
+        Some(lua) => lua,
+        None => return Ok(()),
+    };
+
+    // The tab is resolved here rather than when the event is queued,
+    // as a newly spawned pane is only added to its tab after the mux
+    // has announced it
+    let mux = Mux::get().ok_or_else(|| anyhow::anyhow!("must be called on main thread"))?;
+    let tab = mux
+        .resolve_pane_id(pane_id)
+        .and_then(|(_domain_id, _window_id, tab_id)| mux.get_tab(tab_id))
+        .map(|tab| TabObject::new(&tab));
+    let pane = PaneObject { pane: pane_id };
+
+    let name = event.name();
+    let args = match event {
+        PaneEvent::Spawned | PaneEvent::Bell => lua.pack_multi((pane, tab))?,
+        PaneEvent::Exited(status) => lua.pack_multi((pane, tab, status))?,
+        PaneEvent::CwdChanged(cwd) => lua.pack_multi((pane, tab, cwd))?,
+        PaneEvent::TitleChanged(title) => lua.pack_multi((pane, tab, title))?,
+        PaneEvent::UserVarChanged { name, value } => lua.pack_multi((pane, tab, name, value))?,
+    };
+
+    if let Err(err) = config::lua::emit_event(&lua, (name.to_string(), args)).await {
+        log::error!("while processing {} event: {:#}", name, err);
+    }
+    Ok(())
+}
+
+/// Schedules `event` to be emitted for `pane_id`
+pub fn emit_pane_event(pane_id: PaneId, event: PaneEvent) {
+    promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+        do_pane_event(lua, pane_id, event)
+    }))
+    .detach();
+}
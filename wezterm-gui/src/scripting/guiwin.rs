@@ -7,6 +7,7 @@ use config::keyassignment::KeyAssignment;
 use luahelper::*;
 use mlua::{UserData, UserDataMethods};
 use mux::window::WindowId as MuxWindowId;
+use mux::Mux;
 use serde::*;
 use wezterm_toast_notification::ToastNotification;
 use window::WindowOps;
@@ -31,6 +32,12 @@ impl GuiWin {
 impl UserData for GuiWin {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_method("window_id", |_, this, _: ()| Ok(this.mux_window_id));
+        methods.add_method("active_workspace", |_, _, _: ()| {
+            let mux = Mux::get()
+                .ok_or_else(|| anyhow::anyhow!("must be called on the main thread"))
+                .map_err(luaerr)?;
+            Ok(mux.active_workspace())
+        });
         methods.add_method(
             "toast_notification",
             |_, _, (title, message, url, timeout): (String, String, Option<String>, Option<u64>)| {
@@ -111,6 +118,17 @@ impl UserData for GuiWin {
 
             Ok((*config).clone())
         });
+        methods.add_async_method("active_key_table", |_, this, _: ()| async move {
+            let (tx, rx) = smol::channel::bounded(1);
+            this.window.notify(TermWindowNotif::GetActiveKeyTable(tx));
+            let name = rx
+                .recv()
+                .await
+                .map_err(|e| anyhow::anyhow!("{:#}", e))
+                .map_err(luaerr)?;
+
+            Ok(name)
+        });
         methods.add_async_method("get_config_overrides", |_, this, _: ()| async move {
             let (tx, rx) = smol::channel::bounded(1);
             this.window.notify(TermWindowNotif::GetConfigOverrides(tx));
@@ -1,11 +1,23 @@
 //! PaneObject represents a Mux Pane instance in lua code
 use super::luaerr;
 use anyhow::anyhow;
+use config::keyassignment::{ExportFormat, ExportScope};
+use luahelper::impl_lua_conversion;
 use mlua::{UserData, UserDataMethods};
 use mux::pane::{Pane, PaneId};
 use mux::Mux;
+use serde::*;
 use std::rc::Rc;
 
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct ExportOptions {
+    #[serde(default)]
+    format: ExportFormat,
+    #[serde(default)]
+    scope: ExportScope,
+}
+impl_lua_conversion!(ExportOptions);
+
 #[derive(Clone)]
 pub struct PaneObject {
     pub pane: PaneId,
@@ -30,7 +42,9 @@ impl PaneObject {
 
 impl UserData for PaneObject {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
-        methods.add_method("pane_id", |_, this, _: ()| Ok(this.pane()?.pane_id()));
+        // The id remains valid after the pane has been removed, so
+        // that event handlers can identify a pane that has gone
+        methods.add_method("pane_id", |_, this, _: ()| Ok(this.pane));
         methods.add_method("get_title", |_, this, _: ()| Ok(this.pane()?.get_title()));
         methods.add_method("get_current_working_dir", |_, this, _: ()| {
             Ok(this
@@ -51,6 +65,12 @@ impl UserData for PaneObject {
         methods.add_method("get_user_vars", |_, this, _: ()| {
             Ok(this.pane()?.copy_user_vars())
         });
+        // Closes the pane without prompting, terminating its process
+        methods.add_method("kill", |_, this, _: ()| {
+            let pane = this.pane()?;
+            Mux::get().unwrap().kill_pane(pane.pane_id());
+            Ok(())
+        });
 
         // When called with no arguments, returns the lines from the
         // viewport as plain text (no escape sequences).
@@ -77,5 +97,16 @@ impl UserData for PaneObject {
             text.truncate(trimmed);
             Ok(text)
         });
+
+        // Returns the whole scrollback, or the most recent semantic
+        // zone of a given type, as text, ansi or html
+        methods.add_method(
+            "export_scrollback",
+            |_, this, options: Option<ExportOptions>| {
+                let options = options.unwrap_or_default();
+                let pane = this.pane()?;
+                mux::export::export_pane(&*pane, options.format, options.scope).map_err(luaerr)
+            },
+        );
     }
 }
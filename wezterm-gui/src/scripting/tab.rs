@@ -0,0 +1,71 @@
+//! TabObject represents a Mux Tab instance in lua code
+use super::luaerr;
+use super::pane::PaneObject;
+use anyhow::anyhow;
+use mlua::{UserData, UserDataMethods};
+use mux::tab::{Tab, TabId};
+use mux::{Mux, MuxNotification};
+use std::rc::Rc;
+use wezterm_term::Alert;
+
+#[derive(Clone)]
+pub struct TabObject {
+    pub tab: TabId,
+}
+
+impl TabObject {
+    pub fn new(tab: &Rc<Tab>) -> Self {
+        Self { tab: tab.tab_id() }
+    }
+
+    fn mux(&self) -> mlua::Result<Rc<Mux>> {
+        Mux::get()
+            .ok_or_else(|| anyhow!("must be called on main thread"))
+            .map_err(luaerr)
+    }
+
+    pub fn tab(&self) -> mlua::Result<Rc<Tab>> {
+        self.mux()?
+            .get_tab(self.tab)
+            .ok_or_else(|| anyhow!("tab id {} is not valid", self.tab))
+            .map_err(luaerr)
+    }
+}
+
+impl UserData for TabObject {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("tab_id", |_, this, _: ()| Ok(this.tab));
+        methods.add_method("window_id", |_, this, _: ()| {
+            Ok(this.mux()?.window_containing_tab(this.tab))
+        });
+        methods.add_method("get_title", |_, this, _: ()| Ok(this.tab()?.get_title()));
+        // Setting an empty title reverts to showing the title of
+        // the active pane
+        methods.add_method("set_title", |_, this, title: String| {
+            let tab = this.tab()?;
+            tab.set_title(&title);
+            // Let the gui windows know that the tab bar needs to be updated
+            if let Some(pane) = tab.get_active_pane() {
+                this.mux()?.notify(MuxNotification::Alert {
+                    pane_id: pane.pane_id(),
+                    alert: Alert::TitleMaybeChanged,
+                });
+            }
+            Ok(())
+        });
+        methods.add_method("active_pane", |_, this, _: ()| {
+            Ok(this
+                .tab()?
+                .get_active_pane()
+                .map(|pane| PaneObject::new(&pane)))
+        });
+        methods.add_method("panes", |_, this, _: ()| {
+            Ok(this
+                .tab()?
+                .iter_panes()
+                .iter()
+                .map(|p| PaneObject::new(&p.pane))
+                .collect::<Vec<_>>())
+        });
+    }
+}
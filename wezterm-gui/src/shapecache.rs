@@ -4,13 +4,14 @@ use ::window::bitmaps::Texture2d;
 use config::TextStyle;
 use std::rc::Rc;
 use termwiz::cellcluster::CellCluster;
-use wezterm_font::shaper::GlyphInfo;
+use wezterm_font::shaper::{Direction, GlyphInfo};
 use wezterm_font::units::*;
 
 #[derive(PartialEq, Eq, Hash)]
 pub struct ShapeCacheKey {
     pub style: TextStyle,
     pub text: String,
+    pub direction: Direction,
 }
 
 #[derive(Debug, PartialEq)]
@@ -188,6 +189,7 @@ where
 pub struct BorrowedShapeCacheKey<'a> {
     pub style: &'a TextStyle,
     pub text: &'a str,
+    pub direction: Direction,
 }
 
 impl<'a> BorrowedShapeCacheKey<'a> {
@@ -195,6 +197,7 @@ impl<'a> BorrowedShapeCacheKey<'a> {
         ShapeCacheKey {
             style: self.style.clone(),
             text: self.text.to_owned(),
+            direction: self.direction,
         }
     }
 }
@@ -208,6 +211,7 @@ impl ShapeCacheKeyTrait for ShapeCacheKey {
         BorrowedShapeCacheKey {
             style: &self.style,
             text: &self.text,
+            direction: self.direction,
         }
     }
 }
@@ -276,7 +280,9 @@ mod test {
         let cell_clusters = line.cluster();
         assert_eq!(cell_clusters.len(), 1);
         let cluster = &cell_clusters[0];
-        let infos = font.shape(&cluster.text, || {}, |_| {}).unwrap();
+        let infos = font
+            .shape(&cluster.text, cluster.direction, || {}, |_| {})
+            .unwrap();
         let glyphs = infos
             .iter()
             .map(|info| {
@@ -387,7 +393,9 @@ mod test {
                 let cluster = &cell_clusters[0];
 
                 measurer.measure(|| {
-                    let _x = font.shape(&cluster.text, || {}, |_| {}).unwrap();
+                    let _x = font
+                        .shape(&cluster.text, cluster.direction, || {}, |_| {})
+                        .unwrap();
                     // println!("{:?}", &x[0..2]);
                 });
             })
@@ -110,7 +110,13 @@ fn compute_tab_title(
         Some(title) => title,
         None => {
             let title = if let Some(pane) = &tab.active_pane {
-                let mut title = pane.title.clone();
+                // A title assigned to the tab takes precedence
+                // over the title of its active pane
+                let mut title = if tab.tab_title.is_empty() {
+                    pane.title.clone()
+                } else {
+                    tab.tab_title.clone()
+                };
                 if config.show_tab_index_in_tab_bar {
                     title = format!(
                         " {}: {} ",
@@ -120,7 +126,7 @@ fn compute_tab_title(
                             } else {
                                 1
                             },
-                        pane.title
+                        title
                     );
                 }
                 // We have a preferred soft minimum on tab width to make it
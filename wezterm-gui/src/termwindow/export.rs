@@ -0,0 +1,66 @@
+//! The ExportScrollback key assignment, which saves the contents
+//! of a pane to a file so that it can be attached to a bug report.
+use anyhow::{anyhow, Context};
+use config::keyassignment::{ExportFormat, ExportScope};
+use mux::pane::Pane;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::SystemTime;
+use wezterm_toast_notification::persistent_toast_notification;
+
+impl super::TermWindow {
+    pub fn export_scrollback(
+        &self,
+        pane: &Rc<dyn Pane>,
+        format: ExportFormat,
+        scope: ExportScope,
+        path: Option<PathBuf>,
+    ) {
+        match self.do_export_scrollback(pane, format, scope, path) {
+            Ok(path) => persistent_toast_notification(
+                "Exported pane",
+                &format!("Saved to {}", path.display()),
+            ),
+            Err(err) => log::error!("Failed to export pane: {:#}", err),
+        }
+    }
+
+    fn do_export_scrollback(
+        &self,
+        pane: &Rc<dyn Pane>,
+        format: ExportFormat,
+        scope: ExportScope,
+        path: Option<PathBuf>,
+    ) -> anyhow::Result<PathBuf> {
+        let data = match scope {
+            ExportScope::Selection => {
+                let sel = self
+                    .selection(pane.pane_id())
+                    .range
+                    .as_ref()
+                    .map(|r| r.normalize())
+                    .ok_or_else(|| anyhow!("there is no selection to export"))?;
+                mux::export::export_region(&**pane, sel.rows(), |row| sel.cols_for_row(row), format)
+            }
+            scope => mux::export::export_pane(&**pane, format, scope)?,
+        };
+
+        let path = match path {
+            Some(path) => path,
+            None => {
+                let timestamp = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                config::HOME_DIR.join(format!(
+                    "wezterm-pane-{}-{}.{}",
+                    pane.pane_id(),
+                    timestamp,
+                    format.extension()
+                ))
+            }
+        };
+        std::fs::write(&path, data).with_context(|| format!("writing {}", path.display()))?;
+        Ok(path)
+    }
+}
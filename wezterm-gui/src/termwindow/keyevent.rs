@@ -1,4 +1,12 @@
+use super::TermWindowNotif;
 use ::window::{KeyCode, KeyEvent, Modifiers, WindowOps};
+use config::describe_key;
+use config::keyassignment::{InputMap, KeyAssignment};
+use mux::pane::Pane;
+use smol::Timer;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 pub fn window_mods_to_termwiz_mods(modifiers: ::window::Modifiers) -> termwiz::input::Modifiers {
     let mut result = termwiz::input::Modifiers::NONE;
@@ -33,7 +41,384 @@ pub enum Key {
     None,
 }
 
+#[derive(Debug, Clone)]
+struct KeyTableStateEntry {
+    name: String,
+    /// If is_some, the table is popped once this instant has passed
+    expiration: Option<Instant>,
+    /// If true, the table is popped by the next key press
+    one_shot: bool,
+}
+
+/// The stack of key tables that were activated by ActivateKeyTable.
+/// Key presses are matched against the tables from the top of the
+/// stack down, before falling back to the main key assignments.
+#[derive(Debug, Default, Clone)]
+pub struct KeyTableState {
+    stack: Vec<KeyTableStateEntry>,
+}
+
+impl KeyTableState {
+    /// Push the named table onto the stack.
+    /// Returns the time at which the table expires, if it has a timeout.
+    pub fn activate(
+        &mut self,
+        name: &str,
+        timeout_milliseconds: Option<u64>,
+        one_shot: bool,
+        replace_current: bool,
+    ) -> Option<Instant> {
+        if replace_current {
+            self.pop();
+        }
+        let expiration = timeout_milliseconds.map(|ms| Instant::now() + Duration::from_millis(ms));
+        self.stack.push(KeyTableStateEntry {
+            name: name.to_string(),
+            expiration,
+            one_shot,
+        });
+        expiration
+    }
+
+    pub fn pop(&mut self) {
+        self.stack.pop();
+    }
+
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+
+    /// Removes any tables whose timeout has elapsed.
+    /// Returns true if the stack was changed.
+    pub fn process_expiration(&mut self) -> bool {
+        let now = Instant::now();
+        let len = self.stack.len();
+        self.stack
+            .retain(|entry| entry.expiration.map(|exp| exp > now).unwrap_or(true));
+        self.stack.len() != len
+    }
+
+    /// Returns the name of the table at the top of the stack
+    pub fn current_table(&self) -> Option<&str> {
+        self.stack.last().map(|entry| entry.name.as_str())
+    }
+
+    /// If the table at the top of the stack is one-shot, remove it
+    /// from the stack and return its name
+    pub fn pop_one_shot(&mut self) -> Option<String> {
+        match self.stack.last() {
+            Some(entry) if entry.one_shot => self.stack.pop().map(|entry| entry.name),
+            _ => None,
+        }
+    }
+
+    /// Iterates the active table names, from the top of the stack down
+    pub fn active_tables(&self) -> impl Iterator<Item = &str> {
+        self.stack.iter().rev().map(|entry| entry.name.as_str())
+    }
+}
+
+/// The result of matching key presses against a KeySequenceTree
+#[derive(Debug, PartialEq)]
+pub enum KeySequenceMatch {
+    /// The key presses don't correspond to any sequence
+    None,
+    /// The key presses are the start of at least one sequence
+    Prefix,
+    /// The key presses form a complete sequence
+    Complete(KeyAssignment),
+}
+
+/// A prefix tree of the multi-key sequences defined in the config.
+/// Each node is keyed by a normalized key press.
+#[derive(Debug, Default)]
+pub struct KeySequenceTree {
+    action: Option<KeyAssignment>,
+    children: HashMap<(KeyCode, Modifiers), KeySequenceTree>,
+}
+
+impl KeySequenceTree {
+    /// Build the tree from the `sequences` defined in the config.
+    /// `input_map` holds the single key assignments, including the
+    /// defaults; it is only used to warn about conflicts with the sequences.
+    pub fn new(
+        sequences: Vec<(Vec<(KeyCode, Modifiers)>, KeyAssignment)>,
+        input_map: &InputMap,
+    ) -> Self {
+        let mut root = Self::default();
+        for (sequence, action) in sequences {
+            let mut node = &mut root;
+            for press in sequence {
+                node = node.children.entry(press).or_default();
+            }
+            node.action.replace(action);
+        }
+        root.warn_about_unreachable(&mut vec![]);
+        root.warn_about_shadowed_bindings(input_map);
+        root
+    }
+
+    /// Key presses are matched against the sequences before the single
+    /// key assignments, so a single key assignment for the first key of
+    /// a sequence can never be triggered
+    fn warn_about_shadowed_bindings(&self, input_map: &InputMap) {
+        for (key, mods) in self.children.keys() {
+            if let Some(action) = input_map.lookup_key(key, *mods) {
+                log::warn!(
+                    "key assignment {:?} for `{}` is unreachable because \
+                     that key is the start of a key sequence",
+                    action,
+                    describe_key(key, *mods)
+                );
+            }
+        }
+    }
+
+    /// A sequence that is a prefix of a longer sequence can never
+    /// complete, because we'll always wait for the longer one
+    fn warn_about_unreachable(&self, path: &mut Vec<String>) {
+        if self.action.is_some() && !self.children.is_empty() {
+            log::warn!(
+                "key sequence `{}` is unreachable because it is the \
+                 prefix of a longer sequence",
+                path.join(" ")
+            );
+        }
+        for ((key, mods), child) in &self.children {
+            path.push(describe_key(key, *mods));
+            child.warn_about_unreachable(path);
+            path.pop();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    pub fn lookup(&self, sequence: &[(KeyCode, Modifiers)]) -> KeySequenceMatch {
+        let mut node = self;
+        for press in sequence {
+            match node.children.get(press) {
+                Some(child) => node = child,
+                None => return KeySequenceMatch::None,
+            }
+        }
+        if !node.children.is_empty() {
+            KeySequenceMatch::Prefix
+        } else if let Some(action) = &node.action {
+            KeySequenceMatch::Complete(action.clone())
+        } else {
+            KeySequenceMatch::None
+        }
+    }
+}
+
+/// Tracks a multi-key sequence that has been started but
+/// not yet completed
+#[derive(Debug)]
+pub struct PendingKeySequence {
+    /// The normalized key presses that have matched so far
+    presses: Vec<(KeyCode, Modifiers)>,
+    /// The original key events, which are sent to the pane if
+    /// the sequence is not completed
+    events: Vec<KeyEvent>,
+    expiration: Instant,
+}
+
+/// Returns the ways in which a key event may be matched against a
+/// key sequence, in the same order of preference that is used for
+/// single key assignments
+fn key_sequence_candidates(window_key: &KeyEvent) -> Vec<(KeyCode, Modifiers)> {
+    let normalize = |key: &KeyCode, mods: Modifiers| {
+        key.normalize_shift(mods - (Modifiers::LEFT_ALT | Modifiers::RIGHT_ALT))
+    };
+    let mut candidates = vec![];
+    if let Some(raw_code) = window_key.raw_code {
+        candidates.push(normalize(
+            &KeyCode::RawCode(raw_code),
+            window_key.raw_modifiers,
+        ));
+    }
+    if let Some(raw_key) = &window_key.raw_key {
+        candidates.push(normalize(raw_key, window_key.raw_modifiers));
+    }
+    candidates.push(normalize(&window_key.key, window_key.modifiers));
+    candidates
+}
+
 impl super::TermWindow {
+    /// Match the key press against the multi-key sequences defined in
+    /// the config.  Returns true if the key press was consumed, either
+    /// because it completed a sequence or because it continued one.
+    /// If a pending sequence is broken by this key press, the keys that
+    /// were held back are sent to the pane and false is returned so that
+    /// the caller processes this key press in the usual way.
+    fn process_key_sequence(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        window_key: &KeyEvent,
+        may_start: bool,
+    ) -> bool {
+        if window_key.key.is_modifier() {
+            return false;
+        }
+        if self.pending_key_sequence.is_none() && (!may_start || self.key_sequences.is_empty()) {
+            return false;
+        }
+
+        let prefix = self
+            .pending_key_sequence
+            .as_ref()
+            .map(|pending| pending.presses.clone())
+            .unwrap_or_default();
+
+        for press in key_sequence_candidates(window_key) {
+            let mut presses = prefix.clone();
+            presses.push(press);
+            match self.key_sequences.lookup(&presses) {
+                KeySequenceMatch::None => continue,
+                KeySequenceMatch::Prefix => {
+                    let mut events = self
+                        .pending_key_sequence
+                        .take()
+                        .map(|pending| pending.events)
+                        .unwrap_or_default();
+                    events.push(window_key.clone());
+                    let expiration = Instant::now()
+                        + Duration::from_millis(self.config.key_sequence_timeout_milliseconds);
+                    self.pending_key_sequence.replace(PendingKeySequence {
+                        presses,
+                        events,
+                        expiration,
+                    });
+                    self.schedule_key_sequence_timeout(expiration);
+                    self.update_title_post_status();
+                    return true;
+                }
+                KeySequenceMatch::Complete(assignment) => {
+                    self.pending_key_sequence.take();
+                    self.update_title_post_status();
+                    self.perform_key_assignment(pane, &assignment).ok();
+                    return true;
+                }
+            }
+        }
+
+        if let Some(pending) = self.pending_key_sequence.take() {
+            self.send_pending_keys_to_pane(pane, pending.events);
+            self.update_title_post_status();
+        }
+        false
+    }
+
+    fn schedule_key_sequence_timeout(&self, expiration: Instant) {
+        if let Some(window) = self.window.clone() {
+            promise::spawn::spawn(async move {
+                Timer::at(expiration).await;
+                window.notify(TermWindowNotif::Apply(Box::new(|term_window| {
+                    term_window.expire_key_sequence();
+                })));
+            })
+            .detach();
+        }
+    }
+
+    /// Arrange to remove the key tables that have timed out once
+    /// `expiration` has passed, so that the status reflects the change
+    /// without waiting for the next key press
+    pub fn schedule_key_table_expiration(&self, expiration: Instant) {
+        if let Some(window) = self.window.clone() {
+            promise::spawn::spawn(async move {
+                Timer::at(expiration).await;
+                window.notify(TermWindowNotif::Apply(Box::new(|term_window| {
+                    if term_window.key_table_state.process_expiration() {
+                        term_window.schedule_status_update();
+                    }
+                })));
+            })
+            .detach();
+        }
+    }
+
+    /// Called when the key sequence timeout elapses.  If the pending
+    /// sequence hasn't been extended in the meantime, give up on it
+    /// and send its keys to the pane.
+    fn expire_key_sequence(&mut self) {
+        match self.pending_key_sequence.as_ref() {
+            Some(pending) if pending.expiration <= Instant::now() => {}
+            _ => return,
+        }
+        if let Some(pending) = self.pending_key_sequence.take() {
+            if let Some(pane) = self.get_active_pane_or_overlay() {
+                self.send_pending_keys_to_pane(&pane, pending.events);
+            }
+            self.update_title_post_status();
+        }
+    }
+
+    fn send_pending_keys_to_pane(&mut self, pane: &Rc<dyn Pane>, events: Vec<KeyEvent>) {
+        for event in events {
+            match self.win_key_code_to_termwiz_key_code(&event.key) {
+                Key::Code(key) => {
+                    pane.key_down(key, window_mods_to_termwiz_mods(event.modifiers))
+                        .ok();
+                }
+                Key::Composed(s) => {
+                    pane.writer().write_all(s.as_bytes()).ok();
+                }
+                Key::None => {}
+            }
+        }
+        if self.pane_state(pane.pane_id()).overlay.is_none() {
+            self.maybe_scroll_to_bottom_for_input(pane);
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Returns a description of the keys that have been pressed so
+    /// far in an incomplete multi-key sequence
+    pub fn key_sequence_hint(&self) -> Option<String> {
+        self.pending_key_sequence.as_ref().map(|pending| {
+            pending
+                .presses
+                .iter()
+                .map(|(key, mods)| describe_key(key, *mods))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+    }
+
+    /// Resolve a key press to an assignment by consulting the one-shot
+    /// table that is being consumed by this key press (if any), then
+    /// the stack of active key tables and finally the main key map
+    fn lookup_key(
+        &self,
+        one_shot_table: Option<&str>,
+        key: &KeyCode,
+        mods: Modifiers,
+    ) -> Option<KeyAssignment> {
+        for name in one_shot_table
+            .into_iter()
+            .chain(self.key_table_state.active_tables())
+        {
+            if let Some(assignment) = self.input_map.lookup_key_in_table(name, key, mods) {
+                return Some(assignment);
+            }
+        }
+        self.input_map.lookup_key(key, mods)
+    }
+
+    /// Returns the name of the key table at the top of the stack,
+    /// taking into account any timeouts
+    pub fn active_key_table(&mut self) -> Option<String> {
+        if self.key_table_state.process_expiration() {
+            self.schedule_status_update();
+        }
+        self.key_table_state.current_table().map(|s| s.to_string())
+    }
+
     pub fn key_event_impl(&mut self, window_key: KeyEvent, context: &dyn WindowOps) -> bool {
         if !window_key.key_is_down {
             return false;
@@ -66,6 +451,33 @@ impl super::TermWindow {
             _ => (false, Modifiers::NONE),
         };
 
+        // Key tables that have timed out are no longer eligible for
+        // matching.  A one-shot table is consumed by the next non-modifier
+        // key press, whether or not that key matches an entry in it.
+        let mut key_table_changed = self.key_table_state.process_expiration();
+        let one_shot_table = if window_key.key.is_modifier() {
+            None
+        } else {
+            self.key_table_state.pop_one_shot()
+        };
+        if one_shot_table.is_some() {
+            key_table_changed = true;
+        }
+        if key_table_changed {
+            self.schedule_status_update();
+        }
+        let one_shot_table = one_shot_table.as_deref();
+
+        // Multi-key sequences are only started from the default key
+        // table; the leader and key tables are modes of their own
+        let may_start_sequence = !leader_active
+            && one_shot_table.is_none()
+            && self.key_table_state.current_table().is_none();
+        if self.process_key_sequence(&pane, &window_key, may_start_sequence) {
+            context.invalidate();
+            return true;
+        }
+
         let modifiers = window_mods_to_termwiz_mods(window_key.modifiers);
         let raw_modifiers = window_mods_to_termwiz_mods(window_key.raw_modifiers);
 
@@ -91,10 +503,11 @@ impl super::TermWindow {
                 }
             }
 
-            if let Some(assignment) = self
-                .input_map
-                .lookup_key(&raw_code_key, window_key.raw_modifiers | leader_mod)
-            {
+            if let Some(assignment) = self.lookup_key(
+                one_shot_table,
+                &raw_code_key,
+                window_key.raw_modifiers | leader_mod,
+            ) {
                 self.perform_key_assignment(&pane, &assignment).ok();
                 context.invalidate();
 
@@ -120,9 +533,8 @@ impl super::TermWindow {
                 }
             }
 
-            if let Some(assignment) = self
-                .input_map
-                .lookup_key(key, window_key.raw_modifiers | leader_mod)
+            if let Some(assignment) =
+                self.lookup_key(one_shot_table, key, window_key.raw_modifiers | leader_mod)
             {
                 self.perform_key_assignment(&pane, &assignment).ok();
                 context.invalidate();
@@ -185,10 +597,11 @@ impl super::TermWindow {
             }
         }
 
-        if let Some(assignment) = self
-            .input_map
-            .lookup_key(&window_key.key, window_key.modifiers | leader_mod)
-        {
+        if let Some(assignment) = self.lookup_key(
+            one_shot_table,
+            &window_key.key,
+            window_key.modifiers | leader_mod,
+        ) {
             self.perform_key_assignment(&pane, &assignment).ok();
             context.invalidate();
             if leader_active {
@@ -351,3 +764,134 @@ impl super::TermWindow {
         Key::Code(code)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ctrl(c: char) -> (KeyCode, Modifiers) {
+        (KeyCode::Char(c), Modifiers::CTRL)
+    }
+
+    fn key(c: char) -> (KeyCode, Modifiers) {
+        (KeyCode::Char(c), Modifiers::NONE)
+    }
+
+    fn tables(state: &KeyTableState) -> Vec<&str> {
+        state.active_tables().collect()
+    }
+
+    #[test]
+    fn key_table_push_and_pop() {
+        let mut state = KeyTableState::default();
+        assert_eq!(state.current_table(), None);
+
+        assert_eq!(state.activate("resize", None, false, false), None);
+        state.activate("move", None, false, false);
+        assert_eq!(state.current_table(), Some("move"));
+        assert_eq!(tables(&state), vec!["move", "resize"]);
+
+        // Replacing the current table leaves the rest of the stack alone
+        state.activate("copy", None, false, true);
+        assert_eq!(tables(&state), vec!["copy", "resize"]);
+
+        state.pop();
+        assert_eq!(tables(&state), vec!["resize"]);
+        state.pop();
+        assert_eq!(state.current_table(), None);
+        // Popping an empty stack is harmless
+        state.pop();
+        assert_eq!(state.current_table(), None);
+
+        state.activate("resize", None, false, false);
+        state.activate("move", None, false, false);
+        state.clear();
+        assert!(tables(&state).is_empty());
+    }
+
+    #[test]
+    fn key_table_timeout() {
+        let mut state = KeyTableState::default();
+        state.activate("resize", None, false, false);
+        let expiration = state.activate("expired", Some(0), false, false);
+        assert!(expiration.is_some());
+        state.activate("move", Some(60_000), false, false);
+
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(state.process_expiration());
+        assert_eq!(tables(&state), vec!["move", "resize"]);
+
+        // Nothing else has expired
+        assert!(!state.process_expiration());
+        assert_eq!(tables(&state), vec!["move", "resize"]);
+    }
+
+    #[test]
+    fn key_table_one_shot() {
+        let mut state = KeyTableState::default();
+        state.activate("resize", None, false, false);
+        assert_eq!(state.pop_one_shot(), None);
+        assert_eq!(state.current_table(), Some("resize"));
+
+        state.activate("move", None, true, false);
+        assert_eq!(state.pop_one_shot(), Some("move".to_string()));
+        assert_eq!(tables(&state), vec!["resize"]);
+
+        // Only the top of the stack is considered
+        state.activate("move", None, true, false);
+        state.activate("copy", None, false, false);
+        assert_eq!(state.pop_one_shot(), None);
+        assert_eq!(tables(&state), vec!["copy", "move", "resize"]);
+    }
+
+    #[test]
+    fn key_sequence_lookup() {
+        config::use_test_configuration();
+        let input_map = InputMap::new(&config::configuration());
+
+        let empty = KeySequenceTree::new(vec![], &input_map);
+        assert!(empty.is_empty());
+        assert_eq!(empty.lookup(&[]), KeySequenceMatch::None);
+        assert_eq!(empty.lookup(&[ctrl('a')]), KeySequenceMatch::None);
+
+        let tree = KeySequenceTree::new(
+            vec![
+                (vec![ctrl('a'), key('b')], KeyAssignment::ActivateTab(0)),
+                (vec![ctrl('a'), key('c')], KeyAssignment::ActivateTab(1)),
+                (
+                    vec![ctrl('x'), ctrl('y'), key('z')],
+                    KeyAssignment::ActivateTab(2),
+                ),
+            ],
+            &input_map,
+        );
+        assert!(!tree.is_empty());
+
+        assert_eq!(tree.lookup(&[ctrl('a')]), KeySequenceMatch::Prefix);
+        assert_eq!(
+            tree.lookup(&[ctrl('a'), key('b')]),
+            KeySequenceMatch::Complete(KeyAssignment::ActivateTab(0))
+        );
+        assert_eq!(
+            tree.lookup(&[ctrl('a'), key('c')]),
+            KeySequenceMatch::Complete(KeyAssignment::ActivateTab(1))
+        );
+        assert_eq!(
+            tree.lookup(&[ctrl('x'), ctrl('y')]),
+            KeySequenceMatch::Prefix
+        );
+        assert_eq!(
+            tree.lookup(&[ctrl('x'), ctrl('y'), key('z')]),
+            KeySequenceMatch::Complete(KeyAssignment::ActivateTab(2))
+        );
+
+        // Keys that diverge from, or continue past, a sequence don't match
+        assert_eq!(tree.lookup(&[key('a')]), KeySequenceMatch::None);
+        assert_eq!(tree.lookup(&[ctrl('a'), key('d')]), KeySequenceMatch::None);
+        assert_eq!(
+            tree.lookup(&[ctrl('a'), key('b'), key('b')]),
+            KeySequenceMatch::None
+        );
+        assert_eq!(tree.lookup(&[ctrl('x'), key('y')]), KeySequenceMatch::None);
+    }
+}
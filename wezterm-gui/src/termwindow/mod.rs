@@ -5,9 +5,10 @@ use super::utilsprites::RenderMetrics;
 use crate::cache::LruCache;
 use crate::glium::texture::SrgbTexture2d;
 use crate::overlay::{
-    confirm_close_pane, confirm_close_tab, confirm_close_window, confirm_quit_program, launcher,
-    start_overlay, start_overlay_pane, tab_navigator, CopyOverlay, QuickSelectOverlay,
-    SearchOverlay,
+    color_scheme_picker, command_palette, command_palette_entries, confirm_close_pane,
+    confirm_close_tab, confirm_close_window, confirm_quit_program, launcher, pane_select,
+    start_overlay, start_overlay_pane, tab_navigator, workspace_picker, CopyOverlay, PaneSnapshot,
+    QuickSelectOverlay, SearchOverlay, WorkspaceEntry,
 };
 use crate::scripting::guiwin::GuiWin;
 use crate::scripting::pane::PaneObject;
@@ -20,7 +21,8 @@ use ::window::*;
 use anyhow::Context;
 use anyhow::{anyhow, ensure};
 use config::keyassignment::{
-    ClipboardCopyDestination, ClipboardPasteSource, InputMap, KeyAssignment, SpawnCommand,
+    ClipboardCopyDestination, ClipboardPasteSource, InputMap, KeyAssignment, PaneSelectMode,
+    SpawnCommand,
 };
 use config::{configuration, ConfigHandle, WindowCloseConfirmation};
 use luahelper::impl_lua_conversion;
@@ -44,15 +46,18 @@ use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use termwiz::hyperlink::Hyperlink;
 use termwiz::image::ImageData;
+use termwiz::surface::line::VisualLine;
 use wezterm_font::FontConfiguration;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::input::LastMouseClick;
-use wezterm_term::{Alert, StableRowIndex, TerminalConfiguration};
+use wezterm_term::{Alert, Line, StableRowIndex};
 
 pub mod clipboard;
+mod export;
 mod keyevent;
 mod mouseevent;
 mod prevcursor;
+mod rearrange;
 mod render;
 pub mod resize;
 mod selection;
@@ -88,6 +93,7 @@ pub enum TermWindowNotif {
         tx: Sender<String>,
     },
     GetEffectiveConfig(Sender<ConfigHandle>),
+    GetActiveKeyTable(Sender<Option<String>>),
     FinishWindowEvent {
         name: String,
         again: bool,
@@ -124,6 +130,7 @@ pub struct TabInformation {
     pub tab_index: usize,
     pub is_active: bool,
     pub active_pane: Option<PaneInformation>,
+    pub tab_title: String,
 }
 impl_lua_conversion!(TabInformation);
 
@@ -186,6 +193,10 @@ pub struct TermWindow {
     input_map: InputMap,
     /// If is_some, the LEADER modifier is active until the specified instant.
     leader_is_down: Option<std::time::Instant>,
+    key_table_state: keyevent::KeyTableState,
+    key_sequences: keyevent::KeySequenceTree,
+    /// If is_some, the keys pressed so far in a multi-key sequence
+    pending_key_sequence: Option<keyevent::PendingKeySequence>,
     show_tab_bar: bool,
     show_scroll_bar: bool,
     tab_bar: TabBarState,
@@ -219,6 +230,11 @@ pub struct TermWindow {
     shape_cache:
         RefCell<LruCache<ShapeCacheKey, anyhow::Result<Rc<Vec<ShapedInfo<SrgbTexture2d>>>>>>,
 
+    /// The display order of recently rendered lines that have bidi
+    /// enabled, keyed by pane, stable row and width.  The line is
+    /// kept alongside so that a changed line is reordered again.
+    bidi_cache: RefCell<LruCache<(PaneId, StableRowIndex, usize), (Line, Option<Rc<VisualLine>>)>>,
+
     next_blink_paint: RefCell<Instant>,
     last_status_call: Instant,
 
@@ -446,6 +462,10 @@ impl TermWindow {
 
         let clipboard_contents = Arc::new(Mutex::new(None));
 
+        let input_map = InputMap::new(&config);
+        let key_sequences =
+            keyevent::KeySequenceTree::new(config.key_sequence_bindings(), &input_map);
+
         let myself = Self {
             config_subscription: None,
             gl: None,
@@ -462,8 +482,11 @@ impl TermWindow {
             is_full_screen: false,
             terminal_size,
             render_state,
-            input_map: InputMap::new(&config),
+            input_map,
             leader_is_down: None,
+            key_table_state: keyevent::KeyTableState::default(),
+            key_sequences,
+            pending_key_sequence: None,
             show_tab_bar,
             show_scroll_bar: config.enable_scroll_bar,
             tab_bar: TabBarState::default(),
@@ -487,6 +510,11 @@ impl TermWindow {
                 "shape_cache.miss.rate",
                 65536,
             )),
+            bidi_cache: RefCell::new(LruCache::new(
+                "bidi_cache.hit.rate",
+                "bidi_cache.miss.rate",
+                1024,
+            )),
             next_blink_paint: RefCell::new(Instant::now()),
             last_status_call: Instant::now(),
             event_states: HashMap::new(),
@@ -645,6 +673,11 @@ impl TermWindow {
                     .map_err(chan_err)
                     .context("send GetEffectiveConfig response")?;
             }
+            TermWindowNotif::GetActiveKeyTable(tx) => {
+                tx.try_send(self.active_key_table())
+                    .map_err(chan_err)
+                    .context("send GetActiveKeyTable response")?;
+            }
             TermWindowNotif::FinishWindowEvent { name, again } => {
                 self.finish_window_event(&name, again);
             }
@@ -687,6 +720,13 @@ impl TermWindow {
                         window.close();
                     }
                 }
+                MuxNotification::WindowWorkspaceChanged(_)
+                | MuxNotification::ActiveWorkspaceChanged(_) => {
+                    // The mux window is no longer in the active workspace.
+                    // Close the gui window but keep the mux window so that
+                    // it can be shown again when its workspace is activated.
+                    window.close();
+                }
                 _ => {}
             },
             TermWindowNotif::EmitStatusUpdate => {
@@ -813,6 +853,25 @@ impl TermWindow {
                     return true;
                 }
             }
+            MuxNotification::WindowWorkspaceChanged(window_id) if window_id != mux_window_id => {
+                return true;
+            }
+            MuxNotification::WindowWorkspaceChanged(_)
+            | MuxNotification::ActiveWorkspaceChanged(_) => {
+                let mux = Mux::get().expect("mux is calling us");
+                let workspace = mux.active_workspace();
+                let is_visible = mux
+                    .get_window(mux_window_id)
+                    .map_or(false, |w| w.get_workspace() == workspace);
+                if is_visible {
+                    return true;
+                }
+                // The window will close itself; a new window and
+                // subscription are created if the workspace is
+                // activated again
+                window.notify(TermWindowNotif::MuxNotification(n));
+                return false;
+            }
             _ => return true,
         }
 
@@ -979,11 +1038,26 @@ impl TermWindow {
 impl TermWindow {
     fn palette(&mut self) -> &ColorPalette {
         if self.palette.is_none() {
-            self.palette.replace(config::TermConfig.color_palette());
+            self.palette
+                .replace(self.config.resolved_palette.clone().into());
         }
         self.palette.as_ref().unwrap()
     }
 
+    /// Returns the palette to use when rendering `pane`.
+    /// The palette of the pane reflects the global configuration and
+    /// any changes made to it by escape sequences, but colors that
+    /// have been overridden for this window take precedence.
+    pub fn palette_for_pane(&self, pane: &Rc<dyn Pane>) -> ColorPalette {
+        if self.config_overrides.get("color_scheme").is_some()
+            || self.config_overrides.get("colors").is_some()
+        {
+            self.config.resolved_palette.clone().into()
+        } else {
+            pane.palette()
+        }
+    }
+
     pub fn config_was_reloaded(&mut self) {
         log::debug!(
             "config was reloaded, overrides: {:?}",
@@ -1020,6 +1094,10 @@ impl TermWindow {
         self.shape_cache.borrow_mut().clear();
         self.input_map = InputMap::new(&config);
         self.leader_is_down = None;
+        self.key_table_state.clear();
+        self.key_sequences =
+            keyevent::KeySequenceTree::new(config.key_sequence_bindings(), &self.input_map);
+        self.pending_key_sequence = None;
         let dimensions = self.dimensions;
 
         if let Err(err) = self.fonts.config_changed(&config) {
@@ -1094,6 +1172,13 @@ impl TermWindow {
             0
         };
 
+        // Show the keys pressed so far in an incomplete multi-key
+        // sequence ahead of the user-defined status
+        let right_status = match self.key_sequence_hint() {
+            Some(hint) => format!("\x1b[7m {} \x1b[0m {}", hint, self.right_status),
+            None => self.right_status.clone(),
+        };
+
         let new_tab_bar = TabBarState::new(
             self.terminal_size.cols as usize,
             if self.last_mouse_coords.1 == tab_bar_y {
@@ -1105,7 +1190,7 @@ impl TermWindow {
             &panes,
             self.config.colors.as_ref().and_then(|c| c.tab_bar.as_ref()),
             &self.config,
-            &self.right_status,
+            &right_status,
         );
         if new_tab_bar != self.tab_bar {
             self.tab_bar = new_tab_bar;
@@ -1174,6 +1259,13 @@ impl TermWindow {
             }
         };
 
+        // Without the tab bar there is nowhere to show the status,
+        // so show an incomplete key sequence in the title instead
+        let title = match self.key_sequence_hint() {
+            Some(hint) if !self.show_tab_bar => format!("[{}] {}", hint, title),
+            _ => title,
+        };
+
         if let Some(window) = self.window.as_ref() {
             window.set_title(&title);
 
@@ -1326,8 +1418,17 @@ impl TermWindow {
 
         let gui_win = GuiWin::new(self);
 
+        let memory_usage = mux
+            .iter_panes()
+            .into_iter()
+            .filter_map(|pane| {
+                pane.get_memory_usage()
+                    .map(|usage| (pane.pane_id(), pane.get_title(), usage))
+            })
+            .collect();
+
         let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
-            crate::overlay::show_debug_overlay(term, gui_win)
+            crate::overlay::show_debug_overlay(term, gui_win, memory_usage)
         });
         self.assign_overlay(tab.tab_id(), overlay);
         promise::spawn::spawn(future).detach();
@@ -1369,6 +1470,188 @@ impl TermWindow {
         promise::spawn::spawn(future).detach();
     }
 
+    fn show_command_palette(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let pane = match self.get_active_pane_no_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+
+        // As with the launcher, the overlay runs in another thread, so
+        // we resolve the list of actions and their key bindings up front
+        let entries = command_palette_entries(&self.input_map, &self.config);
+        let window = self.window.clone().unwrap();
+        let pane_id = pane.pane_id();
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            command_palette(term, entries, window, pane_id)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_pane_select(&mut self, mode: PaneSelectMode, alphabet: Option<String>) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        if self.tab_state(tab.tab_id()).overlay.is_some() {
+            return;
+        }
+
+        // Every pane needs to be visible in order to be labelled.
+        // The zoom is restored if no pane is selected.
+        let was_zoomed = tab.iter_panes().iter().any(|pos| pos.is_zoomed);
+        tab.set_zoomed(false);
+
+        // The overlay runs in another thread and cannot access the panes,
+        // so we capture what is currently visible in each of them
+        let panes: Vec<PaneSnapshot> = tab
+            .iter_panes()
+            .into_iter()
+            .map(|pos| {
+                let dims = pos.pane.get_dimensions();
+                let top = self
+                    .get_viewport(pos.pane.pane_id())
+                    .unwrap_or(dims.physical_top);
+                let (_first, lines) = pos
+                    .pane
+                    .get_lines(top..top + dims.viewport_rows as StableRowIndex);
+                PaneSnapshot {
+                    pane_id: pos.pane.pane_id(),
+                    left: pos.left,
+                    top: pos.top,
+                    width: pos.width,
+                    height: pos.height,
+                    lines,
+                }
+            })
+            .collect();
+
+        let alphabet = alphabet.unwrap_or_else(|| self.config.quick_select_alphabet.clone());
+        let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+            pane_select(tab_id, term, panes, alphabet, mode, was_zoomed)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_workspace_picker(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let pane = match self.get_active_pane_no_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+
+        let active = mux.active_workspace();
+        let workspaces: Vec<WorkspaceEntry> = mux
+            .iter_workspaces()
+            .into_iter()
+            .map(|name| WorkspaceEntry {
+                num_windows: mux.iter_windows_in_workspace(&name).len(),
+                is_active: name == active,
+                name,
+            })
+            .collect();
+
+        let window = self.window.clone().unwrap();
+        let pane_id = pane.pane_id();
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            workspace_picker(term, workspaces, window, pane_id)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_color_scheme_picker(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let schemes = self.config.all_color_schemes();
+        let current = self.config.color_scheme.clone();
+        let overrides = self.config_overrides.clone();
+        let window = self.window.clone().unwrap();
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            color_scheme_picker(term, schemes, current, overrides, window)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Makes `name` the active workspace, or a new workspace with a
+    /// generated name if `name` is None.  If the workspace has no
+    /// windows then `spawn` is used to create one.
+    fn switch_to_workspace(&mut self, name: Option<String>, spawn: Option<SpawnCommand>) {
+        let mux = Mux::get().unwrap();
+        let name = match name {
+            Some(name) if name.is_empty() => return,
+            Some(name) => name,
+            None => mux.generate_workspace_name(),
+        };
+        if name == mux.active_workspace() {
+            return;
+        }
+        let is_new = mux.iter_windows_in_workspace(&name).is_empty();
+        let prior_workspace = mux.active_workspace();
+
+        // Switching closes this window, but the spawn below only needs
+        // our window id and clipboard, which remain valid until then
+        mux.set_active_workspace(&name);
+        if is_new {
+            let spawn = spawn.unwrap_or_default();
+            let size = self.config.initial_size();
+            let src_window_id = self.mux_window_id;
+            let clipboard = ClipboardHelper {
+                window: self.window.as_ref().unwrap().clone(),
+                clipboard_contents: Arc::clone(&self.clipboard_contents),
+            };
+            promise::spawn::spawn(async move {
+                if let Err(err) = Self::spawn_command_internal(
+                    spawn,
+                    SpawnWhere::NewWindow,
+                    size,
+                    src_window_id,
+                    clipboard,
+                )
+                .await
+                {
+                    log::error!("Failed to spawn into workspace {}: {:#}", name, err);
+                    // Rather than leaving the new workspace active with no
+                    // windows to show, go back to the one we came from
+                    let mux = Mux::get().unwrap();
+                    if mux.active_workspace() == name {
+                        mux.set_active_workspace(&prior_workspace);
+                    }
+                }
+            })
+            .detach();
+        }
+    }
+
+    fn switch_workspace_relative(&mut self, delta: isize) {
+        let mux = Mux::get().unwrap();
+        let workspaces = mux.iter_workspaces();
+        let active = mux.active_workspace();
+        let idx = match workspaces.iter().position(|name| *name == active) {
+            Some(idx) => idx as isize,
+            None => return,
+        };
+        let len = workspaces.len() as isize;
+        let target = (idx + delta).rem_euclid(len) as usize;
+        self.switch_to_workspace(Some(workspaces[target].clone()), None);
+    }
+
     fn show_launcher(&mut self) {
         let mux = Mux::get().unwrap();
         let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -1599,6 +1882,7 @@ impl TermWindow {
             ShowTabNavigator => self.show_tab_navigator(),
             ShowDebugOverlay => self.show_debug_overlay(),
             ShowLauncher => self.show_launcher(),
+            ActivateCommandPalette => self.show_command_palette(),
             HideApplication => {
                 let con = Connection::get().expect("call on gui thread");
                 con.hide_application();
@@ -1675,6 +1959,36 @@ impl TermWindow {
                     self.assign_overlay_for_pane(pane.pane_id(), qa);
                 }
             }
+            ActivateKeyTable {
+                name,
+                timeout_milliseconds,
+                one_shot,
+                replace_current,
+            } => {
+                if !self.input_map.has_table(name) {
+                    // Errors from key assignments are not surfaced
+                    // to the user, so make sure that this is logged
+                    log::error!("ActivateKeyTable: no key_tables entry named {}", name);
+                    return Ok(());
+                }
+                if let Some(expiration) = self.key_table_state.activate(
+                    name,
+                    *timeout_milliseconds,
+                    *one_shot,
+                    *replace_current,
+                ) {
+                    self.schedule_key_table_expiration(expiration);
+                }
+                self.schedule_status_update();
+            }
+            PopKeyTable => {
+                self.key_table_state.pop();
+                self.schedule_status_update();
+            }
+            ClearKeyTableStack => {
+                self.key_table_state.clear();
+                self.schedule_status_update();
+            }
             ActivateCopyMode => {
                 if let Some(pane) = self.get_active_pane_no_overlay() {
                     let copy = CopyOverlay::with_pane(self, &pane);
@@ -1715,6 +2029,26 @@ impl TermWindow {
                 };
                 tab.toggle_zoom();
             }
+            SwapPaneDirection(direction) => self.swap_pane_direction(*direction),
+            RotatePanes(direction) => self.rotate_panes(*direction),
+            MovePaneToNewTab => self.move_pane_to_new_tab(false),
+            MovePaneToNewWindow => self.move_pane_to_new_tab(true),
+            MovePaneToTab {
+                tab_index,
+                direction,
+            } => self.move_pane_to_tab(*tab_index, *direction),
+            PaneSelect { mode, alphabet } => self.show_pane_select(*mode, alphabet.clone()),
+            SwitchToWorkspace { name, spawn } => {
+                self.switch_to_workspace(name.clone(), spawn.clone())
+            }
+            SwitchWorkspaceRelative(delta) => self.switch_workspace_relative(*delta),
+            ShowWorkspacePicker => self.show_workspace_picker(),
+            ShowColorSchemePicker => self.show_color_scheme_picker(),
+            ExportScrollback {
+                format,
+                scope,
+                path,
+            } => self.export_scrollback(pane, *format, *scope, path.clone()),
         };
         Ok(())
     }
@@ -1969,6 +2303,7 @@ impl TermWindow {
                 TabInformation {
                     tab_index: idx,
                     tab_id: tab.tab_id(),
+                    tab_title: tab.get_title(),
                     is_active: tab_index == idx,
                     active_pane: panes
                         .iter()
@@ -391,15 +391,26 @@ impl super::TermWindow {
             .unwrap_or(dims.physical_top)
             + y as StableRowIndex;
 
-        self.last_mouse_terminal_coords = (x, stable_row); // FIXME: per-pane
-
         let (top, mut lines) = pane.get_lines_with_hyperlinks_applied(
             stable_row..stable_row + 1,
             &self.config.hyperlink_rules,
         );
+
+        // Selection and hyperlinks refer to logical cells, which differ
+        // from the display columns when bidi text has been reordered.
+        // Mouse reports sent to the application use the display column.
+        let logical_x = match lines.get(0) {
+            Some(line) if top == stable_row => self
+                .cached_bidi_visual_line(pane.pane_id(), Some(stable_row), line, dims.cols)
+                .map_or(x, |bidi| bidi.logical_idx(x)),
+            _ => x,
+        };
+
+        self.last_mouse_terminal_coords = (logical_x, stable_row); // FIXME: per-pane
+
         let new_highlight = if top == stable_row {
             if let Some(line) = lines.get_mut(0) {
-                if let Some(cell) = line.cells().get(x) {
+                if let Some(cell) = line.cells().get(logical_x) {
                     cell.attrs().hyperlink().cloned()
                 } else {
                     None
@@ -0,0 +1,153 @@
+//! Key assignments that rearrange the panes in the window.
+//! The work is delegated to the domain of the active pane, so that
+//! panes in a remote mux are rearranged by that mux.
+use ::window::{Window, WindowOps};
+use config::keyassignment::{PaneDirection, RotationDirection};
+use mux::domain::Domain;
+use mux::pane::Pane;
+use mux::tab::Tab;
+use mux::Mux;
+use std::rc::Rc;
+use std::sync::Arc;
+
+fn report_result(window: Option<Window>, operation: &str, result: anyhow::Result<()>) {
+    match result {
+        Ok(()) => {
+            if let Some(window) = window {
+                window.invalidate();
+            }
+        }
+        Err(err) => log::error!("Failed to {}: {:#}", operation, err),
+    }
+}
+
+/// Makes the tab with the specified id the active tab in its window
+fn activate_tab(mux_window_id: mux::window::WindowId, tab_id: mux::tab::TabId) {
+    let mux = Mux::get().unwrap();
+    if let Some(mut window) = mux.get_window_mut(mux_window_id) {
+        if let Some(idx) = window.idx_by_id(tab_id) {
+            window.save_and_then_set_active(idx);
+        }
+    }
+}
+
+impl super::TermWindow {
+    /// Returns the active tab, its active pane and the domain of that
+    /// pane, unless the tab is showing an overlay
+    fn active_pane_for_rearrange(&mut self) -> Option<(Rc<Tab>, Rc<dyn Pane>, Arc<dyn Domain>)> {
+        let mux = Mux::get().unwrap();
+        let tab = mux.get_active_tab_for_window(self.mux_window_id)?;
+        if self.tab_state(tab.tab_id()).overlay.is_some() {
+            return None;
+        }
+        let pane = tab.get_active_pane()?;
+        let domain = mux.get_domain(pane.domain_id())?;
+        Some((tab, pane, domain))
+    }
+
+    pub fn swap_pane_direction(&mut self, direction: PaneDirection) {
+        let (tab, pane, domain) = match self.active_pane_for_rearrange() {
+            Some(active) => active,
+            None => return,
+        };
+        let other_pane_id = match tab
+            .get_pane_direction(direction)
+            .and_then(|idx| tab.iter_panes().into_iter().nth(idx))
+        {
+            Some(pos) => pos.pane.pane_id(),
+            None => return,
+        };
+        let pane_id = pane.pane_id();
+        let window = self.window.clone();
+        promise::spawn::spawn(async move {
+            let result = domain.swap_panes(pane_id, other_pane_id).await;
+            report_result(window, "swap panes", result);
+        })
+        .detach();
+    }
+
+    pub fn rotate_panes(&mut self, direction: RotationDirection) {
+        let (_tab, pane, domain) = match self.active_pane_for_rearrange() {
+            Some(active) => active,
+            None => return,
+        };
+        let pane_id = pane.pane_id();
+        let window = self.window.clone();
+        promise::spawn::spawn(async move {
+            let result = domain.rotate_panes(pane_id, direction).await;
+            report_result(window, "rotate panes", result);
+        })
+        .detach();
+    }
+
+    /// Moves the active pane into a new tab of its own, either in
+    /// this window or in a new window, and activates that tab
+    pub fn move_pane_to_new_tab(&mut self, new_window: bool) {
+        let (_tab, pane, domain) = match self.active_pane_for_rearrange() {
+            Some(active) => active,
+            None => return,
+        };
+        let pane_id = pane.pane_id();
+        let window_id = if new_window {
+            None
+        } else {
+            Some(self.mux_window_id)
+        };
+        let window = self.window.clone();
+        promise::spawn::spawn(async move {
+            let result = domain
+                .move_pane_to_new_tab(pane_id, window_id)
+                .await
+                .map(|(tab, window_id)| activate_tab(window_id, tab.tab_id()));
+            report_result(window, "move pane to a new tab", result);
+        })
+        .detach();
+    }
+
+    /// Moves the active pane into the tab at `tab_index` in this window,
+    /// placing it alongside the active pane of that tab, and then
+    /// activates that tab
+    pub fn move_pane_to_tab(&mut self, tab_index: usize, direction: PaneDirection) {
+        let (tab, pane, domain) = match self.active_pane_for_rearrange() {
+            Some(active) => active,
+            None => return,
+        };
+        let mux = Mux::get().unwrap();
+        let target_tab = match mux
+            .get_window(self.mux_window_id)
+            .and_then(|window| window.get_by_idx(tab_index).map(Rc::clone))
+        {
+            Some(tab) => tab,
+            None => {
+                log::error!("MovePaneToTab: there is no tab with index {}", tab_index);
+                return;
+            }
+        };
+        if target_tab.tab_id() == tab.tab_id() {
+            log::error!("MovePaneToTab: the pane is already in tab {}", tab_index);
+            return;
+        }
+        let target_pane = match target_tab.get_active_pane() {
+            Some(pane) => pane,
+            None => return,
+        };
+        if target_pane.domain_id() != pane.domain_id() {
+            log::error!("MovePaneToTab: cannot move a pane to a tab in a different domain");
+            return;
+        }
+
+        let pane_id = pane.pane_id();
+        let target_pane_id = target_pane.pane_id();
+        let target_tab_id = target_tab.tab_id();
+        let mux_window_id = self.mux_window_id;
+        let window = self.window.clone();
+        promise::spawn::spawn(async move {
+            let result = domain
+                .move_pane_to_tab(pane_id, target_pane_id, direction)
+                .await
+                .map(|()| activate_tab(mux_window_id, target_tab_id));
+            report_result(window, "move pane to another tab", result);
+        })
+        .detach();
+    }
+}
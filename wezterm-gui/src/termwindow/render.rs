@@ -14,7 +14,7 @@ use ::window::glium::{uniform, BlendingFunction, LinearBlendingFactor, Surface};
 use ::window::WindowOps;
 use anyhow::anyhow;
 use config::{ConfigHandle, HsbTransform, TextStyle};
-use mux::pane::Pane;
+use mux::pane::{Pane, PaneId};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::{PositionedPane, PositionedSplit, SplitDirection};
 use smol::Timer;
@@ -23,6 +23,7 @@ use std::rc::Rc;
 use std::time::Duration;
 use std::time::Instant;
 use termwiz::cellcluster::CellCluster;
+use termwiz::surface::line::{LineRendition, VisualLine};
 use termwiz::surface::{CursorShape, CursorVisibility};
 use wezterm_font::units::PixelLength;
 use wezterm_font::{ClearShapeCache, GlyphInfo};
@@ -80,6 +81,123 @@ pub struct ComputeCellFgBgResult {
     pub cursor_shape: Option<CursorShape>,
 }
 
+/// Describes how a slice of a glyph texture is mapped onto a quad.
+/// `left`, `top`, `right` and `bottom` are the texture adjustments
+/// relative to the cell that holds the quad.
+#[derive(Clone, Copy)]
+struct GlyphPlacement {
+    texture: TextureRect,
+    left: f32,
+    top: f32,
+    right: f32,
+    bottom: f32,
+}
+
+impl GlyphPlacement {
+    /// Scales the placement up for a line with a double width or
+    /// double height rendition.  Each cell of such a line spans two
+    /// quads; the result holds the placement for each of them, or
+    /// None for a quad that doesn't show any part of the glyph.
+    fn for_rendition(
+        self,
+        rendition: LineRendition,
+        cell_width: f32,
+        cell_height: f32,
+    ) -> Vec<Option<Self>> {
+        if !rendition.is_double_width() {
+            return vec![Some(self)];
+        }
+
+        let x_span = (self.left * 2., (cell_width + self.right) * 2.);
+        // A double height line shows one half of a glyph that is
+        // twice as tall as the cell; the other half is shown by the
+        // line above or below it.
+        let (y_span, visible_y) = match rendition {
+            LineRendition::DoubleHeightTop => (
+                (self.top * 2., (cell_height + self.bottom) * 2.),
+                f32::NEG_INFINITY..cell_height,
+            ),
+            LineRendition::DoubleHeightBottom => (
+                (
+                    self.top * 2. - cell_height,
+                    (cell_height + self.bottom) * 2. - cell_height,
+                ),
+                0.0..f32::INFINITY,
+            ),
+            _ => (
+                (self.top, cell_height + self.bottom),
+                f32::NEG_INFINITY..f32::INFINITY,
+            ),
+        };
+
+        let (top, bottom, tex_top, tex_bottom) = match clip_span(
+            y_span,
+            (self.texture.min_y(), self.texture.max_y()),
+            &visible_y,
+        ) {
+            Some(span) => span,
+            None => return vec![None, None],
+        };
+
+        [f32::NEG_INFINITY..cell_width, cell_width..f32::INFINITY]
+            .iter()
+            .enumerate()
+            .map(|(idx, visible_x)| {
+                let (left, right, tex_left, tex_right) = clip_span(
+                    x_span,
+                    (self.texture.min_x(), self.texture.max_x()),
+                    visible_x,
+                )?;
+                let origin = idx as f32 * cell_width;
+                Some(Self {
+                    texture: TextureRect::new(
+                        TextureCoord::new(tex_left, tex_top),
+                        TextureSize::new(tex_right - tex_left, tex_bottom - tex_top),
+                    ),
+                    left: left - origin,
+                    top,
+                    right: right - origin - cell_width,
+                    bottom: bottom - cell_height,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Clips `span`, onto which the texture coordinates `tex_span` are
+/// mapped, to the `visible` range.
+/// Returns the clipped span followed by its texture coordinates,
+/// or None if no part of the span is visible.
+fn clip_span(
+    span: (f32, f32),
+    tex_span: (f32, f32),
+    visible: &Range<f32>,
+) -> Option<(f32, f32, f32, f32)> {
+    let (start, end) = span;
+    let clipped_start = start.max(visible.start);
+    let clipped_end = end.min(visible.end);
+    if clipped_start >= clipped_end {
+        return None;
+    }
+    let scale = (tex_span.1 - tex_span.0) / (end - start);
+    Some((
+        clipped_start,
+        clipped_end,
+        tex_span.0 + (clipped_start - start) * scale,
+        tex_span.0 + (clipped_end - start) * scale,
+    ))
+}
+
+/// Returns the range of quads that display the cell at `cell_idx`
+/// of a line with the specified rendition
+fn quad_cols(rendition: LineRendition, cell_idx: usize) -> Range<usize> {
+    if rendition.is_double_width() {
+        cell_idx * 2..cell_idx * 2 + 2
+    } else {
+        cell_idx..cell_idx + 1
+    }
+}
+
 impl super::TermWindow {
     pub fn paint_impl(&mut self, frame: &mut glium::Frame) {
         // If nothing on screen needs animating, then we can avoid
@@ -178,7 +296,7 @@ impl super::TermWindow {
         self.check_for_dirty_lines_and_invalidate_selection(&pos.pane);
 
         let config = &self.config;
-        let palette = pos.pane.palette();
+        let palette = self.palette_for_pane(&pos.pane);
 
         let background_color = palette.resolve_bg(wezterm_term::color::ColorAttribute::Default);
         let first_line_offset = if self.show_tab_bar && !self.config.tab_bar_at_bottom {
@@ -558,7 +676,7 @@ impl super::TermWindow {
             '\u{2500}'
         })
         .expect("to have box drawing glyph");
-        let palette = pane.palette();
+        let palette = self.palette_for_pane(pane);
         let foreground = rgbcolor_to_window_color(palette.split);
         let background = rgbcolor_alpha_to_window_color(
             palette.background,
@@ -639,7 +757,13 @@ impl super::TermWindow {
     ) -> anyhow::Result<()> {
         let gl_state = self.render_state.as_ref().unwrap();
 
-        let num_cols = params.dims.cols;
+        // Each cell of a double width line occupies two columns
+        let rendition = params.line.rendition();
+        let num_cols = if rendition.is_double_width() {
+            params.dims.cols / 2
+        } else {
+            params.dims.cols
+        };
 
         let hsv = if params.is_active {
             None
@@ -652,7 +776,7 @@ impl super::TermWindow {
         // open a vim split horizontally.  Backgrounding vim would leave
         // the right pane with its prior contents instead of showing the
         // cleared lines from the shell in the main screen.
-        for cell_idx in 0..num_cols {
+        for cell_idx in 0..params.dims.cols {
             let mut quad =
                 match quads.cell(cell_idx + params.pos.left, params.line_idx + params.pos.top) {
                     Ok(quad) => quad,
@@ -668,9 +792,27 @@ impl super::TermWindow {
             quad.set_hsv(hsv);
         }
 
+        // Reorder bidirectional text for display.  The selection and the
+        // cursor position refer to logical cells, so each display column
+        // is mapped back to its logical cell when computing its colors.
+        let bidi = self.cached_bidi_visual_line(
+            params.pos.pane.pane_id(),
+            params.stable_line_idx,
+            params.line,
+            num_cols,
+        );
+        let line = bidi.as_ref().map_or(params.line, |bidi| bidi.line());
+        let logical_idx = |cell_idx: usize| match &bidi {
+            Some(bidi) => bidi.logical_idx(cell_idx),
+            None => cell_idx,
+        };
+
         // Break the line into clusters of cells with the same attributes
         let start = Instant::now();
-        let cell_clusters = params.line.cluster();
+        let cell_clusters = match &bidi {
+            Some(bidi) => bidi.cluster(),
+            None => line.cluster(),
+        };
         metrics::histogram!("render_screen_line_opengl.line.cluster", start.elapsed());
         log::trace!(
             "cluster -> {} clusters, elapsed {:?}",
@@ -761,7 +903,7 @@ impl super::TermWindow {
 
             // Shape the printable text from this cluster
             let glyph_info =
-                self.cached_cluster_shape(style_params.style, &cluster, &gl_state, params.line)?;
+                self.cached_cluster_shape(style_params.style, &cluster, &gl_state, line)?;
 
             for info in glyph_info.iter() {
                 let glyph = &info.glyph;
@@ -795,7 +937,7 @@ impl super::TermWindow {
                         cursor_shape,
                     } = self.compute_cell_fg_bg(ComputeCellFgBgParams {
                         stable_line_idx: params.stable_line_idx,
-                        cell_idx,
+                        cell_idx: logical_idx(cell_idx),
                         cursor: params.cursor,
                         selection: &params.selection,
                         fg_color: style_params.fg_color,
@@ -814,7 +956,7 @@ impl super::TermWindow {
                             image,
                             gl_state,
                             quads,
-                            cell_idx,
+                            quad_cols(rendition, cell_idx).start,
                             &params,
                             hsv,
                             cursor_shape,
@@ -825,8 +967,11 @@ impl super::TermWindow {
                         continue;
                     }
 
-                    if self.config.custom_block_glyphs && glyph_idx == 0 {
-                        if let Some(cell) = params.line.cells().get(cell_idx) {
+                    if self.config.custom_block_glyphs
+                        && glyph_idx == 0
+                        && !rendition.is_double_width()
+                    {
+                        if let Some(cell) = line.cells().get(cell_idx) {
                             if let Some(block) = BlockKey::from_cell(cell) {
                                 self.populate_block_quad(
                                     block,
@@ -876,40 +1021,78 @@ impl super::TermWindow {
                     // `left` value to be 0 when glyph_idx > 0.
                     slice_left = right;
 
-                    let mut quad = match quads
-                        .cell(cell_idx + params.pos.left, params.line_idx + params.pos.top)
-                    {
-                        Ok(quad) => quad,
-                        Err(_) => break,
-                    };
-
-                    quad.set_fg_color(glyph_color);
-                    quad.set_bg_color(bg_color);
-                    quad.set_texture(texture_rect);
-                    quad.set_texture_adjust(left, top, right, bottom);
-                    quad.set_underline(style_params.underline_tex_rect);
-                    quad.set_underline_color(style_params.underline_color);
-                    quad.set_hsv(if glyph.brightness_adjust != 1.0 {
-                        let hsv = hsv.unwrap_or_else(|| HsbTransform::default());
-                        Some(HsbTransform {
-                            brightness: hsv.brightness * glyph.brightness_adjust,
-                            ..hsv
-                        })
-                    } else {
-                        hsv
-                    });
-                    quad.set_has_color(glyph.has_color);
-                    quad.set_cursor(
-                        gl_state
-                            .util_sprites
-                            .cursor_sprite(cursor_shape)
-                            .texture_coords(),
+                    let placements = GlyphPlacement {
+                        texture: texture_rect,
+                        left,
+                        top,
+                        right,
+                        bottom,
+                    }
+                    .for_rendition(
+                        rendition,
+                        self.render_metrics.cell_size.width as f32,
+                        self.render_metrics.cell_size.height as f32,
                     );
-                    quad.set_cursor_color(if self.config.force_reverse_video_cursor {
-                        bg_color
+
+                    // The underline belongs to the bottom half of a
+                    // double height line
+                    let underline_tex_rect = if rendition == LineRendition::DoubleHeightTop {
+                        params.white_space
                     } else {
-                        params.cursor_border_color
-                    });
+                        style_params.underline_tex_rect
+                    };
+
+                    for (quad_col, placement) in
+                        quad_cols(rendition, cell_idx).zip(placements.into_iter())
+                    {
+                        let mut quad = match quads
+                            .cell(quad_col + params.pos.left, params.line_idx + params.pos.top)
+                        {
+                            Ok(quad) => quad,
+                            Err(_) => break,
+                        };
+
+                        quad.set_fg_color(glyph_color);
+                        quad.set_bg_color(bg_color);
+                        match placement {
+                            Some(placement) => {
+                                quad.set_texture(placement.texture);
+                                quad.set_texture_adjust(
+                                    placement.left,
+                                    placement.top,
+                                    placement.right,
+                                    placement.bottom,
+                                );
+                            }
+                            None => {
+                                quad.set_texture(params.white_space);
+                                quad.set_texture_adjust(0., 0., 0., 0.);
+                            }
+                        }
+                        quad.set_underline(underline_tex_rect);
+                        quad.set_underline_color(style_params.underline_color);
+                        quad.set_hsv(if glyph.brightness_adjust != 1.0 {
+                            let hsv = hsv.unwrap_or_else(|| HsbTransform::default());
+                            Some(HsbTransform {
+                                brightness: hsv.brightness * glyph.brightness_adjust,
+                                ..hsv
+                            })
+                        } else {
+                            hsv
+                        });
+                        quad.set_has_color(glyph.has_color);
+                        quad.set_cursor(
+                            gl_state
+                                .util_sprites
+                                .cursor_sprite(cursor_shape)
+                                .texture_coords(),
+                        );
+                        quad.set_cursor_color(if self.config.force_reverse_video_cursor {
+                            bg_color
+                        } else {
+                            params.cursor_border_color
+                        });
+                    }
                 }
                 current_idx += info.pos.num_cells as usize;
             }
@@ -920,20 +1103,24 @@ impl super::TermWindow {
         // * Selection
         // * Cursor (although it is really unlikely that the cursor is outside
         //   of one of the clusters)
+        // A reordered line is padded to the full width, so its clusters
+        // have already painted every column.
         let right_fill_start = Instant::now();
-        if last_cell_idx < num_cols {
+        if last_cell_idx < num_cols && bidi.is_none() {
             // We only need to update the cells that have the cursor or the
             // selection.
             if let Some(sel_range) =
                 rangeset::range_intersection(&params.selection, &(last_cell_idx + 1..num_cols))
             {
                 for cell_idx in sel_range {
-                    if let Ok(mut quad) =
-                        quads.cell(cell_idx + params.pos.left, params.line_idx + params.pos.top)
-                    {
-                        quad.set_bg_color(params.selection_bg);
-                        quad.set_fg_color(params.selection_fg);
-                        quad.set_underline_color(params.selection_fg);
+                    for quad_col in quad_cols(rendition, cell_idx) {
+                        if let Ok(mut quad) =
+                            quads.cell(quad_col + params.pos.left, params.line_idx + params.pos.top)
+                        {
+                            quad.set_bg_color(params.selection_bg);
+                            quad.set_fg_color(params.selection_fg);
+                            quad.set_underline_color(params.selection_fg);
+                        }
                     }
                 }
             }
@@ -962,23 +1149,24 @@ impl super::TermWindow {
                     cursor_bg: params.cursor_bg,
                 });
 
-                if let Ok(mut quad) = quads.cell(
-                    params.cursor.x + params.pos.left,
-                    params.line_idx + params.pos.top,
-                ) {
-                    quad.set_bg_color(bg_color);
-                    quad.set_fg_color(glyph_color);
-                    quad.set_cursor(
-                        gl_state
-                            .util_sprites
-                            .cursor_sprite(cursor_shape)
-                            .texture_coords(),
-                    );
-                    quad.set_cursor_color(if self.config.force_reverse_video_cursor {
-                        bg_color
-                    } else {
-                        params.cursor_border_color
-                    });
+                for quad_col in quad_cols(rendition, params.cursor.x) {
+                    if let Ok(mut quad) =
+                        quads.cell(quad_col + params.pos.left, params.line_idx + params.pos.top)
+                    {
+                        quad.set_bg_color(bg_color);
+                        quad.set_fg_color(glyph_color);
+                        quad.set_cursor(
+                            gl_state
+                                .util_sprites
+                                .cursor_sprite(cursor_shape)
+                                .texture_coords(),
+                        );
+                        quad.set_cursor_color(if self.config.force_reverse_video_cursor {
+                            bg_color
+                        } else {
+                            params.cursor_border_color
+                        });
+                    }
                 }
             }
         }
@@ -1264,6 +1452,7 @@ impl super::TermWindow {
         let key = BorrowedShapeCacheKey {
             style,
             text: &cluster.text,
+            direction: cluster.direction,
         };
         let glyph_info = match self.lookup_cached_shape(&key) {
             Some(Ok(info)) => info,
@@ -1273,6 +1462,7 @@ impl super::TermWindow {
                 let window = self.window.as_ref().unwrap().clone();
                 match font.shape(
                     &cluster.text,
+                    cluster.direction,
                     move || window.notify(TermWindowNotif::InvalidateShapeCache),
                     BlockKey::filter_out_synthetic,
                 ) {
@@ -1317,6 +1507,35 @@ impl super::TermWindow {
         Ok(glyph_info)
     }
 
+    /// Reorder a line for display, reusing the result from a prior
+    /// frame when the line at that row hasn't changed since
+    pub fn cached_bidi_visual_line(
+        &self,
+        pane_id: PaneId,
+        stable_row: Option<StableRowIndex>,
+        line: &Line,
+        width: usize,
+    ) -> Option<Rc<VisualLine>> {
+        if !line.bidi_mode().enabled {
+            return None;
+        }
+        let stable_row = match stable_row {
+            Some(stable_row) => stable_row,
+            None => return line.bidi_visual_line(width).map(Rc::new),
+        };
+
+        let key = (pane_id, stable_row, width);
+        let mut cache = self.bidi_cache.borrow_mut();
+        if let Some((cached_line, visual)) = cache.get(&key) {
+            if cached_line == line {
+                return visual.clone();
+            }
+        }
+        let visual = line.bidi_visual_line(width).map(Rc::new);
+        cache.put(key, (line.clone(), visual.clone()));
+        visual
+    }
+
     fn lookup_cached_shape(
         &self,
         key: &dyn ShapeCacheKeyTrait,
@@ -55,7 +55,7 @@ impl super::TermWindow {
         .detach();
     }
 
-    async fn spawn_command_internal(
+    pub(super) async fn spawn_command_internal(
         spawn: SpawnCommand,
         spawn_where: SpawnWhere,
         size: PtySize,
@@ -67,7 +67,7 @@ impl super::TermWindow {
         let mux_builder;
 
         let target_window_id = if spawn_where == SpawnWhere::NewWindow {
-            mux_builder = mux.new_empty_window();
+            mux_builder = mux.new_empty_window(None);
             *mux_builder
         } else {
             src_window_id
@@ -82,6 +82,7 @@ where
                 handler.schedule_pane_push(pane_id);
             }
             Ok(Item::Notif(MuxNotification::PaneAdded(_pane_id))) => {}
+            Ok(Item::Notif(MuxNotification::PaneExited { .. })) => {}
             Ok(Item::Notif(MuxNotification::PaneRemoved(pane_id))) => {
                 Pdu::PaneRemoved(codec::PaneRemoved { pane_id })
                     .encode_async(&mut stream, 0)
@@ -99,6 +100,8 @@ where
             Ok(Item::Notif(MuxNotification::WindowRemoved(_window_id))) => {}
             Ok(Item::Notif(MuxNotification::WindowCreated(_window_id))) => {}
             Ok(Item::Notif(MuxNotification::WindowInvalidated(_window_id))) => {}
+            Ok(Item::Notif(MuxNotification::WindowWorkspaceChanged(_window_id))) => {}
+            Ok(Item::Notif(MuxNotification::ActiveWorkspaceChanged(_workspace))) => {}
             Ok(Item::Notif(MuxNotification::Empty)) => {}
             Err(err) => {
                 log::error!("process_async Err {}", err);
@@ -311,6 +311,88 @@ impl SessionHandler {
                 })
                 .detach();
             }
+            Pdu::SwapPanes(SwapPanes {
+                pane_id,
+                other_pane_id,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.swap_panes(pane_id, other_pane_id)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::RotatePanes(RotatePanes { pane_id, direction }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.rotate_panes(pane_id, direction)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::MovePaneToNewTab(MovePaneToNewTab { pane_id, window_id }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let (tab, window_id) = mux.move_pane_to_new_tab(pane_id, window_id)?;
+                            Ok(Pdu::MovePaneToNewTabResponse(MovePaneToNewTabResponse {
+                                tab_id: tab.tab_id(),
+                                window_id,
+                            }))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::MovePaneToTab(MovePaneToTab {
+                pane_id,
+                target_pane_id,
+                direction,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.move_pane_to_tab(pane_id, target_pane_id, direction)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::ExportPane(ExportPane {
+                pane_id,
+                format,
+                scope,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            let data = mux::export::export_pane(&*pane, format, scope)?;
+                            Ok(Pdu::ExportPaneResponse(ExportPaneResponse { data }))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
             Pdu::SendPaste(SendPaste { pane_id, data }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
@@ -567,6 +649,8 @@ impl SessionHandler {
             | Pdu::NotifyAlert { .. }
             | Pdu::SetPalette { .. }
             | Pdu::SpawnResponse { .. }
+            | Pdu::MovePaneToNewTabResponse { .. }
+            | Pdu::ExportPaneResponse { .. }
             | Pdu::GetPaneRenderChangesResponse { .. }
             | Pdu::UnitResponse { .. }
             | Pdu::LivenessResponse { .. }
@@ -711,7 +795,7 @@ async fn domain_spawn(spawn: Spawn, sender: PduSender) -> anyhow::Result<Pdu> {
             .ok_or_else(|| anyhow!("window_id {} not found on this server", window_id))?;
         window_id
     } else {
-        window_builder = mux.new_empty_window();
+        window_builder = mux.new_empty_window(spawn.workspace);
         *window_builder
     };
 
@@ -755,7 +839,7 @@ async fn domain_spawn_v2(spawn: SpawnV2, sender: PduSender) -> anyhow::Result<Pd
             .ok_or_else(|| anyhow!("window_id {} not found on this server", window_id))?;
         window_id
     } else {
-        window_builder = mux.new_empty_window();
+        window_builder = mux.new_empty_window(spawn.workspace);
         *window_builder
     };
 
@@ -1,5 +1,5 @@
 use anyhow::{anyhow, Context};
-use config::keyassignment::SpawnTabDomain;
+use config::keyassignment::{ExportFormat, ExportScope, SpawnTabDomain};
 use config::wezterm_version;
 use mux::activity::Activity;
 use mux::pane::PaneId;
@@ -15,6 +15,7 @@ use tabout::{tabulate_output, Alignment, Column};
 use umask::UmaskSaver;
 use wezterm_client::client::{unix_connect_with_retry, Client};
 use wezterm_gui_subcommands::*;
+use wezterm_term::SemanticType;
 
 //    let message = "; ❤ 😍🤢\n\x1b[91;mw00t\n\x1b[37;104;m bleet\x1b[0;m.";
 
@@ -67,6 +68,15 @@ enum SubCommand {
     #[structopt(name = "ls-fonts", about = "Display information about fonts")]
     LsFonts(LsFontsCommand),
 
+    #[structopt(
+        name = "record",
+        about = "Record a terminal session in asciicast format"
+    )]
+    Record(RecordCommand),
+
+    #[structopt(name = "replay", about = "Replay an asciicast recording")]
+    Replay(ReplayCommand),
+
     #[structopt(name = "cli", about = "Interact with experimental mux server")]
     Cli(CliCommand),
 
@@ -79,6 +89,15 @@ enum SubCommand {
                  emitting an OSC 7 escape sequence"
     )]
     SetCwd(SetCwdCommand),
+
+    #[structopt(name = "ls-color-schemes", about = "List the available color schemes")]
+    LsColorSchemes(LsColorSchemesCommand),
+
+    #[structopt(
+        name = "convert-color-scheme",
+        about = "Convert a color scheme from another terminal emulator to wezterm's format"
+    )]
+    ConvertColorScheme(ConvertColorSchemeCommand),
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -157,6 +176,12 @@ Outputs the pane-id for the newly created pane on success"
         #[structopt(long = "new-window", conflicts_with = "window_id")]
         new_window: bool,
 
+        /// When spawning into a new window, the workspace in which
+        /// to place that window.  If omitted, the active workspace
+        /// of the mux server is used.
+        #[structopt(long = "workspace", requires = "new_window")]
+        workspace: Option<String>,
+
         /// Specify the current working directory for the initially
         /// spawned program
         #[structopt(long = "cwd", parse(from_os_str))]
@@ -168,6 +193,40 @@ Outputs the pane-id for the newly created pane on success"
         #[structopt(parse(from_os_str))]
         prog: Vec<OsString>,
     },
+
+    #[structopt(
+        name = "export",
+        about = "Export the contents of a pane to stdout as text, ansi or html"
+    )]
+    Export {
+        /// Specify the pane to export.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// The format of the export: one of text, ansi or html
+        #[structopt(long = "format", default_value = "text")]
+        format: ExportFormat,
+
+        /// Rather than the whole scrollback, export the most recent
+        /// semantic zone of this type: one of output, input or prompt.
+        /// The shell needs to use OSC 133 escapes to mark up zones.
+        #[structopt(long = "semantic-zone", parse(try_from_str = parse_semantic_type))]
+        semantic_zone: Option<SemanticType>,
+    },
+}
+
+fn parse_semantic_type(s: &str) -> Result<SemanticType, String> {
+    match s.to_lowercase().as_str() {
+        "output" => Ok(SemanticType::Output),
+        "input" => Ok(SemanticType::Input),
+        "prompt" => Ok(SemanticType::Prompt),
+        _ => Err(format!(
+            "invalid semantic zone type {}; expected one of output, input or prompt",
+            s
+        )),
+    }
 }
 
 use termwiz::escape::osc::{
@@ -264,6 +323,128 @@ impl SetCwdCommand {
     }
 }
 
+#[derive(Debug, StructOpt, Clone)]
+struct LsColorSchemesCommand {
+    /// Print only the names of the schemes, without swatches
+    #[structopt(long = "names-only")]
+    names_only: bool,
+}
+
+impl LsColorSchemesCommand {
+    fn run(&self, config: config::ConfigHandle) -> anyhow::Result<()> {
+        use termwiz::cell::unicode_column_width;
+        use termwiz::color::ColorSpec;
+        use termwiz::escape::csi::{Sgr, CSI};
+
+        let schemes = config.all_color_schemes();
+        let name_width = schemes
+            .iter()
+            .map(|(name, _)| unicode_column_width(name))
+            .max()
+            .unwrap_or(0);
+
+        let color = |c: Option<config::RgbColor>| match c {
+            Some(c) => ColorSpec::TrueColor(c),
+            None => ColorSpec::Default,
+        };
+
+        let mut out = std::io::stdout();
+        for (name, palette) in &schemes {
+            if self.names_only {
+                writeln!(out, "{}", name)?;
+                continue;
+            }
+
+            // The name, followed by some sample text in the foreground
+            // and background colors, and then the ansi and bright colors
+            write!(
+                out,
+                "{}{} {}{} Aa {}",
+                name,
+                " ".repeat(name_width - unicode_column_width(name)),
+                CSI::Sgr(Sgr::Foreground(color(palette.foreground))),
+                CSI::Sgr(Sgr::Background(color(palette.background))),
+                CSI::Sgr(Sgr::Reset),
+            )?;
+            for colors in [palette.ansi, palette.brights].iter() {
+                write!(out, " ")?;
+                for idx in 0..8 {
+                    write!(
+                        out,
+                        "{}  ",
+                        CSI::Sgr(Sgr::Background(color(
+                            colors.as_ref().map(|colors| colors[idx])
+                        )))
+                    )?;
+                }
+                write!(out, "{}", CSI::Sgr(Sgr::Reset))?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct ConvertColorSchemeCommand {
+    /// The format of the input file: one of iterm2, alacritty, kitty
+    /// or xresources.  If omitted, it is determined from the extension
+    /// of the file name.
+    #[structopt(long = "format")]
+    format: Option<config::ColorSchemeFormat>,
+
+    /// The name to give the scheme.  If omitted, the name of the
+    /// input file without its extension is used.
+    #[structopt(long = "name")]
+    name: Option<String>,
+
+    /// Write the scheme to this file rather than to stdout.
+    /// Place it in your `color_scheme_dirs` to make it available
+    /// to `color_scheme`.
+    #[structopt(long = "output", short = "o", parse(from_os_str))]
+    output: Option<OsString>,
+
+    /// The color scheme file to convert
+    #[structopt(parse(from_os_str))]
+    input: OsString,
+}
+
+impl ConvertColorSchemeCommand {
+    fn run(&self) -> anyhow::Result<()> {
+        let path = std::path::Path::new(&self.input);
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow!("{} is not a valid file name", path.display()))?;
+        let from_name = config::ColorSchemeFormat::from_file_name(file_name);
+
+        let format = match (self.format, from_name) {
+            (Some(format), _) => format,
+            (None, Some((format, _))) => format,
+            (None, None) => anyhow::bail!(
+                "cannot determine the format of {} from its name; use --format",
+                path.display()
+            ),
+        };
+        let name = match (&self.name, from_name) {
+            (Some(name), _) => name.as_str(),
+            (None, Some((_, name))) => name,
+            (None, None) => file_name,
+        };
+
+        let scheme = config::ColorSchemeFile::load_file(path, format)
+            .with_context(|| format!("loading color scheme from {}", path.display()))?;
+        let toml = format!("# {}\n{}", name, scheme.to_toml()?);
+
+        match &self.output {
+            Some(output) => std::fs::write(output, toml)
+                .with_context(|| format!("writing {}", std::path::Path::new(output).display()))?,
+            None => print!("{}", toml),
+        }
+        Ok(())
+    }
+}
+
 fn terminate_with_error_message(err: &str) -> ! {
     log::error!("{}; terminating", err);
     std::process::exit(1);
@@ -305,9 +486,13 @@ fn run() -> anyhow::Result<()> {
         | SubCommand::LsFonts(_)
         | SubCommand::Ssh(_)
         | SubCommand::Serial(_)
-        | SubCommand::Connect(_) => delegate_to_gui(saver),
+        | SubCommand::Connect(_)
+        | SubCommand::Record(_)
+        | SubCommand::Replay(_) => delegate_to_gui(saver),
         SubCommand::ImageCat(cmd) => cmd.run(),
         SubCommand::SetCwd(cmd) => cmd.run(),
+        SubCommand::LsColorSchemes(cmd) => cmd.run(config),
+        SubCommand::ConvertColorScheme(cmd) => cmd.run(),
         SubCommand::Cli(cli) => run_cli(config, cli),
     }
 }
@@ -373,6 +558,10 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                     name: "PANEID".to_string(),
                     alignment: Alignment::Right,
                 },
+                Column {
+                    name: "WORKSPACE".to_string(),
+                    alignment: Alignment::Left,
+                },
                 Column {
                     name: "SIZE".to_string(),
                     alignment: Alignment::Left,
@@ -398,6 +587,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                             entry.window_id.to_string(),
                             entry.tab_id.to_string(),
                             entry.pane_id.to_string(),
+                            entry.workspace.clone(),
                             format!("{}x{}", entry.size.cols, entry.size.rows),
                             entry.title.clone(),
                             entry
@@ -464,6 +654,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             domain_name,
             window_id,
             new_window,
+            workspace,
         } => {
             let window_id = if new_window {
                 None
@@ -520,6 +711,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                     },
                     command_dir: cwd.and_then(|c| c.to_str().map(|s| s.to_string())),
                     size: config::configuration().initial_size(),
+                    workspace,
                 })
                 .await?;
 
@@ -557,6 +749,40 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             // Wait forever; the stdio threads will terminate on EOF
             smol::future::pending().await
         }
+        CliSubCommand::Export {
+            pane_id,
+            format,
+            semantic_zone,
+        } => {
+            let pane_id: PaneId = match pane_id {
+                Some(p) => p,
+                None => std::env::var("WEZTERM_PANE")
+                    .map_err(|_| {
+                        anyhow!(
+                            "--pane-id was not specified and $WEZTERM_PANE
+                                    is not set in the environment"
+                        )
+                    })?
+                    .parse()?,
+            };
+
+            let exported = client
+                .export_pane(codec::ExportPane {
+                    pane_id,
+                    format,
+                    scope: semantic_zone.map_or(ExportScope::Scrollback, |semantic_type| {
+                        ExportScope::LastSemanticZone(semantic_type)
+                    }),
+                })
+                .await?;
+
+            let mut stdout = std::io::stdout();
+            stdout.write_all(exported.data.as_bytes())?;
+            if !exported.data.ends_with('\n') {
+                stdout.write_all(b"\n")?;
+            }
+            stdout.flush()?;
+        }
         CliSubCommand::TlsCreds => {
             let creds = client.get_tls_creds().await?;
             codec::Pdu::GetTlsCredsResponse(creds).encode(std::io::stdout().lock(), 0)?;